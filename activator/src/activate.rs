@@ -0,0 +1,195 @@
+//! Waking a runner and waiting for it to serve.
+//!
+//! The runner itself is the only state: the activator resumes it the way a
+//! user would, by turning `spec.suspended` off, and learns it is back from the
+//! `PodReady` condition the controller's status loop writes. Nothing is
+//! remembered between requests, so any number of replicas can share the
+//! work, and a restarted one picks up where its predecessor left off.
+
+use std::time::Duration;
+
+use kubimo::conditions::{POD_READY, SUSPENDED};
+use kubimo::k8s_openapi::apimachinery::pkg::apis::meta::v1::Condition;
+use kubimo::{Runner, json_patch_macros::*};
+
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum ActivateError {
+    #[error("runner {0} not found")]
+    NotFound(RunnerRef),
+    #[error("runner {0} did not become ready in time")]
+    Timeout(RunnerRef),
+    #[error(transparent)]
+    Kubimo(#[from] kubimo::Error),
+}
+
+/// The runner a request is for, from the `Host` the ingress controller sent
+/// upstream: `{name}.{namespace}`, which the controller sets as the runner
+/// Ingress's `upstream-vhost` while it routes here. The namespace is the last
+/// label because it is the one of the two that can hold no dot. Trusted only
+/// because the ingress controller is all that can reach the activator.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct RunnerRef {
+    pub name: String,
+    pub namespace: String,
+}
+
+impl RunnerRef {
+    pub(crate) fn from_host(host: &str) -> Option<Self> {
+        let host = host.rsplit_once(':').map_or(host, |(host, _port)| host);
+        let (name, namespace) = host.rsplit_once('.')?;
+        if name.is_empty() || namespace.is_empty() {
+            return None;
+        }
+        Some(Self {
+            name: name.to_string(),
+            namespace: namespace.to_string(),
+        })
+    }
+}
+
+impl std::fmt::Display for RunnerRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.namespace, self.name)
+    }
+}
+
+fn condition<'a>(runner: &'a Runner, type_: &str) -> Option<&'a Condition> {
+    runner
+        .status
+        .as_ref()?
+        .conditions
+        .iter()
+        .flatten()
+        .find(|cond| cond.type_ == type_)
+}
+
+/// Whether the runner serves again. A `PodReady` older than the resume is the
+/// pod from before the suspension, reported before the status loop noticed
+/// it had gone.
+pub(crate) fn is_ready(runner: &Runner) -> bool {
+    if runner.spec.is_suspended() {
+        return false;
+    }
+    let Some(ready) = condition(runner, POD_READY).filter(|cond| cond.status == "True") else {
+        return false;
+    };
+    match condition(runner, SUSPENDED) {
+        None => true,
+        Some(suspended) => {
+            suspended.status == "False"
+                && ready.last_transition_time.0 >= suspended.last_transition_time.0
+        }
+    }
+}
+
+/// Resume `target` if it is suspended, and wait until it is ready. Returns
+/// whether it already was, which means the request only reached the activator
+/// because routing has not caught up with the resume yet.
+pub(crate) async fn activate(
+    client: &kubimo::Client,
+    target: &RunnerRef,
+    timeout: Duration,
+    poll_interval: Duration,
+) -> Result<bool, ActivateError> {
+    let api = client.api_namespaced::<Runner>(&target.namespace);
+    let get = || async {
+        api.get_opt(&target.name)
+            .await?
+            .ok_or_else(|| ActivateError::NotFound(target.clone()))
+    };
+    let runner = get().await?;
+    if is_ready(&runner) {
+        return Ok(true);
+    }
+    if runner.spec.is_suspended() {
+        tracing::info!(runner = %target, "resuming a suspended runner");
+        // `false` rather than removing the field: a resumed runner keeps
+        // the marker that it was suspended, which stops it from claiming a
+        // warm pod under a new URL.
+        api.patch_json(&target.name, patch![add!(["spec", "suspended"] => false)])
+            .await?;
+    }
+    let wait = async {
+        loop {
+            tokio::time::sleep(poll_interval).await;
+            if is_ready(&get().await?) {
+                return Ok(());
+            }
+        }
+    };
+    match tokio::time::timeout(timeout, wait).await {
+        Ok(result) => result.map(|()| false),
+        Err(_) => Err(ActivateError::Timeout(target.clone())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kubimo::k8s_openapi::apimachinery::pkg::apis::meta::v1::Time;
+    use kubimo::k8s_openapi::jiff::Timestamp;
+    use kubimo::{RunnerSpec, RunnerStatus};
+
+    #[test]
+    fn the_runner_comes_from_the_upstream_host() {
+        assert_eq!(
+            RunnerRef::from_host("bmor-x.team-a:8080"),
+            Some(RunnerRef {
+                name: "bmor-x".into(),
+                namespace: "team-a".into(),
+            })
+        );
+        // A name may hold dots; a namespace may not.
+        assert_eq!(
+            RunnerRef::from_host("bmor.v2.team-a").map(|runner| runner.name),
+            Some("bmor.v2".into())
+        );
+        assert_eq!(RunnerRef::from_host("localhost"), None);
+        assert_eq!(RunnerRef::from_host(".team-a"), None);
+    }
+
+    fn condition(type_: &str, status: &str, at: i64) -> Condition {
+        Condition {
+            type_: type_.into(),
+            status: status.into(),
+            reason: String::new(),
+            message: String::new(),
+            observed_generation: None,
+            last_transition_time: Time(Timestamp::from_second(at).unwrap()),
+        }
+    }
+
+    fn runner(suspended: bool, conditions: Vec<Condition>) -> Runner {
+        let mut runner = Runner::new(
+            "bmor-x",
+            RunnerSpec {
+                suspended: Some(suspended),
+                ..Default::default()
+            },
+        );
+        runner.status = Some(RunnerStatus {
+            conditions: Some(conditions),
+            ..Default::default()
+        });
+        runner
+    }
+
+    #[test]
+    fn only_a_pod_ready_since_the_resume_counts() {
+        let before = condition(POD_READY, "True", 100);
+        let after = condition(POD_READY, "True", 300);
+        let resumed = condition(SUSPENDED, "False", 200);
+        assert!(is_ready(&runner(false, vec![before.clone()])));
+        assert!(!is_ready(&runner(true, vec![before.clone()])));
+        assert!(!is_ready(&runner(false, vec![before, resumed.clone()])));
+        assert!(is_ready(&runner(false, vec![after.clone(), resumed])));
+        assert!(!is_ready(&runner(
+            false,
+            vec![after, condition(SUSPENDED, "True", 200)]
+        )));
+        assert!(!is_ready(&runner(
+            false,
+            vec![condition(POD_READY, "False", 300)]
+        )));
+    }
+}
@@ -0,0 +1,186 @@
+//! kubimo activator.
+//!
+//! Stands in for runners with no pod to serve them. While a runner is
+//! suspended, or its pod is not ready, the controller keeps its Ingress, but
+//! points it here instead of at a pod (see `apply_activator.rs` in the
+//! controller), so a bookmarked runner URL wakes the runner instead of
+//! answering 404. Each request is held while the runner resumes and its pod
+//! becomes ready, then redirected to the URL it asked for, which by then
+//! routes to the runner again.
+//!
+//! The runner is read from the Host, which any client could set, so the chart
+//! lets nothing but the ingress controller reach this (see
+//! `templates/activator.yaml`).
+//!
+//! A redirect rather than a proxy: once the runner is back, the activator has
+//! nothing left to add, and the kernel websocket that follows should not hang
+//! off a process that exists to be idle.
+
+mod activate;
+
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use bytes::Bytes;
+use clap::Parser;
+use http_body_util::Full;
+use hyper::body::Incoming;
+use hyper::header::{CACHE_CONTROL, HOST, LOCATION, RETRY_AFTER};
+use hyper::{Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use tracing_subscriber::{EnvFilter, fmt, prelude::*};
+
+use activate::{ActivateError, RunnerRef};
+
+const MANAGER: &str = "kubimo-activator";
+
+#[derive(Parser, Debug)]
+#[command(name = "kubimo-activator")]
+struct Args {
+    #[arg(long, env = "KUBIMO_ACTIVATOR_BIND", default_value = "0.0.0.0:8080")]
+    bind: SocketAddr,
+    /// How long a request is held while its runner starts. Past it the client
+    /// is told to retry: a cold start that has not finished by then is
+    /// usually waiting on a node, and the next request waits afresh.
+    #[arg(
+        long,
+        env = "KUBIMO_ACTIVATOR_READY_TIMEOUT_SECS",
+        default_value_t = 120
+    )]
+    ready_timeout_secs: u64,
+    /// How often a held request re-reads its runner.
+    #[arg(
+        long,
+        env = "KUBIMO_ACTIVATOR_POLL_INTERVAL_MS",
+        default_value_t = 1000
+    )]
+    poll_interval_ms: u64,
+    /// How long to hold a request for a runner that was already ready when it
+    /// arrived. Such a request got here because the ingress controller has
+    /// not yet reloaded the runner's own route; redirecting it at once would
+    /// only bring it straight back.
+    #[arg(long, env = "KUBIMO_ACTIVATOR_SETTLE_MS", default_value_t = 1000)]
+    settle_ms: u64,
+}
+
+struct State {
+    client: kubimo::Client,
+    ready_timeout: Duration,
+    poll_interval: Duration,
+    settle: Duration,
+}
+
+fn response(status: StatusCode, body: &'static str) -> Response<Full<Bytes>> {
+    let mut response = Response::new(Full::new(Bytes::from_static(body.as_bytes())));
+    *response.status_mut() = status;
+    // Every answer here is about a moment in the runner's life; none of it
+    // may be replayed from a cache once the runner serves again.
+    response
+        .headers_mut()
+        .insert(CACHE_CONTROL, "no-store".parse().unwrap());
+    response
+}
+
+/// Send the client back to the URL it asked for. A 307 keeps the method and
+/// body, so a request other than a page load survives the detour too.
+fn redirect(request: &Request<Incoming>) -> Response<Full<Bytes>> {
+    let location = request
+        .uri()
+        .path_and_query()
+        .map_or("/", |path| path.as_str());
+    let mut response = response(StatusCode::TEMPORARY_REDIRECT, "");
+    match location.parse() {
+        Ok(location) => {
+            response.headers_mut().insert(LOCATION, location);
+            response
+        }
+        Err(_) => self::response(StatusCode::BAD_REQUEST, "invalid request path\n"),
+    }
+}
+
+async fn handle(
+    state: Arc<State>,
+    request: Request<Incoming>,
+) -> Result<Response<Full<Bytes>>, Infallible> {
+    let host = request
+        .headers()
+        .get(HOST)
+        .and_then(|host| host.to_str().ok())
+        .or_else(|| request.uri().host());
+    let Some(target) = host.and_then(RunnerRef::from_host) else {
+        // Probes reach the pod by IP, so they never carry a runner host.
+        return Ok(match request.uri().path() {
+            "/healthz" => response(StatusCode::OK, "ok\n"),
+            _ => response(StatusCode::NOT_FOUND, "no runner in the request host\n"),
+        });
+    };
+    let activated = activate::activate(
+        &state.client,
+        &target,
+        state.ready_timeout,
+        state.poll_interval,
+    )
+    .await;
+    Ok(match activated {
+        Ok(already_ready) => {
+            if already_ready {
+                tokio::time::sleep(state.settle).await;
+            }
+            redirect(&request)
+        }
+        Err(ActivateError::NotFound(_)) => response(StatusCode::NOT_FOUND, "runner not found\n"),
+        Err(err @ ActivateError::Timeout(_)) => {
+            tracing::warn!("{err}");
+            let mut response = response(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "the runner is still starting; retry shortly\n",
+            );
+            response
+                .headers_mut()
+                .insert(RETRY_AFTER, "5".parse().unwrap());
+            response
+        }
+        Err(err) => {
+            tracing::error!(runner = %target, "could not activate a runner: {err}");
+            response(StatusCode::BAD_GATEWAY, "could not start the runner\n")
+        }
+    })
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    tracing_subscriber::registry()
+        .with(fmt::layer())
+        .with(EnvFilter::from_default_env())
+        .init();
+    let args = Args::parse();
+    let state = Arc::new(State {
+        client: kubimo::Client::builder().name(MANAGER).build().await?,
+        ready_timeout: Duration::from_secs(args.ready_timeout_secs),
+        poll_interval: Duration::from_millis(args.poll_interval_ms),
+        settle: Duration::from_millis(args.settle_ms),
+    });
+    let listener = tokio::net::TcpListener::bind(args.bind).await?;
+    tracing::info!("Listening on {}", args.bind);
+    loop {
+        let (stream, _) = tokio::select! {
+            accepted = listener.accept() => accepted?,
+            _ = tokio::signal::ctrl_c() => {
+                tracing::info!("Shutting down activator...");
+                return Ok(());
+            }
+        };
+        let state = state.clone();
+        tokio::spawn(async move {
+            let service = hyper::service::service_fn(move |request| handle(state.clone(), request));
+            if let Err(err) = hyper::server::conn::http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+            {
+                tracing::debug!("connection closed with an error: {err}");
+            }
+        });
+    }
+}
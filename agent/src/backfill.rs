@@ -0,0 +1,616 @@
+//! Finishing a lazily hydrated slot after its runner has started.
+//!
+//! With `spec.indexer.hydration` set, `NodePublishVolume` restores only what
+//! the runner reads as it starts — notebooks, `pyproject.toml`, `uv.lock` and
+//! small files — and the rest is restored here, by the slot's watcher before it
+//! starts watching. What [`crate::hydrate`] guarantees of the whole tree still
+//! holds file by file: each deferred file is downloaded into [`BACKFILL_DIR`],
+//! which only root can enter, and renamed into place once its checksum
+//! matches. A path holds the archived file or nothing; marimo never sees one
+//! half-written.
+//!
+//! While that directory exists the slot is incomplete, and nothing may upload
+//! it: a walk would find the deferred files missing and sweep them from the
+//! archive, which is their only copy. The watcher does not start watching
+//! until it is gone, and a flush refuses while it is there. It lives in the
+//! slot rather than in the agent's memory, so an agent restart picks the
+//! restore back up at the next publish instead of forgetting it, and a slot
+//! that never finishes is never flushed — so it is kept, not evicted.
+//!
+//! Order matters more than throughput, since the runner is already serving. A
+//! path written to [`kubimo::pool::HYDRATE_REQUESTS_RELATIVE_PATH`] goes next;
+//! then files a notebook names; then the smallest, so the count falls fast.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Read as _;
+use std::os::unix::fs::{DirBuilderExt as _, MetadataExt as _, OpenOptionsExt as _};
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use indexer::restore::{PendingFiles, RestoreError, RestoreFile, RestoreOptions};
+use indexer::s3::{RangedDownload, S3Client};
+use kubimo::{WorkspaceHydrationStatus, WorkspaceRestoreSecrets};
+use tokio::task::JoinSet;
+
+use crate::csi::{SLOT_GID, SLOT_UID};
+use crate::hydrate::{ArchiveLocation, WORKSPACE_SUBDIR};
+
+/// Directory in the slot root holding the list of files still to restore and
+/// the ones being downloaded. Its presence is what marks the slot incomplete.
+pub const BACKFILL_DIR: &str = ".hydrating";
+
+/// The list itself: workspace-relative paths, as JSON.
+const PENDING_FILE: &str = "pending";
+
+/// Where a request is moved to be read, out of the runner's reach.
+const TAKEN_REQUESTS_FILE: &str = "requests";
+
+/// The workspace as the runner sees it, for requests that name a file the way
+/// a notebook would.
+const POD_WORKSPACE_DIR: &str = "/home/me/workspace";
+
+/// Files downloaded at once. Fewer than a full hydration's: the runner shares
+/// the node's bandwidth, and a request should not queue behind many others.
+const CONCURRENCY: usize = 4;
+
+/// How often progress is reported and the pending list rewritten. A restart
+/// in between re-checks at most this much work, which is skipped anyway once
+/// its path exists.
+const REPORT_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How often to look for a request while every download slot is busy.
+const REQUEST_POLL: Duration = Duration::from_millis(500);
+
+/// Pause before a pass over files that failed, or after the archive could
+/// not be read at all.
+const RETRY_DELAY: Duration = Duration::from_secs(30);
+
+const MAX_REQUESTS_BYTES: u64 = 64 * 1024;
+const MAX_NOTEBOOK_BYTES: u64 = 4 * 1024 * 1024;
+
+#[derive(Debug, thiserror::Error)]
+enum BackfillError {
+    #[error("reading the archive: {0}")]
+    Restore(#[from] RestoreError),
+    #[error("recording what is still to restore: {0}")]
+    Pending(#[from] std::io::Error),
+}
+
+/// Whether `slot_dir` still has files to restore.
+///
+/// Only a root-owned directory counts, and a symlink never does: the slot
+/// root is the runner's to write, and a `.hydrating` it made itself would
+/// otherwise hold its own uploads off for good.
+pub fn is_pending(slot_dir: &Path) -> bool {
+    std::fs::symlink_metadata(slot_dir.join(BACKFILL_DIR))
+        .is_ok_and(|meta| meta.is_dir() && meta.uid() == 0)
+}
+
+/// Record `files` as still to restore. Called before the runner starts, so
+/// the slot is marked before anything could upload it.
+pub fn begin(slot_dir: &Path, files: &[RestoreFile]) -> std::io::Result<()> {
+    let dir = slot_dir.join(BACKFILL_DIR);
+    std::fs::DirBuilder::new().mode(0o700).create(&dir)?;
+    write_pending(&dir, files.iter().map(|file| file.path.as_path()))
+}
+
+fn write_pending<'a>(dir: &Path, paths: impl Iterator<Item = &'a Path>) -> std::io::Result<()> {
+    let paths = paths.collect::<Vec<_>>();
+    let tmp = dir.join(format!("{PENDING_FILE}.tmp"));
+    std::fs::write(&tmp, serde_json::to_vec(&paths)?)?;
+    std::fs::rename(&tmp, dir.join(PENDING_FILE))
+}
+
+fn read_pending(dir: &Path) -> std::io::Result<BTreeSet<PathBuf>> {
+    match std::fs::read(dir.join(PENDING_FILE)) {
+        Ok(bytes) => Ok(serde_json::from_slice(&bytes)?),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(BTreeSet::new()),
+        Err(err) => Err(err),
+    }
+}
+
+/// A slot to finish, and where from.
+pub struct Backfill<'a> {
+    pub slot_dir: &'a Path,
+    pub workspace: &'a str,
+    /// The slot's id, which the progress report names.
+    pub slot: &'a str,
+    pub archive: &'a ArchiveLocation,
+    /// Reads the workspace's download settings.
+    pub client: &'a kubimo::Client,
+    /// Writes `status.hydration`. `None` restores without reporting.
+    pub status: Option<&'a kubimo::Client>,
+    pub s3: &'a S3Client,
+}
+
+impl Backfill<'_> {
+    /// Restore every file still pending, retrying failures until none is
+    /// left, then drop the marker. Returns at once for a complete slot.
+    ///
+    /// Never gives up: a slot with files missing must not be uploaded, so
+    /// the only way out short of finishing is the caller aborting the task.
+    pub async fn run(&self) {
+        if !is_pending(self.slot_dir) {
+            return;
+        }
+        tracing::info!(
+            workspace = self.workspace,
+            "restoring the rest of the slot in the background"
+        );
+        let mut progress = Progress::default();
+        loop {
+            match self.pass(&mut progress).await {
+                Ok(true) => break,
+                Ok(false) => {}
+                Err(err) => {
+                    tracing::warn!(%err, workspace = self.workspace, "background restore pass failed")
+                }
+            }
+            tokio::time::sleep(RETRY_DELAY).await;
+        }
+        let marker = self.slot_dir.join(BACKFILL_DIR);
+        if let Err(err) = std::fs::remove_dir_all(&marker) {
+            // The slot is whole, but still marked otherwise: it is never
+            // uploaded, and its edits stay on this node until this is fixed.
+            tracing::error!(%err, workspace = self.workspace,
+                "could not clear the background restore marker; the slot will not be synced");
+            return;
+        }
+        self.report(&progress, &BTreeMap::new(), 0).await;
+        tracing::info!(
+            workspace = self.workspace,
+            files = progress.restored_files,
+            bytes = progress.restored_bytes,
+            "background restore complete"
+        );
+    }
+
+    /// One pass over what is pending. `Ok(true)` once nothing is.
+    async fn pass(&self, progress: &mut Progress) -> Result<bool, BackfillError> {
+        let marker = self.slot_dir.join(BACKFILL_DIR);
+        let pending_paths = read_pending(&marker)?;
+        if pending_paths.is_empty() {
+            return Ok(true);
+        }
+        let directory = self.slot_dir.join(WORKSPACE_SUBDIR);
+        let options = RestoreOptions {
+            bucket: self.archive.bucket.clone(),
+            key_prefix: self.archive.key_prefix.clone(),
+            directory: directory.clone(),
+            max_download_concurrency: CONCURRENCY,
+            ranged: self.ranged().await,
+            best_effort: true,
+            // Unused: secrets are never deferred.
+            secrets: WorkspaceRestoreSecrets::NamesOnly,
+            point: None,
+        };
+        let PendingFiles { files, data_key } =
+            indexer::restore::plan_files(&options, self.s3).await?;
+        let notebooks = read_notebooks(&directory, &files);
+        // A path the archive no longer names has nothing left to restore.
+        let files = files
+            .into_iter()
+            .filter(|file| pending_paths.contains(&file.path))
+            .collect::<Vec<_>>();
+        let mut pending = files
+            .iter()
+            .map(|file| (file.path.clone(), file.size.unwrap_or_default()))
+            .collect::<BTreeMap<_, _>>();
+        let mut queue = Queue::new(files, &notebooks);
+        let mut in_flight = JoinSet::new();
+        let mut failed = 0;
+        let mut reported: Option<Instant> = None;
+        loop {
+            queue.prioritize(&take_requests(self.slot_dir));
+            while in_flight.len() < CONCURRENCY
+                && let Some(file) = queue.pop()
+            {
+                let s3 = self.s3.clone();
+                let staging = marker.clone();
+                let data_key = data_key.clone();
+                let ranged = options.ranged;
+                in_flight.spawn(async move {
+                    let staged = indexer::restore::stage_file(
+                        &s3,
+                        &staging,
+                        &file,
+                        data_key.as_ref(),
+                        ranged,
+                    )
+                    .await;
+                    (file, staged)
+                });
+            }
+            if reported.is_none_or(|at| at.elapsed() >= REPORT_INTERVAL) {
+                write_pending(&marker, pending.keys().map(PathBuf::as_path))?;
+                self.report(progress, &pending, failed).await;
+                reported = Some(Instant::now());
+            }
+            let joined = tokio::select! {
+                joined = in_flight.join_next() => joined,
+                () = tokio::time::sleep(REQUEST_POLL) => continue,
+            };
+            let Some(joined) = joined else {
+                break;
+            };
+            // A download that panicked stays pending and is retried.
+            let Ok((file, staged)) = joined else {
+                failed += 1;
+                continue;
+            };
+            let staged = match staged {
+                Ok(staged) => staged,
+                Err(err) => {
+                    tracing::warn!(%err, workspace = self.workspace, path = %file.path.display(),
+                        "could not restore a file in the background; will retry");
+                    failed += 1;
+                    continue;
+                }
+            };
+            match move_into_place(&staged, &directory, &file.path) {
+                Ok(Placed::Moved) => {
+                    progress.restored_files += 1;
+                    progress.restored_bytes += file.size.unwrap_or_default();
+                    pending.remove(&file.path);
+                }
+                Ok(Placed::Superseded) => {
+                    tracing::info!(workspace = self.workspace, path = %file.path.display(),
+                        "the runner changed this path first; keeping its version");
+                    let _ = std::fs::remove_file(&staged);
+                    pending.remove(&file.path);
+                }
+                Err(err) => {
+                    tracing::warn!(%err, workspace = self.workspace, path = %file.path.display(),
+                        "could not move a restored file into place; will retry");
+                    let _ = std::fs::remove_file(&staged);
+                    failed += 1;
+                }
+            }
+        }
+        write_pending(&marker, pending.keys().map(PathBuf::as_path))?;
+        if !pending.is_empty() {
+            self.report(progress, &pending, failed).await;
+        }
+        Ok(pending.is_empty())
+    }
+
+    /// Unlike a hydration, which only has the volume's attributes, the
+    /// backfill can read the workspace, so large files are fetched the way
+    /// its `limits` ask.
+    async fn ranged(&self) -> RangedDownload {
+        match self
+            .client
+            .api::<kubimo::Workspace>()
+            .get_opt(self.workspace)
+            .await
+        {
+            Ok(found) => found
+                .and_then(|found| found.spec.indexer)
+                .and_then(|indexer| indexer.limits)
+                .as_ref()
+                .map(RangedDownload::from)
+                .unwrap_or_default(),
+            Err(err) => {
+                tracing::warn!(%err, workspace = self.workspace, "could not read download limits; using the defaults");
+                RangedDownload::default()
+            }
+        }
+    }
+
+    async fn report(&self, progress: &Progress, pending: &BTreeMap<PathBuf, u64>, failed: u64) {
+        let Some(client) = self.status else {
+            return;
+        };
+        let mut patch = kubimo::Workspace::new(self.workspace, Default::default());
+        patch.status = Some(kubimo::WorkspaceStatus {
+            hydration: Some(WorkspaceHydrationStatus {
+                slot: Some(self.slot.to_string()),
+                pending_files: Some(pending.len() as u64),
+                pending_bytes: Some(pending.values().sum()),
+                restored_files: Some(progress.restored_files),
+                restored_bytes: Some(progress.restored_bytes),
+                failed_files: Some(failed),
+            }),
+            ..Default::default()
+        });
+        if let Err(err) = client.api::<kubimo::Workspace>().patch_status(&patch).await {
+            tracing::warn!(%err, workspace = self.workspace, "could not report background restore progress");
+        }
+    }
+}
+
+/// Restored since this agent took the slot up.
+#[derive(Debug, Default)]
+struct Progress {
+    restored_files: u64,
+    restored_bytes: u64,
+}
+
+/// One pass's files, the next one last.
+struct Queue(Vec<RestoreFile>);
+
+impl Queue {
+    /// Files a notebook names by path before the rest, then smaller before
+    /// larger: a notebook that reads `data/sales.csv` as it opens is waiting
+    /// for exactly that file.
+    fn new(mut files: Vec<RestoreFile>, notebooks: &[String]) -> Self {
+        files.sort_by_cached_key(|file| {
+            let named = file
+                .path
+                .to_str()
+                .is_some_and(|path| notebooks.iter().any(|text| text.contains(path)));
+            (named, std::cmp::Reverse(file.size.unwrap_or(u64::MAX)))
+        });
+        Self(files)
+    }
+
+    /// Move every file `requested` covers ahead of the rest, keeping their
+    /// order among themselves.
+    fn prioritize(&mut self, requested: &[PathBuf]) {
+        if requested.is_empty() {
+            return;
+        }
+        let (wanted, mut rest): (Vec<_>, Vec<_>) = std::mem::take(&mut self.0)
+            .into_iter()
+            .partition(|file| requested.iter().any(|path| file.path.starts_with(path)));
+        rest.extend(wanted);
+        self.0 = rest;
+    }
+
+    fn pop(&mut self) -> Option<RestoreFile> {
+        self.0.pop()
+    }
+}
+
+/// The text of every notebook already in place, for [`Queue::new`].
+fn read_notebooks(directory: &Path, files: &[RestoreFile]) -> Vec<String> {
+    files
+        .iter()
+        .filter(|file| file.notebook)
+        .filter_map(|file| read_untrusted(&directory.join(&file.path), MAX_NOTEBOOK_BYTES).ok())
+        .collect()
+}
+
+/// Take whatever the runner has asked for since the last look.
+///
+/// Moved into [`BACKFILL_DIR`] before it is read, so a request written while
+/// this runs lands in a fresh file rather than being deleted unread.
+fn take_requests(slot_dir: &Path) -> Vec<PathBuf> {
+    let taken = slot_dir.join(BACKFILL_DIR).join(TAKEN_REQUESTS_FILE);
+    if std::fs::rename(
+        slot_dir.join(kubimo::pool::HYDRATE_REQUESTS_RELATIVE_PATH),
+        &taken,
+    )
+    .is_err()
+    {
+        return Vec::new();
+    }
+    let text = read_untrusted(&taken, MAX_REQUESTS_BYTES).unwrap_or_default();
+    let _ = std::fs::remove_file(&taken);
+    parse_requests(&text)
+}
+
+fn parse_requests(text: &str) -> Vec<PathBuf> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .filter_map(|line| {
+            let path = Path::new(line);
+            let path = path.strip_prefix(POD_WORKSPACE_DIR).unwrap_or(path);
+            path.is_relative().then(|| path.to_path_buf())
+        })
+        .collect()
+}
+
+/// Read at most `limit` bytes of a file the runner controls, as root.
+///
+/// Refuses a symlink, which could point anywhere on the node, and anything
+/// but a regular file; `O_NONBLOCK` keeps a FIFO planted there from hanging
+/// the open.
+fn read_untrusted(path: &Path, limit: u64) -> std::io::Result<String> {
+    let file = std::fs::File::options()
+        .read(true)
+        .custom_flags(libc::O_NOFOLLOW | libc::O_NONBLOCK)
+        .open(path)?;
+    if !file.metadata()?.is_file() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "not a regular file",
+        ));
+    }
+    let mut text = String::new();
+    file.take(limit).read_to_string(&mut text)?;
+    Ok(text)
+}
+
+#[derive(Debug, PartialEq)]
+enum Placed {
+    Moved,
+    /// Something the runner made is already there, or a directory on the way
+    /// is gone or no longer a directory. Its version is newer than the
+    /// archive's and is left alone.
+    Superseded,
+}
+
+/// Move `staged` to `path` under `directory` and hand it to the runner's uid.
+///
+/// The runner is live, so the tree may not be the one the restore laid out.
+/// Each directory on the way is opened without following symlinks — one
+/// swapped for a link to `/etc` must not have root write through it — and the
+/// rename never replaces what is at the end: a file the runner wrote there in
+/// the meantime is newer than the archive's.
+fn move_into_place(staged: &Path, directory: &Path, path: &Path) -> std::io::Result<Placed> {
+    use rustix::fs::{AtFlags, Mode, OFlags, RenameFlags};
+    use rustix::io::Errno;
+
+    let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("{} has no file name", path.display()),
+        ));
+    };
+    let flags = OFlags::DIRECTORY | OFlags::NOFOLLOW | OFlags::CLOEXEC | OFlags::RDONLY;
+    let superseded = |err: Errno| match err {
+        Errno::NOENT | Errno::NOTDIR | Errno::LOOP | Errno::EXIST => Ok(Placed::Superseded),
+        err => Err(std::io::Error::from(err)),
+    };
+    let mut dir = match rustix::fs::open(directory, flags, Mode::empty()) {
+        Ok(dir) => dir,
+        Err(err) => return superseded(err),
+    };
+    for component in parent.components() {
+        let Component::Normal(part) = component else {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("unsafe path {}", path.display()),
+            ));
+        };
+        dir = match rustix::fs::openat(&dir, part, flags, Mode::empty()) {
+            Ok(dir) => dir,
+            Err(err) => return superseded(err),
+        };
+    }
+    if let Err(err) =
+        rustix::fs::renameat_with(rustix::fs::CWD, staged, &dir, name, RenameFlags::NOREPLACE)
+    {
+        return superseded(err);
+    }
+    rustix::fs::chownat(
+        &dir,
+        name,
+        Some(rustix::fs::Uid::from_raw(SLOT_UID)),
+        Some(rustix::fs::Gid::from_raw(SLOT_GID)),
+        AtFlags::SYMLINK_NOFOLLOW,
+    )?;
+    Ok(Placed::Moved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(path: &str, size: u64) -> RestoreFile {
+        RestoreFile {
+            path: PathBuf::from(path),
+            url: "s3://bucket/0123456789abc".parse().unwrap(),
+            size: Some(size),
+            crc32: None,
+            modified: None,
+            encrypted: false,
+            codec: None,
+            chunks: None,
+            notebook: false,
+        }
+    }
+
+    fn order(mut queue: Queue) -> Vec<PathBuf> {
+        std::iter::from_fn(|| queue.pop())
+            .map(|file| file.path)
+            .collect()
+    }
+
+    #[test]
+    fn requests_come_first_then_what_notebooks_name_then_small_files() {
+        let files = || {
+            vec![
+                file("big.parquet", 300),
+                file("data/sales.csv", 200),
+                file("small.bin", 100),
+                file("models/a.pt", 400),
+                file("models/b.pt", 500),
+            ]
+        };
+        let notebooks = vec!["df = pd.read_csv(\"data/sales.csv\")".to_string()];
+        let queue = Queue::new(files(), &notebooks);
+        assert_eq!(
+            order(queue),
+            [
+                "data/sales.csv",
+                "small.bin",
+                "big.parquet",
+                "models/a.pt",
+                "models/b.pt"
+            ]
+            .map(PathBuf::from)
+        );
+
+        let mut queue = Queue::new(files(), &notebooks);
+        queue.prioritize(&parse_requests(
+            "\n/home/me/workspace/models\n/etc/shadow\n",
+        ));
+        assert_eq!(
+            order(queue),
+            [
+                "models/a.pt",
+                "models/b.pt",
+                "data/sales.csv",
+                "small.bin",
+                "big.parquet"
+            ]
+            .map(PathBuf::from)
+        );
+    }
+
+    /// A file lands once, at its path, and never on top of something the
+    /// runner wrote there or through a directory it swapped for a symlink.
+    #[test]
+    fn a_restored_file_never_replaces_the_runners_own_or_follows_a_link() {
+        let dir = tempfile::tempdir().unwrap();
+        let workspace = dir.path().join("workspace");
+        let staging = dir.path().join("staging");
+        let elsewhere = dir.path().join("elsewhere");
+        for path in [&workspace.join("data"), &staging, &elsewhere] {
+            std::fs::create_dir_all(path).unwrap();
+        }
+        std::os::unix::fs::symlink(&elsewhere, workspace.join("linked")).unwrap();
+        let stage = |bytes: &[u8]| {
+            let staged = staging.join("staged");
+            std::fs::write(&staged, bytes).unwrap();
+            staged
+        };
+        // Handing the file to the runner's uid needs root; the moves are what
+        // this is about.
+        let moved = move_into_place(&stage(b"archived"), &workspace, Path::new("data/new.csv"));
+        assert!(matches!(moved, Ok(Placed::Moved)) || rustix::process::geteuid().as_raw() != 0);
+        assert_eq!(
+            std::fs::read(workspace.join("data/new.csv")).unwrap(),
+            b"archived"
+        );
+
+        std::fs::write(workspace.join("data/mine.csv"), b"edited").unwrap();
+        assert_eq!(
+            move_into_place(&stage(b"archived"), &workspace, Path::new("data/mine.csv")).unwrap(),
+            Placed::Superseded
+        );
+        assert_eq!(
+            std::fs::read(workspace.join("data/mine.csv")).unwrap(),
+            b"edited"
+        );
+
+        assert_eq!(
+            move_into_place(&stage(b"archived"), &workspace, Path::new("linked/x.csv")).unwrap(),
+            Placed::Superseded
+        );
+        assert!(!elsewhere.join("x.csv").exists());
+    }
+
+    /// The runner can write the slot root, so a marker it made must not
+    /// count; only the agent's own, root-owned one does.
+    #[test]
+    fn only_a_real_marker_directory_holds_uploads_off() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(!is_pending(dir.path()));
+        std::os::unix::fs::symlink(dir.path(), dir.path().join(BACKFILL_DIR)).unwrap();
+        assert!(!is_pending(dir.path()));
+        std::fs::remove_file(dir.path().join(BACKFILL_DIR)).unwrap();
+        begin(dir.path(), &[file("big.parquet", 300)]).unwrap();
+        assert_eq!(
+            is_pending(dir.path()),
+            rustix::process::geteuid().as_raw() == 0
+        );
+        assert_eq!(
+            read_pending(&dir.path().join(BACKFILL_DIR)).unwrap(),
+            BTreeSet::from([PathBuf::from("big.parquet")])
+        );
+    }
+}
@@ -251,6 +251,10 @@ async fn bind(
                 key_prefix: claim.seed_key_prefix.clone(),
             },
             secrets: claim.seed_secrets.unwrap_or_default(),
+            point: indexer::history::RestorePoint::from_parts(
+                claim.seed_generation.clone(),
+                claim.seed_at,
+            ),
         });
     // Hydrating into the live directory is safe: the restore writes file by
     // file and deletes nothing it does not know, marimo's file listing is
@@ -259,6 +263,7 @@ async fn bind(
     let s3 = node.s3_for(pod_namespace, workspace);
     let restored = node
         .hydrate_new_slot(
+            pod_namespace,
             workspace,
             &pool_slot.id,
             &dir,
@@ -334,6 +339,7 @@ async fn bind(
             &pool_slot.volume_id,
             pod_namespace,
             workspace,
+            &pool_slot.id,
             &dir,
             archive,
         )
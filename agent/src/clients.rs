@@ -39,6 +39,23 @@ const INDEXER_MANAGER: &str = "kubimo-indexer";
 /// never overlap, so neither can revoke the other's.
 const AGENT_MANAGER: &str = "kubimo-agent";
 
+/// Field manager for `status.hydration`, the background restore's progress.
+///
+/// Not [`AGENT_MANAGER`] for the same reason that is not the indexer's: the
+/// progress reports mention neither `status.slot` nor `status.archive`, and
+/// under one manager each would relinquish them.
+const HYDRATION_MANAGER: &str = "kubimo-agent-hydration";
+
+/// Events the agent publishes — failed hydrations and flushes, pods evicted
+/// over a stale mount — report as [`AGENT_MANAGER`], one instance per node,
+/// so `kubectl describe` says which node's agent saw the failure.
+pub fn event_recorder(client: &kubimo::Client, node_name: &str) -> kubimo::EventRecorder {
+    kubimo::EventRecorder::new(
+        &kubimo::Client::new(client.kube().clone(), AGENT_MANAGER),
+        Some(node_name.to_string()),
+    )
+}
+
 /// Cache of per-namespace clients, cheap to clone and shared between the CSI
 /// plugin and the reaper.
 #[derive(Clone, Default)]
@@ -71,6 +88,12 @@ impl NamespacedClients {
         self.get_as(namespace, AGENT_MANAGER).await
     }
 
+    /// A client scoped to `namespace` that writes `status.hydration` and
+    /// nothing else.
+    pub async fn get_for_hydration_status(&self, namespace: &str) -> Option<kubimo::Client> {
+        self.get_as(namespace, HYDRATION_MANAGER).await
+    }
+
     async fn get_as(&self, namespace: &str, manager: &'static str) -> Option<kubimo::Client> {
         if !self.enabled {
             return None;
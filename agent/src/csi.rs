@@ -57,6 +57,11 @@ const ATTR_SEED_KEY_PREFIX: &str = "seedKeyPrefix";
 /// which is also what an agent older than this attribute does. A mode name,
 /// never a credential, so it is safe on the Pod object.
 const ATTR_SEED_SECRETS: &str = "seedSecrets";
+/// Restore point within the seed's archive history, from
+/// `spec.restoreFrom.generation` and `spec.restoreFrom.at` (RFC 3339). Absent
+/// means the seed's current manifest.
+const ATTR_SEED_GENERATION: &str = "seedGeneration";
+const ATTR_SEED_AT: &str = "seedAt";
 /// Namespace of the pod being mounted, supplied by kubelet because the
 /// `CSIDriver` sets `podInfoOnMount`.
 ///
@@ -158,6 +163,8 @@ pub struct KubimoNode {
     /// downstream — the indexer's `WorkspaceDirectory` writes, `status.storage`
     /// patches, and every existence check — depends on getting this right.
     clients: crate::clients::NamespacedClients,
+    /// `None` without cluster access, like [`Self::clients`].
+    events: Option<kubimo::EventRecorder>,
     /// Continuous sync task per *`(namespace, workspace)`*, not per published
     /// volume.
     ///
@@ -190,8 +197,15 @@ struct Watcher {
 /// The agent writes as root, so without this the runner (uid 1000) cannot
 /// modify its own files. `fsGroup` cannot do this job: on a shared node volume
 /// kubelet would apply it to every slot on the node, not just this one.
+///
+/// The background restore's directory is left to root: what is in it is not
+/// yet the runner's to read.
 pub(crate) fn chown_tree(dir: &Path) -> std::io::Result<()> {
-    for entry in walkdir(dir)? {
+    let backfill = dir.join(crate::backfill::BACKFILL_DIR);
+    for entry in walkdir(dir)?
+        .into_iter()
+        .filter(|entry| !entry.starts_with(&backfill))
+    {
         // `lchown`, never `chown`: `chown` follows symlinks, and this tree is
         // tenant-controlled. `restore` creates symlinks straight from the
         // manifest without validating their *targets* (only the link path is
@@ -247,6 +261,9 @@ impl KubimoNode {
         allow_unquotaed_slots: bool,
         client: Option<kubimo::Client>,
     ) -> Self {
+        let events = client
+            .as_ref()
+            .map(|client| crate::clients::event_recorder(client, &node_id));
         Self {
             node_id,
             store,
@@ -256,6 +273,7 @@ impl KubimoNode {
             has_env_credentials: std::env::var_os("AWS_ACCESS_KEY_ID").is_some(),
             s3_clients: Default::default(),
             clients: crate::clients::NamespacedClients::new(client.is_some()),
+            events,
             watchers: Default::default(),
         }
     }
@@ -382,6 +400,7 @@ impl KubimoNode {
         volume_id: &str,
         namespace: &str,
         workspace: &str,
+        slot: &crate::slot::SlotId,
         dir: &Path,
         archive: &crate::hydrate::ArchiveLocation,
     ) {
@@ -406,11 +425,14 @@ impl KubimoNode {
                 return;
             }
         }
+        let status = self.clients.get_for_hydration_status(namespace).await;
         let handle = match crate::hydrate::spawn_watcher(
             dir,
             workspace,
+            &slot.to_string(),
             archive,
             client.clone(),
+            status,
             s3,
         )
         .await
@@ -490,6 +512,32 @@ impl KubimoNode {
         }
     }
 
+    /// Publish a Warning on `workspace`, so a failure that would otherwise live
+    /// only in this node's agent log shows up in `kubectl describe`.
+    ///
+    /// Best-effort like the rest of the diagnostics here: without cluster
+    /// access, or when the Workspace cannot be read, the log line the caller
+    /// has already written is all there is.
+    async fn warn_workspace(&self, namespace: &str, workspace: &str, reason: &str, note: String) {
+        let Some(events) = self.events.as_ref() else {
+            return;
+        };
+        let Some(client) = self.client_for(namespace).await else {
+            return;
+        };
+        match client.api::<kubimo::Workspace>().get_opt(workspace).await {
+            Ok(Some(found)) => {
+                events
+                    .publish(&found, kubimo::EventType::Warning, reason, "Sync", note)
+                    .await
+            }
+            Ok(None) => {}
+            Err(err) => {
+                tracing::warn!(%err, workspace, reason, "could not read the workspace to publish an event")
+            }
+        }
+    }
+
     /// Push a published slot's tracked files to S3.
     ///
     /// Never fails the unpublish. The slot keeps its data on disk either way, so
@@ -583,10 +631,32 @@ impl KubimoNode {
                         "flush did not complete, so the slot is being kept; changes since the \
                          last sync exist only on this node"
                     );
+                    self.warn_workspace(
+                        &published.namespace,
+                        workspace,
+                        "FlushFailed",
+                        format!(
+                            "the final flush from node {} did not complete; changes since the \
+                             last sync exist only on that node",
+                            self.node_id
+                        ),
+                    )
+                    .await;
                     return;
                 }
                 Err(err) => {
                     tracing::error!(%err, workspace, "flush failed; slot data is still on disk");
+                    self.warn_workspace(
+                        &published.namespace,
+                        workspace,
+                        "FlushFailed",
+                        format!(
+                            "the final flush from node {} failed: {err}; changes since the last \
+                             sync exist only on that node",
+                            self.node_id
+                        ),
+                    )
+                    .await;
                     return;
                 }
             };
@@ -669,8 +739,10 @@ impl KubimoNode {
     /// Returns whether anything was written. Split out from [`Self::prepare_slot`]
     /// so its failure can be handled in one place: a half-hydrated slot has to be
     /// discarded, or the retry inherits it and publishes it empty.
+    #[allow(clippy::too_many_arguments)]
     pub(crate) async fn hydrate_new_slot(
         &self,
+        namespace: &str,
         workspace: &str,
         slot: &crate::slot::SlotId,
         dir: &Path,
@@ -685,14 +757,32 @@ impl KubimoNode {
         if let Some(archive) = archive {
             // Always `Values`: this is the workspace's *own* archive, so a warm
             // reopen must get its own `.env` back, not placeholders.
-            restored = crate::hydrate::hydrate_slot(
-                dir,
-                archive,
-                s3,
-                kubimo::WorkspaceRestoreSecrets::Values,
-            )
-            .await
-            .map_err(|err| Status::internal(format!("hydrating slot: {err}")))?;
+            let hydrated = match self.eager_files(namespace, workspace).await {
+                Some(eager) => crate::hydrate::hydrate_slot_lazily(dir, archive, s3, eager).await,
+                None => {
+                    crate::hydrate::hydrate_slot(
+                        dir,
+                        archive,
+                        s3,
+                        kubimo::WorkspaceRestoreSecrets::Values,
+                        None,
+                    )
+                    .await
+                }
+            };
+            restored = match hydrated {
+                Ok(restored) => restored,
+                Err(err) => {
+                    self.warn_workspace(
+                        namespace,
+                        workspace,
+                        "HydrationFailed",
+                        format!("restoring a slot on node {}: {err}", self.node_id),
+                    )
+                    .await;
+                    return Err(Status::internal(format!("hydrating slot: {err}")));
+                }
+            };
             tracing::info!(workspace, slot = %slot, hydrated = restored, "slot hydrated");
         }
         // Fall back to the seed only when the workspace's own archive had no
@@ -706,15 +796,61 @@ impl KubimoNode {
         // so reading the seed through `self.s3` sent it to the instance
         // metadata service looking for some, and every clone failed to mount.
         if !restored && let Some(seed) = seed {
-            let seeded = crate::hydrate::hydrate_slot(dir, &seed.location, s3, seed.secrets)
-                .await
-                .map_err(|err| Status::internal(format!("seeding slot: {err}")))?;
+            let seeded = match crate::hydrate::hydrate_slot(
+                dir,
+                &seed.location,
+                s3,
+                seed.secrets,
+                seed.point.as_ref(),
+            )
+            .await
+            {
+                Ok(seeded) => seeded,
+                Err(err) => {
+                    self.warn_workspace(
+                        namespace,
+                        workspace,
+                        "HydrationFailed",
+                        format!("seeding a slot on node {}: {err}", self.node_id),
+                    )
+                    .await;
+                    return Err(Status::internal(format!("seeding slot: {err}")));
+                }
+            };
             tracing::info!(workspace, slot = %slot, seeded, "slot seeded");
             restored |= seeded;
         }
         Ok(restored)
     }
 
+    /// What the runner waits for when `workspace` asks not to wait for every
+    /// file (`spec.indexer.hydration`); `None` to restore them all.
+    ///
+    /// Read from the Workspace rather than carried as a volume attribute, like
+    /// the upload settings in `hydrate::upload_inputs`, and for a further
+    /// reason: only the watcher can finish a partial slot, and it needs the
+    /// same cluster access this read does. Anything short of a clear answer —
+    /// no cluster access, an API error — restores in full, which is slower but
+    /// never wrong.
+    async fn eager_files(
+        &self,
+        namespace: &str,
+        workspace: &str,
+    ) -> Option<indexer::restore::EagerFiles> {
+        let client = self.client_for(namespace).await?;
+        match client.api::<kubimo::Workspace>().get_opt(workspace).await {
+            Ok(found) => found
+                .and_then(|found| found.spec.indexer)
+                .and_then(|indexer| indexer.hydration)
+                .as_ref()
+                .map(indexer::restore::EagerFiles::from),
+            Err(err) => {
+                tracing::warn!(%err, workspace, "could not read the hydration settings; restoring every file");
+                None
+            }
+        }
+    }
+
     /// Resolve the slot for `workspace`, provisioning it on first creation, and
     /// return it together with its directory.
     ///
@@ -810,8 +946,26 @@ impl KubimoNode {
             // arrive as a new `limitBytes` volume attribute on the next publish (see
             // `node_expand_volume`), and skipping existing slots would silently discard
             // them.
-            quota::set_project_limit(self.store.layout().root(), resolved.project_id, limit_bytes)
+            //
+            // Except on a slot a Budget has frozen read-only (see `read_only`):
+            // re-applying the full limit would thaw it on every pod start. The
+            // new limit is what the thaw restores instead.
+            let frozen = self
+                .store
+                .frozen_limit(namespace, workspace)
+                .map_err(|err| Status::internal(format!("reading frozen quota: {err}")))?;
+            if frozen.is_some() {
+                self.store
+                    .mark_frozen(namespace, workspace, limit_bytes)
+                    .map_err(|err| Status::internal(format!("recording frozen quota: {err}")))?;
+            } else {
+                quota::set_project_limit(
+                    self.store.layout().root(),
+                    resolved.project_id,
+                    limit_bytes,
+                )
                 .map_err(|err| Status::internal(format!("setting quota: {err}")))?;
+            }
         } else if !self.allow_unquotaed_slots {
             // Unlike the created-slot case, there is nothing to roll back here:
             // the slot already exists and may hold tenant data, so refusing the
@@ -864,7 +1018,15 @@ impl KubimoNode {
         // genuinely has no archive.
         let s3 = self.s3_for(namespace, workspace);
         let restored = self
-            .hydrate_new_slot(workspace, &resolved.id, dir, archive, seed, s3.as_ref())
+            .hydrate_new_slot(
+                namespace,
+                workspace,
+                &resolved.id,
+                dir,
+                archive,
+                seed,
+                s3.as_ref(),
+            )
             .await?;
         if restored {
             // Restored files land as root; the runner is uid 1000.
@@ -1202,6 +1364,24 @@ impl Node for KubimoNode {
                 ))
             })?,
         };
+        let seed_at = request
+            .volume_context
+            .get(ATTR_SEED_AT)
+            .map(|raw| {
+                kubimo::chrono::DateTime::parse_from_rfc3339(raw)
+                    .map(|at| at.to_utc())
+                    .map_err(|_| {
+                        Status::invalid_argument(format!(
+                            "volume attribute {ATTR_SEED_AT:?} must be an RFC 3339 \
+                             timestamp, got {raw:?}"
+                        ))
+                    })
+            })
+            .transpose()?;
+        let seed_point = indexer::history::RestorePoint::from_parts(
+            request.volume_context.get(ATTR_SEED_GENERATION).cloned(),
+            seed_at,
+        );
         let seed = request.volume_context.get(ATTR_SEED_BUCKET).map(|bucket| {
             crate::hydrate::SeedArchive {
                 location: crate::hydrate::ArchiveLocation {
@@ -1209,6 +1389,7 @@ impl Node for KubimoNode {
                     key_prefix: request.volume_context.get(ATTR_SEED_KEY_PREFIX).cloned(),
                 },
                 secrets: seed_secrets,
+                point: seed_point,
             }
         });
         // Supplied by kubelet because the CSIDriver sets podInfoOnMount. Every
@@ -1265,7 +1446,7 @@ impl Node for KubimoNode {
             &crate::store::PublishedSlot {
                 workspace: workspace.clone(),
                 namespace: namespace.clone(),
-                slot: slot.id,
+                slot: slot.id.clone(),
                 bucket: archive.as_ref().map(|a| a.bucket.clone()),
                 key_prefix: archive.as_ref().and_then(|a| a.key_prefix.clone()),
             },
@@ -1280,6 +1461,7 @@ impl Node for KubimoNode {
                 &request.volume_id,
                 &namespace,
                 &workspace,
+                &slot.id,
                 &slot_dir,
                 archive,
             )
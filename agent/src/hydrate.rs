@@ -10,14 +10,22 @@
 //! The restore itself is the indexer's, not a second implementation: it already
 //! handles manifest parsing, `..`-rejection, symlink write-through, CRC
 //! verification and partial-file cleanup.
+//!
+//! A workspace whose archive is too large to wait for can opt out of the full
+//! wait with `spec.indexer.hydration`; [`hydrate_slot_lazily`] then blocks only
+//! for what the runner reads as it starts, and [`crate::backfill`] keeps the
+//! same guarantee for the rest, one file at a time.
 
 use std::collections::BTreeSet;
 use std::path::{Path, PathBuf};
 
+use indexer::chunking::ChunkingPolicy;
+use indexer::compression::CompressionPolicy;
+use indexer::history::{HistoryRetention, RestorePoint};
 use indexer::object_store;
-use indexer::restore::{RestoreError, RestoreOptions, restore};
-use indexer::s3::{DownloadError, S3Client};
-use kubimo::WorkspaceRestoreSecrets;
+use indexer::restore::{EagerFiles, RestoreError, RestoreOptions, restore, restore_eager};
+use indexer::s3::{DownloadError, RangedDownload, S3Client};
+use kubimo::{WorkspaceArchiveLimits, WorkspaceRestoreSecrets};
 
 /// Notebook directory inside a slot.
 ///
@@ -30,10 +38,6 @@ pub const WORKSPACE_SUBDIR: &str = "workspace";
 /// How many files to transfer concurrently.
 const DOWNLOAD_CONCURRENCY: usize = 16;
 
-/// Matches the standalone indexer's default, so an archive written by either
-/// path has the same contents.
-const MAX_FILE_SIZE: u64 = 100 * 1024 * 1024;
-
 #[derive(Debug, thiserror::Error)]
 pub enum HydrateError {
     #[error("creating {path:?}: {source}")]
@@ -45,6 +49,12 @@ pub enum HydrateError {
     Restore(#[from] RestoreError),
     #[error("building archive urls: {0}")]
     ArchiveUrl(#[from] kubimo::url::ParseError),
+    #[error("reading the workspace's archive history settings: {0}")]
+    Workspace(#[from] kubimo::Error),
+    #[error("settling the archive's data key: {0}")]
+    DataKey(#[from] indexer::upload::DataKeyError),
+    #[error("recording the files left to restore: {0}")]
+    Backfill(std::io::Error),
 }
 
 /// Where a workspace's archive lives.
@@ -61,6 +71,9 @@ pub struct ArchiveLocation {
 pub struct SeedArchive {
     pub location: ArchiveLocation,
     pub secrets: WorkspaceRestoreSecrets,
+    /// Seed from this generation of the seed's history instead of its
+    /// current manifest.
+    pub point: Option<RestorePoint>,
 }
 
 /// What the previous upload of this workspace left behind.
@@ -88,6 +101,14 @@ pub struct PreviousUpload {
 /// second set of directory CRs appears for the same paths. The standalone
 /// indexer avoids this by calling `process_existing_dirs` at startup; the agent
 /// has to do the same, once per publish rather than once per process.
+///
+/// History retention, content addressing, compression, chunking and the size
+/// cap are read from the Workspace here rather than passed down as volume
+/// attributes: they are not properties of the slot, and an edit to them should
+/// reach the next flush without a remount. Failing to read them fails the
+/// upload. Uploading without history into an archive that has some would
+/// overwrite objects its generations restore and sweep the ones only they still
+/// name.
 async fn upload_inputs(
     slot_dir: &Path,
     workspace: &str,
@@ -103,10 +124,24 @@ async fn upload_inputs(
     ),
     HydrateError,
 > {
+    let indexer = client
+        .api::<kubimo::Workspace>()
+        .get_opt(workspace)
+        .await?
+        .and_then(|found| found.spec.indexer)
+        .unwrap_or_default();
+    let history = indexer.history.as_ref().map(HistoryRetention::from);
     let options = indexer::upload::UploadOptions {
         include_gitignored: false,
         exclude_hidden: false,
-        max_file_size: MAX_FILE_SIZE,
+        // The same limit the standalone indexer is handed, so an archive
+        // written by either path has the same contents.
+        max_file_size: indexer
+            .limits
+            .as_ref()
+            .map_or(WorkspaceArchiveLimits::DEFAULT_MAX_FILE_SIZE, |limits| {
+                limits.effective_max_file_size()
+            }),
         max_upload_concurrency: DOWNLOAD_CONCURRENCY,
         bucket: Some(archive.bucket.clone()),
         key_prefix: archive.key_prefix.clone(),
@@ -124,6 +159,11 @@ async fn upload_inputs(
         // A busy workspace still syncs at least every 10s.
         watch_max_wait_millis: 10_000,
         watch_poll_millis: 60_000,
+        history,
+        content_addressed: indexer.content_addressed.unwrap_or(false),
+        compression: indexer.compression.as_ref().map(CompressionPolicy::from),
+        chunking: indexer.chunking.as_ref().map(ChunkingPolicy::from),
+        keep_caches: false,
         name: workspace.to_string(),
         directory: slot_dir.join(WORKSPACE_SUBDIR),
     };
@@ -144,11 +184,18 @@ async fn upload_inputs(
         &mut previous.urls,
     )
     .await;
+    let data_key = indexer::upload::resolve_data_key(
+        s3,
+        Some(&archive.bucket),
+        archive.key_prefix.as_deref(),
+        &mut cache_markers,
+    )
+    .await?;
     // Extend rather than replace: this client is shared by every slot on the
     // node, so replacing would drop every other slot's markers.
     s3.extend_cache(cache_markers).await;
 
-    let keys = indexer::upload::WorkspaceKeys::new(names, urls);
+    let keys = indexer::upload::WorkspaceKeys::new(names, urls, data_key);
     Ok((options, keys, previous))
 }
 
@@ -158,17 +205,36 @@ async fn upload_inputs(
 /// change — so the number of watchers on a node equals the number of running
 /// runners, which is what one indexer pod per active workspace already costs
 /// today.
+///
+/// A lazily hydrated slot is finished first ([`crate::backfill`]): watching
+/// it any earlier would upload a tree with its deferred files missing. The
+/// backfill lives and dies with the watcher, so it stops when the last volume
+/// does and resumes with the next publish.
 pub async fn spawn_watcher(
     slot_dir: &Path,
     workspace: &str,
+    slot: &str,
     archive: &ArchiveLocation,
     client: kubimo::Client,
+    status: Option<kubimo::Client>,
     s3: indexer::s3::S3Client,
 ) -> Result<tokio::task::JoinHandle<()>, HydrateError> {
     let (options, keys, previous) =
         upload_inputs(slot_dir, workspace, archive, true, &client, &s3).await?;
     let name = workspace.to_string();
+    let slot_dir = slot_dir.to_path_buf();
+    let slot = slot.to_string();
+    let archive = archive.clone();
     Ok(tokio::spawn(async move {
+        let backfill = crate::backfill::Backfill {
+            slot_dir: &slot_dir,
+            workspace: &name,
+            slot: &slot,
+            archive: &archive,
+            client: &client,
+            status: status.as_ref(),
+            s3: &s3,
+        };
         // Racing the watcher against the workspace's disappearance, rather than
         // relying on the unpublish to stop it. Deleting a workspace purges its
         // S3 prefix, but the runner pod lingers for its termination grace
@@ -176,9 +242,12 @@ pub async fn spawn_watcher(
         // that window recreates the prefix the platform just emptied, with no
         // CR left to find it by. The final flush is guarded separately.
         tokio::select! {
-            () = indexer::upload::watch(
-                &options, &client, &s3, &keys, previous.names, previous.urls,
-            ) => {}
+            () = async {
+                backfill.run().await;
+                indexer::upload::watch(
+                    &options, &client, &s3, &keys, previous.names, previous.urls,
+                ).await
+            } => {}
             () = wait_until_deleted(&client, &name) => {
                 tracing::info!(workspace = %name, "workspace deleted; stopping watcher");
             }
@@ -240,6 +309,12 @@ pub async fn flush_slot(
         // match a slot whose contents were never walked.
         return Ok(None);
     }
+    if crate::backfill::is_pending(slot_dir) {
+        // Files the background restore has not reached are in the archive
+        // and nowhere else; a walk would read them as deleted and sweep them.
+        // The slot stays unflushed, and so on this node, until it is whole.
+        return Ok(None);
+    }
     let (options, keys, previous) =
         upload_inputs(slot_dir, workspace, archive, false, client, s3).await?;
     let result = indexer::upload::run(
@@ -284,7 +359,47 @@ pub async fn hydrate_slot(
     archive: &ArchiveLocation,
     s3: &S3Client,
     secrets: WorkspaceRestoreSecrets,
+    point: Option<&RestorePoint>,
 ) -> Result<bool, HydrateError> {
+    let options = restore_options(slot_dir, archive, secrets, point).await?;
+    match restore(&options, s3).await {
+        Ok(()) => Ok(true),
+        Err(err) if never_indexed(&err) => Ok(false),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// [`hydrate_slot`] for a workspace's own archive, restoring only what
+/// `eager` wants and recording the rest in the slot for [`crate::backfill`].
+///
+/// Never for a seed: a seed is a template, restored once into a workspace
+/// with no archive of its own, and a slot that is still filling in must not
+/// be uploaded — which is the first thing a seeded workspace does.
+pub async fn hydrate_slot_lazily(
+    slot_dir: &Path,
+    archive: &ArchiveLocation,
+    s3: &S3Client,
+    eager: EagerFiles,
+) -> Result<bool, HydrateError> {
+    let options = restore_options(slot_dir, archive, WorkspaceRestoreSecrets::Values, None).await?;
+    match restore_eager(&options, s3, eager).await {
+        Ok(pending) => {
+            if !pending.files.is_empty() {
+                crate::backfill::begin(slot_dir, &pending.files).map_err(HydrateError::Backfill)?;
+            }
+            Ok(true)
+        }
+        Err(err) if never_indexed(&err) => Ok(false),
+        Err(err) => Err(err.into()),
+    }
+}
+
+async fn restore_options(
+    slot_dir: &Path,
+    archive: &ArchiveLocation,
+    secrets: WorkspaceRestoreSecrets,
+    point: Option<&RestorePoint>,
+) -> Result<RestoreOptions, HydrateError> {
     let directory: PathBuf = slot_dir.join(WORKSPACE_SUBDIR);
     tokio::fs::create_dir_all(&directory)
         .await
@@ -292,30 +407,35 @@ pub async fn hydrate_slot(
             path: directory.display().to_string(),
             source,
         })?;
-    let options = RestoreOptions {
+    Ok(RestoreOptions {
         bucket: archive.bucket.clone(),
         key_prefix: archive.key_prefix.clone(),
         directory,
         max_download_concurrency: DOWNLOAD_CONCURRENCY,
+        // The defaults rather than the workspace's `limits`: a slot is
+        // hydrated from the volume's attributes, which carry where the archive
+        // is and nothing about how to fetch it.
+        ranged: RangedDownload::default(),
         // Not best-effort: a slot that is silently missing files looks like the
         // user lost data. Fail the mount instead, so the runner never starts on
         // a partial workspace.
         best_effort: false,
         secrets,
-    };
-    match restore(&options, s3).await {
-        Ok(()) => Ok(true),
-        // A workspace that has never been indexed has no manifest. That is the
-        // normal state for a freshly created workspace, not a failure.
-        //
-        // Only the manifest fetch can surface `NotFound` here: per-file
-        // download errors are counted inside `restore` and come back as
-        // `Failed`, so this cannot quietly swallow missing file content.
-        Err(RestoreError::Download(DownloadError::S3(object_store::Error::NotFound {
-            ..
-        }))) => Ok(false),
-        Err(err) => Err(err.into()),
-    }
+        point: point.cloned(),
+    })
+}
+
+/// A workspace that has never been indexed has no manifest. That is the
+/// normal state for a freshly created workspace, not a failure.
+///
+/// Only the manifest fetch can surface `NotFound` here: per-file download
+/// errors are counted inside the restore and come back as `Failed`, so this
+/// cannot quietly swallow missing file content.
+fn never_indexed(err: &RestoreError) -> bool {
+    matches!(
+        err,
+        RestoreError::Download(DownloadError::S3(object_store::Error::NotFound { .. }))
+    )
 }
 
 #[cfg(test)]
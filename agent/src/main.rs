@@ -5,6 +5,7 @@
 //! project quota, hand it to a runner pod as a bind mount, and reclaim it when
 //! the workspace is done with it.
 
+mod backfill;
 mod claim;
 mod clients;
 mod csi;
@@ -13,6 +14,7 @@ mod hydrate;
 mod kernel;
 mod mount;
 mod quota;
+mod read_only;
 mod reaper;
 mod slot;
 mod store;
@@ -428,11 +430,19 @@ fn serve(
                     node_name.clone(),
                     idle_slot_ttl,
                     client.clone().map(|client| reaper::StaleMountSweep {
+                        events: clients::event_recorder(&client, &sweep_node_name),
                         client,
                         node_name: sweep_node_name,
                         pods_dir: kubelet_pods_dir,
                     }),
                 ));
+                // Budgets freeze pooled workspaces by condition alone; only the
+                // node holding the slot can make that bite. Shares the store,
+                // and so the lock map, for the same reason as the reaper.
+                tokio::spawn(read_only::run(
+                    store.clone(),
+                    clients::NamespacedClients::new(true),
+                ));
             } else {
                 tracing::warn!(
                     "no Kubernetes access; slots for deleted workspaces will not be reclaimed"
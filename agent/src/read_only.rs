@@ -0,0 +1,131 @@
+//! Freezing the slots of workspaces a Budget has made read-only.
+//!
+//! A Budget that stays over its `softStorage` past its grace period marks the
+//! pooled workspaces it governs `ReadOnly`; this is what makes that true. The
+//! slot's project quota is lowered to what the slot already holds, so every
+//! write needing a new block fails with `ENOSPC` while reads, and deletes —
+//! the way back under the limit — keep working. Nothing is remounted and no
+//! pod restarts, which a read-only bind mount would have needed.
+//!
+//! The quota the slot had is kept in a store marker and put back as soon as
+//! the condition clears. Like the reaper, an API error is never read as a
+//! change either way: a slot is only frozen or thawed on a Workspace the API
+//! actually returned.
+
+use std::time::Duration;
+
+use crate::clients::NamespacedClients;
+use crate::quota;
+use crate::store::SlotStore;
+
+/// How often to sync. A freeze follows a grace period of the Budget's
+/// choosing, so a minute either way is noise; a thaw is what the tenant is
+/// waiting on, so it should not take the reaper's five.
+pub const SYNC_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Sync until the process exits.
+pub async fn run(store: SlotStore, clients: NamespacedClients) {
+    loop {
+        tokio::time::sleep(SYNC_INTERVAL).await;
+        match quota::project_quota_enforced(store.layout().root()) {
+            // Without enforcement a frozen limit would not hold anything, and
+            // the slot's status would still claim it did.
+            Ok(false) => continue,
+            Ok(true) => {}
+            Err(err) => {
+                tracing::warn!(%err, "could not check quota support; not syncing read-only slots");
+                continue;
+            }
+        }
+        let workspaces = match store.workspaces() {
+            Ok(workspaces) => workspaces,
+            Err(err) => {
+                tracing::warn!(%err, "could not list slots to sync read-only state");
+                continue;
+            }
+        };
+        for (namespace, workspace) in workspaces {
+            sync(&store, &clients, &namespace, &workspace).await;
+        }
+    }
+}
+
+async fn sync(store: &SlotStore, clients: &NamespacedClients, namespace: &str, workspace: &str) {
+    let Some(client) = clients.get(namespace).await else {
+        return;
+    };
+    let read_only = match client.api::<kubimo::Workspace>().get_opt(workspace).await {
+        Ok(Some(found)) => found.is_read_only(),
+        // The reaper's to deal with.
+        Ok(None) => return,
+        Err(err) => {
+            tracing::warn!(%err, workspace, "could not check workspace; leaving its quota");
+            return;
+        }
+    };
+    // Under the slot's lock: a publish re-applies the quota too, and must see
+    // the marker and the limit agree.
+    let lock = store.lock_for(namespace, workspace);
+    let _guard = lock.lock().await;
+    let result = if read_only {
+        freeze(store, namespace, workspace)
+    } else {
+        thaw(store, namespace, workspace)
+    };
+    if let Err(err) = result {
+        tracing::warn!(%err, workspace, read_only, "could not sync the slot's read-only quota");
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+enum SyncError {
+    #[error(transparent)]
+    Store(#[from] crate::store::StoreError),
+    #[error(transparent)]
+    Quota(#[from] quota::QuotaError),
+    #[error("measuring the slot: {0}")]
+    Usage(#[from] rustix::io::Errno),
+}
+
+/// Lower the slot's quota to what it holds, recording the one it had first.
+///
+/// Re-applied on every pass while frozen rather than once: files deleted in
+/// the meantime lower it further, so a frozen slot cannot refill what it
+/// freed, and an agent killed between recording the marker and applying the
+/// limit catches up here.
+fn freeze(store: &SlotStore, namespace: &str, workspace: &str) -> Result<(), SyncError> {
+    let Some(slot) = store.lookup(namespace, workspace)? else {
+        return Ok(());
+    };
+    // Under `prjquota`, `statvfs` on the slot reports its own quota as the
+    // capacity and its own usage as used.
+    let usage = indexer::disk::disk_usage(store.layout().slot_dir(&slot.id))?;
+    if store.frozen_limit(namespace, workspace)?.is_none() {
+        store.mark_frozen(namespace, workspace, usage.capacity)?;
+        tracing::info!(
+            workspace,
+            used = usage.used,
+            limit = usage.capacity,
+            "freezing a slot read-only"
+        );
+    }
+    if usage.capacity > usage.used {
+        // Never zero: XFS reads a zero limit as none at all.
+        quota::set_project_limit(store.layout().root(), slot.project_id, usage.used.max(1))?;
+    }
+    Ok(())
+}
+
+/// Put back the quota the slot had before it was frozen, if it was.
+fn thaw(store: &SlotStore, namespace: &str, workspace: &str) -> Result<(), SyncError> {
+    let Some(limit) = store.frozen_limit(namespace, workspace)? else {
+        return Ok(());
+    };
+    let Some(slot) = store.lookup(namespace, workspace)? else {
+        return Ok(());
+    };
+    quota::set_project_limit(store.layout().root(), slot.project_id, limit)?;
+    store.clear_frozen(namespace, workspace)?;
+    tracing::info!(workspace, limit, "thawed a read-only slot");
+    Ok(())
+}
@@ -34,6 +34,7 @@ pub const DEFAULT_IDLE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
 /// it cannot list or delete pods without it.
 pub struct StaleMountSweep {
     pub client: kubimo::Client,
+    pub events: kubimo::EventRecorder,
     pub node_name: String,
     pub pods_dir: std::path::PathBuf,
 }
@@ -53,8 +54,13 @@ pub async fn run(
 ) {
     loop {
         if let Some(config) = stale_mounts.as_ref()
-            && let Err(err) =
-                crate::sweep::run(&config.client, &config.node_name, &config.pods_dir).await
+            && let Err(err) = crate::sweep::run(
+                &config.client,
+                &config.events,
+                &config.node_name,
+                &config.pods_dir,
+            )
+            .await
         {
             tracing::warn!(%err, "dead-mount sweep failed");
         }
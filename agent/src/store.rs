@@ -205,6 +205,12 @@ impl SlotStore {
         self.index_dir().join(format!("flushed-{id}"))
     }
 
+    /// Marker recording that this slot is frozen read-only, holding the
+    /// quota (in bytes) to restore when it is thawed.
+    fn frozen_path(&self, id: &SlotId) -> PathBuf {
+        self.index_dir().join(format!("frozen-{id}"))
+    }
+
     fn counter_path(&self) -> PathBuf {
         self.index_dir().join("next-project-id")
     }
@@ -291,6 +297,66 @@ impl SlotStore {
             .and_then(|at| std::time::SystemTime::now().duration_since(at).ok()))
     }
 
+    /// The quota `workspace`'s slot had before it was frozen read-only, or
+    /// `None` when it is not frozen.
+    ///
+    /// Kept on disk rather than in memory: the frozen limit is all the slot's
+    /// quota says afterwards, and an agent restarted while a slot was frozen
+    /// must still know what to give back.
+    pub fn frozen_limit(
+        &self,
+        namespace: &str,
+        workspace: &str,
+    ) -> Result<Option<u64>, StoreError> {
+        validate_workspace_name(namespace)?;
+        validate_workspace_name(workspace)?;
+        let Some(id) = self.lookup_slot_id(namespace, workspace)? else {
+            return Ok(None);
+        };
+        let path = self.frozen_path(&id);
+        let raw = match std::fs::read_to_string(&path) {
+            Ok(raw) => raw,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(io_err(format!("reading {}", path.display()))(err)),
+        };
+        raw.trim().parse().map(Some).map_err(|_| StoreError::Io {
+            context: format!("reading {}", path.display()),
+            source: io::Error::new(io::ErrorKind::InvalidData, "not a byte count"),
+        })
+    }
+
+    /// Record that `workspace`'s slot is frozen, and the quota to restore
+    /// when it is thawed.
+    pub fn mark_frozen(
+        &self,
+        namespace: &str,
+        workspace: &str,
+        limit_bytes: u64,
+    ) -> Result<(), StoreError> {
+        validate_workspace_name(namespace)?;
+        validate_workspace_name(workspace)?;
+        let Some(id) = self.lookup_slot_id(namespace, workspace)? else {
+            return Ok(());
+        };
+        let path = self.frozen_path(&id);
+        std::fs::write(&path, limit_bytes.to_string())
+            .map_err(io_err(format!("writing {}", path.display())))
+    }
+
+    /// Forget that `workspace`'s slot was frozen, once its quota is restored.
+    pub fn clear_frozen(&self, namespace: &str, workspace: &str) -> Result<(), StoreError> {
+        validate_workspace_name(namespace)?;
+        validate_workspace_name(workspace)?;
+        let Some(id) = self.lookup_slot_id(namespace, workspace)? else {
+            return Ok(());
+        };
+        match std::fs::remove_file(self.frozen_path(&id)) {
+            Ok(()) => Ok(()),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(io_err(format!("clearing frozen marker for {id}"))(err)),
+        }
+    }
+
     /// Look up the slot recorded for `workspace` in `namespace`, if any.
     pub fn lookup(
         &self,
@@ -739,6 +805,7 @@ impl SlotStore {
         let _ = std::fs::remove_file(&link);
         let _ = std::fs::remove_file(self.project_id_path(&id));
         let _ = std::fs::remove_file(self.flushed_path(&id));
+        let _ = std::fs::remove_file(self.frozen_path(&id));
         Ok(true)
     }
 
@@ -1625,4 +1692,31 @@ mod tests {
         store.clear_flushed("platform", "bmow-reflush").unwrap();
         assert_eq!(store.flushed_ago("platform", "bmow-reflush").unwrap(), None);
     }
+
+    /// The quota to restore survives with the slot and goes with it: a slot
+    /// re-created after a reclaim starts out thawed, at whatever its publish
+    /// asks for.
+    #[test]
+    fn the_frozen_marker_holds_the_quota_to_restore() {
+        let (_dir, store) = store();
+        store.resolve_or_create("platform", "bmow-frozen").unwrap();
+        assert_eq!(store.frozen_limit("platform", "bmow-frozen").unwrap(), None);
+
+        store
+            .mark_frozen("platform", "bmow-frozen", 2 << 30)
+            .unwrap();
+        assert_eq!(
+            store.frozen_limit("platform", "bmow-frozen").unwrap(),
+            Some(2 << 30)
+        );
+        store.clear_frozen("platform", "bmow-frozen").unwrap();
+        assert_eq!(store.frozen_limit("platform", "bmow-frozen").unwrap(), None);
+
+        store
+            .mark_frozen("platform", "bmow-frozen", 2 << 30)
+            .unwrap();
+        store.remove_slot("platform", "bmow-frozen").unwrap();
+        store.resolve_or_create("platform", "bmow-frozen").unwrap();
+        assert_eq!(store.frozen_limit("platform", "bmow-frozen").unwrap(), None);
+    }
 }
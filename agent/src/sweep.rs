@@ -22,9 +22,10 @@
 
 use std::path::{Path, PathBuf};
 
-use kubimo::Client;
-use kubimo::k8s_openapi::api::core::v1::Pod;
+use kubimo::k8s_openapi::api::core::v1::{ObjectReference, Pod};
+use kubimo::kube::Resource;
 use kubimo::kube::api::{DeleteParams, ListParams};
+use kubimo::{Client, EventRecorder, EventType};
 
 use crate::csi::DRIVER_NAME;
 use crate::mount::{self, MountState};
@@ -69,9 +70,30 @@ fn slot_volume_names(pod: &Pod) -> Vec<String> {
         .unwrap_or_default()
 }
 
+/// What the recycle is reported on: the pod's Runner when it has one, since the pod is
+/// about to be deleted and its events would go with it — and the Runner is what a user
+/// asking "why did my notebook restart" looks at.
+fn event_target(pod: &Pod) -> ObjectReference {
+    pod.metadata
+        .owner_references
+        .iter()
+        .flatten()
+        .find(|owner| owner.controller == Some(true) && owner.kind == "Runner")
+        .map(|owner| ObjectReference {
+            api_version: Some(owner.api_version.clone()),
+            kind: Some(owner.kind.clone()),
+            name: Some(owner.name.clone()),
+            namespace: pod.metadata.namespace.clone(),
+            uid: Some(owner.uid.clone()),
+            ..Default::default()
+        })
+        .unwrap_or_else(|| pod.object_ref(&()))
+}
+
 /// Delete pods on this node whose slot mount is dead. Returns how many were deleted.
 pub async fn run(
     client: &Client,
+    events: &EventRecorder,
     node_name: &str,
     pods_dir: &Path,
 ) -> Result<usize, Box<dyn std::error::Error>> {
@@ -115,7 +137,21 @@ pub async fn run(
                 .delete(name, &DeleteParams::default())
                 .await
             {
-                Ok(_) => deleted += 1,
+                Ok(_) => {
+                    deleted += 1;
+                    events
+                        .publish_to(
+                            &event_target(pod),
+                            EventType::Warning,
+                            "StaleMount",
+                            "Recycle",
+                            format!(
+                                "pod {name} was deleted because its slot mount on node \
+                                 {node_name} is dead; it will be recreated"
+                            ),
+                        )
+                        .await;
+                }
                 // One undeletable pod must not stop the sweep reaching the others.
                 Err(err) => tracing::error!(%err, pod = name, namespace, "could not delete pod"),
             }
@@ -132,6 +168,7 @@ pub async fn run(
 mod tests {
     use super::*;
     use kubimo::k8s_openapi::api::core::v1::{CSIVolumeSource, PodSpec, Volume};
+    use kubimo::k8s_openapi::apimachinery::pkg::apis::meta::v1::OwnerReference;
 
     fn pod_with(volumes: Vec<Volume>) -> Pod {
         Pod {
@@ -182,4 +219,26 @@ mod tests {
         let pod = pod_with(vec![csi_volume("other", "csi.scaleway.com")]);
         assert!(slot_volume_names(&pod).is_empty());
     }
+
+    /// The pod is deleted right after, so an event on it would vanish with it.
+    #[test]
+    fn reports_on_the_runner_that_owns_the_pod() {
+        let mut pod = pod_with(vec![]);
+        pod.metadata.name = Some("notebook".to_string());
+        pod.metadata.namespace = Some("tenant".to_string());
+        assert_eq!(event_target(&pod).kind.as_deref(), Some("Pod"));
+
+        pod.metadata.owner_references = Some(vec![OwnerReference {
+            api_version: "kubimo.aqora.io/v1".to_string(),
+            kind: "Runner".to_string(),
+            name: "notebook".to_string(),
+            uid: "runner-uid".to_string(),
+            controller: Some(true),
+            ..Default::default()
+        }]);
+        let target = event_target(&pod);
+        assert_eq!(target.kind.as_deref(), Some("Runner"));
+        assert_eq!(target.uid.as_deref(), Some("runner-uid"));
+        assert_eq!(target.namespace.as_deref(), Some("tenant"));
+    }
 }
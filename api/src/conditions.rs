@@ -1,4 +1,5 @@
-//! Condition types a `Runner` reports as it starts up.
+//! Condition types a `Runner` reports as it starts up, and the one a
+//! `Workspace` reports besides `Ready`.
 //!
 //! These strings are a public contract, not an implementation detail. Consumers
 //! match on them byte-exactly and treat a *missing* condition as unsatisfied, so
@@ -23,3 +24,67 @@ pub const POD_READY: &str = "PodReady";
 
 /// Every startup condition, in the order they are fulfilled.
 pub const STARTUP_CONDITIONS: [&str; 4] = [PVC_BOUND, WORKSPACE_READY, POD_SCHEDULED, POD_READY];
+
+/// Every file of the runner's workspace is on disk.
+///
+/// Deliberately not a startup condition: with `spec.indexer.hydration` set the
+/// runner serves while large files are still arriving, and holding it at
+/// "starting" until the last of them lands would undo the point of starting
+/// it early. `True` from the outset when the slot was restored in full.
+pub const HYDRATED: &str = "Hydrated";
+
+/// The runner is suspended (`spec.suspended`): it has no pod on purpose.
+///
+/// Not a startup condition either. Absent until the runner is first
+/// suspended, and `False` once it is resumed: its transition time marks the
+/// last suspend or resume, and its presence that the runner's URL and token
+/// predate its current pod.
+pub const SUSPENDED: &str = "Suspended";
+
+/// The runner will be deleted soon by `lifecycle.maxLifetimeSecs` or
+/// `lifecycle.activeHours`; `status.shutdownTime` says when.
+///
+/// Not a startup condition. Absent until a shutdown first comes within the
+/// warning window, and `False` once none is. `False` from the outset, with
+/// reason `InvalidActiveHours`, when the active hours cannot be read.
+pub const SHUTDOWN_SCHEDULED: &str = "ShutdownScheduled";
+
+/// The runner's marimo does not report when it last ran a cell or heard from
+/// the editor, so `lifecycle.inactiveAfterSecsWithoutExecution` cannot be
+/// measured and only open connections keep the runner active.
+///
+/// Not a startup condition. Absent until such a runner is first polled with
+/// that window set, and `False` once its marimo reports activity or the
+/// window is unset.
+pub const ACTIVITY_UNSUPPORTED: &str = "ActivityUnsupported";
+
+/// The runner's pod is failing in a way that needs someone to act: it keeps
+/// running out of memory, its image cannot be pulled, its dependencies do not
+/// install, its workspace cannot be mounted, or it keeps crashing. The reason
+/// says which.
+///
+/// Not a startup condition. Absent until the pod first fails, and `False`
+/// with reason `Recovered` once it no longer does.
+pub const FAILED: &str = "Failed";
+
+/// The runner's budget refused to start it: starting it would take a budget
+/// matching its labels past one of its CPU, memory or runner-count limits.
+/// The message says which.
+///
+/// Not a startup condition. Absent until the runner is first refused, and
+/// `False` with reason `WithinBudget` once it is admitted. Only a runner with
+/// nothing running is ever refused: lowering a limit never stops a runner
+/// that is already up.
+pub const BUDGET_EXCEEDED: &str = "BudgetExceeded";
+
+/// A `Pooled` workspace's slot is frozen at what it already holds: the agent
+/// has lowered the slot's quota to its usage, so writes fail with `ENOSPC`
+/// while reads and deletes keep working. Set by a Budget whose usage has
+/// stayed over its `softStorage` for longer than its grace period; the
+/// message names the Budget.
+///
+/// A `Workspace` condition, unlike the rest. Absent until the workspace is
+/// first frozen, and `False` with reason `WithinBudget` once every Budget
+/// matching it is back under its soft limit, when the agent restores the
+/// quota.
+pub const READ_ONLY: &str = "ReadOnly";
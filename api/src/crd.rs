@@ -11,15 +11,18 @@ use serde::{Deserialize, Serialize};
 use strum::{Display, EnumString};
 use url::Url;
 
-use crate::selector::Selector;
+use crate::conditions;
+use crate::selector::{Expr, Expression, Selector};
 use crate::validation::{
-    budget_selector_not_empty, log_level, pool_command_not_render, pool_immutable_fields,
+    budget_selector_expression_values, budget_selector_not_empty, log_level,
+    pool_autoscale_max_greater_than_min, pool_command_not_render, pool_immutable_fields,
     pool_max_cpu_greater_than_min, pool_max_memory_greater_than_min, pool_python_runtime_uv,
     runner_immutable_fields, runner_max_cpu_greater_than_min, runner_max_memory_greater_than_min,
     workspace_auto_scale_bounds, workspace_clone_not_pooled, workspace_immutable_fields,
     workspace_max_storage_greater_than_min, workspace_mode_no_downgrade,
     workspace_no_new_dedicated, workspace_no_volume_with_name, workspace_python_runtime_exclusive,
     workspace_restore_from_exclusive, workspace_restore_from_not_indexer_prefix,
+    workspace_restore_from_point_exclusive, workspace_snapshot_immutable,
 };
 
 use crate::{
@@ -107,6 +110,9 @@ pub struct WorkspaceIndexerPod {
 #[derive(Clone, Debug, Deserialize, Serialize, JsonSchema, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct WorkspaceIndexer {
+    /// Where the archive lives. A bare name is an S3 bucket; `gs://bucket`,
+    /// `az://container` and `file:///path` select Google Cloud Storage, Azure
+    /// Blob Storage or a directory on the indexer's own disk instead.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub bucket: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -115,6 +121,217 @@ pub struct WorkspaceIndexer {
     pub upload_content: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub pod: Option<WorkspaceIndexerPod>,
+    /// Keep point-in-time copies of the manifest so the archive can be
+    /// restored as it was before a file was changed or deleted. Absent means
+    /// the manifest is overwritten on every sync and nothing older survives.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub history: Option<WorkspaceArchiveHistory>,
+    /// Store file content in the bucket's shared content-addressed store,
+    /// keyed by its sha256, instead of under this workspace's own keys. A file
+    /// any workspace in the bucket already uploaded is not uploaded again, so
+    /// a clone costs its manifest rather than a second copy of every dataset.
+    /// Objects there outlive the workspaces naming them until `indexer gc`
+    /// collects them.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_addressed: Option<bool>,
+    /// Compress file content on its way to the bucket. Absent means every
+    /// object is stored as the file's own bytes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compression: Option<WorkspaceArchiveCompression>,
+    /// Store large files as content-defined chunks, so a change to one part
+    /// of a file re-uploads that part rather than the whole file. Absent
+    /// means every file is one object, and files over `limits.maxFileSize`
+    /// are not archived at all.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chunking: Option<WorkspaceArchiveChunking>,
+    /// Size limits for moving this workspace's files in and out of the
+    /// bucket. Absent means the defaults every indexer and node agent shares.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limits: Option<WorkspaceArchiveLimits>,
+    /// Let a `Pooled` runner start before its whole archive is restored:
+    /// notebooks, the project's dependency files and small files first, the
+    /// rest in the background, tracked by `status.hydration`. Absent means
+    /// the runner waits for every file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hydration: Option<WorkspaceArchiveHydration>,
+}
+
+/// Which files a `Pooled` runner waits for.
+///
+/// Everything else is restored after the runner has started, each file under
+/// a private name and moved into place once complete, so a path either holds
+/// the archived bytes or does not exist yet.
+#[derive(Clone, Debug, Deserialize, Serialize, JsonSchema, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceArchiveHydration {
+    /// Files up to this size are restored before the runner starts, along
+    /// with every notebook, `pyproject.toml` and `uv.lock` whatever their
+    /// size. Absent means
+    /// [`WorkspaceArchiveHydration::DEFAULT_EAGER_MAX_FILE_SIZE`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub eager_max_file_size: Option<u64>,
+}
+
+impl WorkspaceArchiveHydration {
+    pub const DEFAULT_EAGER_MAX_FILE_SIZE: u64 = 1024 * 1024;
+
+    pub fn effective_eager_max_file_size(&self) -> u64 {
+        self.eager_max_file_size
+            .unwrap_or(Self::DEFAULT_EAGER_MAX_FILE_SIZE)
+    }
+}
+
+/// How large a file may be archived, and how a large one is restored.
+#[derive(Clone, Debug, Deserialize, Serialize, JsonSchema, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceArchiveLimits {
+    /// Largest file uploaded as one object. Larger files are chunked when
+    /// `chunking` is set and left out of the archive otherwise. Absent means
+    /// [`WorkspaceArchiveLimits::DEFAULT_MAX_FILE_SIZE`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_file_size: Option<u64>,
+    /// A file stored as one plain object larger than this is restored as
+    /// ranges of this size, fetched side by side, and a range the connection
+    /// drops in is picked up where it stopped. Absent means
+    /// [`WorkspaceArchiveLimits::DEFAULT_DOWNLOAD_PART_SIZE`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub download_part_size: Option<u64>,
+    /// How many ranges of one file are fetched at once. Absent means
+    /// [`WorkspaceArchiveLimits::DEFAULT_DOWNLOAD_PART_CONCURRENCY`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub download_part_concurrency: Option<u32>,
+}
+
+impl WorkspaceArchiveLimits {
+    /// What the indexer has always capped files at.
+    pub const DEFAULT_MAX_FILE_SIZE: u64 = 100 * 1024 * 1024;
+    pub const DEFAULT_DOWNLOAD_PART_SIZE: u64 = 32 * 1024 * 1024;
+    pub const DEFAULT_DOWNLOAD_PART_CONCURRENCY: u32 = 8;
+
+    pub fn effective_max_file_size(&self) -> u64 {
+        self.max_file_size.unwrap_or(Self::DEFAULT_MAX_FILE_SIZE)
+    }
+
+    /// Floored at 1 MiB: smaller ranges cost a request each for no gain.
+    pub fn effective_download_part_size(&self) -> u64 {
+        self.download_part_size
+            .unwrap_or(Self::DEFAULT_DOWNLOAD_PART_SIZE)
+            .max(1024 * 1024)
+    }
+
+    pub fn effective_download_part_concurrency(&self) -> u32 {
+        self.download_part_concurrency
+            .unwrap_or(Self::DEFAULT_DOWNLOAD_PART_CONCURRENCY)
+            .max(1)
+    }
+}
+
+/// Which files to chunk, and how large a file may then be.
+///
+/// Chunk boundaries are found from the bytes themselves, so an append or an
+/// insertion moves only the boundaries near it: the chunks before and after
+/// keep their names and are not uploaded again.
+#[derive(Clone, Debug, Deserialize, Serialize, JsonSchema, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceArchiveChunking {
+    /// Files of at least this many bytes are chunked; smaller ones stay one
+    /// object each. Absent means [`WorkspaceArchiveChunking::DEFAULT_MIN_SIZE`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_size: Option<u64>,
+    /// Largest file archived at all once chunking is on. Replaces the
+    /// whole-object limit for chunked files, which exists because a changed
+    /// file used to be re-uploaded in full. Absent means
+    /// [`WorkspaceArchiveChunking::DEFAULT_MAX_FILE_SIZE`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_file_size: Option<u64>,
+}
+
+impl WorkspaceArchiveChunking {
+    pub const DEFAULT_MIN_SIZE: u64 = 64 * 1024 * 1024;
+    pub const DEFAULT_MAX_FILE_SIZE: u64 = 16 * 1024 * 1024 * 1024;
+
+    pub fn effective_min_size(&self) -> u64 {
+        self.min_size.unwrap_or(Self::DEFAULT_MIN_SIZE)
+    }
+
+    pub fn effective_max_file_size(&self) -> u64 {
+        self.max_file_size.unwrap_or(Self::DEFAULT_MAX_FILE_SIZE)
+    }
+}
+
+/// Which files to compress, and how hard.
+///
+/// Only content is compressed, and only where it pays: files already in a
+/// compressed format are stored as they are, and so is a small file whose
+/// compressed form would not be smaller.
+#[derive(Clone, Debug, Deserialize, Serialize, JsonSchema, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceArchiveCompression {
+    /// Files smaller than this many bytes are stored as they are: below a
+    /// few KiB the saving is lost in per-request cost. Absent means
+    /// [`WorkspaceArchiveCompression::DEFAULT_MIN_SIZE`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_size: Option<u64>,
+    /// zstd level, 1 (fastest) to 19. Absent means
+    /// [`WorkspaceArchiveCompression::DEFAULT_LEVEL`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub level: Option<i32>,
+}
+
+impl WorkspaceArchiveCompression {
+    pub const DEFAULT_MIN_SIZE: u64 = 4 * 1024;
+    pub const DEFAULT_LEVEL: i32 = 3;
+
+    pub fn effective_min_size(&self) -> u64 {
+        self.min_size.unwrap_or(Self::DEFAULT_MIN_SIZE)
+    }
+
+    /// The level with the default applied and clamped to what the indexer
+    /// accepts, so a typo in the spec costs speed rather than the upload.
+    pub fn effective_level(&self) -> i32 {
+        self.level.unwrap_or(Self::DEFAULT_LEVEL).clamp(1, 19)
+    }
+}
+
+/// How many archive generations to keep, and how often to cut one.
+///
+/// Both limits apply when both are set. With neither, the most recent
+/// [`WorkspaceArchiveHistory::DEFAULT_GENERATIONS`] are kept: an archive whose
+/// history grows without bound is a storage bill nobody asked for.
+#[derive(Clone, Debug, Deserialize, Serialize, JsonSchema, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceArchiveHistory {
+    /// Keep at most this many generations.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub generations: Option<u32>,
+    /// Drop generations older than this. The newest generation is never
+    /// dropped, however old: it is what the archive currently looks like.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_age_secs: Option<u64>,
+    /// Minimum spacing between generations. The indexer syncs on every save,
+    /// so without a floor a busy notebook would push its own history out
+    /// within minutes. Absent means
+    /// [`WorkspaceArchiveHistory::DEFAULT_INTERVAL_SECS`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interval_secs: Option<u64>,
+}
+
+impl WorkspaceArchiveHistory {
+    pub const DEFAULT_GENERATIONS: u32 = 24;
+    pub const DEFAULT_INTERVAL_SECS: u64 = 5 * 60;
+
+    /// The count limit with the default applied: a spec that limits neither
+    /// count nor age is limited by count.
+    pub fn effective_generations(&self) -> Option<u32> {
+        match (self.generations, self.max_age_secs) {
+            (None, None) => Some(Self::DEFAULT_GENERATIONS),
+            (generations, _) => generations,
+        }
+    }
+
+    pub fn effective_interval_secs(&self) -> u64 {
+        self.interval_secs.unwrap_or(Self::DEFAULT_INTERVAL_SECS)
+    }
 }
 
 /// The slot a `Pooled` workspace occupies on a node's shared data volume.
@@ -155,6 +372,20 @@ pub struct WorkspaceArchiveStatus {
     pub total_content_bytes: Option<u64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub last_synced_at: Option<DateTime<Utc>>,
+    /// The most recent point-in-time copies of the manifest, newest first.
+    /// Only written when `spec.indexer.history` is set; any of these names can
+    /// be handed to another workspace's `restoreFrom.generation`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub generations: Option<Vec<WorkspaceArchiveGeneration>>,
+}
+
+/// One retained copy of an archive's manifest.
+#[derive(Clone, Debug, Deserialize, Serialize, JsonSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceArchiveGeneration {
+    /// Stable name of the generation, derived from `createdAt`.
+    pub name: String,
+    pub created_at: DateTime<Utc>,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize, JsonSchema, Default)]
@@ -176,6 +407,41 @@ pub struct WorkspaceStatus {
     pub archive: Option<WorkspaceArchiveStatus>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub python_runtime: Option<WorkspacePythonRuntime>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hydration: Option<WorkspaceHydrationStatus>,
+}
+
+/// How far the background restore of a lazily hydrated slot has got.
+/// Agent-owned, under a field manager of its own.
+///
+/// Describes the slot named by `slot` and no other: a later slot on another
+/// node is hydrated in full or reports afresh, so readers compare it with
+/// `slot.id` rather than trusting a count left by a slot that is gone.
+#[derive(Clone, Debug, Deserialize, Serialize, JsonSchema, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceHydrationStatus {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub slot: Option<String>,
+    /// Files still to restore, including failed ones, which are retried.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pending_files: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pending_bytes: Option<u64>,
+    /// Files restored in the background so far. Files restored before the
+    /// runner started are not counted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub restored_files: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub restored_bytes: Option<u64>,
+    /// Files whose last attempt failed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failed_files: Option<u64>,
+}
+
+impl WorkspaceHydrationStatus {
+    pub fn is_complete(&self) -> bool {
+        self.pending_files == Some(0)
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize, JsonSchema, Default)]
@@ -226,7 +492,9 @@ pub enum WorkspaceRestoreSecrets {
 #[derive(Clone, Debug, Deserialize, Serialize, JsonSchema, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct WorkspaceRestoreFrom {
-    /// Bucket holding the source archive.
+    /// Bucket holding the source archive, in the same form as
+    /// `indexer.bucket`: a bare S3 bucket name or a `gs://`, `az://` or
+    /// `file://` url.
     pub bucket: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub key_prefix: Option<String>,
@@ -242,6 +510,17 @@ pub struct WorkspaceRestoreFrom {
     /// rejected with "Unsupported value: null".
     #[serde(skip_serializing_if = "Option::is_none")]
     pub secrets: Option<WorkspaceRestoreSecrets>,
+    /// Restore this generation of the source archive (one of the names in
+    /// its `status.archive.generations`) instead of its current manifest.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub generation: Option<String>,
+    /// Restore the source archive as it was at this moment: the newest
+    /// generation cut at or before it. Mutually exclusive with `generation`.
+    ///
+    /// Secret values always come from the archive's current secrets object —
+    /// generations copy the manifest, not the values it names.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub at: Option<DateTime<Utc>>,
 }
 
 /// Determines how a workspace environment is installed.
@@ -285,11 +564,16 @@ impl std::fmt::Display for WorkspacePythonRuntime {
     shortname = "bmow",
     namespaced,
     status = "WorkspaceStatus",
+    printcolumn = r#"{"name":"Mode","type":"string","jsonPath":".status.mode"}"#,
+    printcolumn = r#"{"name":"Ready","type":"string","jsonPath":".status.conditions[?(@.type==\"Ready\")].status"}"#,
+    printcolumn = r#"{"name":"Reason","type":"string","jsonPath":".status.conditions[?(@.type==\"Ready\")].reason"}"#,
+    printcolumn = r#"{"name":"Age","type":"date","jsonPath":".metadata.creationTimestamp"}"#,
     validation = workspace_max_storage_greater_than_min(),
     validation = workspace_auto_scale_bounds(),
     validation = workspace_no_volume_with_name(),
     validation = workspace_restore_from_exclusive(),
     validation = workspace_restore_from_not_indexer_prefix(),
+    validation = workspace_restore_from_point_exclusive(),
     validation = workspace_mode_no_downgrade(),
     validation = workspace_clone_not_pooled(),
     validation = workspace_no_new_dedicated(),
@@ -369,6 +653,64 @@ pub struct RunnerIngress {
 #[serde(rename_all = "camelCase")]
 pub struct RunnerLifecycle {
     pub delete_after_secs_inactive: Option<u32>,
+    /// Set `spec.suspended` once the runner has had no connection for this
+    /// long, measured like `deleteAfterSecsInactive` but restarted by every
+    /// resume. Meant to be shorter than it: a suspended runner still counts
+    /// as inactive, and is deleted once that deadline passes too.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suspend_after_secs_inactive: Option<u32>,
+    /// Count the runner as inactive once no cell has run and no input has
+    /// come from the editor for this long, even with connections open: a
+    /// forgotten browser tab otherwise keeps it active, and both deadlines
+    /// above out of reach, for good. Unset, an open connection is activity.
+    /// Needs a marimo image that reports its last execution; on one that
+    /// does not, this has no effect.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inactive_after_secs_without_execution: Option<u32>,
+    /// Delete the runner this long after it was created, whatever it is
+    /// doing. Suspensions count towards it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_lifetime_secs: Option<u32>,
+    /// When the runner may run at all. Outside these hours it is deleted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub active_hours: Option<RunnerActiveHours>,
+    /// How long before either of the two above deletes the runner the
+    /// `ShutdownScheduled` condition turns `True`, so that whoever is using
+    /// it can be told. Absent means
+    /// [`RunnerLifecycle::DEFAULT_SHUTDOWN_WARNING_SECS`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shutdown_warning_secs: Option<u32>,
+    /// After the runner is killed for running out of memory, replace its pod
+    /// with one requesting twice the memory, at most this many times and
+    /// never above `memory.max`. Only the request is raised, never the limit:
+    /// this helps a runner killed while its node ran short, not one that
+    /// outgrew `memory.max`. Unset, an OOM kill is only reported, through
+    /// the `Failed` condition. Has no effect on a claimed warm pod, whose
+    /// resources are the pool's.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub oom_retries: Option<u32>,
+}
+
+impl RunnerLifecycle {
+    pub const DEFAULT_SHUTDOWN_WARNING_SECS: u32 = 10 * 60;
+
+    pub fn effective_shutdown_warning_secs(&self) -> u32 {
+        self.shutdown_warning_secs
+            .unwrap_or(Self::DEFAULT_SHUTDOWN_WARNING_SECS)
+    }
+}
+
+/// The minutes a runner may run in, as a cron expression read like
+/// [`CacheJobSchedule::cron`]: a minute the expression matches is inside the
+/// hours. `* 8-19 * * mon-fri` keeps runners to weekday office hours.
+#[derive(Clone, Debug, Deserialize, Serialize, JsonSchema, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct RunnerActiveHours {
+    pub cron: String,
+    /// IANA time zone the expression is read in, e.g. `Europe/Paris`. Absent
+    /// means UTC.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_zone: Option<String>,
 }
 
 #[derive(
@@ -397,6 +739,43 @@ pub struct RunnerClaim {
     /// The pod's pre-minted access token.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub token: Option<String>,
+    /// The claimed pod was deleted by a suspension. The claim is kept so the
+    /// runner's URL and token survive it: once resumed, the runner's own pod
+    /// serves `ingressPath` and `token` in the claimed pod's place, and no
+    /// new claim is made.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub released: Option<bool>,
+}
+
+impl RunnerClaim {
+    pub fn is_released(&self) -> bool {
+        self.released == Some(true)
+    }
+}
+
+/// A one-word answer to "is my runner up?", computed by the controller from
+/// the startup conditions and the last status poll. The conditions stay the
+/// detail; this is for a glance, and for `kubectl get`.
+#[derive(Clone, Copy, Debug, Display, Deserialize, Serialize, JsonSchema, PartialEq, Eq)]
+pub enum RunnerPhase {
+    /// Waiting for its pod to be created or scheduled.
+    Pending,
+    /// Scheduled, waiting for the workspace's files: the PVC or slot to be
+    /// bound, or the Workspace to become ready.
+    Hydrating,
+    /// The files are in place and marimo is starting.
+    Starting,
+    /// Serving, and active at the last poll.
+    Ready,
+    /// Serving, and inactive at the last poll: nothing connected, or nothing
+    /// run for `lifecycle.inactiveAfterSecsWithoutExecution`.
+    Idle,
+    /// Stuck in a way that waiting will not fix: the pod crashes or its image
+    /// cannot be pulled, or the workspace's storage or init failed. The
+    /// condition that says so carries the detail.
+    Failed,
+    /// `spec.suspended`: no pod, on purpose.
+    Suspended,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize, JsonSchema, Default, PartialEq)]
@@ -410,10 +789,32 @@ pub struct RunnerStatus {
     pub conditions: Option<Vec<Condition>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub last_active: Option<DateTime<Utc>>,
+    /// When a cell last ran, as marimo last reported it. Recorded whether or
+    /// not `lifecycle.inactiveAfterSecsWithoutExecution` is set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_execution: Option<DateTime<Utc>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub marimo_version: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub claim: Option<RunnerClaim>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub phase: Option<RunnerPhase>,
+    /// When `lifecycle.maxLifetimeSecs` or `lifecycle.activeHours` will delete
+    /// the runner, once that is within `lifecycle.shutdownWarningSecs`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shutdown_time: Option<DateTime<Utc>>,
+    /// Where the runner is served: the first of its ingress hosts and the
+    /// path it is actually served under (a claim's, once claimed). Absent
+    /// when no host is configured. Never carries the token.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// The memory request the runner's pod is created with in place of
+    /// `memory.min`, once `lifecycle.oomRetries` has raised it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory_request: Option<StorageQuantity>,
+    /// How many of `lifecycle.oomRetries` have been spent.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub oom_retries: Option<u32>,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize, JsonSchema, Default)]
@@ -433,6 +834,11 @@ pub struct RunnerToken {
     selectable = ".spec.command",
     namespaced,
     status = "RunnerStatus",
+    printcolumn = r#"{"name":"Workspace","type":"string","jsonPath":".spec.workspace"}"#,
+    printcolumn = r#"{"name":"Command","type":"string","jsonPath":".spec.command"}"#,
+    printcolumn = r#"{"name":"Phase","type":"string","jsonPath":".status.phase"}"#,
+    printcolumn = r#"{"name":"URL","type":"string","jsonPath":".status.url"}"#,
+    printcolumn = r#"{"name":"Age","type":"date","jsonPath":".metadata.creationTimestamp"}"#,
     validation = runner_immutable_fields(),
     validation = runner_max_memory_greater_than_min(),
     validation = runner_max_cpu_greater_than_min(),
@@ -458,6 +864,37 @@ pub struct RunnerSpec {
     /// once a runner has a cold pod, a claim would strand it.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub pool: Option<String>,
+    /// Keep the runner but not its pod, Service or Ingress: nothing runs, its
+    /// URL and token stay what they were, and under `Pooled` the workspace's
+    /// slot stays on its node until the agent reclaims it. Unsetting it
+    /// recreates the pod, preferring that node so the slot is not hydrated
+    /// again. Reported by the `Suspended` condition.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suspended: Option<bool>,
+}
+
+impl RunnerSpec {
+    pub fn is_suspended(&self) -> bool {
+        self.suspended == Some(true)
+    }
+}
+
+impl Runner {
+    /// `spec.memory`, with the request raised to `status.memoryRequest` once
+    /// an OOM retry has set it. What the runner's pod is created with.
+    pub fn effective_memory(&self) -> Option<Requirement<StorageQuantity>> {
+        let raised = self
+            .status
+            .as_ref()
+            .and_then(|status| status.memory_request.clone());
+        match (self.spec.memory.clone(), raised) {
+            (memory, None) => memory,
+            (memory, Some(raised)) => Some(Requirement {
+                min: Some(raised),
+                max: memory.and_then(|memory| memory.max),
+            }),
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug, Display)]
@@ -504,6 +941,19 @@ impl Workspace {
             .unwrap_or(default_mode)
     }
 
+    /// Whether a Budget has frozen this workspace's slot (see
+    /// [`conditions::READ_ONLY`]).
+    pub fn is_read_only(&self) -> bool {
+        self.status
+            .as_ref()
+            .and_then(|status| status.conditions.as_ref())
+            .is_some_and(|current| {
+                current
+                    .iter()
+                    .any(|cond| cond.type_ == conditions::READ_ONLY && cond.status == "True")
+            })
+    }
+
     pub fn new_runner(&self, name: &str, spec: RunnerSpec) -> Result<Runner> {
         let mut runner = Runner::new(
             name,
@@ -529,6 +979,7 @@ impl Workspace {
     shortname = "bmocj",
     selectable = ".spec.workspace",
     namespaced,
+    status = "CacheJobStatus",
     validation = log_level(),
 )]
 #[serde(rename_all = "camelCase")]
@@ -547,6 +998,160 @@ pub struct CacheJobSpec {
     /// warm-pod claim.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub start_after: Option<DateTime<Utc>>,
+    /// Run again on a schedule rather than once. Each run is a Job of its own,
+    /// named after the CacheJob and the time it was due; with `startAfter` set
+    /// as well, no run is due before it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub schedule: Option<CacheJobSchedule>,
+    /// Render only notebooks whose path, relative to the workspace root,
+    /// matches one of these globs: `*` and `?` within a path segment, `**`
+    /// across any number of them. Absent means every notebook. Matched before
+    /// anything is imported, so a notebook left out never runs.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include: Option<Vec<String>>,
+    /// Leave out notebooks matching one of these globs, same syntax as
+    /// `include`, even when `include` names them.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exclude: Option<Vec<String>>,
+    /// What to export each notebook as. Absent means `html` and `md`.
+    #[schemars(length(min = 1))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub formats: Option<Vec<CacheFormat>>,
+}
+
+/// A format a notebook's cache is exported in, named for its extension under
+/// `__marimo__` — the indexer uploads exactly these.
+#[derive(Clone, Copy, Debug, Display, Deserialize, Serialize, JsonSchema, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum CacheFormat {
+    /// The notebook run, with its outputs. Runs the notebook's code.
+    Html,
+    /// The notebook's source as markdown. Runs nothing.
+    Md,
+    /// A Jupyter notebook of its cells. Runs nothing.
+    Ipynb,
+}
+
+/// When a scheduled [`CacheJob`] runs.
+///
+/// A run that falls due while the previous one is still going is held back
+/// until it finishes rather than started beside it: both would mount the same
+/// workspace and write the same caches. Runs missed altogether — the controller
+/// was down, or one run outlasted several periods — collapse into one, started
+/// as soon as it can be.
+#[derive(Clone, Debug, Deserialize, Serialize, JsonSchema, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheJobSchedule {
+    /// Five-field cron expression: minute, hour, day of month, month and day
+    /// of week, each `*`, a value, a range or a list of them, optionally with a
+    /// `/step`. Months and days of week also take their three-letter English
+    /// names. `@hourly`, `@daily`, `@weekly`, `@monthly` and `@yearly` stand
+    /// for the usual expressions.
+    pub cron: String,
+    /// IANA time zone the expression is read in, e.g. `Europe/Paris`. Absent
+    /// means UTC.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_zone: Option<String>,
+    /// How many finished runs to keep, their Jobs and pods included, so their
+    /// logs can still be read. Absent means
+    /// [`CacheJobSchedule::DEFAULT_HISTORY_LIMIT`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub history_limit: Option<u32>,
+}
+
+impl CacheJobSchedule {
+    pub const DEFAULT_HISTORY_LIMIT: u32 = 3;
+
+    pub fn effective_history_limit(&self) -> u32 {
+        self.history_limit.unwrap_or(Self::DEFAULT_HISTORY_LIMIT)
+    }
+}
+
+/// What a [`CacheJob`]'s runs came to.
+///
+/// Written by the controller alone. `conditions`, the start and completion
+/// times and the notebook results describe one run: the only one, or for a
+/// scheduled CacheJob the latest, and they are cleared when the next starts.
+/// The `last*` times survive the Jobs they were read from: a scheduled
+/// CacheJob prunes its history, and the last failure is worth knowing about
+/// long after its Job is gone.
+#[derive(Clone, Debug, Deserialize, Serialize, JsonSchema, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheJobStatus {
+    /// A single `Succeeded` condition: `Unknown` until the run finishes,
+    /// `False` when it failed outright or any notebook failed to render.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub conditions: Option<Vec<Condition>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_time: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub completion_time: Option<DateTime<Utc>>,
+    /// Notebooks the run rendered or tried to, once it has finished.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notebooks: Option<u32>,
+    /// How many of `notebooks` failed in at least one format.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failed_notebooks: Option<u32>,
+    /// What each notebook came to, failed ones first. May list fewer than
+    /// `notebooks`: see [`CacheReport::truncated`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub results: Option<Vec<CacheNotebookResult>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub results_truncated: Option<bool>,
+    /// When the latest scheduled run to have started was due.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_schedule_time: Option<DateTime<Utc>>,
+    /// When the latest run that succeeded finished.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_success_time: Option<DateTime<Utc>>,
+    /// When the latest run that failed was given up on.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_failure_time: Option<DateTime<Utc>>,
+}
+
+/// One notebook's exports.
+#[derive(Clone, Debug, Deserialize, Serialize, JsonSchema, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheNotebookResult {
+    /// Relative to the workspace root.
+    pub path: String,
+    pub formats: Vec<CacheFormatResult>,
+}
+
+impl CacheNotebookResult {
+    pub fn failed(&self) -> bool {
+        self.formats.iter().any(|format| format.error.is_some())
+    }
+}
+
+/// One export of a notebook: produced, or the error that prevented it.
+#[derive(Clone, Debug, Deserialize, Serialize, JsonSchema, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheFormatResult {
+    /// The cache's extension under `__marimo__`: `html`, `md` or `ipynb`.
+    pub format: String,
+    /// Absent when the export was written.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// What a cache run rendered. Written by `cache.py` as the cache container's
+/// termination message and read back by the controller into the CacheJob's
+/// status, so its serialized shape is a protocol between the two.
+///
+/// A termination message is capped at 4KiB, so the writer keeps errors short
+/// and, when the results still do not fit, drops successful notebooks before
+/// failed ones and says so with `truncated`. The counts are always whole.
+#[derive(Clone, Debug, Deserialize, Serialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheReport {
+    pub notebooks: u32,
+    pub failed_notebooks: u32,
+    #[serde(default)]
+    pub results: Vec<CacheNotebookResult>,
+    #[serde(default)]
+    pub truncated: bool,
 }
 
 #[derive(Clone, Copy, Debug, Display)]
@@ -583,13 +1188,118 @@ impl Workspace {
     }
 }
 
+/// Where a ready snapshot's copy lives and what it holds.
+///
+/// Same field-manager caveat as [`WorkspaceSlotStatus`].
 #[derive(Clone, Debug, Deserialize, Serialize, JsonSchema, Default)]
 #[serde(rename_all = "camelCase")]
-pub struct BudgetResourceStatus {
+pub struct WorkspaceSnapshotStatus {
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub used: Option<StorageQuantity>,
+    pub conditions: Option<Vec<Condition>>,
+    /// Bucket holding the copy: the source workspace's indexer bucket.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bucket: Option<String>,
+    /// Key prefix of the copy. Together with `bucket` this is a complete
+    /// `restoreFrom` source, and unlike the workspace's own prefix nothing
+    /// ever writes under it again.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key_prefix: Option<String>,
+    /// Number of files with content in the copy.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub files: Option<u64>,
+    /// Sum of those files' sizes, same measure as
+    /// `WorkspaceArchiveStatus.totalContentBytes`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_content_bytes: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ready_at: Option<DateTime<Utc>>,
+}
+
+/// A named, immutable checkpoint of a workspace's archive.
+///
+/// `Dedicated` workspaces can be snapshotted at the volume level; `Pooled`
+/// ones have no volume of their own, so this copies the archive instead: the
+/// current manifest and every content object it names, under a prefix of
+/// their own that the workspace's indexer never rewrites or prunes. Once
+/// `Ready`, `status.bucket`/`status.keyPrefix` can be handed to another
+/// workspace's `restoreFrom`.
+///
+/// Deliberately not owned by the workspace: a checkpoint that went away with
+/// its source would be no checkpoint at all.
+#[derive(CustomResource, Clone, Debug, Deserialize, Serialize, JsonSchema, Default)]
+#[kube(
+    group = "kubimo.aqora.io",
+    version = "v1",
+    kind = "WorkspaceSnapshot",
+    shortname = "bmows",
+    selectable = ".spec.workspace",
+    namespaced,
+    status = "WorkspaceSnapshotStatus",
+    validation = workspace_snapshot_immutable(),
+)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceSnapshotSpec {
+    /// Workspace to snapshot. It must archive with `indexer.uploadContent`:
+    /// a manifest without content has nothing to copy.
+    pub workspace: String,
+}
+
+#[derive(Clone, Copy, Debug, Display)]
+pub enum WorkspaceSnapshotField {
+    #[strum(serialize = "metadata.name")]
+    Name,
+    #[strum(serialize = "metadata.namespace")]
+    Namespace,
+    #[strum(serialize = "spec.workspace")]
+    Workspace,
+}
+
+impl ResourceFactory for WorkspaceSnapshot {
+    fn new(name: &str, spec: Self::Spec) -> Self {
+        Self::new(name, spec)
+    }
+}
+
+impl WorkspaceSnapshot {
+    /// A `restoreFrom` pointing at this snapshot's copy, once it is ready.
+    ///
+    /// `pod` is left for the caller: the credentials that read the copy are
+    /// the source workspace's, which this object does not carry.
+    pub fn restore_from(&self) -> Option<WorkspaceRestoreFrom> {
+        let status = self.status.as_ref()?;
+        let ready = status
+            .conditions
+            .iter()
+            .flatten()
+            .any(|cond| cond.type_ == "Ready" && cond.status == "True");
+        if !ready {
+            return None;
+        }
+        Some(WorkspaceRestoreFrom {
+            bucket: status.bucket.clone()?,
+            key_prefix: status.key_prefix.clone(),
+            ..Default::default()
+        })
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct BudgetResourceStatus<T = StorageQuantity> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub used: Option<T>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub limit: Option<StorageQuantity>,
+    pub limit: Option<T>,
+}
+
+// Derived, this would demand `T: Default`, which neither limit needs.
+impl<T> Default for BudgetResourceStatus<T> {
+    fn default() -> Self {
+        Self {
+            used: None,
+            limit: None,
+        }
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize, JsonSchema, Default)]
@@ -599,6 +1309,24 @@ pub struct BudgetStatus {
     pub conditions: Option<Vec<Condition>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub storage: Option<BudgetResourceStatus>,
+    /// CPU requested by the matching Runners, as they count against `spec.cpu`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpu: Option<BudgetResourceStatus<CpuQuantity>>,
+    /// Memory requested by the matching Runners, as they count against
+    /// `spec.memory`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory: Option<BudgetResourceStatus>,
+    /// Matching Runners counted against `spec.runners`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub runners: Option<BudgetResourceStatus<u32>>,
+    /// Matching Runners serving from a claimed warm pod.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub claims: Option<BudgetResourceStatus<u32>>,
+    /// The Budget among this one and its ancestors with the least storage
+    /// left under its `storage` limit: what actually caps the objects this
+    /// one governs. Unset when none of them has a storage limit.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub constrained_by: Option<String>,
 }
 
 #[derive(CustomResource, Clone, Debug, Deserialize, Serialize, JsonSchema, Default)]
@@ -609,37 +1337,120 @@ pub struct BudgetStatus {
     shortname = "bmob",
     namespaced,
     status = "BudgetStatus",
+    printcolumn = r#"{"name":"Storage","type":"string","jsonPath":".spec.storage"}"#,
+    printcolumn = r#"{"name":"Used","type":"string","jsonPath":".status.storage.used"}"#,
+    printcolumn = r#"{"name":"Parent","type":"string","jsonPath":".spec.parent"}"#,
+    printcolumn = r#"{"name":"Age","type":"date","jsonPath":".metadata.creationTimestamp"}"#,
     validation = budget_selector_not_empty(),
+    validation = budget_selector_expression_values(),
 )]
 #[serde(rename_all = "camelCase")]
 pub struct BudgetSpec {
     /// matchLabels — objects whose labels are a superset of this map are governed by the budget.
+    #[serde(default)]
     pub selector: BTreeMap<String, String>,
+    /// matchExpressions — set-based requirements an object's labels must
+    /// also satisfy, ANDed with `selector`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub selector_expressions: Option<Vec<BudgetSelectorRequirement>>,
+    /// Another Budget in the same namespace this one is nested under. It
+    /// governs only what its parent governs too — its selectors are ANDed
+    /// with every ancestor's — so an object under a `user` Budget is also
+    /// checked against its `team` and `org` ones, and each limit holds for
+    /// its whole subtree.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent: Option<String>,
     /// Maximum total storage summed across all matching Workspaces. Only
     /// Workspaces that declare an explicit `spec.storage.min` count against and
     /// are constrained by this limit; storage-class-default Workspaces (no `min`)
     /// are neither sized nor refused here.
     pub storage: Option<StorageQuantity>,
+    /// Maximum total CPU requested across all matching Runners that are not
+    /// suspended. A Runner that would take the total past it is not started,
+    /// and reports `BudgetExceeded`; one already running is never stopped.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpu: Option<CpuQuantity>,
+    /// Maximum total memory requested across all matching Runners that are
+    /// not suspended, enforced like `cpu`. A Runner counts the request it
+    /// actually runs with, which `lifecycle.oomRetries` may have raised.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory: Option<StorageQuantity>,
+    /// Maximum number of matching Runners that are not suspended, enforced
+    /// like `cpu`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub runners: Option<u32>,
+    /// Maximum number of matching Runners serving from a claimed warm pod. A
+    /// Runner past it is not refused: it cold-starts instead of claiming.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub claims: Option<u32>,
+    /// A warning threshold on the same total as `storage`, refusing nothing.
+    /// The Budget reports `NearLimit` from 80% of it and `OverSoftLimit`
+    /// past it, with an event at each transition.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub soft_storage: Option<StorageQuantity>,
+    /// How long usage may stay over `softStorage` before the matching
+    /// `Pooled` Workspaces are frozen read-only (see the `ReadOnly`
+    /// condition). They are thawed as soon as usage is back under it. Unset,
+    /// going over only warns.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub soft_storage_grace_period_secs: Option<u64>,
+}
+
+/// One set-based requirement of [`BudgetSpec::selector_expressions`], as in
+/// a Kubernetes `LabelSelector`'s `matchExpressions`.
+#[derive(Clone, Debug, Deserialize, Serialize, JsonSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct BudgetSelectorRequirement {
+    pub key: String,
+    pub operator: BudgetSelectorOperator,
+    /// Required and non-empty for `In` and `NotIn`, absent otherwise.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub values: Option<Vec<String>>,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, JsonSchema, PartialEq, Eq)]
+pub enum BudgetSelectorOperator {
+    In,
+    NotIn,
+    Exists,
+    DoesNotExist,
+}
+
+impl From<&BudgetSelectorRequirement> for Expression {
+    fn from(requirement: &BudgetSelectorRequirement) -> Self {
+        let key = Expr::new(&requirement.key);
+        let values = requirement.values.iter().flatten();
+        match requirement.operator {
+            BudgetSelectorOperator::In => key.in_(values),
+            BudgetSelectorOperator::NotIn => key.not_in(values),
+            BudgetSelectorOperator::Exists => key.exists(),
+            BudgetSelectorOperator::DoesNotExist => key.not_exists(),
+        }
+    }
 }
 
 impl BudgetSpec {
-    /// Whether `labels` satisfy every entry in this budget's selector.
+    /// Whether `labels` satisfy this budget's own selectors. Its ancestors'
+    /// are not consulted: resolving `parent` needs the other Budgets.
     pub fn matches(&self, labels: Option<&BTreeMap<String, String>>) -> bool {
-        let Some(labels) = labels else {
-            return self.selector.is_empty();
-        };
-        self.selector
-            .iter()
-            .all(|(key, value)| labels.get(key) == Some(value))
+        self.label_selector().matches(labels)
     }
 
-    /// Equality label selector for listing the objects this budget governs.
+    /// Label selector for listing the objects this budget's own selectors
+    /// govern: `selector` as equalities, then `selectorExpressions`.
     pub fn label_selector(&self) -> Selector {
-        self.selector.iter().collect()
+        let mut selector: Selector = self.selector.iter().collect();
+        selector.extend(
+            self.selector_expressions
+                .iter()
+                .flatten()
+                .map(Expression::from),
+        );
+        selector
     }
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize, JsonSchema, Default)]
+#[derive(Clone, Debug, Deserialize, Serialize, JsonSchema, Default, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct PoolStatus {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -651,13 +1462,117 @@ pub struct PoolStatus {
     /// still alive. Informational — claimed pods belong to their Runners.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub claimed: Option<u32>,
+    /// The last autoscaling decision. Only set when `spec.autoscale` is.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub autoscale: Option<PoolAutoscaleStatus>,
+}
+
+/// Sizes a pool's warm fleet from how fast it is being claimed, in place of a
+/// fixed `replicas`.
+///
+/// Every reconcile recommends enough warm pods to absorb the runners that
+/// named this pool over the last `claimWindowSecs` — claimed or cold-started
+/// alike, since a cold start is a claim the pool was too small for — plus
+/// `targetIdle`, clamped to `[minReplicas, maxReplicas]`. As with a
+/// HorizontalPodAutoscaler, the fleet only grows to the lowest recommendation
+/// of the scale-up window and only shrinks to the highest of the scale-down
+/// window, so a single burst does not flap it.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PoolAutoscale {
+    pub min_replicas: u32,
+    pub max_replicas: u32,
+    /// Warm pods to keep on top of the recent demand, so the next claim after
+    /// a quiet window still finds one. Defaults to 1.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_idle: Option<u32>,
+    /// How far back claims count towards demand. Defaults to 5 minutes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub claim_window_secs: Option<u64>,
+    /// How long a higher recommendation must hold before the fleet grows to
+    /// it. Defaults to 0: a class starting should not wait.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scale_up_stabilization_secs: Option<u64>,
+    /// How long a lower recommendation must hold before the fleet shrinks to
+    /// it. Defaults to 5 minutes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scale_down_stabilization_secs: Option<u64>,
+}
+
+impl PoolAutoscale {
+    pub const DEFAULT_TARGET_IDLE: u32 = 1;
+    pub const DEFAULT_CLAIM_WINDOW_SECS: u64 = 5 * 60;
+    pub const DEFAULT_SCALE_UP_STABILIZATION_SECS: u64 = 0;
+    pub const DEFAULT_SCALE_DOWN_STABILIZATION_SECS: u64 = 5 * 60;
+
+    pub fn effective_target_idle(&self) -> u32 {
+        self.target_idle.unwrap_or(Self::DEFAULT_TARGET_IDLE)
+    }
+
+    pub fn effective_claim_window_secs(&self) -> u64 {
+        self.claim_window_secs
+            .unwrap_or(Self::DEFAULT_CLAIM_WINDOW_SECS)
+    }
+
+    pub fn effective_scale_up_stabilization_secs(&self) -> u64 {
+        self.scale_up_stabilization_secs
+            .unwrap_or(Self::DEFAULT_SCALE_UP_STABILIZATION_SECS)
+    }
+
+    pub fn effective_scale_down_stabilization_secs(&self) -> u64 {
+        self.scale_down_stabilization_secs
+            .unwrap_or(Self::DEFAULT_SCALE_DOWN_STABILIZATION_SECS)
+    }
+
+    /// `replicas` within `[minReplicas, maxReplicas]`. Never panics on an
+    /// inverted range, which validation refuses but an old object may hold:
+    /// `maxReplicas` wins.
+    pub fn clamp(&self, replicas: u32) -> u32 {
+        replicas.max(self.min_replicas).min(self.max_replicas)
+    }
+}
+
+/// What the autoscaler decided, and on what.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, JsonSchema, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PoolAutoscaleStatus {
+    /// Warm pods the pool is keeping, in place of `spec.replicas`.
+    pub desired_replicas: u32,
+    /// Runners that claimed from this pool within the claim window.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recent_claims: Option<u32>,
+    /// Runners that named this pool within the claim window but started cold.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recent_cold_starts: Option<u32>,
+    /// `ScaledUp`, `ScaledDown`, `Stable`, or `Stabilizing` when a
+    /// recommendation differs but has not held for its window yet.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// When `desiredReplicas` last changed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_scale_time: Option<DateTime<Utc>>,
+    /// Recent recommendations, oldest first, each stamped with when it was
+    /// first made: it stands until the next one. Kept for as long as the
+    /// longer stabilization window looks back.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recommendations: Option<Vec<PoolAutoscaleRecommendation>>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, JsonSchema, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PoolAutoscaleRecommendation {
+    pub time: DateTime<Utc>,
+    pub replicas: u32,
 }
 
 /// A pool of pre-booted warm runner pods.
 ///
-/// The pool controller keeps `replicas` anonymous pods running: marimo fully
-/// booted on a template-seeded slot, serving `/health` under a base-url and
-/// token minted at the pod's birth. A Runner naming this pool in `spec.pool`
+/// The pool controller keeps `replicas` (or, with `autoscale`, as many as the
+/// claim rate calls for) anonymous pods running: marimo fully booted on a
+/// template-seeded slot, serving `/health` under a base-url and token minted
+/// at the pod's birth. A Runner naming this pool in `spec.pool`
 /// claims one instead of cold-starting, and the pool mints a replacement.
 ///
 /// Every field here is a *template*: a runner is only eligible to claim when
@@ -672,16 +1587,24 @@ pub struct PoolStatus {
     shortname = "bmop",
     namespaced,
     status = "PoolStatus",
+    printcolumn = r#"{"name":"Command","type":"string","jsonPath":".spec.command"}"#,
+    printcolumn = r#"{"name":"Replicas","type":"integer","jsonPath":".spec.replicas"}"#,
+    printcolumn = r#"{"name":"Desired","type":"integer","jsonPath":".status.autoscale.desiredReplicas"}"#,
+    printcolumn = r#"{"name":"Warm","type":"integer","jsonPath":".status.warm"}"#,
+    printcolumn = r#"{"name":"Claimed","type":"integer","jsonPath":".status.claimed"}"#,
+    printcolumn = r#"{"name":"Age","type":"date","jsonPath":".metadata.creationTimestamp"}"#,
     validation = pool_command_not_render(),
     validation = pool_python_runtime_uv(),
     validation = pool_immutable_fields(),
     validation = pool_max_memory_greater_than_min(),
     validation = pool_max_cpu_greater_than_min(),
+    validation = pool_autoscale_max_greater_than_min(),
     validation = log_level(),
 )]
 #[serde(rename_all = "camelCase")]
 pub struct PoolSpec {
-    /// Desired number of unclaimed warm pods.
+    /// Desired number of unclaimed warm pods. With `autoscale` set, only the
+    /// starting point: the fleet is sized from the claim rate thereafter.
     pub replicas: u32,
     /// The marimo command warm pods are booted with. Immutable, and Render is
     /// refused: a renderer's slot is bound read-only at publish time, which an
@@ -718,6 +1641,9 @@ pub struct PoolSpec {
     /// workspace's `storage.max`, so this only needs to fit the venv template.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub storage: Option<StorageQuantity>,
+    /// Size the fleet from the recent claim rate instead of `replicas`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub autoscale: Option<PoolAutoscale>,
 }
 
 impl ResourceFactory for Pool {
@@ -742,8 +1668,67 @@ pub struct WorkspaceDirSymlink {
 #[serde(rename_all = "camelCase")]
 pub struct WorkspaceDirContentUrl {
     pub url: Url,
+    /// Of the plain bytes, sealed or not: it is what a restore verifies once
+    /// the object is opened, and what tells an upload the file is unchanged.
     pub crc32: Option<u32>,
     pub e_tag: Option<String>,
+    /// The object is sealed with the archive's data key (see the manifest's
+    /// `encryption`). Absent means plain.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encrypted: Option<bool>,
+    /// How the bytes were compressed before being stored (and sealed, when
+    /// `encrypted`). Absent means they were not.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub codec: Option<ContentCodec>,
+    /// The file's content as chunk objects, in order. When present, `url`
+    /// names no object: it only keeps the file's own key reserved, so the
+    /// file goes back under it should it shrink below the chunking threshold.
+    /// `crc32` is still of the whole file, and `encrypted` covers every chunk.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chunks: Option<Vec<WorkspaceDirContentChunk>>,
+}
+
+impl WorkspaceDirContentUrl {
+    /// The objects holding the content: its chunks, or the one at `url`.
+    pub fn object_urls(&self) -> impl Iterator<Item = &Url> {
+        let chunks = self.chunks.as_deref();
+        chunks
+            .is_none()
+            .then_some(&self.url)
+            .into_iter()
+            .chain(chunks.unwrap_or_default().iter().map(|chunk| &chunk.url))
+    }
+}
+
+/// One piece of a chunked file.
+///
+/// Named by the digest of its bytes (keyed by the archive's data key when
+/// sealed), so the object at a chunk url never changes, and a chunk two
+/// versions of a file share is stored once.
+#[derive(Clone, Debug, Deserialize, Serialize, JsonSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceDirContentChunk {
+    pub url: Url,
+    /// Of the chunk's plain bytes, before compression.
+    pub size: u64,
+    pub crc32: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub e_tag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub codec: Option<ContentCodec>,
+}
+
+/// A compression format for stored content.
+///
+/// Recorded per object rather than per archive: which files compress is
+/// decided one upload at a time, and an unchanged file keeps the object an
+/// earlier upload wrote for as long as its bytes stay the same. A reader that
+/// predates a codec fails to parse the manifest naming it, rather than
+/// restoring compressed bytes as the file.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, JsonSchema, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentCodec {
+    Zstd,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize, JsonSchema, Default)]
@@ -771,6 +1756,30 @@ pub struct WorkspaceDirFile {
     pub marimo: Option<WorkspaceDirMarimo>,
 }
 
+impl WorkspaceDirFile {
+    /// Every object url the file names: its content, and the marimo meta and
+    /// caches derived from it.
+    pub fn urls(&self) -> impl Iterator<Item = &Url> {
+        let marimo = self.marimo.as_ref();
+        self.content
+            .iter()
+            .flat_map(WorkspaceDirContentUrl::object_urls)
+            .chain(
+                marimo
+                    .and_then(|marimo| marimo.meta_json.as_ref())
+                    .into_iter()
+                    .chain(
+                        marimo
+                            .and_then(|marimo| marimo.caches.as_deref())
+                            .unwrap_or_default()
+                            .iter()
+                            .filter_map(|cache| cache.url.as_ref()),
+                    )
+                    .map(|url| &url.url),
+            )
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize, JsonSchema, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct WorkspaceDirEntry {
@@ -848,6 +1857,7 @@ pub fn all_crds() -> Vec<CustomResourceDefinition> {
         WorkspaceDir::crd(),
         Budget::crd(),
         Pool::crd(),
+        WorkspaceSnapshot::crd(),
     ]
 }
 
@@ -870,6 +1880,8 @@ mod tests {
                 key_prefix: Some("workspace/".to_string()),
                 pod: None,
                 secrets: Some(WorkspaceRestoreSecrets::Values),
+                generation: Some("20260101T000000.000Z".to_string()),
+                at: None,
             }),
             ..Default::default()
         };
@@ -877,6 +1889,8 @@ mod tests {
         assert_eq!(json["restoreFrom"]["bucket"], "bucket");
         assert_eq!(json["restoreFrom"]["keyPrefix"], "workspace/");
         assert_eq!(json["restoreFrom"]["secrets"], "Values");
+        assert_eq!(json["restoreFrom"]["generation"], "20260101T000000.000Z");
+        assert!(json["restoreFrom"].get("at").is_none());
         let parsed: WorkspaceSpec = serde_json::from_value(json).unwrap();
         let restore_from = parsed.restore_from.unwrap();
         assert_eq!(restore_from.bucket, "bucket");
@@ -983,12 +1997,40 @@ mod tests {
         assert!(json.contains("500m"));
     }
 
+    /// An empty `history: {}` still has to bound the archive; only a spec that
+    /// limits by age may leave the count open.
+    #[test]
+    fn archive_history_defaults_to_a_count_limit() {
+        let history = WorkspaceArchiveHistory::default();
+        assert_eq!(
+            history.effective_generations(),
+            Some(WorkspaceArchiveHistory::DEFAULT_GENERATIONS)
+        );
+        assert_eq!(
+            history.effective_interval_secs(),
+            WorkspaceArchiveHistory::DEFAULT_INTERVAL_SECS
+        );
+        let by_age = WorkspaceArchiveHistory {
+            max_age_secs: Some(86_400),
+            ..Default::default()
+        };
+        assert_eq!(by_age.effective_generations(), None);
+        let both = WorkspaceArchiveHistory {
+            generations: Some(3),
+            max_age_secs: Some(86_400),
+            interval_secs: Some(60),
+        };
+        assert_eq!(both.effective_generations(), Some(3));
+        assert_eq!(both.effective_interval_secs(), 60);
+    }
+
     #[test]
     fn workspace_crd_has_restore_from_validations() {
         let crd = serde_json::to_string(&Workspace::crd()).unwrap();
         assert!(crd.contains("restoreFrom"));
         assert!(crd.contains("restoreFrom and cloneWorkspaceName are mutually exclusive"));
         assert!(crd.contains("must not write to the restoreFrom archive location"));
+        assert!(crd.contains("restoreFrom.generation and restoreFrom.at are mutually exclusive"));
         // The secrets enum's permitted values are part of the schema.
         assert!(crd.contains("NamesOnly"));
     }
@@ -1164,6 +2206,7 @@ mod tests {
                 pod_name: "editors-a1b2c3d4".to_string(),
                 ingress_path: "/editors-a1b2c3d4".to_string(),
                 token: None,
+                released: None,
             }),
             ..Default::default()
         };
@@ -1220,11 +2263,94 @@ mod tests {
         assert!(expression.contains("self.spec.pool == oldSelf.spec.pool"));
     }
 
+    /// `kubectl get` shows what `status.phase` and friends are for. Declaring
+    /// any column drops the default `Age`, so each kind declares its own.
+    #[test]
+    fn crds_print_their_summary_columns() {
+        fn columns(crd: CustomResourceDefinition) -> Vec<(String, String)> {
+            crd.spec.versions[0]
+                .additional_printer_columns
+                .iter()
+                .flatten()
+                .map(|column| (column.name.clone(), column.json_path.clone()))
+                .collect()
+        }
+        let runner = columns(Runner::crd());
+        assert!(runner.contains(&("Phase".into(), ".status.phase".into())));
+        assert!(runner.contains(&("URL".into(), ".status.url".into())));
+        let workspace = columns(Workspace::crd());
+        assert!(workspace.contains(&(
+            "Ready".into(),
+            ".status.conditions[?(@.type==\"Ready\")].status".into()
+        )));
+        assert!(columns(Pool::crd()).contains(&("Warm".into(), ".status.warm".into())));
+        assert!(columns(Budget::crd()).contains(&("Used".into(), ".status.storage.used".into())));
+        for crd in [Runner::crd(), Workspace::crd(), Pool::crd(), Budget::crd()] {
+            assert!(columns(crd).iter().any(|(name, _)| name == "Age"));
+        }
+    }
+
+    #[test]
+    fn runner_phase_serializes_as_its_name() {
+        let status = RunnerStatus {
+            phase: Some(RunnerPhase::Hydrating),
+            ..Default::default()
+        };
+        let json = serde_json::to_value(&status).unwrap();
+        assert_eq!(json, serde_json::json!({"phase": "Hydrating"}));
+    }
+
+    /// Only a ready snapshot is a restore source: a half-written copy has no
+    /// manifest yet, and a restore from it would read as "never indexed".
+    #[test]
+    fn workspace_snapshot_restores_only_once_ready() {
+        let mut snapshot = WorkspaceSnapshot::new(
+            "v1",
+            WorkspaceSnapshotSpec {
+                workspace: "bmow-x".to_string(),
+            },
+        );
+        let condition = |status: &str| Condition {
+            last_transition_time: k8s_openapi::apimachinery::pkg::apis::meta::v1::Time(
+                k8s_openapi::jiff::Timestamp::UNIX_EPOCH,
+            ),
+            message: String::new(),
+            observed_generation: None,
+            reason: "Test".to_string(),
+            status: status.to_string(),
+            type_: "Ready".to_string(),
+        };
+        assert!(snapshot.restore_from().is_none());
+        snapshot.status = Some(WorkspaceSnapshotStatus {
+            conditions: Some(vec![condition("False")]),
+            bucket: Some("bucket".to_string()),
+            key_prefix: Some("ws/snapshots/uid/".to_string()),
+            ..Default::default()
+        });
+        assert!(snapshot.restore_from().is_none());
+        snapshot.status.as_mut().unwrap().conditions = Some(vec![condition("True")]);
+        let restore_from = snapshot.restore_from().unwrap();
+        assert_eq!(restore_from.bucket, "bucket");
+        assert_eq!(
+            restore_from.key_prefix.as_deref(),
+            Some("ws/snapshots/uid/")
+        );
+        assert!(restore_from.generation.is_none() && restore_from.at.is_none());
+    }
+
+    #[test]
+    fn workspace_snapshot_crd_pins_its_workspace() {
+        let crd = serde_json::to_string(&WorkspaceSnapshot::crd()).unwrap();
+        assert!(crd.contains("workspace snapshot is immutable"));
+        assert!(crd.contains("bmows"));
+    }
+
     #[test]
     fn budget_matches_label_superset() {
         let budget = BudgetSpec {
             selector: labels(&[("user", "alice")]),
             storage: None,
+            ..Default::default()
         };
         // a superset of the selector matches
         assert!(budget.matches(Some(&labels(&[("user", "alice"), ("team", "x")]))));
@@ -1235,4 +2361,36 @@ mod tests {
         assert!(!budget.matches(Some(&labels(&[("team", "x")]))));
         assert!(!budget.matches(None));
     }
+
+    #[test]
+    fn budget_expressions_and_with_the_label_map() {
+        let budget = BudgetSpec {
+            selector: labels(&[("org", "acme")]),
+            selector_expressions: Some(vec![
+                BudgetSelectorRequirement {
+                    key: "team".to_string(),
+                    operator: BudgetSelectorOperator::In,
+                    values: Some(vec!["a".to_string(), "b".to_string()]),
+                },
+                BudgetSelectorRequirement {
+                    key: "trial".to_string(),
+                    operator: BudgetSelectorOperator::DoesNotExist,
+                    values: None,
+                },
+            ]),
+            ..Default::default()
+        };
+        assert!(budget.matches(Some(&labels(&[("org", "acme"), ("team", "b")]))));
+        assert!(!budget.matches(Some(&labels(&[("org", "acme"), ("team", "c")]))));
+        assert!(!budget.matches(Some(&labels(&[("team", "a")]))));
+        assert!(!budget.matches(Some(&labels(&[
+            ("org", "acme"),
+            ("team", "a"),
+            ("trial", "yes")
+        ]))));
+        assert_eq!(
+            budget.label_selector().to_string(),
+            "org=acme,team in (a,b),!trial"
+        );
+    }
 }
@@ -0,0 +1,117 @@
+//! Kubernetes Events for kubimo's own state transitions.
+//!
+//! Conditions say where an object *is*; nothing said how it got there. A
+//! runner that vanished because it sat idle, a workspace whose storage was
+//! refused by its budget, a claim that fell back to a cold start — all of it
+//! was in the controller's log and nowhere a user with `kubectl describe`
+//! could see. Events put each transition on the object it happened to.
+//!
+//! Publishing is best-effort throughout: an event that cannot be written is
+//! logged and dropped, never surfaced as a reconcile error, because failing
+//! (and retrying) a deletion or a resize over its audit trail would be worse
+//! than a gap in that trail.
+
+use k8s_openapi::api::core::v1::ObjectReference;
+use kube::Resource;
+use kube::runtime::events::{Event, Recorder, Reporter};
+
+pub use kube::runtime::events::EventType;
+
+use crate::Client;
+
+/// The API server rejects an event whose note exceeds 1 kB, and the messages
+/// published here quote errors of arbitrary length.
+const MAX_NOTE_BYTES: usize = 1024;
+
+/// Publishes events as one reporting component.
+///
+/// Cheap to clone: clones share the recorder's series cache, so a transition
+/// repeated on every reconcile becomes one event with a growing count rather
+/// than a new event each time.
+#[derive(Clone)]
+pub struct EventRecorder {
+    recorder: Recorder,
+}
+
+impl EventRecorder {
+    /// Report as `client`'s field manager name, distinguished by `instance`
+    /// (the pod or node name) when several replicas report at once.
+    pub fn new(client: &Client, instance: Option<String>) -> Self {
+        let reporter = Reporter {
+            controller: client.name().to_string(),
+            instance,
+        };
+        Self {
+            recorder: Recorder::new(client.kube().clone(), reporter),
+        }
+    }
+
+    /// Record `reason` on `object`.
+    pub async fn publish<K>(
+        &self,
+        object: &K,
+        type_: EventType,
+        reason: &str,
+        action: &str,
+        note: impl Into<String>,
+    ) where
+        K: Resource<DynamicType = ()>,
+    {
+        self.publish_to(&object.object_ref(&()), type_, reason, action, note)
+            .await
+    }
+
+    /// Record `reason` on the object `reference` points at, for callers that
+    /// only hold a reference — or a name — rather than the object itself.
+    pub async fn publish_to(
+        &self,
+        reference: &ObjectReference,
+        type_: EventType,
+        reason: &str,
+        action: &str,
+        note: impl Into<String>,
+    ) {
+        let event = Event {
+            type_,
+            reason: reason.to_string(),
+            note: Some(truncate_note(note.into())),
+            action: action.to_string(),
+            secondary: None,
+        };
+        if let Err(err) = self.recorder.publish(&event, reference).await {
+            tracing::warn!(
+                %err,
+                reason,
+                name = reference.name.as_deref(),
+                namespace = reference.namespace.as_deref(),
+                "could not publish event"
+            );
+        }
+    }
+}
+
+fn truncate_note(mut note: String) -> String {
+    if note.len() > MAX_NOTE_BYTES {
+        let mut end = MAX_NOTE_BYTES;
+        while !note.is_char_boundary(end) {
+            end -= 1;
+        }
+        note.truncate(end);
+    }
+    note
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_long_note_is_cut_at_a_character_boundary() {
+        assert_eq!(truncate_note("short".into()), "short");
+
+        let long = "é".repeat(MAX_NOTE_BYTES);
+        let cut = truncate_note(long);
+        assert!(cut.len() <= MAX_NOTE_BYTES);
+        assert!(cut.chars().all(|c| c == 'é'));
+    }
+}
@@ -10,6 +10,8 @@ mod crd;
 // Same reasoning as `conditions`: the warm-pod-pool protocol strings are
 // matched byte-exactly by the controller and the node agent.
 mod error;
+#[cfg(all(feature = "client", feature = "runtime"))]
+mod events;
 mod factory;
 mod filter_params;
 mod label;
@@ -38,27 +40,40 @@ pub use api::{Api, ApiListStream};
 #[cfg(feature = "client")]
 pub use client::{Client, ClientBuilder};
 pub use crd::{
-    AutoScale, Budget, BudgetResourceStatus, BudgetSpec, BudgetStatus, CacheJob, CacheJobField,
-    CacheJobSpec, LogLevel, Pool, PoolSpec, PoolStatus, Requirement, Runner, RunnerClaim,
-    RunnerCommand, RunnerField, RunnerIngress, RunnerLifecycle, RunnerSpec, RunnerStatus,
-    RunnerTls, RunnerToken, StorageRequirement, Workspace, WorkspaceArchiveStatus, WorkspaceDir,
+    AutoScale, Budget, BudgetResourceStatus, BudgetSelectorOperator, BudgetSelectorRequirement,
+    BudgetSpec, BudgetStatus, CacheFormatResult, CacheJob, CacheJobField, CacheJobSchedule,
+    CacheJobSpec, CacheJobStatus, CacheNotebookResult, CacheReport, ContentCodec, LogLevel, Pool,
+    PoolAutoscale, PoolAutoscaleRecommendation, PoolAutoscaleStatus, PoolSpec, PoolStatus,
+    Requirement, Runner, RunnerActiveHours, RunnerClaim, RunnerCommand, RunnerField, RunnerIngress,
+    RunnerLifecycle, RunnerPhase, RunnerSpec, RunnerStatus, RunnerTls, RunnerToken,
+    StorageRequirement, Workspace, WorkspaceArchiveChunking, WorkspaceArchiveCompression,
+    WorkspaceArchiveGeneration, WorkspaceArchiveHistory, WorkspaceArchiveHydration,
+    WorkspaceArchiveLimits, WorkspaceArchiveStatus, WorkspaceDir, WorkspaceDirContentChunk,
     WorkspaceDirContentUrl, WorkspaceDirDirectory, WorkspaceDirEntry, WorkspaceDirField,
     WorkspaceDirFile, WorkspaceDirMarimo, WorkspaceDirMarimoCache, WorkspaceDirSpec,
-    WorkspaceDirSymlink, WorkspaceField, WorkspaceIndexer, WorkspaceIndexerPod, WorkspaceMode,
-    WorkspacePythonRuntime, WorkspaceRestoreFrom, WorkspaceRestoreSecrets, WorkspaceSlotStatus,
-    WorkspaceSpec, WorkspaceStatus, WorkspaceStorageStatus, all_crds,
+    WorkspaceDirSymlink, WorkspaceField, WorkspaceHydrationStatus, WorkspaceIndexer,
+    WorkspaceIndexerPod, WorkspaceMode, WorkspacePythonRuntime, WorkspaceRestoreFrom,
+    WorkspaceRestoreSecrets, WorkspaceSlotStatus, WorkspaceSnapshot, WorkspaceSnapshotField,
+    WorkspaceSnapshotSpec, WorkspaceSnapshotStatus, WorkspaceSpec, WorkspaceStatus,
+    WorkspaceStorageStatus, all_crds,
 };
 #[cfg(feature = "client")]
 pub use error::ClientBuildError;
 pub use error::{Error, Result};
+#[cfg(all(feature = "client", feature = "runtime"))]
+pub use events::{EventRecorder, EventType};
 pub use factory::ResourceFactory;
 pub use filter_params::FilterParams;
 pub use label::KubimoLabel;
 #[cfg(feature = "client")]
 pub use list_stream::{ApiListStreamExt, ListStream};
 pub use manifest::{
-    MANIFEST_FILE_NAME, ManifestDirectory, ManifestSecrets, ManifestVersion, SECRETS_FILE_NAME,
-    WorkspaceManifest, build_manifest, manifest_url, secrets_url,
+    ARCHIVE_URL_SCHEMES, CHUNKS_DIR_NAME, CONTENT_STORE_DIR_NAME, CONTENT_STORE_REFRESH_SECS,
+    HISTORY_DIR_NAME, MANIFEST_FILE_NAME, ManifestDirectory, ManifestEncryption, ManifestSecrets,
+    ManifestVersion, SECRETS_FILE_NAME, SNAPSHOTS_DIR_NAME, SnapshotSummary, WorkspaceManifest,
+    bucket_url, build_manifest, chunk_url, content_store_url, generation_name, generation_url,
+    history_url, is_content_store_url, manifest_url, parse_generation_name, secrets_url,
+    snapshot_key_prefix,
 };
 pub use meta::{ObjectMetaExt, ResourceNameExt, ResourceNamespaceExt, ResourceOwnerRefExt};
 pub use quantity::{CpuQuantity, CpuUnit, Quantity, StorageQuantity, StorageUnit};
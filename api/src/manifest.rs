@@ -1,10 +1,11 @@
+use chrono::{DateTime, NaiveDateTime, Utc};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use url::Url;
 
 use std::collections::BTreeMap;
 
-use crate::crd::{WorkspaceDir, WorkspaceDirEntry};
+use crate::crd::{WorkspaceDir, WorkspaceDirEntry, WorkspaceDirFile};
 
 /// Name of the manifest object under the indexer key prefix. Cannot collide
 /// with content keys, which are always exactly 13 base32 characters.
@@ -15,6 +16,47 @@ pub const MANIFEST_FILE_NAME: &str = "manifest.json";
 /// argument as [`MANIFEST_FILE_NAME`].
 pub const SECRETS_FILE_NAME: &str = "secrets.json";
 
+/// Directory under the indexer key prefix holding point-in-time copies of the
+/// manifest, one `{generation}.json` object per generation. Cannot collide with
+/// content keys either: those never contain a `/`.
+pub const HISTORY_DIR_NAME: &str = "history";
+
+/// Directory under the indexer key prefix holding snapshot copies of the
+/// archive, one `{uid}/` sub-prefix per `WorkspaceSnapshot`. Each is laid out
+/// exactly like an archive root, so it restores like one.
+pub const SNAPSHOTS_DIR_NAME: &str = "snapshots";
+
+/// Directory at the *bucket root* holding content-addressed objects, shared by
+/// every workspace that opts in with `spec.indexer.contentAddressed`. Keys are
+/// `{CONTENT_STORE_DIR_NAME}/sha256/{hex}`: the digest names the bytes, so two
+/// workspaces holding the same file hold one object between them.
+///
+/// Unlike everything above, nothing under here belongs to a single archive.
+/// No per-workspace sweep or purge may delete from it; only the bucket-wide
+/// collector does, and only what no manifest names.
+pub const CONTENT_STORE_DIR_NAME: &str = "cas";
+
+/// Content-store objects older than this are re-uploaded rather than reused.
+///
+/// The collector deletes unreferenced objects older than its grace period. A
+/// writer that found an old object and is about to name it in a manifest it
+/// has not written yet would otherwise lose that race. Refreshing keeps every
+/// object a writer reuses younger than this, so any grace period comfortably
+/// longer than it plus one sync is safe.
+pub const CONTENT_STORE_REFRESH_SECS: i64 = 6 * 60 * 60;
+
+const CONTENT_STORE_ALGORITHM: &str = "sha256";
+
+/// Directory under the indexer key prefix holding the chunks of the archive's
+/// chunked files, one object per distinct chunk. Shared by every file in the
+/// archive and by all its generations, which is how an unchanged chunk is
+/// stored once however many versions name it.
+pub const CHUNKS_DIR_NAME: &str = "chunks";
+
+/// Generation names are the UTC instant the copy was cut, fixed-width so that
+/// the bucket's lexical listing order is also chronological order.
+const GENERATION_FORMAT: &str = "%Y%m%dT%H%M%S%.3fZ";
+
 #[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 pub enum ManifestVersion {
     V1,
@@ -40,6 +82,67 @@ pub struct WorkspaceManifest {
     /// restore can tell the two apart.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub secrets: Option<ManifestSecrets>,
+    /// The data key this archive's sealed objects were encrypted with, itself
+    /// encrypted under a key-encryption key the bucket never sees.
+    ///
+    /// `None` means nothing in the archive is sealed. `Some` does not mean
+    /// everything is: each content url says for itself
+    /// ([`WorkspaceDirContentUrl::encrypted`]), because an archive that turned
+    /// encryption on keeps naming its plain objects until they are next
+    /// uploaded. Marimo meta and caches are never sealed.
+    ///
+    /// [`WorkspaceDirContentUrl::encrypted`]: crate::WorkspaceDirContentUrl::encrypted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encryption: Option<ManifestEncryption>,
+}
+
+impl WorkspaceManifest {
+    /// Every file content url the manifest names, chunks rather than the
+    /// file's own url for a chunked file. Marimo meta and cache urls are left
+    /// out: they are derived artifacts, rewritten in place and never restored,
+    /// so no older generation depends on them.
+    pub fn content_urls(&self) -> impl Iterator<Item = &Url> {
+        self.contents()
+            .flat_map(crate::crd::WorkspaceDirContentUrl::object_urls)
+    }
+
+    fn contents(&self) -> impl Iterator<Item = &crate::crd::WorkspaceDirContentUrl> {
+        self.directories
+            .iter()
+            .flat_map(|dir| dir.entries.iter())
+            .filter_map(|entry| entry.file.as_ref()?.content.as_ref())
+    }
+
+    /// Every object url the manifest names, derived artifacts included: what
+    /// keeps an object from being collected, as opposed to what a restore
+    /// reads.
+    pub fn urls(&self) -> impl Iterator<Item = &Url> {
+        self.directories
+            .iter()
+            .flat_map(|dir| dir.entries.iter())
+            .filter_map(|entry| entry.file.as_ref())
+            .flat_map(WorkspaceDirFile::urls)
+    }
+}
+
+/// What a snapshot copy holds. Written by the indexer as the snapshot Job's
+/// termination message and read back by the controller into the snapshot's
+/// status, so its serialized shape is a protocol between the two.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotSummary {
+    /// Files with content.
+    pub files: u64,
+    pub total_content_bytes: u64,
+}
+
+impl From<&WorkspaceManifest> for SnapshotSummary {
+    fn from(manifest: &WorkspaceManifest) -> Self {
+        Self {
+            files: manifest.contents().count() as u64,
+            total_content_bytes: manifest.total_content_bytes,
+        }
+    }
 }
 
 /// Names-only view of the archive's secrets.
@@ -54,6 +157,19 @@ pub struct ManifestSecrets {
     pub file_paths: Vec<String>,
 }
 
+/// A wrapped data key: what a restore needs, besides the key-encryption key
+/// itself, to open the archive's sealed objects.
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestEncryption {
+    /// Which key-encryption key wrapped the data key. Names an entry of the
+    /// keyring the workspace's credentials carry, never the key itself, so a
+    /// keyring holding retired keys can still open an old archive.
+    pub key_id: String,
+    /// The data key sealed under that key, base64.
+    pub wrapped_key: String,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct ManifestDirectory {
@@ -63,10 +179,35 @@ pub struct ManifestDirectory {
     pub entries: Vec<WorkspaceDirEntry>,
 }
 
+/// URL schemes an archive can live under, one per object-store backend.
+pub const ARCHIVE_URL_SCHEMES: &[&str] = &["s3", "gs", "az", "file"];
+
+/// The url of the root of `bucket`, which everything in an archive is keyed
+/// under. Always ends in `/`, so keys join onto it.
+///
+/// A bare name is an S3 bucket: that is what every `bucket` field held before
+/// there was any other backend, and what most of them still hold. A value
+/// with a scheme names its backend outright — `gs://bucket`, `az://container`,
+/// or `file:///srv/archive` for a directory the indexer can reach on disk.
+/// A scheme outside [`ARCHIVE_URL_SCHEMES`] still parses here; it is the
+/// indexer, which has to open a store for it, that refuses it.
+pub fn bucket_url(bucket: &str) -> Result<Url, url::ParseError> {
+    let mut url = if bucket.contains("://") {
+        Url::parse(bucket)?
+    } else {
+        Url::parse(&format!("s3://{bucket}/"))?
+    };
+    if !url.path().ends_with('/') {
+        let path = format!("{}/", url.path());
+        url.set_path(&path);
+    }
+    Ok(url)
+}
+
 /// The url of the manifest object for an archive, matching the indexer's raw
 /// `{prefix}{name}` key concatenation.
 pub fn manifest_url(bucket: &str, key_prefix: Option<&str>) -> Result<Url, url::ParseError> {
-    Url::parse(&format!("s3://{bucket}/"))?.join(&format!(
+    bucket_url(bucket)?.join(&format!(
         "{}{}",
         key_prefix.unwrap_or(""),
         MANIFEST_FILE_NAME
@@ -76,13 +217,111 @@ pub fn manifest_url(bucket: &str, key_prefix: Option<&str>) -> Result<Url, url::
 /// The url of the secrets object for an archive, same layout as
 /// [`manifest_url`].
 pub fn secrets_url(bucket: &str, key_prefix: Option<&str>) -> Result<Url, url::ParseError> {
-    Url::parse(&format!("s3://{bucket}/"))?.join(&format!(
+    bucket_url(bucket)?.join(&format!(
         "{}{}",
         key_prefix.unwrap_or(""),
         SECRETS_FILE_NAME
     ))
 }
 
+/// The url of the directory holding an archive's manifest generations, same
+/// layout as [`manifest_url`]. Ends in `/`, so it can be listed and joined.
+pub fn history_url(bucket: &str, key_prefix: Option<&str>) -> Result<Url, url::ParseError> {
+    bucket_url(bucket)?.join(&format!(
+        "{}{}/",
+        key_prefix.unwrap_or(""),
+        HISTORY_DIR_NAME
+    ))
+}
+
+/// The key prefix a snapshot's copy is written under, in the same raw
+/// `{prefix}{name}` layout as everything else in the archive.
+///
+/// Keyed by the snapshot's uid rather than its name: a snapshot deleted and
+/// recreated under the same name must not find the old copy and report it as
+/// its own.
+pub fn snapshot_key_prefix(key_prefix: Option<&str>, uid: &str) -> String {
+    format!("{}{SNAPSHOTS_DIR_NAME}/{uid}/", key_prefix.unwrap_or(""))
+}
+
+/// The url of the content-store object holding the bytes whose sha256 is
+/// `digest` (lowercase hex).
+pub fn content_store_url(bucket: &str, digest: &str) -> Result<Url, url::ParseError> {
+    bucket_url(bucket)?.join(&format!(
+        "{CONTENT_STORE_DIR_NAME}/{CONTENT_STORE_ALGORITHM}/{digest}"
+    ))
+}
+
+/// The url of the chunk object named `name`, under the archive's own prefix
+/// in the same raw `{prefix}{name}` layout as [`history_url`].
+pub fn chunk_url(
+    bucket: &str,
+    key_prefix: Option<&str>,
+    name: &str,
+) -> Result<Url, url::ParseError> {
+    bucket_url(bucket)?.join(&format!(
+        "{}{CHUNKS_DIR_NAME}/{name}",
+        key_prefix.unwrap_or("")
+    ))
+}
+
+/// Does `url` name a content-store object, rather than one owned by a single
+/// archive?
+///
+/// Strict on purpose: a workspace whose key prefix happens to be `cas/` still
+/// mints 13-character keys, and those must go on being swept like any other.
+///
+/// A `file://` root is a directory anywhere on disk, with no host to mark
+/// where the bucket ends, so there only the last three segments are checked.
+/// Callers that know the bucket compare against [`content_store_url`].
+pub fn is_content_store_url(url: &Url) -> bool {
+    if !ARCHIVE_URL_SCHEMES.contains(&url.scheme()) {
+        return false;
+    }
+    let path = url.path().trim_start_matches('/');
+    let segments: Vec<&str> = path.split('/').collect();
+    let store_key = match (url.scheme(), segments.as_slice()) {
+        ("file", [.., dir, algorithm, digest]) => [*dir, *algorithm, *digest],
+        (_, [dir, algorithm, digest]) => [*dir, *algorithm, *digest],
+        _ => return false,
+    };
+    matches!(
+        store_key,
+        [CONTENT_STORE_DIR_NAME, CONTENT_STORE_ALGORITHM, digest]
+            if digest.len() == 64
+                && digest.bytes().all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+    )
+}
+
+/// The url of the manifest generation cut at `created_at`.
+///
+/// Built from the timestamp rather than from a name so that nothing a client
+/// typed into `restoreFrom.generation` is ever spliced into a key: restore
+/// resolves such a name against the listing instead.
+pub fn generation_url(
+    bucket: &str,
+    key_prefix: Option<&str>,
+    created_at: DateTime<Utc>,
+) -> Result<Url, url::ParseError> {
+    history_url(bucket, key_prefix)?.join(&format!("{}.json", generation_name(created_at)))
+}
+
+/// The name of the generation cut at `at`, at millisecond resolution.
+pub fn generation_name(at: DateTime<Utc>) -> String {
+    at.format(GENERATION_FORMAT).to_string()
+}
+
+/// Inverse of [`generation_name`]. `None` for anything that is not one, so a
+/// stray object under the history directory is ignored rather than restored.
+pub fn parse_generation_name(name: &str) -> Option<DateTime<Utc>> {
+    let parsed = NaiveDateTime::parse_from_str(name, GENERATION_FORMAT)
+        .ok()?
+        .and_utc();
+    // Refuse anything that would not round-trip, so one generation has exactly
+    // one spelling.
+    (generation_name(parsed) == name).then_some(parsed)
+}
+
 /// Project the freshly indexed batch of workspace dirs into a manifest that
 /// fully describes the archive without the `WorkspaceDirectory` CRs.
 pub fn build_manifest(
@@ -90,6 +329,7 @@ pub fn build_manifest(
     upload_content: bool,
     dirs: &BTreeMap<String, WorkspaceDir>,
     secrets: ManifestSecrets,
+    encryption: Option<ManifestEncryption>,
 ) -> WorkspaceManifest {
     let mut directories = dirs
         .values()
@@ -119,6 +359,7 @@ pub fn build_manifest(
         // Always `Some`: this is what distinguishes a filtered archive from a
         // legacy one on restore.
         secrets: Some(secrets),
+        encryption,
     }
 }
 
@@ -127,6 +368,85 @@ mod tests {
     use super::*;
     use crate::crd::{WorkspaceDirContentUrl, WorkspaceDirEntry, WorkspaceDirFile};
 
+    const DIGEST: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+    #[test]
+    fn content_store_urls_sit_at_the_bucket_root() {
+        let url = content_store_url("bucket", DIGEST).unwrap();
+        assert_eq!(url.as_str(), format!("s3://bucket/cas/sha256/{DIGEST}"));
+        assert!(is_content_store_url(&url));
+    }
+
+    /// Everything that is not recognised as shared is swept like archive
+    /// content, so the check must not claim a workspace's own keys.
+    #[test]
+    fn only_well_formed_digests_are_content_store_urls() {
+        let not = |url: &str| !is_content_store_url(&url.parse().unwrap());
+        // A workspace keyed under `cas/` still mints 13-character keys.
+        assert!(not("s3://bucket/cas/abcdefghijklm.py"));
+        assert!(not("s3://bucket/cas/sha256/abcdefghijklm"));
+        assert!(not(&format!(
+            "s3://bucket/cas/sha256/{}",
+            DIGEST.to_uppercase()
+        )));
+        assert!(not(&format!("s3://bucket/cas/sha256/{DIGEST}/x")));
+        assert!(not(&format!("s3://bucket/ws/cas/sha256/{DIGEST}")));
+        assert!(not(&format!("http://bucket/cas/sha256/{DIGEST}")));
+    }
+
+    #[test]
+    fn the_bucket_scheme_picks_the_backend() {
+        let root = |bucket: &str| bucket_url(bucket).unwrap().to_string();
+        assert_eq!(root("bucket"), "s3://bucket/");
+        assert_eq!(root("s3://bucket"), "s3://bucket/");
+        assert_eq!(root("gs://bucket"), "gs://bucket/");
+        assert_eq!(root("az://container/"), "az://container/");
+        assert_eq!(root("file:///srv/archive"), "file:///srv/archive/");
+
+        let url = manifest_url("gs://bucket", Some("ws/")).unwrap();
+        assert_eq!(url.as_str(), "gs://bucket/ws/manifest.json");
+        let url = secrets_url("file:///srv/archive", Some("ws/")).unwrap();
+        assert_eq!(url.as_str(), "file:///srv/archive/ws/secrets.json");
+        let url = content_store_url("file:///srv/archive", DIGEST).unwrap();
+        assert_eq!(
+            url.as_str(),
+            format!("file:///srv/archive/cas/sha256/{DIGEST}")
+        );
+        assert!(is_content_store_url(&url));
+        let url = content_store_url("az://container", DIGEST).unwrap();
+        assert!(is_content_store_url(&url));
+    }
+
+    #[test]
+    fn snapshot_key_prefix_sits_beside_the_manifest() {
+        assert_eq!(
+            snapshot_key_prefix(Some("workspace/"), "0b5c"),
+            "workspace/snapshots/0b5c/"
+        );
+        assert_eq!(snapshot_key_prefix(None, "0b5c"), "snapshots/0b5c/");
+        // A copy is an archive root of its own: its manifest lands where a
+        // restore pointed at the prefix looks for it.
+        let prefix = snapshot_key_prefix(Some("workspace/"), "0b5c");
+        assert_eq!(
+            manifest_url("bucket", Some(&prefix)).unwrap().as_str(),
+            "s3://bucket/workspace/snapshots/0b5c/manifest.json"
+        );
+    }
+
+    #[test]
+    fn snapshot_summary_round_trips_in_camel_case() {
+        let summary = SnapshotSummary {
+            files: 3,
+            total_content_bytes: 42,
+        };
+        let json = serde_json::to_string(&summary).unwrap();
+        assert_eq!(json, r#"{"files":3,"totalContentBytes":42}"#);
+        assert_eq!(
+            serde_json::from_str::<SnapshotSummary>(&json).unwrap(),
+            summary
+        );
+    }
+
     #[test]
     fn test_manifest_url_with_prefix() {
         let url = manifest_url("bucket", Some("workspace/")).unwrap();
@@ -154,6 +474,112 @@ mod tests {
         assert_eq!(url.as_str(), "s3://bucket/secrets.json");
     }
 
+    #[test]
+    fn test_history_urls_sit_under_the_prefix() {
+        let url = history_url("bucket", Some("workspace/")).unwrap();
+        assert_eq!(url.as_str(), "s3://bucket/workspace/history/");
+        let at = DateTime::from_timestamp_millis(1_767_225_600_123).unwrap();
+        let url = generation_url("bucket", Some("workspace/"), at).unwrap();
+        assert_eq!(
+            url.as_str(),
+            "s3://bucket/workspace/history/20260101T000000.123Z.json"
+        );
+        let url = generation_url("bucket", None, at).unwrap();
+        assert_eq!(
+            url.as_str(),
+            "s3://bucket/history/20260101T000000.123Z.json"
+        );
+    }
+
+    /// The listing is sorted by key, and retention and restore-to-timestamp
+    /// both read it in that order.
+    #[test]
+    fn test_generation_names_sort_chronologically_and_round_trip() {
+        let earlier = DateTime::from_timestamp_millis(999_999_999_999).unwrap();
+        let later = DateTime::from_timestamp_millis(1_000_000_000_000).unwrap();
+        assert!(generation_name(earlier) < generation_name(later));
+        assert_eq!(parse_generation_name(&generation_name(later)), Some(later));
+        for name in [
+            "",
+            "latest",
+            "../manifest",
+            "20260101T000000Z",
+            "20260101T000000.1Z",
+        ] {
+            assert_eq!(parse_generation_name(name), None, "{name:?} parsed");
+        }
+    }
+
+    #[test]
+    fn test_content_urls_skip_marimo_artifacts() {
+        let manifest = WorkspaceManifest {
+            version: ManifestVersion::V1,
+            workspace: "ws".to_string(),
+            upload_content: true,
+            total_content_bytes: 1,
+            directories: vec![ManifestDirectory {
+                path: "".to_string(),
+                entries: vec![WorkspaceDirEntry {
+                    name: "notebook.py".to_string(),
+                    file: Some(WorkspaceDirFile {
+                        size: Some(1),
+                        content: Some(WorkspaceDirContentUrl {
+                            url: "s3://bucket/0123456789abc.py".parse().unwrap(),
+                            crc32: None,
+                            e_tag: None,
+                            encrypted: None,
+                            codec: None,
+                            chunks: None,
+                        }),
+                        marimo: Some(crate::crd::WorkspaceDirMarimo {
+                            meta_json: Some(WorkspaceDirContentUrl {
+                                url: "s3://bucket/0123456789abd.json".parse().unwrap(),
+                                crc32: None,
+                                e_tag: None,
+                                encrypted: None,
+                                codec: None,
+                                chunks: None,
+                            }),
+                            caches: None,
+                        }),
+                    }),
+                    ..Default::default()
+                }],
+            }],
+            secrets: None,
+            encryption: None,
+        };
+        assert_eq!(
+            manifest.content_urls().map(Url::as_str).collect::<Vec<_>>(),
+            vec!["s3://bucket/0123456789abc.py"]
+        );
+    }
+
+    /// A chunked file's own url names no object, so nothing that keeps or
+    /// reads objects may see it; its chunks stand in for it.
+    #[test]
+    fn test_content_urls_of_a_chunked_file_are_its_chunks() {
+        let chunk = |name: &str| crate::crd::WorkspaceDirContentChunk {
+            url: chunk_url("bucket", Some("ws/"), name).unwrap(),
+            size: 1,
+            crc32: 0,
+            e_tag: None,
+            codec: None,
+        };
+        let content = WorkspaceDirContentUrl {
+            url: "s3://bucket/ws/0123456789abc".parse().unwrap(),
+            crc32: None,
+            e_tag: None,
+            encrypted: None,
+            codec: None,
+            chunks: Some(vec![chunk("one"), chunk("two")]),
+        };
+        assert_eq!(
+            content.object_urls().map(Url::as_str).collect::<Vec<_>>(),
+            vec!["s3://bucket/ws/chunks/one", "s3://bucket/ws/chunks/two"]
+        );
+    }
+
     #[test]
     fn test_manifest_serde_round_trip() {
         let manifest = WorkspaceManifest {
@@ -171,6 +597,9 @@ mod tests {
                             url: "s3://bucket/workspace/0123456789abc.py".parse().unwrap(),
                             crc32: Some(7),
                             e_tag: None,
+                            encrypted: None,
+                            codec: None,
+                            chunks: None,
                         }),
                         marimo: None,
                     }),
@@ -181,6 +610,7 @@ mod tests {
                 env_keys: vec!["API_KEY".to_string()],
                 file_paths: vec!["creds/key.pem".to_string()],
             }),
+            encryption: None,
         };
 
         let json = serde_json::to_value(&manifest).unwrap();
@@ -249,6 +679,9 @@ mod build_manifest_tests {
                     url: "s3://bucket/0123456789abc".parse().unwrap(),
                     crc32: Some(7),
                     e_tag: None,
+                    encrypted: None,
+                    codec: None,
+                    chunks: None,
                 }),
                 marimo: None,
             }),
@@ -295,7 +728,7 @@ mod build_manifest_tests {
                 ],
             ),
         ]);
-        let manifest = build_manifest("ws", true, &dirs, ManifestSecrets::default());
+        let manifest = build_manifest("ws", true, &dirs, ManifestSecrets::default(), None);
         assert_eq!(manifest.directories[0].path, "");
         assert_eq!(manifest.directories[1].path, "sub");
         assert_eq!(manifest.directories[0].entries[0].name, "sub");
@@ -312,13 +745,19 @@ mod build_manifest_tests {
                 file_entry("too-big.bin", 5, false),
             ],
         )]);
-        let manifest = build_manifest("ws", true, &dirs, ManifestSecrets::default());
+        let manifest = build_manifest("ws", true, &dirs, ManifestSecrets::default(), None);
         assert_eq!(manifest.total_content_bytes, 10);
     }
 
     #[test]
     fn test_build_manifest_header() {
-        let manifest = build_manifest("ws", false, &BTreeMap::new(), ManifestSecrets::default());
+        let manifest = build_manifest(
+            "ws",
+            false,
+            &BTreeMap::new(),
+            ManifestSecrets::default(),
+            None,
+        );
         assert!(matches!(manifest.version, ManifestVersion::V1));
         assert_eq!(manifest.workspace, "ws");
         assert!(!manifest.upload_content);
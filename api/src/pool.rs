@@ -7,6 +7,7 @@
 //! labels, annotations or volume attributes — never as CLI flags, which older
 //! pinned images reject where unknown metadata is simply ignored.
 
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
 use crate::crd::WorkspaceRestoreSecrets;
@@ -66,6 +67,14 @@ pub const CLAIM_MARKER_ENV: &str = "KUBIMO_CLAIM_MARKER";
 /// subtree, so the indexer never uploads it.
 pub const CLAIM_MARKER_RELATIVE_PATH: &str = ".kubimo/claimed";
 
+/// Where, relative to the slot root, the runner asks for a file still being
+/// restored in the background to come next: one path per line, relative to
+/// `workspace/` or absolute as the pod sees it, a directory standing for
+/// everything under it. The agent reads and removes it; anything that can
+/// write the slot may ask. At the slot root rather than under `.kubimo/`,
+/// which a claim leaves owned by root.
+pub const HYDRATE_REQUESTS_RELATIVE_PATH: &str = ".hydrate-next";
+
 /// The payload of [`CLAIM_ANNOTATION`]: everything the agent needs to turn a
 /// pod's anonymous slot into the workspace's slot. Carries no credentials —
 /// the agent holds the S3 secret kubelet delivered at NodePublishVolume, which
@@ -87,6 +96,12 @@ pub struct PoolClaim {
     pub seed_key_prefix: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub seed_secrets: Option<WorkspaceRestoreSecrets>,
+    /// Restore point within the seed's archive history, from
+    /// `restoreFrom.generation`/`restoreFrom.at`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed_generation: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed_at: Option<DateTime<Utc>>,
     /// The workspace's `storage.max`, re-applied to the slot's XFS project
     /// before hydration.
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -122,6 +137,8 @@ mod tests {
             seed_bucket: Some("seeds".into()),
             seed_key_prefix: Some("template/".into()),
             seed_secrets: Some(WorkspaceRestoreSecrets::NamesOnly),
+            seed_generation: None,
+            seed_at: Some("2026-01-01T12:00:00Z".parse().unwrap()),
             limit_bytes: Some(64 << 30),
         };
         let json = serde_json::to_string(&claim).unwrap();
@@ -109,6 +109,13 @@ impl<T> Clone for Quantity<T> {
     }
 }
 
+/// Textual, like [`KubeQuantity`]'s own: `1Gi` and `1024Mi` differ.
+impl<T> PartialEq for Quantity<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.quantity == other.quantity
+    }
+}
+
 impl<T> Quantity<T> {
     pub fn new(value: impl Into<f64>, unit: T) -> Self
     where
@@ -158,6 +165,29 @@ impl Quantity<StorageUnit> {
     }
 }
 
+impl Quantity<CpuUnit> {
+    /// Normalize to millicores, so "1" and "1000m" compare equal. A bare
+    /// magnitude is cores. Returns `None` on malformed input.
+    pub fn to_millis(&self) -> Option<u64> {
+        let s = self.to_string();
+        let s = s.trim();
+        let millis = if let Ok(value) = s.parse::<f64>() {
+            value * 1000.0
+        } else {
+            let split = s.find(|c: char| c.is_ascii_alphabetic())?;
+            let value: f64 = s[..split].parse().ok()?;
+            match s[split..].parse::<CpuUnit>().ok()? {
+                CpuUnit::Core => value * 1000.0,
+                CpuUnit::Milli => value,
+            }
+        };
+        if !millis.is_finite() || millis < 0.0 || millis >= 2f64.powi(64) {
+            return None;
+        }
+        Some(millis as u64)
+    }
+}
+
 impl<T> FromStr for Quantity<T> {
     type Err = core::convert::Infallible;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
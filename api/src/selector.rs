@@ -1,4 +1,4 @@
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fmt;
 
 use strum::Display;
@@ -53,6 +53,22 @@ pub enum Expression {
     NotExists(String),
 }
 
+impl Expression {
+    /// Whether `labels` satisfy this expression, as the API server would judge
+    /// it: `!=` and `notin` also match an object without the key at all.
+    pub fn matches(&self, labels: Option<&BTreeMap<String, String>>) -> bool {
+        let value = |key: &String| labels.and_then(|labels| labels.get(key));
+        match self {
+            Self::Eq(key, expected) => value(key) == Some(expected),
+            Self::Neq(key, expected) => value(key) != Some(expected),
+            Self::In(key, values) => value(key).is_some_and(|value| values.contains(value)),
+            Self::NotIn(key, values) => value(key).is_none_or(|value| !values.contains(value)),
+            Self::Exists(key) => value(key).is_some(),
+            Self::NotExists(key) => value(key).is_none(),
+        }
+    }
+}
+
 impl<K, V> From<(K, V)> for Expression
 where
     K: ToString,
@@ -106,6 +122,18 @@ impl Selector {
     pub fn iter(&self) -> std::slice::Iter<'_, Expression> {
         self.0.iter()
     }
+
+    /// Whether `labels` satisfy every expression. An empty selector matches
+    /// everything, as it does when listing.
+    pub fn matches(&self, labels: Option<&BTreeMap<String, String>>) -> bool {
+        self.0.iter().all(|expr| expr.matches(labels))
+    }
+}
+
+impl Extend<Expression> for Selector {
+    fn extend<T: IntoIterator<Item = Expression>>(&mut self, iter: T) {
+        self.0.extend(iter)
+    }
 }
 
 impl From<&Selector> for Selector {
@@ -176,3 +204,46 @@ pub enum WellKnownField {
     #[strum(serialize = "metadata.namespace")]
     Namespace,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn set_based_expressions_match_like_the_api_server() {
+        let team = labels(&[("team", "a"), ("user", "alice")]);
+        let bare = labels(&[]);
+
+        assert!(Expr::new("team").in_(["a", "b"]).matches(Some(&team)));
+        assert!(!Expr::new("team").in_(["b"]).matches(Some(&team)));
+        assert!(!Expr::new("team").in_(["a"]).matches(Some(&bare)));
+
+        assert!(!Expr::new("team").not_in(["a"]).matches(Some(&team)));
+        assert!(Expr::new("team").not_in(["b"]).matches(Some(&team)));
+        // A missing key is not in any set.
+        assert!(Expr::new("team").not_in(["a"]).matches(Some(&bare)));
+        assert!(Expr::new("team").neq("a").matches(None));
+
+        assert!(Expr::new("user").exists().matches(Some(&team)));
+        assert!(Expr::new("user").not_exists().matches(Some(&bare)));
+    }
+
+    #[test]
+    fn a_selector_needs_every_expression() {
+        let selector: Selector = vec![
+            Expr::new("team").in_(["a", "b"]),
+            Expr::new("user").exists(),
+        ]
+        .into();
+        assert!(selector.matches(Some(&labels(&[("team", "b"), ("user", "bob")]))));
+        assert!(!selector.matches(Some(&labels(&[("team", "b")]))));
+        assert!(Selector::new().matches(None));
+    }
+}
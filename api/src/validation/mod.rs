@@ -15,10 +15,16 @@ pub fn workspace_max_storage_greater_than_min() -> Rule {
 
 pub fn budget_selector_not_empty() -> Rule {
     Rule::new(include_str!("./budget_selector_not_empty.cel"))
-        .message("budget selector must not be empty")
+        .message("budget selector must not be empty unless it has selectorExpressions or a parent")
         .field_path(".spec.selector")
 }
 
+pub fn budget_selector_expression_values() -> Rule {
+    Rule::new(include_str!("./budget_selector_expression_values.cel"))
+        .message("In and NotIn need values; Exists and DoesNotExist take none")
+        .field_path(".spec.selectorExpressions")
+}
+
 pub fn workspace_auto_scale_bounds() -> Rule {
     Rule::new(include_str!("./workspace_auto_scale_bounds.cel"))
         .message("storage auto-scale requires 0 < from < 1 and to > 1")
@@ -45,6 +51,14 @@ pub fn workspace_restore_from_not_indexer_prefix() -> Rule {
     .field_path(".spec.restoreFrom")
 }
 
+/// A generation names one point in time and `at` picks one; accepting both
+/// would leave the restore to guess which the client meant.
+pub fn workspace_restore_from_point_exclusive() -> Rule {
+    Rule::new(include_str!("./workspace_restore_from_point_exclusive.cel"))
+        .message("restoreFrom.generation and restoreFrom.at are mutually exclusive")
+        .field_path(".spec.restoreFrom")
+}
+
 /// A workspace that is Pooled — by spec *or* by materialized status — may never
 /// be told it is Dedicated.
 ///
@@ -146,6 +160,14 @@ pub fn runner_immutable_fields() -> Rule {
         .field_path(".spec.workspace")
 }
 
+/// A snapshot names one copy of one workspace; pointing it elsewhere after
+/// the fact would leave `status` describing a copy of something else.
+pub fn workspace_snapshot_immutable() -> Rule {
+    Rule::new(include_str!("./workspace_snapshot_immutable.cel"))
+        .message("workspace snapshot is immutable")
+        .field_path(".spec.workspace")
+}
+
 /// Render is excluded from pools: a renderer's slot is bound read-only at
 /// publish time, but a warm pod's anonymous slot is published read-write long
 /// before any Runner exists, so a pooled renderer would lose that guarantee.
@@ -189,6 +211,13 @@ pub fn pool_max_cpu_greater_than_min() -> Rule {
         .field_path(".spec.cpu.max")
 }
 
+/// An inverted range would leave the autoscaler nothing to pick from.
+pub fn pool_autoscale_max_greater_than_min() -> Rule {
+    Rule::new(include_str!("./pool_autoscale_max_greater_than_min.cel"))
+        .message("pool autoscale maxReplicas must be greater than or equal to minReplicas")
+        .field_path(".spec.autoscale.maxReplicas")
+}
+
 /// `max >= min`, for the same reason and in the same shape as
 /// [`workspace_max_storage_greater_than_min`].
 ///
@@ -233,14 +262,17 @@ mod tests {
         test_compiles(workspace_auto_scale_bounds());
         test_compiles(workspace_restore_from_exclusive());
         test_compiles(workspace_restore_from_not_indexer_prefix());
+        test_compiles(workspace_restore_from_point_exclusive());
         test_compiles(workspace_mode_no_downgrade());
         test_compiles(budget_selector_not_empty());
+        test_compiles(budget_selector_expression_values());
         test_compiles(workspace_no_volume_with_name());
         test_compiles(workspace_clone_not_pooled());
         test_compiles(workspace_no_new_dedicated());
         test_compiles(workspace_immutable_fields());
         test_compiles(workspace_python_runtime_exclusive());
         test_compiles(runner_immutable_fields());
+        test_compiles(workspace_snapshot_immutable());
         test_compiles(runner_max_memory_greater_than_min());
         test_compiles(runner_max_cpu_greater_than_min());
         test_compiles(pool_command_not_render());
@@ -248,6 +280,7 @@ mod tests {
         test_compiles(pool_immutable_fields());
         test_compiles(pool_max_memory_greater_than_min());
         test_compiles(pool_max_cpu_greater_than_min());
+        test_compiles(pool_autoscale_max_greater_than_min());
         test_compiles(log_level());
     }
 }
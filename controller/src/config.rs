@@ -104,6 +104,96 @@ impl Default for MetricsConfig {
     }
 }
 
+/// A controller-scheduled `indexer gc`: one CronJob collecting the
+/// unreachable objects of one bucket.
+///
+/// Off unless configured. Collection deletes, and what it may delete depends
+/// on every archive in the bucket having a readable manifest or CRs, which is
+/// a property of the deployment only its operator can vouch for.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveGcConfig {
+    /// Cron schedule, in the CronJob's own syntax.
+    pub schedule: String,
+    pub bucket: String,
+    /// Only collect under this raw key prefix.
+    #[serde(default)]
+    pub key_prefix: Option<String>,
+    /// Passed through as `--grace-secs`; the indexer's default when unset.
+    #[serde(default)]
+    pub grace_secs: Option<u64>,
+    /// Log what would be deleted instead of deleting it. Worth leaving on for
+    /// the first few runs against an existing bucket.
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Secret with the `AWS_*` credentials, mounted as `envFrom`.
+    #[serde(default)]
+    pub s3_secret_name: Option<String>,
+    /// Needs to list `WorkspaceDirectory` CRs cluster-wide; the chart creates
+    /// one that can.
+    pub service_account_name: String,
+}
+
+#[inline]
+fn default_usage_sample_interval_secs() -> u64 {
+    60
+}
+
+#[inline]
+fn default_usage_report_interval_secs() -> u64 {
+    3600
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum UsageReportFormat {
+    #[default]
+    Json,
+    Csv,
+}
+
+/// A periodic report of the usage accrued per Budget and per Workspace, for
+/// billing to ingest: one object per report, covering the time since the one
+/// before it.
+///
+/// Off unless configured. Usage is accrued in the controller's memory between
+/// reports, and the last partial report is written on shutdown; only a
+/// controller that dies without shutting down loses what it accrued since its
+/// last report.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageReportConfig {
+    /// A bucket name, which is S3, or an `s3://`, `gs://`, `az://` or
+    /// `file://` url, as for workspace archives.
+    pub bucket: String,
+    /// Reports are written under this raw key prefix.
+    #[serde(default)]
+    pub key_prefix: Option<String>,
+    #[serde(default)]
+    pub format: UsageReportFormat,
+    /// How much time each report covers.
+    #[serde(default = "default_usage_report_interval_secs")]
+    pub interval_secs: u64,
+}
+
+#[inline]
+fn default_activator_port() -> i32 {
+    80
+}
+
+/// Where suspended runners are routed: the activator's Service, which wakes a
+/// runner on its first request (see `activator/`).
+///
+/// Off unless configured, in which case a suspended runner keeps no Service or
+/// Ingress at all and its URL answers 404 until something resumes it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActivatorConfig {
+    /// The activator Service's in-cluster DNS name. Runner Ingresses can only
+    /// name a Service in their own namespace, so each suspended runner gets
+    /// an `ExternalName` Service pointing here.
+    pub host: String,
+    #[serde(default = "default_activator_port")]
+    pub port: i32,
+}
+
 fn deserialize_hosts<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
 where
     D: serde::Deserializer<'de>,
@@ -158,6 +248,22 @@ pub struct Config {
     /// in status on first reconcile, so flipping this is reversible.
     #[serde(default)]
     pub default_workspace_mode: WorkspaceMode,
+    #[serde(default)]
+    pub archive_gc: Option<ArchiveGcConfig>,
+    #[serde(default)]
+    pub activator: Option<ActivatorConfig>,
+    /// How often usage is sampled for the usage counters and report: what a
+    /// Budget or Workspace holds is assumed to hold until the next sample.
+    #[serde(default = "default_usage_sample_interval_secs")]
+    pub usage_sample_interval_secs: u64,
+    /// Credentials are read from the controller's own environment: `AWS_*`,
+    /// or `GOOGLE_*` / `AZURE_*` for a bucket on those backends.
+    #[serde(default)]
+    pub usage_report: Option<UsageReportConfig>,
+    /// This replica's pod name, reported as the instance on the events it
+    /// publishes. The chart sets it from the downward API.
+    #[serde(default)]
+    pub pod_name: Option<String>,
 }
 
 impl Config {
@@ -252,6 +358,53 @@ mod tests {
         assert_eq!(config.default_workspace_mode, WorkspaceMode::Pooled);
     }
 
+    #[test]
+    fn archive_gc_is_off_by_default_and_parses_from_env() {
+        assert!(load_from(&[]).unwrap().archive_gc.is_none());
+        let config = load_from(&[
+            ("KUBIMO__ARCHIVE_GC__SCHEDULE", "0 3 * * *"),
+            ("KUBIMO__ARCHIVE_GC__BUCKET", "bucket"),
+            ("KUBIMO__ARCHIVE_GC__GRACE_SECS", "172800"),
+            ("KUBIMO__ARCHIVE_GC__DRY_RUN", "true"),
+            ("KUBIMO__ARCHIVE_GC__SERVICE_ACCOUNT_NAME", "kubimo-gc"),
+        ])
+        .unwrap();
+        let gc = config.archive_gc.unwrap();
+        assert_eq!(gc.schedule, "0 3 * * *");
+        assert_eq!(gc.grace_secs, Some(172800));
+        assert!(gc.dry_run);
+        assert_eq!(gc.key_prefix, None);
+    }
+
+    #[test]
+    fn activator_is_off_by_default_and_parses_from_env() {
+        assert!(load_from(&[]).unwrap().activator.is_none());
+        let config = load_from(&[(
+            "KUBIMO__ACTIVATOR__HOST",
+            "kubimo-activator.kubimo.svc.cluster.local",
+        )])
+        .unwrap();
+        let activator = config.activator.unwrap();
+        assert_eq!(activator.host, "kubimo-activator.kubimo.svc.cluster.local");
+        assert_eq!(activator.port, 80);
+    }
+
+    #[test]
+    fn usage_report_is_off_by_default_and_parses_from_env() {
+        let config = load_from(&[]).unwrap();
+        assert!(config.usage_report.is_none());
+        assert_eq!(config.usage_sample_interval_secs, 60);
+        let config = load_from(&[
+            ("KUBIMO__USAGE_REPORT__BUCKET", "billing"),
+            ("KUBIMO__USAGE_REPORT__FORMAT", "csv"),
+        ])
+        .unwrap();
+        let report = config.usage_report.unwrap();
+        assert_eq!(report.bucket, "billing");
+        assert_eq!(report.format, UsageReportFormat::Csv);
+        assert_eq!(report.interval_secs, 3600);
+    }
+
     #[test]
     fn default_workspace_mode_rejects_unknown_value() {
         assert!(load_from(&[("KUBIMO__DEFAULT_WORKSPACE_MODE", "Nonsense")]).is_err());
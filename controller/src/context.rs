@@ -1,11 +1,16 @@
 use std::ops::Deref;
 
+use kubimo::EventRecorder;
+
 use crate::Config;
 
 #[derive(Clone)]
 pub struct Context {
     pub client: kubimo::Client,
     pub config: Config,
+    /// Shared by every reconciler, so a transition repeated across
+    /// reconciles folds into one event series instead of a new event each.
+    pub events: EventRecorder,
 }
 
 impl Deref for Context {
@@ -18,6 +23,11 @@ impl Deref for Context {
 
 impl Context {
     pub fn new(client: kubimo::Client, config: Config) -> Self {
-        Self { client, config }
+        let events = EventRecorder::new(&client, config.pod_name.clone());
+        Self {
+            client,
+            config,
+            events,
+        }
     }
 }
@@ -0,0 +1,175 @@
+//! The optional CronJob running `indexer gc` against the archive bucket.
+//!
+//! Not a reconciler: there is no resource to reconcile, only configuration,
+//! and that only changes with a controller rollout. So the CronJob is applied
+//! once at startup, and removed again at startup once the configuration no
+//! longer asks for it.
+
+use kubimo::k8s_openapi::api::batch::v1::{CronJob, CronJobSpec, JobSpec, JobTemplateSpec};
+use kubimo::k8s_openapi::api::core::v1::{
+    Container, EnvFromSource, PodSpec, PodTemplateSpec, SecretEnvSource,
+};
+use kubimo::kube::api::ObjectMeta;
+
+use crate::command::cmd;
+use crate::config::ArchiveGcConfig;
+use crate::context::Context;
+use crate::controllers::indexer;
+
+#[inline]
+fn cron_job_name(manager_name: &str) -> String {
+    format!("{manager_name}-archive-gc")
+}
+
+fn gc_args(config: &ArchiveGcConfig) -> Vec<String> {
+    let mut args = cmd!["gc", "--bucket", config.bucket];
+    if let Some(key_prefix) = config.key_prefix.as_ref() {
+        args.extend(cmd!["--key-prefix", key_prefix]);
+    }
+    if let Some(grace_secs) = config.grace_secs {
+        args.extend(cmd!["--grace-secs", grace_secs.to_string()]);
+    }
+    if config.dry_run {
+        args.extend(cmd!["--dry-run"]);
+    }
+    args
+}
+
+fn cron_job(image: &str, namespace: &str, name: String, config: &ArchiveGcConfig) -> CronJob {
+    CronJob {
+        metadata: ObjectMeta {
+            name: Some(name),
+            namespace: Some(namespace.to_string()),
+            ..Default::default()
+        },
+        spec: Some(CronJobSpec {
+            schedule: config.schedule.clone(),
+            // Two collections at once would each delete what the other is
+            // still marking from, which is harmless but pointless work.
+            concurrency_policy: Some("Forbid".to_string()),
+            job_template: JobTemplateSpec {
+                spec: Some(JobSpec {
+                    // A failed collection is retried by the next schedule,
+                    // which reads the bucket afresh anyway.
+                    backoff_limit: Some(0),
+                    template: PodTemplateSpec {
+                        spec: Some(PodSpec {
+                            restart_policy: Some("Never".into()),
+                            service_account_name: Some(config.service_account_name.clone()),
+                            containers: vec![Container {
+                                name: "indexer".to_string(),
+                                image: Some(image.to_string()),
+                                command: Some(cmd!["/app/indexer"]),
+                                args: Some(gc_args(config)),
+                                env: indexer::pod_env(None),
+                                env_from: config.s3_secret_name.as_ref().map(|name| {
+                                    vec![EnvFromSource {
+                                        secret_ref: Some(SecretEnvSource {
+                                            name: name.clone(),
+                                            ..Default::default()
+                                        }),
+                                        ..Default::default()
+                                    }]
+                                }),
+                                ..Default::default()
+                            }],
+                            ..Default::default()
+                        }),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+/// Bring the CronJob in line with `ctx.config.archive_gc`, in the namespace
+/// the controller runs in.
+pub async fn apply(ctx: &Context) -> Result<(), kubimo::Error> {
+    let namespace = ctx.kube().default_namespace();
+    let name = cron_job_name(&ctx.config.manager_name);
+    let api = ctx.api_namespaced::<CronJob>(namespace);
+    match ctx.config.archive_gc.as_ref() {
+        Some(config) => {
+            api.patch(&cron_job(&ctx.config.marimo_image, namespace, name, config))
+                .await?;
+        }
+        None => {
+            api.delete_opt(&name).await?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> ArchiveGcConfig {
+        ArchiveGcConfig {
+            schedule: "0 3 * * *".to_string(),
+            bucket: "bucket".to_string(),
+            key_prefix: None,
+            grace_secs: None,
+            dry_run: false,
+            s3_secret_name: Some("s3".to_string()),
+            service_account_name: "kubimo-archive-gc".to_string(),
+        }
+    }
+
+    /// Unset options are left off the command line so the indexer's own
+    /// defaults apply, rather than a copy of them that could drift.
+    #[test]
+    fn gc_args_only_carry_what_is_configured() {
+        assert_eq!(gc_args(&config()), vec!["gc", "--bucket", "bucket"]);
+        assert_eq!(
+            gc_args(&ArchiveGcConfig {
+                key_prefix: Some("workspace/".to_string()),
+                grace_secs: Some(172800),
+                dry_run: true,
+                ..config()
+            }),
+            vec![
+                "gc",
+                "--bucket",
+                "bucket",
+                "--key-prefix",
+                "workspace/",
+                "--grace-secs",
+                "172800",
+                "--dry-run",
+            ]
+        );
+    }
+
+    #[test]
+    fn cron_job_runs_the_indexer_as_the_gc_service_account() {
+        let cron_job = cron_job(
+            "marimo:tag",
+            "kubimo",
+            "kubimo-archive-gc".into(),
+            &config(),
+        );
+        let spec = cron_job.spec.unwrap();
+        assert_eq!(spec.concurrency_policy.as_deref(), Some("Forbid"));
+        let pod = spec.job_template.spec.unwrap().template.spec.unwrap();
+        assert_eq!(
+            pod.service_account_name.as_deref(),
+            Some("kubimo-archive-gc")
+        );
+        let container = &pod.containers[0];
+        assert_eq!(container.image.as_deref(), Some("marimo:tag"));
+        assert_eq!(
+            container.env_from.as_ref().unwrap()[0]
+                .secret_ref
+                .as_ref()
+                .unwrap()
+                .name,
+            "s3"
+        );
+    }
+}
@@ -1,3 +1,9 @@
+mod soft_limit;
+mod tree;
+
+pub(crate) use soft_limit::{read_only_condition, upsert_condition};
+pub(crate) use tree::BudgetTree;
+
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -8,10 +14,12 @@ use kubimo::k8s_openapi::apimachinery::pkg::apis::meta::v1::{Condition, Time};
 use kubimo::k8s_openapi::jiff::Timestamp;
 use kubimo::kube::runtime::{Controller, controller::Action};
 use kubimo::{
-    Budget, BudgetResourceStatus, BudgetStatus, FilterParams, Selector, StorageQuantity,
-    StorageUnit, Workspace, prelude::*,
+    Budget, BudgetResourceStatus, BudgetSpec, BudgetStatus, CpuQuantity, CpuUnit, FilterParams,
+    Runner, Selector, StorageQuantity, StorageUnit, Workspace, prelude::*,
 };
 
+use soft_limit::{apply_read_only, soft_limit_conditions, soft_limit_events};
+
 use crate::backoff::default_error_policy;
 use crate::context::Context;
 use crate::controllers::workspace::{BUDGET_EXCEEDED_REASON, pvc_storage_request};
@@ -23,6 +31,9 @@ use crate::reconciler::{ReconcileError, Reconciler, ReconcilerExt};
 const REFRESH_INTERVAL: Duration = Duration::from_secs(30);
 
 const EXCEEDED: &str = "Exceeded";
+/// Only reported by a Budget with a `parent`: whether its whole chain of
+/// ancestors could be found.
+const PARENT_RESOLVED: &str = "ParentResolved";
 
 #[derive(Debug, Clone, Copy)]
 struct BudgetReconciler;
@@ -34,35 +45,127 @@ impl Reconciler for BudgetReconciler {
 
     async fn apply(&self, ctx: &Context, budget: &Budget) -> Result<Action, Self::Error> {
         let namespace = budget.require_namespace()?;
-        let used =
-            sum_workspace_storage(ctx, namespace, &budget.spec.label_selector(), None).await?;
+        let tree = BudgetTree::load(ctx, namespace).await?.with(budget);
+        let selector = tree.selector(budget);
+        let used = sum_workspace_storage(ctx, namespace, &selector, None).await?;
         let limit = budget.spec.storage.clone();
+        let runners = sum_runner_usage(ctx, namespace, &selector, None).await?;
         let exceeded = limit
             .as_ref()
             .and_then(StorageQuantity::to_bytes)
-            .is_some_and(|limit| used > limit);
+            .is_some_and(|limit| used > limit)
+            || runners.exceeds(&budget.spec);
+
+        let (chain, broken) = tree.chain(budget);
+        let constrained_by = constrained_by(ctx, namespace, &tree, &chain, used).await?;
+
+        let now = Timestamp::now();
+        let mut conditions = vec![exceeded_condition(budget, exceeded, now)];
+        conditions.extend(soft_limit_conditions(budget, used, now));
+        if let Some(parent) = budget.spec.parent.as_deref() {
+            conditions.push(match &broken {
+                Some(problem) => budget_condition(
+                    budget,
+                    PARENT_RESOLVED,
+                    "False",
+                    "ParentNotFound",
+                    format!("{problem}; the chain stops below it"),
+                    now,
+                ),
+                None => budget_condition(
+                    budget,
+                    PARENT_RESOLVED,
+                    "True",
+                    "Resolved",
+                    format!("Nested under Budget {parent}"),
+                    now,
+                ),
+            });
+        }
+        let events = soft_limit_events(budget, &conditions);
 
         let mut patched = budget.clone();
         patched.status = Some(BudgetStatus {
-            conditions: Some(vec![exceeded_condition(budget, exceeded)]),
+            conditions: Some(conditions),
             storage: Some(BudgetResourceStatus {
                 used: Some(StorageQuantity::new(used as f64, StorageUnit::B)),
                 limit,
             }),
+            cpu: Some(BudgetResourceStatus {
+                used: Some(CpuQuantity::new(runners.cpu_millis as f64, CpuUnit::Milli)),
+                limit: budget.spec.cpu.clone(),
+            }),
+            memory: Some(BudgetResourceStatus {
+                used: Some(StorageQuantity::new(
+                    runners.memory_bytes as f64,
+                    StorageUnit::B,
+                )),
+                limit: budget.spec.memory.clone(),
+            }),
+            runners: Some(BudgetResourceStatus {
+                used: Some(runners.runners),
+                limit: budget.spec.runners,
+            }),
+            claims: Some(BudgetResourceStatus {
+                used: Some(runners.claims),
+                limit: budget.spec.claims,
+            }),
+            constrained_by,
         });
         ctx.api_namespaced::<Budget>(namespace)
             .patch_status(&patched)
             .await?;
+        for (type_, reason, note) in events {
+            ctx.events
+                .publish(budget, type_, reason, "Reconcile", note)
+                .await;
+        }
+        // Also what ends a grace period: nothing else wakes the Budget when
+        // it runs out, so it is noticed on the refresh after.
+        apply_read_only(ctx, &patched).await?;
         Ok(Action::requeue(REFRESH_INTERVAL))
     }
 }
 
+/// Of `chain` — a Budget and its ancestors, nearest first, the first using
+/// `used` bytes — the one with the least storage left. Ties go to the
+/// nearest, being the more specific.
+async fn constrained_by(
+    ctx: &Context,
+    namespace: &str,
+    tree: &BudgetTree,
+    chain: &[&Budget],
+    used: u64,
+) -> Result<Option<String>, kubimo::Error> {
+    let mut tightest: Option<(u64, &Budget)> = None;
+    for (depth, member) in chain.iter().enumerate() {
+        let Some(limit) = member
+            .spec
+            .storage
+            .as_ref()
+            .and_then(StorageQuantity::to_bytes)
+        else {
+            continue;
+        };
+        let member_used = if depth == 0 {
+            used
+        } else {
+            sum_workspace_storage(ctx, namespace, &tree.selector(member), None).await?
+        };
+        let headroom = limit.saturating_sub(member_used);
+        if tightest.is_none_or(|(least, _)| headroom < least) {
+            tightest = Some((headroom, member));
+        }
+    }
+    Ok(tightest.map(|(_, budget)| budget.name_any()))
+}
+
 /// Committed storage (bytes) a Workspace contributes to its budget: its bound
 /// PVC request if it has one, otherwise its configured `spec.storage.min` —
 /// except a refused Workspace reserves nothing (it has no PVC and will not
 /// provision until budget frees, so counting its minimum would inflate usage
 /// and headroom for storage that is never allocated).
-fn workspace_committed_bytes(
+pub(crate) fn workspace_committed_bytes(
     workspace: &Workspace,
     mode: WorkspaceMode,
     pvc_request: Option<u64>,
@@ -157,28 +260,29 @@ pub(crate) async fn sum_workspace_storage(
     Ok(total)
 }
 
+/// How much storage a Workspace may hold, and which Budget says so.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct StorageAllowance {
+    pub(crate) bytes: u64,
+    /// The Budget with the least room left, named in a refusal.
+    pub(crate) budget: String,
+}
+
 /// Maximum total storage (bytes) the given Workspace may hold without breaching
-/// any Budget that governs it. `None` means unbounded (no matching budget).
+/// any Budget that governs it — nested ones included, as each ancestor governs
+/// it too. `None` means unbounded (no matching budget).
 pub(crate) async fn workspace_storage_allowance(
     ctx: &Context,
     workspace: &Workspace,
-) -> Result<Option<u64>, kubimo::Error> {
+) -> Result<Option<StorageAllowance>, kubimo::Error> {
     let namespace = workspace.require_namespace()?;
     let name = workspace.name()?;
     let labels = workspace.metadata.labels.as_ref();
 
-    let budgets: Vec<Budget> = ctx
-        .api_namespaced::<Budget>(namespace)
-        .list(&FilterParams::new())
-        .map_ok(|item| item.item)
-        .try_collect()
-        .await?;
+    let tree = BudgetTree::load(ctx, namespace).await?;
 
-    let mut allowance: Option<u64> = None;
-    for budget in &budgets {
-        if !budget.spec.matches(labels) {
-            continue;
-        }
+    let mut allowance: Option<StorageAllowance> = None;
+    for budget in tree.governing(namespace, labels) {
         let Some(cap) = budget
             .spec
             .storage
@@ -188,17 +292,225 @@ pub(crate) async fn workspace_storage_allowance(
             continue;
         };
         let others =
-            sum_workspace_storage(ctx, namespace, &budget.spec.label_selector(), Some(name))
-                .await?;
+            sum_workspace_storage(ctx, namespace, &tree.selector(budget), Some(name)).await?;
         let headroom = cap.saturating_sub(others);
-        allowance = Some(allowance.map_or(headroom, |current| current.min(headroom)));
+        if allowance
+            .as_ref()
+            .is_none_or(|current| headroom < current.bytes)
+        {
+            allowance = Some(StorageAllowance {
+                bytes: headroom,
+                budget: budget.name_any(),
+            });
+        }
     }
     Ok(allowance)
 }
 
+/// What Runners hold against a budget's compute limits.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct RunnerUsage {
+    pub(crate) cpu_millis: u64,
+    pub(crate) memory_bytes: u64,
+    pub(crate) runners: u32,
+    pub(crate) claims: u32,
+}
+
+impl RunnerUsage {
+    fn add(self, other: Self) -> Self {
+        Self {
+            cpu_millis: self.cpu_millis.saturating_add(other.cpu_millis),
+            memory_bytes: self.memory_bytes.saturating_add(other.memory_bytes),
+            runners: self.runners.saturating_add(other.runners),
+            claims: self.claims.saturating_add(other.claims),
+        }
+    }
+
+    /// The limit of `spec` this usage is over, as a refusal message.
+    fn over(&self, spec: &BudgetSpec) -> Option<String> {
+        if let Some(limit) = spec.cpu.as_ref().and_then(CpuQuantity::to_millis)
+            && self.cpu_millis > limit
+        {
+            return Some(format!(
+                "requested CPU {}m exceeds the budget's {limit}m",
+                self.cpu_millis
+            ));
+        }
+        if let Some(limit) = spec.memory.as_ref().and_then(StorageQuantity::to_bytes)
+            && self.memory_bytes > limit
+        {
+            return Some(format!(
+                "requested memory {} bytes exceeds the budget's {limit} bytes",
+                self.memory_bytes
+            ));
+        }
+        if let Some(limit) = spec.runners
+            && self.runners > limit
+        {
+            return Some(format!(
+                "{} runners exceed the budget's {limit}",
+                self.runners
+            ));
+        }
+        None
+    }
+
+    fn exceeds(&self, spec: &BudgetSpec) -> bool {
+        self.over(spec).is_some() || spec.claims.is_some_and(|limit| self.claims > limit)
+    }
+}
+
+/// What `runner` asks of its budgets once started: its CPU and memory
+/// requests, and one runner. A requirement with only a `max` counts the
+/// `max`, since that is what Kubernetes requests for it too.
+pub(crate) fn runner_demand(runner: &Runner) -> RunnerUsage {
+    let request = |min: Option<u64>, max: Option<u64>| min.or(max).unwrap_or(0);
+    let cpu = runner.spec.cpu.as_ref();
+    let memory = runner.effective_memory();
+    RunnerUsage {
+        cpu_millis: request(
+            cpu.and_then(|cpu| cpu.min.as_ref())
+                .and_then(CpuQuantity::to_millis),
+            cpu.and_then(|cpu| cpu.max.as_ref())
+                .and_then(CpuQuantity::to_millis),
+        ),
+        memory_bytes: request(
+            memory
+                .as_ref()
+                .and_then(|memory| memory.min.as_ref())
+                .and_then(StorageQuantity::to_bytes),
+            memory
+                .as_ref()
+                .and_then(|memory| memory.max.as_ref())
+                .and_then(StorageQuantity::to_bytes),
+        ),
+        runners: 1,
+        claims: 0,
+    }
+}
+
+/// What `runner` holds against its budgets right now. A suspended or
+/// deleting runner holds nothing, and neither does one its budget refused —
+/// like a refused Workspace, it has no pod and will not get one until
+/// another runner frees room, so counting it would only refuse more.
+fn runner_usage(runner: &Runner) -> RunnerUsage {
+    if runner.spec.is_suspended()
+        || runner.metadata.deletion_timestamp.is_some()
+        || is_runner_refused(runner)
+    {
+        return RunnerUsage::default();
+    }
+    let claimed = runner
+        .status
+        .as_ref()
+        .and_then(|status| status.claim.as_ref())
+        .is_some_and(|claim| !claim.is_released());
+    RunnerUsage {
+        claims: claimed.into(),
+        ..runner_demand(runner)
+    }
+}
+
+fn is_runner_refused(runner: &Runner) -> bool {
+    runner
+        .status
+        .as_ref()
+        .and_then(|status| status.conditions.as_ref())
+        .is_some_and(|conditions| {
+            conditions.iter().any(|cond| {
+                cond.type_ == kubimo::conditions::BUDGET_EXCEEDED && cond.status == "True"
+            })
+        })
+}
+
+/// Sum the usage of every Runner matching `selector` (see [`runner_usage`]).
+/// Optionally excludes one runner by name (used when admitting that runner).
+pub(crate) async fn sum_runner_usage(
+    ctx: &Context,
+    namespace: &str,
+    selector: &Selector,
+    exclude: Option<&str>,
+) -> Result<RunnerUsage, kubimo::Error> {
+    let runners: Vec<Runner> = ctx
+        .api_namespaced::<Runner>(namespace)
+        .list(&FilterParams::new().with_labels(selector.clone()))
+        .map_ok(|item| item.item)
+        .try_collect()
+        .await?;
+    Ok(runners
+        .iter()
+        .filter(|runner| exclude.is_none() || runner.name().ok() != exclude)
+        .map(runner_usage)
+        .fold(RunnerUsage::default(), RunnerUsage::add))
+}
+
+/// Every Budget governing `runner`, each with what the other Runners it
+/// governs already hold.
+async fn runner_budgets(
+    ctx: &Context,
+    runner: &Runner,
+) -> Result<Vec<(Budget, RunnerUsage)>, kubimo::Error> {
+    let namespace = runner.require_namespace()?;
+    let name = runner.name()?;
+    let labels = runner.metadata.labels.as_ref();
+
+    let tree = BudgetTree::load(ctx, namespace).await?;
+
+    let mut governing = Vec::new();
+    for budget in tree.governing(namespace, labels) {
+        let spec = &budget.spec;
+        if spec.cpu.is_none()
+            && spec.memory.is_none()
+            && spec.runners.is_none()
+            && spec.claims.is_none()
+        {
+            continue;
+        }
+        let others = sum_runner_usage(ctx, namespace, &tree.selector(budget), Some(name)).await?;
+        governing.push((budget.clone(), others));
+    }
+    Ok(governing)
+}
+
+/// Why `runner` may not start, if some Budget governing it has no room left
+/// for its CPU, memory or one more runner.
+pub(crate) async fn runner_refusal(
+    ctx: &Context,
+    runner: &Runner,
+) -> Result<Option<String>, kubimo::Error> {
+    let demand = runner_demand(runner);
+    Ok(runner_budgets(ctx, runner)
+        .await?
+        .into_iter()
+        .find_map(|(budget, others)| {
+            let reason = others.add(demand).over(&budget.spec)?;
+            Some(format!("{reason} (Budget {})", budget.name_any()))
+        }))
+}
+
+/// Why `runner` may not claim a warm pod, if some Budget governing it has
+/// no claims left.
+pub(crate) async fn runner_claim_refusal(
+    ctx: &Context,
+    runner: &Runner,
+) -> Result<Option<String>, kubimo::Error> {
+    Ok(runner_budgets(ctx, runner)
+        .await?
+        .into_iter()
+        .find_map(|(budget, others)| {
+            let limit = budget.spec.claims?;
+            (others.claims >= limit).then(|| {
+                format!(
+                    "the budget's claim limit of {limit} is reached (Budget {})",
+                    budget.name_any()
+                )
+            })
+        }))
+}
+
 /// `Exceeded` condition, preserving the previous transition time when the status
 /// is unchanged.
-fn exceeded_condition(budget: &Budget, exceeded: bool) -> Condition {
+fn exceeded_condition(budget: &Budget, exceeded: bool, now: Timestamp) -> Condition {
     let (status, reason, message) = if exceeded {
         (
             "True",
@@ -212,14 +524,27 @@ fn exceeded_condition(budget: &Budget, exceeded: bool) -> Condition {
             "Budget usage is within the configured limit",
         )
     };
+    budget_condition(budget, EXCEEDED, status, reason, message, now)
+}
+
+/// A condition of `budget`'s, keeping the transition time it last reported
+/// for `type_` while `status` is unchanged.
+fn budget_condition(
+    budget: &Budget,
+    type_: &str,
+    status: &str,
+    reason: &str,
+    message: impl Into<String>,
+    now: Timestamp,
+) -> Condition {
     let previous = budget
         .status
         .as_ref()
         .and_then(|status| status.conditions.as_ref())
-        .and_then(|conditions| conditions.iter().find(|cond| cond.type_ == EXCEEDED));
+        .and_then(|conditions| conditions.iter().find(|cond| cond.type_ == type_));
     let last_transition_time = match previous {
         Some(previous) if previous.status == status => previous.last_transition_time.clone(),
-        _ => Time(Timestamp::now()),
+        _ => Time(now),
     };
     Condition {
         last_transition_time,
@@ -227,10 +552,22 @@ fn exceeded_condition(budget: &Budget, exceeded: bool) -> Condition {
         message: message.into(),
         reason: reason.into(),
         status: status.into(),
-        type_: EXCEEDED.into(),
+        type_: type_.into(),
     }
 }
 
+/// Every Budget in `namespace`.
+pub(crate) async fn namespace_budgets(
+    ctx: &Context,
+    namespace: &str,
+) -> Result<Vec<Budget>, kubimo::Error> {
+    ctx.api_namespaced::<Budget>(namespace)
+        .list(&FilterParams::new())
+        .map_ok(|item| item.item)
+        .try_collect()
+        .await
+}
+
 pub async fn run(
     ctx: Arc<Context>,
     shutdown_signal: impl Future<Output = ()> + Send + Sync + 'static,
@@ -251,7 +588,10 @@ pub async fn run(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use kubimo::{StorageRequirement, WorkspaceSpec, WorkspaceStatus};
+    use kubimo::{
+        Requirement, RunnerClaim, RunnerSpec, RunnerStatus, StorageRequirement, WorkspaceSpec,
+        WorkspaceStatus,
+    };
 
     fn gib(n: u64) -> u64 {
         n * 1024 * 1024 * 1024
@@ -374,4 +714,99 @@ mod tests {
             0
         );
     }
+
+    fn runner(cpu: &str, memory: &str) -> Runner {
+        Runner::new(
+            "runner",
+            RunnerSpec {
+                cpu: Some(Requirement {
+                    min: Some(cpu.parse().unwrap()),
+                    max: None,
+                }),
+                memory: Some(Requirement {
+                    min: None,
+                    max: Some(memory.parse().unwrap()),
+                }),
+                ..Default::default()
+            },
+        )
+    }
+
+    /// A requirement with only a `max` is requested at the `max` by
+    /// Kubernetes, so that is what it counts.
+    #[test]
+    fn a_runner_counts_its_requests() {
+        let usage = runner_usage(&runner("500m", "2Gi"));
+        assert_eq!(
+            usage,
+            RunnerUsage {
+                cpu_millis: 500,
+                memory_bytes: gib(2),
+                runners: 1,
+                claims: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn suspended_and_refused_runners_count_nothing() {
+        let mut suspended = runner("1", "1Gi");
+        suspended.spec.suspended = Some(true);
+        assert_eq!(runner_usage(&suspended), RunnerUsage::default());
+
+        let mut refused = runner("1", "1Gi");
+        refused.status = Some(RunnerStatus {
+            conditions: Some(vec![Condition {
+                type_: kubimo::conditions::BUDGET_EXCEEDED.to_string(),
+                status: "True".to_string(),
+                reason: kubimo::conditions::BUDGET_EXCEEDED.to_string(),
+                message: String::new(),
+                observed_generation: None,
+                last_transition_time: Time(Timestamp::UNIX_EPOCH),
+            }]),
+            ..Default::default()
+        });
+        assert_eq!(runner_usage(&refused), RunnerUsage::default());
+        // Its own admission still weighs what it would take.
+        assert_eq!(runner_demand(&refused).cpu_millis, 1000);
+    }
+
+    #[test]
+    fn only_an_unreleased_claim_counts() {
+        let claim = RunnerClaim {
+            pool: "pool".to_string(),
+            pod_name: "pod".to_string(),
+            ingress_path: "/".to_string(),
+            ..Default::default()
+        };
+        let mut claimed = runner("1", "1Gi");
+        claimed.status = Some(RunnerStatus {
+            claim: Some(claim.clone()),
+            ..Default::default()
+        });
+        assert_eq!(runner_usage(&claimed).claims, 1);
+
+        claimed.status.as_mut().unwrap().claim = Some(RunnerClaim {
+            released: Some(true),
+            ..claim
+        });
+        assert_eq!(runner_usage(&claimed).claims, 0);
+    }
+
+    #[test]
+    fn the_first_limit_over_is_the_refusal() {
+        let spec = BudgetSpec {
+            cpu: Some("2".parse().unwrap()),
+            runners: Some(1),
+            ..Default::default()
+        };
+        let one = runner_usage(&runner("1500m", "1Gi"));
+        assert!(one.over(&spec).is_none());
+
+        let two = one.add(one);
+        assert!(two.over(&spec).unwrap().contains("CPU 3000m"));
+        let small = runner_usage(&runner("100m", "1Gi"));
+        assert!(small.add(small).over(&spec).unwrap().contains("2 runners"));
+        assert!(small.add(small).exceeds(&spec));
+    }
 }
@@ -0,0 +1,452 @@
+//! Soft storage limits: warnings before a Budget's wall, and a freeze of the
+//! pooled workspaces it governs once a grace period has run out.
+//!
+//! `storage` is enforced when a PVC is provisioned, so all it can do is
+//! refuse. A pooled workspace provisions nothing — its slot is created on
+//! demand and grows within its quota — so nothing stopped one going past the
+//! limit at all. `softStorage` refuses nothing either: it warns, and with a
+//! grace period has the node agent freeze each pooled slot at what it already
+//! holds. A frozen workspace still opens and runs, and deleting files is how
+//! its owner gets back under; it is thawed as soon as they have.
+
+use futures::prelude::*;
+use kubimo::k8s_openapi::apimachinery::pkg::apis::meta::v1::{Condition, Time};
+use kubimo::k8s_openapi::jiff::{SignedDuration, Timestamp};
+use kubimo::{
+    Budget, EventType, FilterParams, StorageQuantity, Workspace, WorkspaceMode, WorkspaceStatus,
+    conditions::READ_ONLY, prelude::*,
+};
+
+use crate::context::Context;
+
+use super::{BudgetTree, budget_condition};
+
+/// Usage is at least [`NEAR_LIMIT_PERCENT`] of `softStorage`, and not past it.
+pub(super) const NEAR_LIMIT: &str = "NearLimit";
+/// Usage is past `softStorage`. The reason says where the grace period is.
+pub(super) const OVER_SOFT_LIMIT: &str = "OverSoftLimit";
+
+const NEAR_LIMIT_PERCENT: u128 = 80;
+
+/// `reason` of `OverSoftLimit` once the grace period has run out. Budgets
+/// with it are what freeze a workspace, so it doubles as the signal.
+const READ_ONLY_REASON: &str = "ReadOnly";
+
+/// `NearLimit` and `OverSoftLimit` for `used` bytes, or neither when the
+/// budget has no `softStorage`.
+///
+/// The grace period runs from `OverSoftLimit`'s transition time, so it is
+/// not restarted by the controller restarting, nor by usage moving while it
+/// stays over; only dropping back under the limit resets it.
+pub(super) fn soft_limit_conditions(budget: &Budget, used: u64, now: Timestamp) -> Vec<Condition> {
+    let Some(soft) = budget
+        .spec
+        .soft_storage
+        .as_ref()
+        .and_then(StorageQuantity::to_bytes)
+    else {
+        return Vec::new();
+    };
+    let over = used > soft;
+    let near = !over && u128::from(used) * 100 >= u128::from(soft) * NEAR_LIMIT_PERCENT;
+
+    let near_limit = if near {
+        budget_condition(
+            budget,
+            NEAR_LIMIT,
+            "True",
+            NEAR_LIMIT,
+            format!(
+                "Storage usage of {used} bytes is at {}% of the soft limit of {soft} bytes",
+                u128::from(used) * 100 / u128::from(soft.max(1))
+            ),
+            now,
+        )
+    } else {
+        budget_condition(
+            budget,
+            NEAR_LIMIT,
+            "False",
+            if over {
+                OVER_SOFT_LIMIT
+            } else {
+                "BelowThreshold"
+            },
+            format!(
+                "Storage usage is below {NEAR_LIMIT_PERCENT}% of the soft limit of {soft} bytes"
+            ),
+            now,
+        )
+    };
+
+    let over_soft_limit = if !over {
+        budget_condition(
+            budget,
+            OVER_SOFT_LIMIT,
+            "False",
+            "WithinSoftLimit",
+            format!("Storage usage is within the soft limit of {soft} bytes"),
+            now,
+        )
+    } else {
+        let over_since = previous(budget, OVER_SOFT_LIMIT)
+            .filter(|cond| cond.status == "True")
+            .map_or(now, |cond| cond.last_transition_time.0);
+        let exceeds =
+            format!("Storage usage of {used} bytes exceeds the soft limit of {soft} bytes");
+        let (reason, message) = match budget.spec.soft_storage_grace_period_secs {
+            None => (OVER_SOFT_LIMIT, exceeds),
+            Some(grace) => {
+                let deadline = grace_deadline(over_since, grace);
+                if now >= deadline {
+                    (
+                        READ_ONLY_REASON,
+                        format!("{exceeds}; its pooled workspaces are read-only"),
+                    )
+                } else {
+                    (
+                        "GracePeriod",
+                        format!("{exceeds}; its pooled workspaces become read-only at {deadline}"),
+                    )
+                }
+            }
+        };
+        budget_condition(budget, OVER_SOFT_LIMIT, "True", reason, message, now)
+    };
+    vec![near_limit, over_soft_limit]
+}
+
+fn grace_deadline(over_since: Timestamp, grace_secs: u64) -> Timestamp {
+    i64::try_from(grace_secs)
+        .ok()
+        .and_then(|secs| over_since.checked_add(SignedDuration::from_secs(secs)).ok())
+        .unwrap_or(Timestamp::MAX)
+}
+
+fn previous<'a>(budget: &'a Budget, type_: &str) -> Option<&'a Condition> {
+    budget
+        .status
+        .as_ref()
+        .and_then(|status| status.conditions.as_ref())
+        .and_then(|conditions| conditions.iter().find(|cond| cond.type_ == type_))
+}
+
+/// The events `conditions` call for, against what `budget` reported last:
+/// one per warning as it starts, one as a freeze begins, and one once usage
+/// is back under the soft limit.
+pub(super) fn soft_limit_events(
+    budget: &Budget,
+    conditions: &[Condition],
+) -> Vec<(EventType, &'static str, String)> {
+    let mut events = Vec::new();
+    for condition in conditions {
+        let before = previous(budget, &condition.type_);
+        let was_true = before.is_some_and(|cond| cond.status == "True");
+        let is_true = condition.status == "True";
+        match condition.type_.as_str() {
+            NEAR_LIMIT if is_true && !was_true => {
+                events.push((EventType::Warning, NEAR_LIMIT, condition.message.clone()));
+            }
+            OVER_SOFT_LIMIT if is_true && !was_true => {
+                events.push((
+                    EventType::Warning,
+                    OVER_SOFT_LIMIT,
+                    condition.message.clone(),
+                ));
+            }
+            OVER_SOFT_LIMIT if !is_true && was_true => {
+                events.push((
+                    EventType::Normal,
+                    "WithinSoftLimit",
+                    condition.message.clone(),
+                ));
+            }
+            _ => {}
+        }
+        if condition.type_ == OVER_SOFT_LIMIT
+            && condition.reason == READ_ONLY_REASON
+            && before.is_none_or(|cond| cond.reason != READ_ONLY_REASON)
+        {
+            events.push((
+                EventType::Warning,
+                READ_ONLY_REASON,
+                condition.message.clone(),
+            ));
+        }
+    }
+    events
+}
+
+/// Whether `budget`'s grace period has run out, so the pooled workspaces it
+/// governs are to be read-only.
+fn holds_read_only(budget: &Budget) -> bool {
+    previous(budget, OVER_SOFT_LIMIT)
+        .is_some_and(|cond| cond.status == "True" && cond.reason == READ_ONLY_REASON)
+}
+
+/// `ReadOnly` for a pooled `workspace` given every Budget in its namespace:
+/// `True` naming the first that holds it read-only, `False` once none does
+/// for a workspace that was. `None` for one that never was.
+pub(crate) fn read_only_condition(
+    workspace: &Workspace,
+    budgets: &BudgetTree,
+) -> Option<Condition> {
+    let labels = workspace.metadata.labels.as_ref();
+    let holder = workspace
+        .metadata
+        .namespace
+        .as_deref()
+        .and_then(|namespace| {
+            budgets
+                .governing(namespace, labels)
+                .find(|budget| holds_read_only(budget))
+        });
+    let current = workspace
+        .status
+        .as_ref()
+        .and_then(|status| status.conditions.as_ref())
+        .and_then(|conditions| conditions.iter().find(|cond| cond.type_ == READ_ONLY));
+    let (status, reason, message) = match holder {
+        Some(budget) => (
+            "True",
+            OVER_SOFT_LIMIT,
+            format!(
+                "Budget {} has been over its soft storage limit for longer than its grace period",
+                budget.name_any()
+            ),
+        ),
+        None if current.is_some() => (
+            "False",
+            "WithinBudget",
+            "Every budget is within its soft storage limit".to_string(),
+        ),
+        None => return None,
+    };
+    let last_transition_time = match current {
+        Some(current) if current.status == status => current.last_transition_time.clone(),
+        _ => Time(Timestamp::now()),
+    };
+    Some(Condition {
+        type_: READ_ONLY.to_string(),
+        status: status.to_string(),
+        reason: reason.to_string(),
+        message,
+        observed_generation: workspace.metadata.generation,
+        last_transition_time,
+    })
+}
+
+/// Replace the condition of the same type in `conditions`, or add it.
+pub(crate) fn upsert_condition(conditions: &mut Vec<Condition>, condition: Condition) {
+    match conditions
+        .iter_mut()
+        .find(|current| current.type_ == condition.type_)
+    {
+        Some(current) => *current = condition,
+        None => conditions.push(condition),
+    }
+}
+
+/// Freeze or thaw every pooled Workspace `budget` governs, now that its
+/// status says whether it holds them read-only. Other Budgets are consulted
+/// too: one that is back under its limit must not thaw a workspace another
+/// still holds.
+///
+/// The workspace reconciler applies the same condition on its own passes,
+/// which is what thaws a workspace whose Budget was deleted while holding it.
+pub(super) async fn apply_read_only(ctx: &Context, budget: &Budget) -> Result<(), kubimo::Error> {
+    let namespace = budget.require_namespace()?;
+    let budgets = BudgetTree::load(ctx, namespace).await?.with(budget);
+    let workspaces: Vec<Workspace> = ctx
+        .api_namespaced::<Workspace>(namespace)
+        .list(&FilterParams::new().with_labels(budgets.selector(budget)))
+        .map_ok(|item| item.item)
+        .try_collect()
+        .await?;
+    for workspace in &workspaces {
+        if workspace.metadata.deletion_timestamp.is_some()
+            || workspace.effective_mode(ctx.config.default_workspace_mode) != WorkspaceMode::Pooled
+        {
+            continue;
+        }
+        let Some(condition) = read_only_condition(workspace, &budgets) else {
+            continue;
+        };
+        let status = workspace.status.clone().unwrap_or_default();
+        let mut conditions = status.conditions.unwrap_or_default();
+        let changed = !conditions.iter().any(|current| {
+            current.type_ == condition.type_
+                && current.status == condition.status
+                && current.message == condition.message
+        });
+        if !changed {
+            continue;
+        }
+        let frozen = condition.status == "True";
+        let note = condition.message.clone();
+        upsert_condition(&mut conditions, condition);
+        // Only what the workspace reconciler writes too, under the same field
+        // manager: leaving out `mode` or `pythonRuntime` would drop them, and
+        // sending `storage`, `slot` or the rest would claim fields the agent
+        // owns (see `build_workspace_status`).
+        let mut patched = workspace.clone();
+        patched.status = Some(WorkspaceStatus {
+            conditions: Some(conditions),
+            mode: status.mode,
+            python_runtime: status.python_runtime,
+            ..Default::default()
+        });
+        ctx.api_namespaced::<Workspace>(namespace)
+            .patch_status(&patched)
+            .await?;
+        tracing::info!(
+            workspace = workspace.name()?,
+            budget = budget.name()?,
+            frozen,
+            "applied soft storage limit"
+        );
+        let (type_, reason, action) = if frozen {
+            (EventType::Warning, READ_ONLY, "Freeze")
+        } else {
+            (EventType::Normal, "Writable", "Thaw")
+        };
+        ctx.events
+            .publish(workspace, type_, reason, action, note)
+            .await;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kubimo::{BudgetSpec, BudgetStatus, WorkspaceSpec};
+
+    const GIB: u64 = 1 << 30;
+
+    fn budget(grace_secs: Option<u64>) -> Budget {
+        let mut budget = Budget::new(
+            "team",
+            BudgetSpec {
+                selector: [("user".to_string(), "alice".to_string())].into(),
+                soft_storage: Some("10Gi".parse().unwrap()),
+                soft_storage_grace_period_secs: grace_secs,
+                ..Default::default()
+            },
+        );
+        budget.metadata.namespace = Some("ns".to_string());
+        budget
+    }
+
+    fn reported(mut budget: Budget, conditions: Vec<Condition>) -> Budget {
+        budget.status = Some(BudgetStatus {
+            conditions: Some(conditions),
+            ..Default::default()
+        });
+        budget
+    }
+
+    fn find<'a>(conditions: &'a [Condition], type_: &str) -> &'a Condition {
+        conditions.iter().find(|cond| cond.type_ == type_).unwrap()
+    }
+
+    #[test]
+    fn no_soft_limit_reports_nothing() {
+        let mut budget = budget(None);
+        budget.spec.soft_storage = None;
+        assert!(soft_limit_conditions(&budget, 100 * GIB, Timestamp::now()).is_empty());
+    }
+
+    #[test]
+    fn near_and_over_are_exclusive() {
+        let budget = budget(None);
+        let now = Timestamp::now();
+
+        let below = soft_limit_conditions(&budget, 7 * GIB, now);
+        assert_eq!(find(&below, NEAR_LIMIT).status, "False");
+        assert_eq!(find(&below, OVER_SOFT_LIMIT).status, "False");
+
+        let near = soft_limit_conditions(&budget, 8 * GIB, now);
+        assert_eq!(find(&near, NEAR_LIMIT).status, "True");
+        assert_eq!(find(&near, OVER_SOFT_LIMIT).status, "False");
+
+        let over = soft_limit_conditions(&budget, 11 * GIB, now);
+        assert_eq!(find(&over, NEAR_LIMIT).status, "False");
+        assert_eq!(find(&over, OVER_SOFT_LIMIT).status, "True");
+        // Without a grace period, going over only ever warns.
+        assert_eq!(find(&over, OVER_SOFT_LIMIT).reason, OVER_SOFT_LIMIT);
+    }
+
+    /// The grace period counts from when usage first went over, not from
+    /// the latest reconcile, so it runs out even while usage keeps moving.
+    #[test]
+    fn the_grace_period_runs_from_the_first_time_over() {
+        let start: Timestamp = "2026-01-01T00:00:00Z".parse().unwrap();
+        let budget = budget(Some(3600));
+
+        let first = soft_limit_conditions(&budget, 11 * GIB, start);
+        assert_eq!(find(&first, OVER_SOFT_LIMIT).reason, "GracePeriod");
+        let budget = reported(budget, first);
+
+        let later = start + SignedDuration::from_mins(30);
+        let during = soft_limit_conditions(&budget, 12 * GIB, later);
+        assert_eq!(find(&during, OVER_SOFT_LIMIT).reason, "GracePeriod");
+        assert_eq!(find(&during, OVER_SOFT_LIMIT).last_transition_time.0, start);
+
+        let after = soft_limit_conditions(&budget, 12 * GIB, start + SignedDuration::from_hours(1));
+        assert_eq!(find(&after, OVER_SOFT_LIMIT).reason, READ_ONLY_REASON);
+        assert!(holds_read_only(&reported(budget.clone(), after)));
+
+        // Back under resets it.
+        let under = soft_limit_conditions(&budget, 9 * GIB, later);
+        assert_eq!(find(&under, OVER_SOFT_LIMIT).status, "False");
+    }
+
+    #[test]
+    fn events_fire_on_transitions_only() {
+        let now = Timestamp::now();
+        let budget = budget(Some(0));
+        let over = soft_limit_conditions(&budget, 11 * GIB, now);
+        let reasons: Vec<_> = soft_limit_events(&budget, &over)
+            .into_iter()
+            .map(|(_, reason, _)| reason)
+            .collect();
+        assert_eq!(reasons, [OVER_SOFT_LIMIT, READ_ONLY_REASON]);
+
+        let budget = reported(budget, over);
+        let still_over = soft_limit_conditions(&budget, 12 * GIB, now);
+        assert!(soft_limit_events(&budget, &still_over).is_empty());
+
+        let under = soft_limit_conditions(&budget, GIB, now);
+        let reasons: Vec<_> = soft_limit_events(&budget, &under)
+            .into_iter()
+            .map(|(_, reason, _)| reason)
+            .collect();
+        assert_eq!(reasons, ["WithinSoftLimit"]);
+    }
+
+    #[test]
+    fn a_workspace_is_read_only_while_any_matching_budget_holds_it() {
+        let now = Timestamp::now();
+        let mut workspace = Workspace::new("ws", WorkspaceSpec::default());
+        workspace.metadata.namespace = Some("ns".to_string());
+        workspace.metadata.labels = Some([("user".to_string(), "alice".to_string())].into());
+
+        let within = budget(Some(0));
+        assert!(read_only_condition(&workspace, &BudgetTree::new(vec![within.clone()])).is_none());
+
+        let over = soft_limit_conditions(&within, 11 * GIB, now);
+        let holding = reported(within.clone(), over);
+        let frozen = read_only_condition(&workspace, &BudgetTree::new(vec![holding])).unwrap();
+        assert_eq!(frozen.status, "True");
+        assert!(frozen.message.contains("Budget team"));
+
+        workspace.status = Some(WorkspaceStatus {
+            conditions: Some(vec![frozen]),
+            ..Default::default()
+        });
+        assert!(workspace.is_read_only());
+        let thawed = read_only_condition(&workspace, &BudgetTree::new(vec![within])).unwrap();
+        assert_eq!(thawed.status, "False");
+    }
+}
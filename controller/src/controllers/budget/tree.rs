@@ -0,0 +1,173 @@
+//! Nested Budgets: resolving `spec.parent` into what each Budget governs.
+//!
+//! A Budget's own selectors say nothing about its parent's, so matching one
+//! alone would let a `user` Budget count a workspace its `team` Budget never
+//! sees. What a Budget governs is therefore its own selectors ANDed with every
+//! ancestor's, and every check — matching, listing, summing — goes through
+//! here rather than `BudgetSpec::matches`. The upshot is that an object under
+//! a child is always under each ancestor too, so checking it against every
+//! Budget that matches it checks the whole chain.
+
+use std::collections::BTreeMap;
+
+use kubimo::{Budget, Selector};
+
+use crate::context::Context;
+
+use super::namespace_budgets;
+
+/// Every Budget in scope, to resolve parents against.
+pub(crate) struct BudgetTree {
+    budgets: Vec<Budget>,
+}
+
+impl BudgetTree {
+    pub(crate) fn new(budgets: Vec<Budget>) -> Self {
+        Self { budgets }
+    }
+
+    /// Every Budget in `namespace`. A parent is only ever looked up in its
+    /// child's own namespace, so nothing outside it is needed.
+    pub(crate) async fn load(ctx: &Context, namespace: &str) -> Result<Self, kubimo::Error> {
+        Ok(Self::new(namespace_budgets(ctx, namespace).await?))
+    }
+
+    /// Swap in a fresher copy of one of the Budgets, or add it.
+    pub(crate) fn with(mut self, budget: &Budget) -> Self {
+        match self.budgets.iter_mut().find(|other| {
+            other.metadata.namespace == budget.metadata.namespace
+                && other.metadata.name == budget.metadata.name
+        }) {
+            Some(stale) => *stale = budget.clone(),
+            None => self.budgets.push(budget.clone()),
+        }
+        self
+    }
+
+    /// `budget` and its ancestors, nearest first, and what stopped the chain
+    /// short if something did: a parent that does not exist, or one already
+    /// in the chain. The Budgets above a broken link are simply not part of
+    /// it — the chain still holds everything below, so the Budget governs
+    /// more than intended rather than nothing at all.
+    pub(crate) fn chain<'a>(&'a self, budget: &'a Budget) -> (Vec<&'a Budget>, Option<String>) {
+        let mut chain = vec![budget];
+        let mut current = budget;
+        while let Some(parent) = current.spec.parent.as_deref() {
+            if chain
+                .iter()
+                .any(|seen| seen.metadata.name.as_deref() == Some(parent))
+            {
+                return (
+                    chain,
+                    Some(format!("parent Budget {parent} is its own ancestor")),
+                );
+            }
+            let Some(found) = self.budgets.iter().find(|other| {
+                other.metadata.namespace == budget.metadata.namespace
+                    && other.metadata.name.as_deref() == Some(parent)
+            }) else {
+                return (
+                    chain,
+                    Some(format!("parent Budget {parent} does not exist")),
+                );
+            };
+            chain.push(found);
+            current = found;
+        }
+        (chain, None)
+    }
+
+    /// What `budget` governs: its own selectors and every ancestor's.
+    pub(crate) fn selector(&self, budget: &Budget) -> Selector {
+        let mut selector = Selector::new();
+        for member in self.chain(budget).0 {
+            selector.extend(member.spec.label_selector());
+        }
+        selector
+    }
+
+    /// Every Budget in `namespace` that governs an object labelled `labels`.
+    pub(crate) fn governing<'a>(
+        &'a self,
+        namespace: &'a str,
+        labels: Option<&'a BTreeMap<String, String>>,
+    ) -> impl Iterator<Item = &'a Budget> + 'a {
+        self.budgets.iter().filter(move |budget| {
+            budget.metadata.namespace.as_deref() == Some(namespace)
+                && self.selector(budget).matches(labels)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kubimo::BudgetSpec;
+
+    fn budget(name: &str, parent: Option<&str>, label: (&str, &str)) -> Budget {
+        let mut budget = Budget::new(
+            name,
+            BudgetSpec {
+                selector: [(label.0.to_string(), label.1.to_string())].into(),
+                parent: parent.map(str::to_string),
+                ..Default::default()
+            },
+        );
+        budget.metadata.namespace = Some("ns".to_string());
+        budget
+    }
+
+    fn labels(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    fn names<'a>(budgets: impl Iterator<Item = &'a Budget>) -> Vec<&'a str> {
+        budgets
+            .map(|budget| budget.metadata.name.as_deref().unwrap())
+            .collect()
+    }
+
+    /// A workspace under `alice` is checked against her team and org too,
+    /// and one that carries `alice`'s label outside her org is not hers.
+    #[test]
+    fn a_child_governs_only_within_its_ancestors() {
+        let alice = budget("alice", Some("data"), ("user", "alice"));
+        let tree = BudgetTree::new(vec![
+            budget("acme", None, ("org", "acme")),
+            budget("data", Some("acme"), ("team", "data")),
+            alice.clone(),
+        ]);
+        let inside = labels(&[("org", "acme"), ("team", "data"), ("user", "alice")]);
+        assert_eq!(
+            names(tree.governing("ns", Some(&inside))),
+            ["acme", "data", "alice"]
+        );
+        let outside = labels(&[("org", "other"), ("team", "data"), ("user", "alice")]);
+        assert!(tree.governing("ns", Some(&outside)).next().is_none());
+        assert_eq!(
+            tree.selector(&alice).to_string(),
+            "user=alice,team=data,org=acme"
+        );
+    }
+
+    #[test]
+    fn a_broken_chain_is_reported_and_cut_short() {
+        let orphan = budget("orphan", Some("gone"), ("user", "bob"));
+        let a = budget("a", Some("b"), ("x", "1"));
+        let tree = BudgetTree::new(vec![
+            orphan.clone(),
+            a.clone(),
+            budget("b", Some("a"), ("y", "1")),
+        ]);
+        let (chain, problem) = tree.chain(&orphan);
+        assert_eq!(chain.len(), 1);
+        assert!(problem.unwrap().contains("does not exist"));
+
+        let (chain, problem) = tree.chain(&a);
+        assert_eq!(chain.len(), 2);
+        assert!(problem.unwrap().contains("its own ancestor"));
+    }
+}
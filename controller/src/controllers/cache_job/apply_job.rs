@@ -1,5 +1,7 @@
 use kubimo::k8s_openapi::api::batch::v1::{Job, JobSpec};
-use kubimo::k8s_openapi::api::core::v1::{Container, PodSpec, PodTemplateSpec, VolumeMount};
+use kubimo::k8s_openapi::api::core::v1::{
+    Container, EnvVar, PodSpec, PodTemplateSpec, VolumeMount,
+};
 use kubimo::kube::api::ObjectMeta;
 use kubimo::{CacheJob, Workspace, WorkspaceMode, WorkspacePythonRuntime, prelude::*};
 
@@ -13,6 +15,23 @@ use crate::resources::Resources;
 
 use super::CacheJobReconciler;
 
+/// Where `cache.py` leaves its report; kubelet's default
+/// `terminationMessagePath`, so the message needs no further wiring.
+const TERMINATION_LOG: &str = "/dev/termination-log";
+
+/// The indexer's upload env, keeping the archived caches of every notebook
+/// and format the job did not regenerate. Env rather than `--keep-caches`, for
+/// the reason `upload_env` gives for its own settings.
+fn cache_upload_env(workspace: &Workspace) -> Vec<EnvVar> {
+    let mut env = indexer::upload_env(workspace).unwrap_or_default();
+    env.push(EnvVar {
+        name: "KUBIMO_KEEP_CACHES".into(),
+        value: Some("true".into()),
+        ..Default::default()
+    });
+    env
+}
+
 impl CacheJobReconciler {
     fn cache_container(
         &self,
@@ -25,7 +44,26 @@ impl CacheJobReconciler {
         if let Some(log_level) = cache_job.spec.log_level.as_ref() {
             command.extend(cmd!["--log-level", log_level]);
         }
+        // Repeated flags rather than one list: a glob may hold a comma, and
+        // `start.sh` passes each straight through to `cache.py`.
+        for glob in cache_job.spec.include.iter().flatten() {
+            command.extend(cmd!["--include", glob]);
+        }
+        for glob in cache_job.spec.exclude.iter().flatten() {
+            command.extend(cmd!["--exclude", glob]);
+        }
+        for format in cache_job.spec.formats.iter().flatten() {
+            command.extend(cmd!["--format", format]);
+        }
         command.push("cache".into());
+        // An env var rather than a flag, like every other setting `start.sh`
+        // gains: an older image ignores it instead of refusing to start.
+        let mut env = cache_job.spec.env.clone().unwrap_or_default();
+        env.push(EnvVar {
+            name: "KUBIMO_CACHE_REPORT".into(),
+            value: Some(TERMINATION_LOG.into()),
+            ..Default::default()
+        });
         Container {
             name: "cache".into(),
             image: Some(ctx.config.marimo_image(python_runtime).to_string()),
@@ -38,7 +76,7 @@ impl CacheJobReconciler {
                 name: workspace_name,
                 ..Default::default()
             }]),
-            env: cache_job.spec.env.clone(),
+            env: Some(env),
             env_from: cache_job.spec.env_from.clone(),
             command: Some(command),
             ..Default::default()
@@ -56,7 +94,7 @@ impl CacheJobReconciler {
             image: Some(ctx.config.marimo_image(python_runtime).to_string()),
             command: Some(cmd!["/app/indexer"]),
             args: Some(indexer::upload_args(workspace, false)?),
-            env: indexer::env(workspace),
+            env: Some(cache_upload_env(workspace)),
             env_from: indexer::env_from(workspace),
             volume_mounts: Some(vec![VolumeMount {
                 mount_path: indexer::MOUNT_DIR.to_string(),
@@ -71,13 +109,13 @@ impl CacheJobReconciler {
         &self,
         ctx: &Context,
         cache_job: &CacheJob,
+        job_name: &str,
     ) -> Result<Job, kubimo::Error> {
-        let cache_job_name = cache_job.name()?;
         let namespace = cache_job.require_namespace()?;
 
         if let Some(job) = ctx
             .api_namespaced::<Job>(namespace)
-            .get_opt(cache_job_name)
+            .get_opt(job_name)
             .await?
         {
             return Ok(job);
@@ -138,8 +176,13 @@ impl CacheJobReconciler {
         let pod_labels = workspace_affinity::workspace_label_map(workspace_name);
         let job = Job {
             metadata: ObjectMeta {
-                name: Some(cache_job_name.to_string()),
+                name: Some(job_name.to_string()),
                 namespace: Some(namespace.to_string()),
+                labels: Some(
+                    [super::cache_job_label(cache_job.name()?)]
+                        .into_iter()
+                        .collect(),
+                ),
                 owner_references: Some(vec![cache_job.static_controller_owner_ref()?]),
                 ..Default::default()
             },
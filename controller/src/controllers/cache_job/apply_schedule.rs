@@ -0,0 +1,219 @@
+use std::time::Duration;
+
+use futures::prelude::*;
+use kubimo::chrono::{DateTime, Utc};
+use kubimo::k8s_openapi::api::batch::v1::Job;
+use kubimo::k8s_openapi::jiff::Timestamp;
+use kubimo::kube::api::DeleteParams;
+use kubimo::kube::runtime::controller::Action;
+use kubimo::{CacheJob, CacheJobSchedule, FilterParams, prelude::*};
+
+use crate::context::Context;
+
+use super::CacheJobReconciler;
+use super::apply_status::{
+    REASON_WAITING, has_run, job_outcome, record_outcomes, set_succeeded, to_chrono,
+};
+use crate::controllers::schedule::{self, Cron};
+
+/// The Job of the run due at `due`. Named by the due time rather than the
+/// moment it was created, so a reconcile repeated before the status records
+/// the run finds it instead of starting it twice.
+pub(super) fn run_name(cache_job_name: &str, due: Timestamp) -> String {
+    format!("{cache_job_name}-{}", due.as_second() / 60)
+}
+
+fn to_timestamp(time: DateTime<Utc>) -> Option<Timestamp> {
+    Timestamp::from_second(time.timestamp()).ok()
+}
+
+/// Finished runs beyond the newest `limit`, oldest first.
+fn expired_runs(jobs: &[Job], limit: u32) -> Vec<&Job> {
+    let mut finished = jobs
+        .iter()
+        .filter(|job| job_outcome(job).is_some())
+        .collect::<Vec<_>>();
+    finished.sort_by_key(|job| job.metadata.creation_timestamp.clone().map(|time| time.0));
+    let expired = finished.len().saturating_sub(limit as usize);
+    finished.truncate(expired);
+    finished
+}
+
+impl CacheJobReconciler {
+    pub(crate) async fn apply_schedule(
+        &self,
+        ctx: &Context,
+        cache_job: &CacheJob,
+        schedule: &CacheJobSchedule,
+    ) -> Result<Action, kubimo::Error> {
+        let invalid = |err: schedule::ScheduleError| {
+            kubimo::Error::Custom(format!("CacheJob has an invalid schedule: {err}"))
+        };
+        let cron = schedule.cron.parse::<Cron>().map_err(invalid)?;
+        let tz = schedule::time_zone(schedule.time_zone.as_deref()).map_err(invalid)?;
+        let name = cache_job.name()?;
+        let jobs_api = ctx.api_namespaced::<Job>(cache_job.require_namespace()?);
+        let mut jobs = jobs_api
+            .list(&FilterParams::new().with_labels(super::cache_job_label(name)))
+            .map_ok(|item| item.item)
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        let mut status = cache_job.status.clone().unwrap_or_default();
+        record_outcomes(&mut status, &jobs);
+
+        // Runs fall due after the last one started or, before the first, after
+        // the CacheJob was created (or was told to start, if later): a new
+        // schedule does not owe a run for every period before it existed.
+        let since = [
+            status.last_schedule_time.and_then(to_timestamp),
+            cache_job
+                .metadata
+                .creation_timestamp
+                .as_ref()
+                .map(|time| time.0),
+            cache_job.spec.start_after.and_then(to_timestamp),
+        ]
+        .into_iter()
+        .flatten()
+        .max()
+        .unwrap_or_else(Timestamp::now);
+        let now = Timestamp::now();
+        let running = jobs.iter().any(|job| job_outcome(job).is_none());
+        if !running && let Some(due) = cron.latest_due(&since.to_zoned(tz.clone()), now) {
+            let job = self
+                .apply_job(ctx, cache_job, &run_name(name, due.timestamp()))
+                .await?;
+            if !jobs
+                .iter()
+                .any(|listed| listed.metadata.name == job.metadata.name)
+            {
+                jobs.push(job);
+            }
+            status.last_schedule_time = to_chrono(due.timestamp());
+        }
+
+        // The status describes the newest run; before the first, why there is
+        // none yet.
+        match jobs
+            .iter()
+            .max_by_key(|job| job.metadata.creation_timestamp.clone().map(|time| time.0))
+        {
+            Some(current) => {
+                self.observe_run(ctx, cache_job, current, &mut status)
+                    .await?
+            }
+            None if !has_run(&status) => set_succeeded(
+                cache_job,
+                &mut status,
+                None,
+                REASON_WAITING,
+                "Waiting for the first scheduled run",
+            ),
+            None => {}
+        }
+
+        for job in expired_runs(&jobs, schedule.effective_history_limit()) {
+            // Deleting a Job orphans its pods by default: without this they,
+            // and the logs anyone would have kept them for, outlive it.
+            match jobs_api
+                .kube()
+                .delete(job.name()?, &DeleteParams::background())
+                .await
+            {
+                Ok(_) => {}
+                Err(kubimo::kube::Error::Api(status)) if status.code == 404 => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        self.apply_status(ctx, cache_job, status).await?;
+
+        // A run held back behind a running one is picked up when that Job
+        // finishes, which wakes this CacheJob as its owner.
+        Ok(match cron.next_after(&now.to_zoned(tz)) {
+            Some(next) => {
+                let wait = next.timestamp().duration_since(now);
+                Action::requeue(Duration::try_from(wait).unwrap_or(Duration::from_secs(1)))
+            }
+            None => Action::await_change(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kubimo::k8s_openapi::api::batch::v1::{JobCondition, JobStatus};
+    use kubimo::k8s_openapi::apimachinery::pkg::apis::meta::v1::Time;
+
+    fn job(created: i64, outcome: Option<&str>) -> Job {
+        let mut job = Job::default();
+        job.metadata.name = Some(format!("run-{created}"));
+        job.metadata.creation_timestamp = Some(Time(Timestamp::from_second(created).unwrap()));
+        job.status = outcome.map(|outcome| JobStatus {
+            conditions: Some(vec![JobCondition {
+                type_: outcome.into(),
+                status: "True".into(),
+                last_transition_time: Some(Time(Timestamp::from_second(created + 60).unwrap())),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        });
+        job
+    }
+
+    #[test]
+    fn the_oldest_finished_runs_beyond_the_limit_expire() {
+        let jobs = [
+            job(300, Some("Complete")),
+            job(100, Some("Failed")),
+            job(400, None),
+            job(200, Some("Complete")),
+        ];
+        let names = |limit| {
+            expired_runs(&jobs, limit)
+                .into_iter()
+                .map(|job| job.metadata.name.clone().unwrap())
+                .collect::<Vec<_>>()
+        };
+        // The running one is never counted, let alone deleted.
+        assert_eq!(names(1), ["run-100", "run-200"]);
+        assert_eq!(names(0), ["run-100", "run-200", "run-300"]);
+        assert!(names(3).is_empty());
+    }
+
+    #[test]
+    fn outcomes_only_move_forward() {
+        let mut status = Default::default();
+        record_outcomes(
+            &mut status,
+            &[
+                job(300, Some("Complete")),
+                job(100, Some("Complete")),
+                job(200, Some("Failed")),
+                job(400, None),
+            ],
+        );
+        assert_eq!(
+            status.last_success_time,
+            to_chrono(Timestamp::from_second(360).unwrap())
+        );
+        assert_eq!(
+            status.last_failure_time,
+            to_chrono(Timestamp::from_second(260).unwrap())
+        );
+        // The Jobs behind those times were pruned; an older one does not win.
+        record_outcomes(&mut status, &[job(50, Some("Complete"))]);
+        assert_eq!(
+            status.last_success_time,
+            to_chrono(Timestamp::from_second(360).unwrap())
+        );
+    }
+
+    #[test]
+    fn a_run_is_named_by_the_minute_it_was_due() {
+        let due = Timestamp::from_second(1_767_225_600).unwrap();
+        assert_eq!(run_name("nightly", due), "nightly-29453760");
+    }
+}
@@ -0,0 +1,351 @@
+use futures::prelude::*;
+use kubimo::chrono::{DateTime, Utc};
+use kubimo::k8s_openapi::api::batch::v1::Job;
+use kubimo::k8s_openapi::api::core::v1::Pod;
+use kubimo::k8s_openapi::apimachinery::pkg::apis::meta::v1::{Condition, Time};
+use kubimo::k8s_openapi::jiff::Timestamp;
+use kubimo::{CacheJob, CacheJobStatus, CacheReport, FilterParams, prelude::*};
+
+use crate::context::Context;
+
+use super::CacheJobReconciler;
+
+const SUCCEEDED: &str = "Succeeded";
+
+/// Reasons on the `Succeeded` condition.
+pub(super) const REASON_WAITING: &str = "Waiting";
+const REASON_RUNNING: &str = "Running";
+const REASON_FAILED: &str = "Failed";
+const REASON_NOTEBOOKS_FAILED: &str = "NotebooksFailed";
+const REASON_RENDERED: &str = "Rendered";
+/// The Job succeeded but left no report: its pods were already collected, or
+/// the image predates the report.
+const REASON_COMPLETED: &str = "Completed";
+
+pub(super) fn to_chrono(time: Timestamp) -> Option<DateTime<Utc>> {
+    DateTime::from_timestamp(time.as_second(), 0)
+}
+
+/// Whether a finished Job succeeded, and when it finished. `None` while it
+/// runs.
+pub(super) fn job_outcome(job: &Job) -> Option<(bool, DateTime<Utc>)> {
+    job.status
+        .as_ref()?
+        .conditions
+        .iter()
+        .flatten()
+        .filter(|cond| cond.status == "True")
+        .find_map(|cond| {
+            let succeeded = match cond.type_.as_str() {
+                "Complete" => true,
+                "Failed" => false,
+                _ => return None,
+            };
+            Some((succeeded, to_chrono(cond.last_transition_time.as_ref()?.0)?))
+        })
+}
+
+/// Fold the outcomes of `jobs` into `status`, never moving a time backwards:
+/// the Job that set it may already have been pruned.
+pub(super) fn record_outcomes<'a>(
+    status: &mut CacheJobStatus,
+    jobs: impl IntoIterator<Item = &'a Job>,
+) {
+    for (succeeded, at) in jobs.into_iter().filter_map(job_outcome) {
+        let last = if succeeded {
+            &mut status.last_success_time
+        } else {
+            &mut status.last_failure_time
+        };
+        if last.is_none_or(|last| last < at) {
+            *last = Some(at);
+        }
+    }
+}
+
+/// Replace the `Succeeded` condition, preserving the previous transition time
+/// when its status is unchanged (cf. `workspace_snapshot::ready_condition`).
+pub(super) fn set_succeeded(
+    cache_job: &CacheJob,
+    status: &mut CacheJobStatus,
+    value: Option<bool>,
+    reason: &str,
+    message: &str,
+) {
+    let value = match value {
+        Some(true) => "True",
+        Some(false) => "False",
+        None => "Unknown",
+    };
+    let previous = status
+        .conditions
+        .iter()
+        .flatten()
+        .find(|cond| cond.type_ == SUCCEEDED);
+    let last_transition_time = match previous {
+        Some(previous) if previous.status == value => previous.last_transition_time.clone(),
+        _ => Time(Timestamp::now()),
+    };
+    status.conditions = Some(vec![Condition {
+        last_transition_time,
+        observed_generation: cache_job.metadata.generation,
+        message: message.into(),
+        reason: reason.into(),
+        status: value.into(),
+        type_: SUCCEEDED.into(),
+    }]);
+}
+
+/// Whether `status` already describes a run. Waiting for the next one leaves
+/// the last one's outcome in place.
+pub(super) fn has_run(status: &CacheJobStatus) -> bool {
+    status.start_time.is_some() || status.completion_time.is_some()
+}
+
+/// Fill the run fields of `status` from a finished run's report, or leave
+/// them empty when there is none.
+fn record_report(status: &mut CacheJobStatus, report: Option<CacheReport>) {
+    status.notebooks = report.as_ref().map(|report| report.notebooks);
+    status.failed_notebooks = report.as_ref().map(|report| report.failed_notebooks);
+    status.results_truncated = report.as_ref().map(|report| report.truncated);
+    status.results = report.map(|report| report.results);
+}
+
+/// The condition a finished run leaves, from the Job's outcome and the
+/// run's fields in `status`.
+fn finished_condition(succeeded: bool, status: &CacheJobStatus) -> (bool, &'static str, String) {
+    if !succeeded {
+        return (
+            false,
+            REASON_FAILED,
+            "The cache Job failed; see its pods' logs".into(),
+        );
+    }
+    match (status.notebooks, status.failed_notebooks) {
+        (Some(notebooks), Some(failed)) if failed > 0 => (
+            false,
+            REASON_NOTEBOOKS_FAILED,
+            format!("{failed} of {notebooks} notebooks failed to render"),
+        ),
+        (Some(notebooks), _) => (
+            true,
+            REASON_RENDERED,
+            format!("{notebooks} notebooks rendered"),
+        ),
+        (None, _) => (true, REASON_COMPLETED, "The cache Job succeeded".into()),
+    }
+}
+
+/// The report `cache.py` left as the cache container's termination message,
+/// from whichever of the Job's pods has one. The cache container is an init
+/// container when an indexer runs beside it.
+fn termination_report(pod: &Pod) -> Option<CacheReport> {
+    let status = pod.status.as_ref()?;
+    status
+        .init_container_statuses
+        .iter()
+        .flatten()
+        .chain(status.container_statuses.iter().flatten())
+        .filter(|status| status.name == "cache")
+        .filter_map(|status| status.state.as_ref()?.terminated.as_ref())
+        .find_map(|terminated| serde_json::from_str(terminated.message.as_deref()?).ok())
+}
+
+impl CacheJobReconciler {
+    /// Bring the run fields of `status` up to date with `job`, the current
+    /// run. The pods are only read once per run, when it is first seen
+    /// finished: the report is final, and the pods may be gone by the next
+    /// reconcile.
+    pub(crate) async fn observe_run(
+        &self,
+        ctx: &Context,
+        cache_job: &CacheJob,
+        job: &Job,
+        status: &mut CacheJobStatus,
+    ) -> Result<(), kubimo::Error> {
+        let start_time = job
+            .status
+            .as_ref()
+            .and_then(|status| status.start_time.as_ref())
+            .and_then(|time| to_chrono(time.0));
+        if status.start_time != start_time {
+            status.start_time = start_time;
+            status.completion_time = None;
+            record_report(status, None);
+        }
+        let Some((succeeded, at)) = job_outcome(job) else {
+            status.completion_time = None;
+            record_report(status, None);
+            set_succeeded(
+                cache_job,
+                status,
+                None,
+                REASON_RUNNING,
+                "Rendering the workspace's notebooks",
+            );
+            return Ok(());
+        };
+        if status.completion_time != Some(at) {
+            let pods = ctx
+                .api_namespaced::<Pod>(cache_job.require_namespace()?)
+                .list(
+                    &FilterParams::new().with_labels(("batch.kubernetes.io/job-name", job.name()?)),
+                )
+                .map_ok(|item| item.item)
+                .try_collect::<Vec<_>>()
+                .await?;
+            record_report(status, pods.iter().find_map(termination_report));
+            status.completion_time = Some(at);
+        }
+        let (value, reason, message) = finished_condition(succeeded, status);
+        set_succeeded(cache_job, status, Some(value), reason, &message);
+        Ok(())
+    }
+
+    /// Write `status` if it differs from what the CacheJob has. The controller
+    /// is its only writer, so the read-modify-write is safe.
+    pub(crate) async fn apply_status(
+        &self,
+        ctx: &Context,
+        cache_job: &CacheJob,
+        status: CacheJobStatus,
+    ) -> Result<(), kubimo::Error> {
+        if cache_job.status.as_ref() == Some(&status) {
+            return Ok(());
+        }
+        let mut patched = cache_job.clone();
+        patched.status = Some(status);
+        ctx.api_namespaced::<CacheJob>(cache_job.require_namespace()?)
+            .patch_status(&patched)
+            .await?;
+        Ok(())
+    }
+
+    /// Report that no run has started yet, and why. Leaves the outcome of a
+    /// previous run alone.
+    pub(crate) async fn apply_waiting(
+        &self,
+        ctx: &Context,
+        cache_job: &CacheJob,
+        message: &str,
+    ) -> Result<(), kubimo::Error> {
+        let mut status = cache_job.status.clone().unwrap_or_default();
+        if has_run(&status) {
+            return Ok(());
+        }
+        set_succeeded(cache_job, &mut status, None, REASON_WAITING, message);
+        self.apply_status(ctx, cache_job, status).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kubimo::k8s_openapi::api::core::v1::{
+        ContainerState, ContainerStateTerminated, ContainerStatus, PodStatus,
+    };
+    use kubimo::{CacheFormatResult, CacheJobSpec, CacheNotebookResult};
+
+    fn terminated(name: &str, message: &str) -> ContainerStatus {
+        ContainerStatus {
+            name: name.into(),
+            state: Some(ContainerState {
+                terminated: Some(ContainerStateTerminated {
+                    message: Some(message.into()),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn the_report_comes_from_the_cache_container_wherever_it_ran() {
+        let report = r#"{"notebooks":2,"failedNotebooks":1,"results":[
+            {"path":"a.py","formats":[{"format":"html","error":"boom"},{"format":"md"}]}
+        ],"truncated":true}"#;
+        let expected = CacheReport {
+            notebooks: 2,
+            failed_notebooks: 1,
+            results: vec![CacheNotebookResult {
+                path: "a.py".into(),
+                formats: vec![
+                    CacheFormatResult {
+                        format: "html".into(),
+                        error: Some("boom".into()),
+                    },
+                    CacheFormatResult {
+                        format: "md".into(),
+                        error: None,
+                    },
+                ],
+            }],
+            truncated: true,
+        };
+        // `Dedicated`: an init container, with the indexer as the container.
+        let pod = Pod {
+            status: Some(PodStatus {
+                init_container_statuses: Some(vec![terminated("cache", report)]),
+                container_statuses: Some(vec![terminated("indexer", "")]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert_eq!(termination_report(&pod), Some(expected.clone()));
+        // `Pooled`: the only container.
+        let pod = Pod {
+            status: Some(PodStatus {
+                container_statuses: Some(vec![terminated("cache", report)]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert_eq!(termination_report(&pod), Some(expected));
+        let pod = Pod {
+            status: Some(PodStatus {
+                container_statuses: Some(vec![terminated("cache", "Killed")]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert_eq!(termination_report(&pod), None);
+    }
+
+    #[test]
+    fn failed_notebooks_fail_the_run() {
+        let mut status = CacheJobStatus::default();
+        record_report(
+            &mut status,
+            Some(CacheReport {
+                notebooks: 12,
+                failed_notebooks: 3,
+                ..Default::default()
+            }),
+        );
+        let (value, reason, message) = finished_condition(true, &status);
+        assert!(!value);
+        assert_eq!(reason, REASON_NOTEBOOKS_FAILED);
+        assert_eq!(message, "3 of 12 notebooks failed to render");
+
+        status.failed_notebooks = Some(0);
+        assert_eq!(finished_condition(true, &status).1, REASON_RENDERED);
+        assert_eq!(finished_condition(false, &status).1, REASON_FAILED);
+        record_report(&mut status, None);
+        assert_eq!(finished_condition(true, &status).1, REASON_COMPLETED);
+    }
+
+    #[test]
+    fn the_succeeded_condition_keeps_its_transition_time() {
+        let cache_job = CacheJob::new("nightly", CacheJobSpec::default());
+        let mut status = CacheJobStatus::default();
+        set_succeeded(&cache_job, &mut status, None, REASON_WAITING, "waiting");
+        let first = status.conditions.clone().unwrap().remove(0);
+        set_succeeded(&cache_job, &mut status, None, REASON_RUNNING, "running");
+        let running = status.conditions.clone().unwrap().remove(0);
+        assert_eq!(running.reason, REASON_RUNNING);
+        assert_eq!(running.last_transition_time, first.last_transition_time);
+        set_succeeded(&cache_job, &mut status, Some(true), REASON_RENDERED, "done");
+        assert_eq!(status.conditions.unwrap()[0].status, "True");
+    }
+}
@@ -1,5 +1,7 @@
 mod apply_job;
 mod apply_owner_reference;
+mod apply_schedule;
+mod apply_status;
 
 use std::sync::Arc;
 use std::time::Duration;
@@ -7,7 +9,7 @@ use std::time::Duration;
 use futures::prelude::*;
 use kubimo::k8s_openapi::api::batch::v1::Job;
 use kubimo::kube::runtime::{Controller, controller::Action};
-use kubimo::{CacheJob, Workspace, prelude::*};
+use kubimo::{CacheJob, KubimoLabel, Workspace, prelude::*};
 
 use crate::backoff::default_error_policy;
 use crate::context::Context;
@@ -18,6 +20,15 @@ use crate::reconciler::{ReconcileError, Reconciler, ReconcilerExt};
 #[derive(Debug, Clone, Copy)]
 struct CacheJobReconciler;
 
+/// Label on every Job a CacheJob runs, naming the CacheJob: how a scheduled
+/// one finds its history. Owner references would do, but cannot be listed by.
+fn cache_job_label(cache_job_name: &str) -> (String, String) {
+    (
+        KubimoLabel::borrow("cache-job").to_string(),
+        cache_job_name.to_string(),
+    )
+}
+
 #[async_trait::async_trait]
 impl Reconciler for CacheJobReconciler {
     type Resource = CacheJob;
@@ -45,6 +56,8 @@ impl Reconciler for CacheJobReconciler {
         // The cache job mounts the workspace volume; running it before the
         // workspace's init job has populated the volume fails `uv sync`.
         if !is_workspace_ready(&workspace) {
+            self.apply_waiting(ctx, cache_job, "Waiting for the workspace to be ready")
+                .await?;
             return Ok(Action::requeue(Duration::from_secs(5)));
         }
 
@@ -54,11 +67,21 @@ impl Reconciler for CacheJobReconciler {
         if let Some(start_after) = cache_job.spec.start_after {
             let remaining = start_after - chrono::Utc::now();
             if let Ok(remaining) = remaining.to_std() {
+                self.apply_waiting(ctx, cache_job, "Waiting for startAfter")
+                    .await?;
                 return Ok(Action::requeue(remaining));
             }
         }
 
-        self.apply_job(ctx, cache_job).await?;
+        if let Some(schedule) = cache_job.spec.schedule.as_ref() {
+            return self.apply_schedule(ctx, cache_job, schedule).await;
+        }
+
+        let job = self.apply_job(ctx, cache_job, cache_job.name()?).await?;
+        let mut status = cache_job.status.clone().unwrap_or_default();
+        apply_status::record_outcomes(&mut status, [&job]);
+        self.observe_run(ctx, cache_job, &job, &mut status).await?;
+        self.apply_status(ctx, cache_job, status).await?;
         Ok(Action::await_change())
     }
 }
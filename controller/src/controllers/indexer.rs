@@ -1,5 +1,7 @@
 use kubimo::k8s_openapi::api::core::v1::{EnvFromSource, EnvVar, Pod};
-use kubimo::{Workspace, WorkspaceIndexerPod, WorkspaceRestoreFrom, prelude::*};
+use kubimo::{
+    Workspace, WorkspaceArchiveLimits, WorkspaceIndexerPod, WorkspaceRestoreFrom, prelude::*,
+};
 
 use crate::command::cmd;
 use crate::context::Context;
@@ -115,7 +117,10 @@ pub(crate) fn download_args(restore: &WorkspaceRestoreFrom) -> Vec<String> {
 ///
 /// Set explicitly even for the default, so the behavior is pinned by this
 /// controller rather than by whatever binary default ships in the image.
-pub(crate) fn download_env(restore: &WorkspaceRestoreFrom) -> Option<Vec<EnvVar>> {
+pub(crate) fn download_env(
+    restore: &WorkspaceRestoreFrom,
+    limits: Option<&WorkspaceArchiveLimits>,
+) -> Option<Vec<EnvVar>> {
     let mut env = pod_env(restore.pod.as_ref()).unwrap_or_default();
     let mode = restore.secrets.unwrap_or_default().to_string();
     env.retain(|existing| existing.name != "KUBIMO_RESTORE_SECRETS");
@@ -124,9 +129,121 @@ pub(crate) fn download_env(restore: &WorkspaceRestoreFrom) -> Option<Vec<EnvVar>
         value: Some(mode),
         ..Default::default()
     });
+    // A restore point, when one is asked for. An indexer that predates history
+    // ignores these and restores the current manifest instead — the one skew
+    // case that degrades rather than wedges, and an archive written by such an
+    // indexer has no generations to pick from anyway.
+    if let Some(generation) = restore.generation.as_ref() {
+        set_var(&mut env, "KUBIMO_RESTORE_GENERATION", generation.clone());
+    }
+    if let Some(at) = restore.at.as_ref() {
+        set_var(&mut env, "KUBIMO_RESTORE_AT", at.to_rfc3339());
+    }
+    // The restoring workspace's limits, not the source's: they size this
+    // workspace's volume and its share of the bucket. An older indexer
+    // ignores them and streams every file whole.
+    if let Some(limits) = limits {
+        set_var(
+            &mut env,
+            "KUBIMO_DOWNLOAD_PART_SIZE",
+            limits.effective_download_part_size().to_string(),
+        );
+        set_var(
+            &mut env,
+            "KUBIMO_DOWNLOAD_PART_CONCURRENCY",
+            limits.effective_download_part_concurrency().to_string(),
+        );
+    }
+    Some(env)
+}
+
+/// Pod env for an uploading indexer: [`env`] plus the archive's history
+/// retention, content addressing, compression, chunking and size cap, as
+/// environment for the same version-skew reason as [`download_env`]. Unset when
+/// the spec leaves them unset, which an indexer of any age reads as "no
+/// history" and "own keys".
+pub(crate) fn upload_env(workspace: &Workspace) -> Option<Vec<EnvVar>> {
+    let mut env = env(workspace).unwrap_or_default();
+    let indexer = workspace.spec.indexer.as_ref();
+    // An indexer that predates the store ignores this and keeps uploading
+    // under the workspace's own keys, which stays correct: a restore reads
+    // whichever urls the manifest names.
+    if indexer.and_then(|indexer| indexer.content_addressed) == Some(true) {
+        set_var(&mut env, "KUBIMO_CONTENT_ADDRESSED", "true".to_string());
+    }
+    // An indexer that predates compression ignores these and stores content
+    // raw. Its manifest then names no codec for the files it rewrites, and
+    // leaves unchanged ones naming the codec they were written with.
+    if let Some(compression) = indexer.and_then(|indexer| indexer.compression.as_ref()) {
+        set_var(
+            &mut env,
+            "KUBIMO_COMPRESSION_MIN_SIZE",
+            compression.effective_min_size().to_string(),
+        );
+        set_var(
+            &mut env,
+            "KUBIMO_COMPRESSION_LEVEL",
+            compression.effective_level().to_string(),
+        );
+    }
+    // An indexer that predates this reads its own default, which is the
+    // default here too.
+    if let Some(limits) = indexer.and_then(|indexer| indexer.limits.as_ref()) {
+        set_var(
+            &mut env,
+            "KUBIMO_MAX_FILE_SIZE",
+            limits.effective_max_file_size().to_string(),
+        );
+    }
+    // An indexer that predates chunking ignores these and skips files over
+    // its own size cap, as it always has. Files it does upload replace their
+    // chunk lists with whole objects, and the sweep takes the chunks.
+    if let Some(chunking) = indexer.and_then(|indexer| indexer.chunking.as_ref()) {
+        set_var(
+            &mut env,
+            "KUBIMO_CHUNKING_MIN_SIZE",
+            chunking.effective_min_size().to_string(),
+        );
+        set_var(
+            &mut env,
+            "KUBIMO_CHUNKING_MAX_FILE_SIZE",
+            chunking.effective_max_file_size().to_string(),
+        );
+    }
+    let Some(history) = indexer.and_then(|indexer| indexer.history.as_ref()) else {
+        return Some(env);
+    };
+    if let Some(generations) = history.effective_generations() {
+        set_var(
+            &mut env,
+            "KUBIMO_HISTORY_GENERATIONS",
+            generations.to_string(),
+        );
+    }
+    if let Some(max_age_secs) = history.max_age_secs {
+        set_var(
+            &mut env,
+            "KUBIMO_HISTORY_MAX_AGE_SECS",
+            max_age_secs.to_string(),
+        );
+    }
+    set_var(
+        &mut env,
+        "KUBIMO_HISTORY_INTERVAL_SECS",
+        history.effective_interval_secs().to_string(),
+    );
     Some(env)
 }
 
+fn set_var(env: &mut Vec<EnvVar>, name: &str, value: String) {
+    env.retain(|existing| existing.name != name);
+    env.push(EnvVar {
+        name: name.to_string(),
+        value: Some(value),
+        ..Default::default()
+    });
+}
+
 pub(crate) fn pod_env(pod: Option<&WorkspaceIndexerPod>) -> Option<Vec<EnvVar>> {
     let mut env = pod
         .and_then(|pod| pod.env.as_ref())
@@ -245,10 +362,13 @@ mod tests {
     #[test]
     fn test_download_env_pins_the_secrets_mode_explicitly() {
         // Absent from the spec: the controller still writes the safe default.
-        let env = download_env(&WorkspaceRestoreFrom {
-            bucket: "bucket".to_string(),
-            ..Default::default()
-        })
+        let env = download_env(
+            &WorkspaceRestoreFrom {
+                bucket: "bucket".to_string(),
+                ..Default::default()
+            },
+            None,
+        )
         .unwrap();
         let value = |name: &str| {
             env.iter()
@@ -262,11 +382,14 @@ mod tests {
         // `pod_env`'s RUST_LOG injection still applies.
         assert_eq!(value("RUST_LOG").as_deref(), Some("info"));
 
-        let env = download_env(&WorkspaceRestoreFrom {
-            bucket: "bucket".to_string(),
-            secrets: Some(kubimo::WorkspaceRestoreSecrets::Values),
-            ..Default::default()
-        })
+        let env = download_env(
+            &WorkspaceRestoreFrom {
+                bucket: "bucket".to_string(),
+                secrets: Some(kubimo::WorkspaceRestoreSecrets::Values),
+                ..Default::default()
+            },
+            None,
+        )
         .unwrap();
         assert_eq!(
             env.iter()
@@ -286,11 +409,14 @@ mod tests {
             kubimo::WorkspaceRestoreSecrets::Values,
             kubimo::WorkspaceRestoreSecrets::NamesOnly,
         ] {
-            let env = download_env(&WorkspaceRestoreFrom {
-                bucket: "bucket".to_string(),
-                secrets: Some(mode),
-                ..Default::default()
-            })
+            let env = download_env(
+                &WorkspaceRestoreFrom {
+                    bucket: "bucket".to_string(),
+                    secrets: Some(mode),
+                    ..Default::default()
+                },
+                None,
+            )
             .unwrap();
             let written = env
                 .iter()
@@ -356,6 +482,223 @@ mod tests {
         assert_eq!(clean_args(&workspace).unwrap(), vec!["clean", "ws"]);
     }
 
+    #[test]
+    fn test_limits_reach_the_upload_and_download_env() {
+        let limits = kubimo::WorkspaceArchiveLimits {
+            max_file_size: Some(5 << 30),
+            download_part_size: Some(1),
+            download_part_concurrency: None,
+        };
+        let value = |env: &[EnvVar], name: &str| {
+            env.iter()
+                .find(|var| var.name == name)
+                .and_then(|var| var.value.clone())
+        };
+        let workspace = kubimo::Workspace::new(
+            "ws",
+            kubimo::WorkspaceSpec {
+                indexer: Some(kubimo::WorkspaceIndexer {
+                    limits: Some(limits.clone()),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+        );
+        let env = upload_env(&workspace).unwrap();
+        assert_eq!(
+            value(&env, "KUBIMO_MAX_FILE_SIZE").as_deref(),
+            Some("5368709120")
+        );
+
+        let restore = WorkspaceRestoreFrom {
+            bucket: "bucket".to_string(),
+            ..Default::default()
+        };
+        let env = download_env(&restore, None).unwrap();
+        assert_eq!(value(&env, "KUBIMO_DOWNLOAD_PART_SIZE"), None);
+        let env = download_env(&restore, Some(&limits)).unwrap();
+        // Floored: a one-byte range is a request per byte.
+        assert_eq!(
+            value(&env, "KUBIMO_DOWNLOAD_PART_SIZE").as_deref(),
+            Some("1048576")
+        );
+        assert_eq!(
+            value(&env, "KUBIMO_DOWNLOAD_PART_CONCURRENCY").as_deref(),
+            Some("8")
+        );
+    }
+
+    #[test]
+    fn test_download_env_carries_the_restore_point() {
+        let env = download_env(
+            &WorkspaceRestoreFrom {
+                bucket: "bucket".to_string(),
+                at: Some("2026-01-01T12:00:00Z".parse().unwrap()),
+                ..Default::default()
+            },
+            None,
+        )
+        .unwrap();
+        let value = |name: &str| {
+            env.iter()
+                .find(|var| var.name == name)
+                .and_then(|var| var.value.clone())
+        };
+        assert_eq!(
+            value("KUBIMO_RESTORE_AT").as_deref(),
+            Some("2026-01-01T12:00:00+00:00")
+        );
+        assert_eq!(value("KUBIMO_RESTORE_GENERATION"), None);
+    }
+
+    /// An empty `history: {}` still means "keep history": the default count
+    /// has to reach the indexer, or it sees no limit and keeps none.
+    #[test]
+    fn test_upload_env_carries_history_retention() {
+        let workspace = |history| {
+            kubimo::Workspace::new(
+                "ws",
+                kubimo::WorkspaceSpec {
+                    indexer: Some(kubimo::WorkspaceIndexer {
+                        history,
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+            )
+        };
+        let names = |env: Vec<EnvVar>| {
+            env.into_iter()
+                .filter(|var| var.name.starts_with("KUBIMO_HISTORY_"))
+                .map(|var| (var.name, var.value.unwrap_or_default()))
+                .collect::<Vec<_>>()
+        };
+        assert!(names(upload_env(&workspace(None)).unwrap()).is_empty());
+        assert_eq!(
+            names(upload_env(&workspace(Some(Default::default()))).unwrap()),
+            vec![
+                ("KUBIMO_HISTORY_GENERATIONS".to_string(), "24".to_string()),
+                (
+                    "KUBIMO_HISTORY_INTERVAL_SECS".to_string(),
+                    "300".to_string()
+                ),
+            ]
+        );
+        assert_eq!(
+            names(
+                upload_env(&workspace(Some(kubimo::WorkspaceArchiveHistory {
+                    max_age_secs: Some(86400),
+                    interval_secs: Some(60),
+                    ..Default::default()
+                })))
+                .unwrap()
+            ),
+            vec![
+                (
+                    "KUBIMO_HISTORY_MAX_AGE_SECS".to_string(),
+                    "86400".to_string()
+                ),
+                ("KUBIMO_HISTORY_INTERVAL_SECS".to_string(), "60".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_upload_env_carries_content_addressing_only_when_enabled() {
+        let flag = |content_addressed| {
+            let workspace = kubimo::Workspace::new(
+                "ws",
+                kubimo::WorkspaceSpec {
+                    indexer: Some(kubimo::WorkspaceIndexer {
+                        content_addressed,
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+            );
+            upload_env(&workspace)
+                .unwrap()
+                .into_iter()
+                .find(|var| var.name == "KUBIMO_CONTENT_ADDRESSED")
+                .and_then(|var| var.value)
+        };
+        assert_eq!(flag(None), None);
+        assert_eq!(flag(Some(false)), None);
+        assert_eq!(flag(Some(true)).as_deref(), Some("true"));
+    }
+
+    #[test]
+    fn test_upload_env_carries_compression_with_defaults_applied() {
+        let vars = |compression| {
+            let workspace = kubimo::Workspace::new(
+                "ws",
+                kubimo::WorkspaceSpec {
+                    indexer: Some(kubimo::WorkspaceIndexer {
+                        compression,
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+            );
+            upload_env(&workspace)
+                .unwrap()
+                .into_iter()
+                .filter(|var| var.name.starts_with("KUBIMO_COMPRESSION_"))
+                .map(|var| (var.name, var.value.unwrap_or_default()))
+                .collect::<Vec<_>>()
+        };
+        assert!(vars(None).is_empty());
+        assert_eq!(
+            vars(Some(kubimo::WorkspaceArchiveCompression {
+                min_size: None,
+                level: Some(40),
+            })),
+            vec![
+                (
+                    "KUBIMO_COMPRESSION_MIN_SIZE".to_string(),
+                    "4096".to_string()
+                ),
+                ("KUBIMO_COMPRESSION_LEVEL".to_string(), "19".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_upload_env_carries_chunking_with_defaults_applied() {
+        let vars = |chunking| {
+            let workspace = kubimo::Workspace::new(
+                "ws",
+                kubimo::WorkspaceSpec {
+                    indexer: Some(kubimo::WorkspaceIndexer {
+                        chunking,
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+            );
+            upload_env(&workspace)
+                .unwrap()
+                .into_iter()
+                .filter(|var| var.name.starts_with("KUBIMO_CHUNKING_"))
+                .map(|var| (var.name, var.value.unwrap_or_default()))
+                .collect::<Vec<_>>()
+        };
+        assert!(vars(None).is_empty());
+        assert_eq!(
+            vars(Some(kubimo::WorkspaceArchiveChunking {
+                min_size: Some(1024),
+                max_file_size: None,
+            })),
+            vec![
+                ("KUBIMO_CHUNKING_MIN_SIZE".to_string(), "1024".to_string()),
+                (
+                    "KUBIMO_CHUNKING_MAX_FILE_SIZE".to_string(),
+                    kubimo::WorkspaceArchiveChunking::DEFAULT_MAX_FILE_SIZE.to_string()
+                ),
+            ]
+        );
+    }
+
     #[test]
     fn test_pod_env_injects_rust_log() {
         let env = pod_env(None).unwrap();
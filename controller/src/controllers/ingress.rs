@@ -36,3 +36,63 @@ pub fn effective_ingress_path(runner: &Runner) -> kubimo::Result<String> {
     }
     ingress_path(runner)
 }
+
+/// The URL a runner is reached at, for `status.url`: its own TLS host if it
+/// names one, else the first configured runner host, and the path it is
+/// actually served under. Every host gets TLS in the Ingress, hence `https`.
+/// `None` without a host — a host-less Ingress has no URL to give.
+pub fn runner_url(runner_hosts: &[String], runner: &Runner) -> kubimo::Result<Option<String>> {
+    let host = runner
+        .spec
+        .ingress
+        .as_ref()
+        .and_then(|ingress| ingress.tls.as_ref())
+        .and_then(|tls| tls.hosts.as_ref())
+        .and_then(|hosts| hosts.first())
+        .or(runner_hosts.first());
+    let Some(host) = host else {
+        return Ok(None);
+    };
+    let path = effective_ingress_path(runner)?;
+    Ok(Some(format!(
+        "https://{host}{path}/",
+        path = path.trim_end_matches('/')
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kubimo::{RunnerClaim, RunnerIngress, RunnerSpec, RunnerStatus, RunnerTls};
+
+    #[test]
+    fn the_url_prefers_the_runner_host_and_the_claimed_path() {
+        let mut runner = Runner::new("bmor-x", RunnerSpec::default());
+        assert_eq!(runner_url(&[], &runner).unwrap(), None);
+        let hosts = ["runners.example.com".to_string()];
+        assert_eq!(
+            runner_url(&hosts, &runner).unwrap().as_deref(),
+            Some("https://runners.example.com/bmor-x/")
+        );
+        runner.spec.ingress = Some(RunnerIngress {
+            tls: Some(RunnerTls {
+                hosts: Some(vec!["mine.example.com".into()]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+        runner.status = Some(RunnerStatus {
+            claim: Some(RunnerClaim {
+                pool: "editors".into(),
+                pod_name: "editors-a1b2c3d4".into(),
+                ingress_path: "/editors-a1b2c3d4".into(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+        assert_eq!(
+            runner_url(&hosts, &runner).unwrap().as_deref(),
+            Some("https://mine.example.com/editors-a1b2c3d4/")
+        );
+    }
+}
@@ -1,3 +1,4 @@
+pub mod archive_gc;
 pub mod budget;
 pub mod cache_job;
 pub mod indexer;
@@ -6,8 +7,11 @@ pub mod pool;
 pub mod runner;
 pub(crate) mod runner_pod;
 pub mod runner_status;
+pub(crate) mod schedule;
 pub(crate) mod slot_volume;
+pub mod usage;
 pub mod workspace;
 pub mod workspace_affinity;
 pub mod workspace_directory;
 pub mod workspace_python_runtime;
+pub mod workspace_snapshot;
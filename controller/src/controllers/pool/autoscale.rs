@@ -0,0 +1,353 @@
+//! Sizing a Pool's warm fleet from its recent claim rate (`spec.autoscale`).
+//!
+//! Demand is every Runner that named the pool within the claim window: the
+//! ones that claimed a warm pod and the ones that fell back to a cold start,
+//! which are the claims the fleet was too small (or too drifted) to serve. A
+//! fleet that absorbed the last window's demand, plus `targetIdle` for the
+//! next one, is what each reconcile recommends.
+//!
+//! Recommendations are stabilized the way a HorizontalPodAutoscaler's are,
+//! and kept in the Pool's status for it: the fleet only grows to the lowest
+//! recommendation of the scale-up window and only shrinks to the highest of
+//! the scale-down window. A class starting therefore grows the fleet at once
+//! under the default zero scale-up window, while a lull has to last the whole
+//! scale-down window before warm pods are retired.
+
+use futures::prelude::*;
+use kubimo::chrono::{DateTime, TimeDelta, Utc};
+use kubimo::{
+    FilterParams, Pool, PoolAutoscale, PoolAutoscaleRecommendation, PoolAutoscaleStatus, Runner,
+    prelude::*,
+};
+
+use crate::context::Context;
+
+pub(super) const SCALED_UP: &str = "ScaledUp";
+pub(super) const SCALED_DOWN: &str = "ScaledDown";
+const STABILIZING: &str = "Stabilizing";
+const STABLE: &str = "Stable";
+
+/// Runners that named a pool within its claim window.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(super) struct Demand {
+    pub(super) claims: u32,
+    pub(super) cold_starts: u32,
+}
+
+impl Demand {
+    fn total(self) -> u32 {
+        self.claims.saturating_add(self.cold_starts)
+    }
+}
+
+/// The demand on `pool` over the `autoscale` claim window up to `now`.
+///
+/// Runners carry no pool label, so this lists the namespace's and filters on
+/// `spec.pool`; a pool's Runners never live anywhere else.
+pub(super) async fn recent_demand(
+    ctx: &Context,
+    pool: &Pool,
+    autoscale: &PoolAutoscale,
+    now: DateTime<Utc>,
+) -> Result<Demand, kubimo::Error> {
+    let runners: Vec<Runner> = ctx
+        .api_namespaced::<Runner>(pool.require_namespace()?)
+        .list(&FilterParams::new())
+        .map_ok(|item| item.item)
+        .try_collect()
+        .await?;
+    let window = TimeDelta::seconds(autoscale.effective_claim_window_secs() as i64);
+    Ok(demand(&runners, pool.name()?, now - window))
+}
+
+/// Tally the Runners naming `pool` created since `since`. A Runner the runner
+/// reconciler has not written a status for yet has neither claimed nor gone
+/// cold, and counts as neither until it has: guessing would flip a cold start
+/// into a claim one reconcile later.
+fn demand(runners: &[Runner], pool: &str, since: DateTime<Utc>) -> Demand {
+    let mut demand = Demand::default();
+    for runner in runners {
+        if runner.spec.pool.as_deref() != Some(pool) {
+            continue;
+        }
+        let recent = runner
+            .metadata
+            .creation_timestamp
+            .as_ref()
+            .is_some_and(|created| created.0.as_second() >= since.timestamp());
+        if !recent {
+            continue;
+        }
+        let Some(status) = runner.status.as_ref() else {
+            continue;
+        };
+        if status
+            .claim
+            .as_ref()
+            .is_some_and(|claim| claim.pool == pool)
+        {
+            demand.claims += 1;
+        } else if status.conditions.as_ref().is_some_and(|c| !c.is_empty()) {
+            demand.cold_starts += 1;
+        }
+    }
+    demand
+}
+
+/// The next autoscaling decision, given the last one (`previous`) and the
+/// demand since. Without a previous decision the fleet starts from
+/// `spec.replicas`, so turning autoscaling on does not resize it at once.
+pub(super) fn decide(
+    autoscale: &PoolAutoscale,
+    replicas: u32,
+    previous: Option<&PoolAutoscaleStatus>,
+    demand: Demand,
+    now: DateTime<Utc>,
+) -> PoolAutoscaleStatus {
+    let current = autoscale.clamp(previous.map_or(replicas, |previous| previous.desired_replicas));
+    let recommended = autoscale.clamp(
+        demand
+            .total()
+            .saturating_add(autoscale.effective_target_idle()),
+    );
+
+    let up_window = autoscale.effective_scale_up_stabilization_secs();
+    let down_window = autoscale.effective_scale_down_stabilization_secs();
+    // Each entry is where a run of equal recommendations *started*; the run
+    // lasts until the next entry starts, or until now for the last. A
+    // recommendation that has not changed is therefore not written again, so
+    // a reconcile on unchanged demand leaves the status exactly as it was —
+    // which is what stops the status write waking the Pool back up.
+    //
+    // With no history yet, turning autoscaling on counts as having
+    // recommended `replicas` just now: otherwise the scale-down window would
+    // hold nothing to stabilize on, and the first quiet reconcile would
+    // retire most of the fleet.
+    let mut recommendations: Vec<PoolAutoscaleRecommendation> = match previous {
+        Some(previous) => previous.recommendations.clone().unwrap_or_default(),
+        None => vec![PoolAutoscaleRecommendation {
+            time: now,
+            replicas: current,
+        }],
+    };
+    if recommendations
+        .last()
+        .is_none_or(|last| last.replicas != recommended)
+    {
+        recommendations.push(PoolAutoscaleRecommendation {
+            time: now,
+            replicas: recommended,
+        });
+    }
+    // When each run ended, in step with `recommendations`; `None` for the
+    // one still running, which is in every window.
+    let ends: Vec<Option<DateTime<Utc>>> = recommendations
+        .iter()
+        .skip(1)
+        .map(|next| Some(next.time))
+        .chain([None])
+        .collect();
+    // Strictly: a run that ended the moment the current one started is not
+    // part of a zero-length window.
+    let within = |end: Option<DateTime<Utc>>, secs: u64| {
+        end.is_none_or(|end| now - end < TimeDelta::seconds(secs as i64))
+    };
+    let longest = up_window.max(down_window);
+    let (recommendations, ends): (Vec<_>, Vec<_>) = recommendations
+        .into_iter()
+        .zip(ends)
+        .filter(|(_, end)| within(*end, longest))
+        .unzip();
+
+    // Both windows hold the run of the recommendation just made, so
+    // `up <= recommended <= down` and at most one of them moves the fleet.
+    let held = |secs: u64| {
+        recommendations
+            .iter()
+            .zip(&ends)
+            .filter(move |(_, end)| within(**end, secs))
+            .map(|(recommendation, _)| recommendation.replicas)
+    };
+    let up = held(up_window).min().unwrap_or(recommended);
+    let down = held(down_window).max().unwrap_or(recommended);
+    let desired = if up > current {
+        up
+    } else if down < current {
+        down
+    } else {
+        current
+    };
+
+    let reason = match desired.cmp(&current) {
+        std::cmp::Ordering::Greater => SCALED_UP,
+        std::cmp::Ordering::Less => SCALED_DOWN,
+        std::cmp::Ordering::Equal if recommended != current => STABILIZING,
+        std::cmp::Ordering::Equal => STABLE,
+    };
+    let window = autoscale.effective_claim_window_secs();
+    let message = match reason {
+        STABILIZING => format!(
+            "{} claims and {} cold starts in the last {window}s recommend {recommended} warm pods; keeping {current} until that holds",
+            demand.claims, demand.cold_starts,
+        ),
+        _ => format!(
+            "{} claims and {} cold starts in the last {window}s; keeping {desired} warm pods",
+            demand.claims, demand.cold_starts,
+        ),
+    };
+    PoolAutoscaleStatus {
+        desired_replicas: desired,
+        recent_claims: Some(demand.claims),
+        recent_cold_starts: Some(demand.cold_starts),
+        reason: Some(reason.to_string()),
+        message: Some(message),
+        last_scale_time: if desired != current {
+            Some(now)
+        } else {
+            previous.and_then(|previous| previous.last_scale_time)
+        },
+        recommendations: Some(recommendations),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kubimo::k8s_openapi::apimachinery::pkg::apis::meta::v1::{Condition, Time};
+    use kubimo::k8s_openapi::jiff::Timestamp;
+    use kubimo::{RunnerClaim, RunnerSpec, RunnerStatus};
+
+    fn autoscale() -> PoolAutoscale {
+        PoolAutoscale {
+            min_replicas: 1,
+            max_replicas: 10,
+            scale_up_stabilization_secs: Some(0),
+            scale_down_stabilization_secs: Some(300),
+            ..Default::default()
+        }
+    }
+
+    fn at(secs: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(1_800_000_000 + secs, 0).unwrap()
+    }
+
+    fn demand_of(total: u32) -> Demand {
+        Demand {
+            claims: total,
+            cold_starts: 0,
+        }
+    }
+
+    #[test]
+    fn a_burst_scales_up_at_once_and_a_lull_only_after_the_window() {
+        let autoscale = autoscale();
+        let first = decide(&autoscale, 2, None, demand_of(0), at(0));
+        // Recommends 1, but nothing has held yet: keeps `replicas`.
+        assert_eq!(first.desired_replicas, 2);
+        assert_eq!(first.reason.as_deref(), Some(STABILIZING));
+
+        let burst = decide(&autoscale, 2, Some(&first), demand_of(6), at(30));
+        assert_eq!(burst.desired_replicas, 7);
+        assert_eq!(burst.reason.as_deref(), Some(SCALED_UP));
+        assert_eq!(burst.last_scale_time, Some(at(30)));
+
+        // Quiet from 200 on: the burst held until then, which is still
+        // within the scale-down window at 400.
+        let quiet = decide(&autoscale, 2, Some(&burst), demand_of(0), at(200));
+        assert_eq!(quiet.desired_replicas, 7);
+        assert_eq!(quiet.reason.as_deref(), Some(STABILIZING));
+        let quiet = decide(&autoscale, 2, Some(&quiet), demand_of(0), at(400));
+        assert_eq!(quiet.desired_replicas, 7);
+
+        let later = decide(&autoscale, 2, Some(&quiet), demand_of(0), at(520));
+        assert_eq!(later.desired_replicas, 1);
+        assert_eq!(later.reason.as_deref(), Some(SCALED_DOWN));
+        // Only runs that ended within the longer window are kept, and the
+        // quiet one still starts where it started.
+        assert_eq!(
+            later.recommendations.unwrap(),
+            [PoolAutoscaleRecommendation {
+                time: at(200),
+                replicas: 1,
+            }]
+        );
+    }
+
+    /// A reconcile on unchanged demand must not change the status, or its
+    /// own write would wake the Pool to reconcile again.
+    #[test]
+    fn an_unchanged_decision_leaves_the_status_as_it_was() {
+        let autoscale = autoscale();
+        let first = decide(&autoscale, 3, None, demand_of(2), at(0));
+        let again = decide(&autoscale, 3, Some(&first), demand_of(2), at(10));
+        assert_eq!(
+            serde_json::to_vec(&first).unwrap(),
+            serde_json::to_vec(&again).unwrap()
+        );
+    }
+
+    #[test]
+    fn recommendations_are_clamped_to_the_range() {
+        let autoscale = PoolAutoscale {
+            min_replicas: 2,
+            max_replicas: 4,
+            ..autoscale()
+        };
+        let status = decide(&autoscale, 9, None, demand_of(50), at(0));
+        assert_eq!(status.desired_replicas, 4);
+        assert_eq!(status.reason.as_deref(), Some(STABLE));
+        let status = decide(&autoscale, 0, None, demand_of(0), at(0));
+        assert_eq!(status.desired_replicas, 2);
+        assert_eq!(status.reason.as_deref(), Some(STABLE));
+    }
+
+    fn runner(pool: &str, created: i64, claimed: Option<&str>, reconciled: bool) -> Runner {
+        let mut runner = Runner::new(
+            "runner",
+            RunnerSpec {
+                pool: Some(pool.to_string()),
+                ..Default::default()
+            },
+        );
+        runner.metadata.creation_timestamp = Some(Time(
+            Timestamp::from_second(at(created).timestamp()).unwrap(),
+        ));
+        runner.status = reconciled.then(|| RunnerStatus {
+            conditions: Some(vec![Condition {
+                type_: kubimo::conditions::POD_SCHEDULED.to_string(),
+                status: "True".to_string(),
+                reason: String::new(),
+                message: String::new(),
+                observed_generation: None,
+                last_transition_time: Time(Timestamp::UNIX_EPOCH),
+            }]),
+            claim: claimed.map(|pool| RunnerClaim {
+                pool: pool.to_string(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+        runner
+    }
+
+    #[test]
+    fn demand_counts_recent_claims_and_cold_starts_of_this_pool() {
+        let runners = [
+            runner("editors", 100, Some("editors"), true),
+            runner("editors", 200, None, true),
+            // Not reconciled yet: neither.
+            runner("editors", 250, None, false),
+            // Too old.
+            runner("editors", -500, Some("editors"), true),
+            // Another pool's.
+            runner("other", 100, Some("other"), true),
+        ];
+        assert_eq!(
+            demand(&runners, "editors", at(0)),
+            Demand {
+                claims: 1,
+                cold_starts: 1,
+            }
+        );
+    }
+}
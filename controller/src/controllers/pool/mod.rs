@@ -6,12 +6,14 @@
 //! states with a JSON-Patch `test` on the previous state label, so a claim and
 //! a retire can race and exactly one wins.
 
+mod autoscale;
 pub(crate) mod warm_pod;
 
 use std::sync::Arc;
 use std::time::Duration;
 
 use futures::prelude::*;
+use kubimo::chrono::Utc;
 use kubimo::k8s_openapi::api::core::v1::{Pod, Secret};
 use kubimo::k8s_openapi::apimachinery::pkg::apis::meta::v1::{Condition, Time};
 use kubimo::k8s_openapi::jiff::Timestamp;
@@ -20,7 +22,7 @@ use kubimo::pool::{
     POOL_LABEL, POOL_STATE_CLAIMED, POOL_STATE_LABEL, POOL_STATE_RETIRING, POOL_STATE_WARM,
     POOL_TEMPLATE_HASH_ANNOTATION,
 };
-use kubimo::{Api, FilterParams, Pool, PoolStatus, json_patch_macros::*, prelude::*};
+use kubimo::{Api, EventType, FilterParams, Pool, PoolStatus, json_patch_macros::*, prelude::*};
 
 use crate::backoff::default_error_policy;
 use crate::context::Context;
@@ -53,6 +55,29 @@ impl Reconciler for PoolReconciler {
             .try_collect()
             .await?;
 
+        // The fleet size: `spec.replicas`, or what the autoscaler decides.
+        let autoscale = match &pool.spec.autoscale {
+            Some(autoscale) => {
+                let now = Utc::now();
+                let demand = autoscale::recent_demand(ctx, pool, autoscale, now).await?;
+                let previous = pool
+                    .status
+                    .as_ref()
+                    .and_then(|status| status.autoscale.as_ref());
+                Some(autoscale::decide(
+                    autoscale,
+                    pool.spec.replicas,
+                    previous,
+                    demand,
+                    now,
+                ))
+            }
+            None => None,
+        };
+        let replicas = autoscale
+            .as_ref()
+            .map_or(pool.spec.replicas, |status| status.desired_replicas);
+
         let template_hash = warm_pod::template_hash(&ctx.config, pool);
         let mut warm = Vec::new();
         let mut retiring = Vec::new();
@@ -89,7 +114,7 @@ impl Reconciler for PoolReconciler {
                     == Some(&template_hash)
             });
             let mut excess = drifted;
-            let keep = (pool.spec.replicas as usize).min(fresh.len());
+            let keep = (replicas as usize).min(fresh.len());
             excess.extend_from_slice(&fresh[keep..]);
             (fresh[..keep].to_vec(), excess)
         };
@@ -121,7 +146,7 @@ impl Reconciler for PoolReconciler {
             }
         }
 
-        let deficit = (pool.spec.replicas as usize).saturating_sub(kept.len());
+        let deficit = (replicas as usize).saturating_sub(kept.len());
         for _ in 0..deficit {
             let identity = warm_pod::mint_identity(name);
             // Create, never converge: a warm pod's command embeds its minted
@@ -141,10 +166,34 @@ impl Reconciler for PoolReconciler {
             conditions: Some(vec![ready_condition(pool, deficit == 0)]),
             warm: Some(kept.len() as u32),
             claimed: Some(claimed),
+            autoscale: autoscale.clone(),
         });
-        ctx.api_namespaced::<Pool>(namespace)
-            .patch_status(&patched)
-            .await?;
+        // Only when something changed: the watch on Pools is unfiltered, so
+        // every status write wakes this Pool again, and with autoscaling each
+        // pass lists the namespace's Runners. An unchanged pass ends the
+        // chain; the timer below is what keeps counts and demand fresh.
+        if patched.status != pool.status {
+            ctx.api_namespaced::<Pool>(namespace)
+                .patch_status(&patched)
+                .await?;
+        }
+
+        if let Some(status) = autoscale.as_ref().filter(|status| {
+            matches!(
+                status.reason.as_deref(),
+                Some(autoscale::SCALED_UP | autoscale::SCALED_DOWN)
+            )
+        }) {
+            ctx.events
+                .publish(
+                    pool,
+                    EventType::Normal,
+                    status.reason.as_deref().unwrap_or_default(),
+                    "Autoscale",
+                    status.message.clone().unwrap_or_default(),
+                )
+                .await;
+        }
 
         Ok(Action::requeue(REFRESH_INTERVAL))
     }
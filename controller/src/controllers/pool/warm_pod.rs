@@ -263,6 +263,16 @@ mod tests {
             template_hash(&config, &resized),
             "replicas is a sizing knob, not a pod shape"
         );
+        resized.spec.autoscale = Some(kubimo::PoolAutoscale {
+            min_replicas: 1,
+            max_replicas: 9,
+            ..Default::default()
+        });
+        assert_eq!(
+            template_hash(&config, &base),
+            template_hash(&config, &resized),
+            "neither is autoscale"
+        );
 
         let cpu = pool(PoolSpec {
             cpu: Some(kubimo::Requirement {
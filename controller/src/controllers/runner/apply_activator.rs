@@ -0,0 +1,148 @@
+//! Routing a runner with nothing to serve it to the activator, which resumes
+//! it on its first request if it is suspended and holds that request until
+//! its pod is ready either way.
+//!
+//! The Ingress stays where it is — same host, same path — and only its
+//! backend changes: the runner's Service becomes an `ExternalName` for the
+//! activator's. A ready pod reverses it through the ordinary path, whose
+//! `apply_service` replaces the `ExternalName` Service with the pod's.
+
+use kubimo::k8s_openapi::api::core::v1::{Pod, Service, ServicePort, ServiceSpec};
+use kubimo::kube::api::ObjectMeta;
+use kubimo::kube::runtime::controller::Action;
+use kubimo::{Runner, prelude::*};
+
+use crate::config::ActivatorConfig;
+use crate::context::Context;
+
+use super::RunnerReconciler;
+use super::apply_service::delete_if_retyped;
+
+/// The activator to route `runner` to: the configured one, while the runner
+/// is suspended or its pod is not ready — a pod lost to an eviction or a node
+/// would otherwise leave the route pointing at a Service with no endpoints.
+pub(super) fn activator<'a>(
+    ctx: &'a Context,
+    runner: &Runner,
+    pod: Option<&Pod>,
+) -> Option<&'a ActivatorConfig> {
+    ctx.config
+        .activator
+        .as_ref()
+        .filter(|_| runner.spec.is_suspended() || !is_pod_ready(pod))
+}
+
+/// Whether `pod` exists and passes its readiness probe.
+pub(super) fn is_pod_ready(pod: Option<&Pod>) -> bool {
+    pod.and_then(|pod| pod.status.as_ref())
+        .and_then(|status| status.conditions.as_ref())
+        .is_some_and(|conditions| {
+            conditions
+                .iter()
+                .any(|cond| cond.type_ == "Ready" && cond.status == "True")
+        })
+}
+
+/// The Host the activator receives for `runner`, from which it reads the
+/// runner back. Namespaces hold no dots, so the last one separates the two.
+pub(super) fn activator_vhost(runner: &Runner) -> kubimo::Result<String> {
+    Ok(format!(
+        "{}.{}",
+        runner.name()?,
+        runner.require_namespace()?
+    ))
+}
+
+fn activator_service(
+    runner: &Runner,
+    activator: &ActivatorConfig,
+) -> Result<Service, kubimo::Error> {
+    Ok(Service {
+        metadata: ObjectMeta {
+            name: runner.metadata.name.clone(),
+            namespace: runner.metadata.namespace.clone(),
+            owner_references: Some(vec![runner.static_controller_owner_ref()?]),
+            ..Default::default()
+        },
+        spec: Some(ServiceSpec {
+            type_: Some("ExternalName".to_string()),
+            external_name: Some(activator.host.clone()),
+            ports: Some(vec![ServicePort {
+                name: Some("activator".to_string()),
+                port: activator.port,
+                ..Default::default()
+            }]),
+            ..Default::default()
+        }),
+        ..Default::default()
+    })
+}
+
+impl RunnerReconciler {
+    pub(crate) async fn apply_activator_route(
+        &self,
+        ctx: &Context,
+        runner: &Runner,
+        activator: &ActivatorConfig,
+    ) -> Result<Action, kubimo::Error> {
+        let services = ctx.api_namespaced::<Service>(runner.require_namespace()?);
+        delete_if_retyped(&services, runner.name()?, "ExternalName").await?;
+        match services.patch(&activator_service(runner, activator)?).await {
+            Ok(_) => {}
+            // The pod's Service is still going; it wakes this runner as it
+            // does, being owned.
+            Err(err) if super::is_already_exists(&err) => return Ok(Action::await_change()),
+            Err(err) => return Err(err),
+        }
+        self.apply_ingress(ctx, runner, Some(activator)).await?;
+        Ok(Action::await_change())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kubimo::RunnerSpec;
+
+    #[test]
+    fn the_activator_is_named_by_the_runner_and_its_namespace() {
+        let mut runner = Runner::new("bmor-x", RunnerSpec::default());
+        runner.metadata.namespace = Some("team-a".into());
+        runner.metadata.uid = Some("uid".into());
+        assert_eq!(activator_vhost(&runner).unwrap(), "bmor-x.team-a");
+
+        let activator = ActivatorConfig {
+            host: "kubimo-activator.kubimo.svc.cluster.local".into(),
+            port: 80,
+        };
+        let spec = activator_service(&runner, &activator)
+            .unwrap()
+            .spec
+            .unwrap();
+        assert_eq!(spec.type_.as_deref(), Some("ExternalName"));
+        assert_eq!(spec.external_name.as_deref(), Some(activator.host.as_str()));
+        assert_eq!(spec.ports.unwrap()[0].port, 80);
+        assert!(spec.selector.is_none());
+    }
+
+    #[test]
+    fn only_a_ready_pod_keeps_the_runner_off_the_activator() {
+        use kubimo::k8s_openapi::api::core::v1::{PodCondition, PodStatus};
+
+        let pod = |ready: &str| Pod {
+            status: Some(PodStatus {
+                conditions: Some(vec![PodCondition {
+                    type_: "Ready".into(),
+                    status: ready.into(),
+                    ..Default::default()
+                }]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert!(is_pod_ready(Some(&pod("True"))));
+        assert!(!is_pod_ready(Some(&pod("False"))));
+        assert!(!is_pod_ready(Some(&Pod::default())));
+        assert!(!is_pod_ready(None));
+    }
+}
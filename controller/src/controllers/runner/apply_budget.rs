@@ -0,0 +1,154 @@
+//! Holding a runner back until its budgets have room for it.
+//!
+//! Admission, not eviction: only a runner with nothing running is checked, so
+//! lowering a budget's limits, or a runner's memory request being raised by
+//! an OOM retry, never stops a runner that is already serving. A refused
+//! runner waits, with `BudgetExceeded` saying why, and starts as soon as
+//! another runner's suspension or deletion frees the room.
+
+use std::time::Duration;
+
+use kubimo::conditions::BUDGET_EXCEEDED;
+use kubimo::k8s_openapi::api::core::v1::Pod;
+use kubimo::k8s_openapi::apimachinery::pkg::apis::meta::v1::{Condition, Time};
+use kubimo::k8s_openapi::jiff::Timestamp;
+use kubimo::{EventType, Runner, prelude::*};
+
+use crate::context::Context;
+use crate::controllers::budget::runner_refusal;
+
+use super::RunnerReconciler;
+
+/// Other runners suspending or going away does not trigger a refused runner,
+/// so recheck periodically (cf. the workspace reconciler's own).
+pub(super) const BUDGET_REQUEUE_INTERVAL: Duration = Duration::from_secs(30);
+
+pub(crate) enum BudgetAdmission {
+    /// Go ahead and start (or keep) the runner's pod.
+    Admitted,
+    /// Do not start it; `BudgetExceeded` is `True`.
+    Refused,
+}
+
+impl RunnerReconciler {
+    pub(crate) async fn apply_budget(
+        &self,
+        ctx: &Context,
+        runner: &Runner,
+    ) -> Result<BudgetAdmission, kubimo::Error> {
+        if is_running(ctx, runner).await? {
+            return Ok(BudgetAdmission::Admitted);
+        }
+        let refusal = runner_refusal(ctx, runner).await?;
+        let conditions = runner
+            .status
+            .as_ref()
+            .and_then(|status| status.conditions.as_deref())
+            .unwrap_or_default();
+        let Some(condition) = budget_condition(refusal.as_deref(), conditions, runner) else {
+            return Ok(BudgetAdmission::Admitted);
+        };
+        let refused = condition.status == "True";
+        let changed = !conditions.iter().any(|current| {
+            current.type_ == condition.type_
+                && current.status == condition.status
+                && current.message == condition.message
+        });
+        if changed {
+            let mut patched = runner.clone();
+            let mut status = runner.status.clone().unwrap_or_default();
+            let conditions = status.conditions.get_or_insert_with(Vec::new);
+            match conditions
+                .iter_mut()
+                .find(|current| current.type_ == condition.type_)
+            {
+                Some(current) if current.status == condition.status => {
+                    current.reason = condition.reason;
+                    current.message = condition.message;
+                    current.observed_generation = condition.observed_generation;
+                }
+                Some(current) => *current = condition,
+                None => conditions.push(condition),
+            }
+            patched.status = Some(status);
+            ctx.api_namespaced::<Runner>(runner.require_namespace()?)
+                .patch_status(&patched)
+                .await?;
+        }
+        if let Some(reason) = refusal {
+            tracing::info!(runner = runner.name()?, reason, "refused by its budget");
+            ctx.events
+                .publish(runner, EventType::Warning, BUDGET_EXCEEDED, "Start", reason)
+                .await;
+        }
+        Ok(if refused {
+            BudgetAdmission::Refused
+        } else {
+            BudgetAdmission::Admitted
+        })
+    }
+}
+
+/// Whether the runner already has a pod: its own cold pod, or a warm pod it
+/// claimed.
+async fn is_running(ctx: &Context, runner: &Runner) -> Result<bool, kubimo::Error> {
+    let claimed = runner
+        .status
+        .as_ref()
+        .and_then(|status| status.claim.as_ref())
+        .is_some_and(|claim| !claim.is_released());
+    if claimed {
+        return Ok(true);
+    }
+    Ok(ctx
+        .api_namespaced::<Pod>(runner.require_namespace()?)
+        .get_opt(runner.name()?)
+        .await?
+        .is_some())
+}
+
+/// `True` with the refusal while there is one, then `False` once a runner
+/// that was refused is admitted. `None` for a runner that never was.
+fn budget_condition(
+    refusal: Option<&str>,
+    conditions: &[Condition],
+    runner: &Runner,
+) -> Option<Condition> {
+    let (status, reason, message) = match refusal {
+        Some(refusal) => ("True", BUDGET_EXCEEDED, refusal.to_string()),
+        None if conditions.iter().any(|cond| cond.type_ == BUDGET_EXCEEDED) => (
+            "False",
+            "WithinBudget",
+            "The runner fits its budgets".to_string(),
+        ),
+        None => return None,
+    };
+    Some(Condition {
+        type_: BUDGET_EXCEEDED.to_string(),
+        status: status.to_string(),
+        reason: reason.to_string(),
+        message,
+        observed_generation: runner.metadata.generation,
+        last_transition_time: Time(Timestamp::now()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kubimo::RunnerSpec;
+
+    #[test]
+    fn the_condition_appears_on_refusal_and_clears_on_admission() {
+        let runner = Runner::new("test", RunnerSpec::default());
+        assert!(budget_condition(None, &[], &runner).is_none());
+
+        let refused = budget_condition(Some("no room"), &[], &runner).unwrap();
+        assert_eq!(refused.status, "True");
+        assert_eq!(refused.message, "no room");
+
+        let admitted = budget_condition(None, &[refused], &runner).unwrap();
+        assert_eq!(admitted.status, "False");
+        assert_eq!(admitted.reason, "WithinBudget");
+    }
+}
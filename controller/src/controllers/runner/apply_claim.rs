@@ -17,12 +17,13 @@ use kubimo::pool::{
     WARM_TOKEN_ANNOTATION,
 };
 use kubimo::{
-    CpuQuantity, CpuUnit, FilterParams, KubimoLabel, Pool, Requirement, Runner, RunnerClaim,
+    CpuQuantity, EventType, FilterParams, KubimoLabel, Pool, Requirement, Runner, RunnerClaim,
     StorageQuantity, Workspace, WorkspaceMode, WorkspacePythonRuntime, json_patch_macros::*,
     prelude::*,
 };
 
 use crate::context::Context;
+use crate::controllers::budget::runner_claim_refusal;
 use crate::controllers::slot_volume::SlotSources;
 use crate::controllers::workspace_affinity;
 
@@ -49,6 +50,12 @@ impl RunnerReconciler {
         let Some(pool_name) = runner.spec.pool.as_deref() else {
             return Ok(ClaimOutcome::ColdPath);
         };
+        // A runner back from a suspension already has a URL and token in
+        // users' hands, and a warm pod's are minted at its birth: claiming one
+        // would trade them away. It cold-starts under the ones it had.
+        if was_suspended(runner) {
+            return Ok(ClaimOutcome::ColdPath);
+        }
         let namespace = runner.require_namespace()?;
         let runner_name = runner.name()?;
         let pods = ctx.api_namespaced::<Pod>(namespace);
@@ -83,10 +90,13 @@ impl RunnerReconciler {
             .get_opt(pool_name)
             .await?
         else {
-            return cold(runner_name, pool_name, "pool does not exist");
+            return cold(ctx, runner, pool_name, "pool does not exist").await;
         };
         if let Err(reason) = eligible(ctx, runner, workspace, &pool, python_runtime) {
-            return cold(runner_name, pool_name, reason);
+            return cold(ctx, runner, pool_name, reason).await;
+        }
+        if let Some(reason) = runner_claim_refusal(ctx, runner).await? {
+            return cold(ctx, runner, pool_name, &reason).await;
         }
         // One workspace, one slot: any live pod of this workspace (even one
         // still terminating) is bound to a specific node's slot, and a claim
@@ -107,10 +117,12 @@ impl RunnerReconciler {
         workspace_pods.retain(may_hold_a_slot);
         if !workspace_pods.is_empty() {
             return cold(
-                runner_name,
+                ctx,
+                runner,
                 pool_name,
                 "the workspace already has runner pods",
-            );
+            )
+            .await;
         }
 
         let mut warm: Vec<Pod> = list_pods(
@@ -156,6 +168,15 @@ impl RunnerReconciler {
                         pod = pod_name,
                         "claimed a warm pod"
                     );
+                    ctx.events
+                        .publish(
+                            runner,
+                            EventType::Normal,
+                            "Claimed",
+                            "Claim",
+                            format!("claimed warm pod {pod_name} from pool {pool_name}"),
+                        )
+                        .await;
                     return self.adopt_claimed_pod(ctx, runner, pool_name, pod).await;
                 }
                 // Lost the race for this pod; try the next.
@@ -168,7 +189,7 @@ impl RunnerReconciler {
                 Err(err) => return Err(err),
             }
         }
-        cold(runner_name, pool_name, "no warm pods available")
+        cold(ctx, runner, pool_name, "no warm pods available").await
     }
 
     /// Converge on a pod this runner has already claimed: heal `status.claim`,
@@ -201,6 +222,22 @@ impl RunnerReconciler {
                 .delete_opt(pod.name()?)
                 .await?;
             self.record_claim(ctx, runner, None).await?;
+            ctx.events
+                .publish(
+                    runner,
+                    EventType::Warning,
+                    "ClaimFailed",
+                    "Claim",
+                    format!(
+                        "warm pod {} could not be bound ({}); falling back to a cold start",
+                        pod.name()?,
+                        annotations
+                            .get(kubimo::pool::CLAIM_ERROR_ANNOTATION)
+                            .map(String::as_str)
+                            .unwrap_or("unknown"),
+                    ),
+                )
+                .await;
             return Ok(ClaimOutcome::ColdPath);
         }
 
@@ -254,6 +291,7 @@ impl RunnerReconciler {
                 .cloned()
                 .unwrap_or_default(),
             token: annotations.get(WARM_TOKEN_ANNOTATION).cloned(),
+            released: None,
         };
         if runner.status.as_ref().and_then(|s| s.claim.as_ref()) != Some(&claim) {
             self.record_claim(ctx, runner, Some(claim)).await?;
@@ -279,11 +317,13 @@ impl RunnerReconciler {
         ctx: &Context,
         runner: &Runner,
     ) -> Result<bool, kubimo::Error> {
+        // A released claim has no pod on purpose; it is what the runner's
+        // own pod serves now.
         if runner
             .status
             .as_ref()
             .and_then(|status| status.claim.as_ref())
-            .is_none()
+            .is_none_or(RunnerClaim::is_released)
         {
             return Ok(false);
         }
@@ -291,7 +331,7 @@ impl RunnerReconciler {
         Ok(true)
     }
 
-    async fn record_claim(
+    pub(super) async fn record_claim(
         &self,
         ctx: &Context,
         runner: &Runner,
@@ -358,8 +398,46 @@ impl RunnerReconciler {
     }
 }
 
-fn cold(runner: &str, pool: &str, reason: &str) -> Result<ClaimOutcome, kubimo::Error> {
-    tracing::info!(runner, pool, reason, "not claiming; taking the cold path");
+/// Whether the runner has been suspended since it was created: a released
+/// claim, a `spec.suspended` left at `false` by the resume, or a `Suspended`
+/// condition. The last covers a resume that dropped the field, and the first
+/// a resume quick enough to beat the status loop to writing the condition.
+fn was_suspended(runner: &Runner) -> bool {
+    runner.spec.suspended.is_some()
+        || runner.status.as_ref().is_some_and(|status| {
+            status.claim.as_ref().is_some_and(RunnerClaim::is_released)
+                || status
+                    .conditions
+                    .iter()
+                    .flatten()
+                    .any(|cond| cond.type_ == kubimo::conditions::SUSPENDED)
+        })
+}
+
+/// Take the cold path, saying why on the runner as well as in the log: a
+/// `spec.pool` that never claims is otherwise indistinguishable, from the
+/// user's side, from one that is merely slow.
+async fn cold(
+    ctx: &Context,
+    runner: &Runner,
+    pool: &str,
+    reason: &str,
+) -> Result<ClaimOutcome, kubimo::Error> {
+    tracing::info!(
+        runner = runner.name()?,
+        pool,
+        reason,
+        "not claiming; taking the cold path"
+    );
+    ctx.events
+        .publish(
+            runner,
+            EventType::Normal,
+            "ColdStart",
+            "Claim",
+            format!("not claiming from pool {pool}: {reason}"),
+        )
+        .await;
     Ok(ClaimOutcome::ColdPath)
 }
 
@@ -378,7 +456,7 @@ async fn list_pods(
 /// slot. Terminal pods cannot: their volumes are unpublished, and kubelet
 /// never republishes a Succeeded/Failed pod. Everything else — running,
 /// pending, terminating, unknown — must count.
-fn may_hold_a_slot(pod: &Pod) -> bool {
+pub(crate) fn may_hold_a_slot(pod: &Pod) -> bool {
     !matches!(
         pod.status
             .as_ref()
@@ -417,17 +495,16 @@ fn has_label(pod: &Pod, key: &str) -> bool {
 fn pool_claim(runner: &Runner, workspace: &Workspace) -> PoolClaim {
     let sources = SlotSources::from_workspace(Some(workspace));
     let (bucket, key_prefix) = sources.archive.unwrap_or_default();
-    let (seed_bucket, seed_key_prefix, seed_secrets) = match sources.seed {
-        Some((bucket, key_prefix, secrets)) => (Some(bucket), key_prefix, Some(secrets)),
-        None => (None, None, None),
-    };
+    let seed = sources.seed.as_ref();
     PoolClaim {
         workspace: runner.spec.workspace.clone(),
         bucket,
         key_prefix,
-        seed_bucket,
-        seed_key_prefix,
-        seed_secrets,
+        seed_bucket: seed.map(|seed| seed.bucket.clone()),
+        seed_key_prefix: seed.and_then(|seed| seed.key_prefix.clone()),
+        seed_secrets: seed.map(|seed| seed.secrets),
+        seed_generation: seed.and_then(|seed| seed.generation.clone()),
+        seed_at: seed.and_then(|seed| seed.at),
         limit_bytes: sources.limit_bytes,
     }
 }
@@ -548,33 +625,15 @@ fn cpu_requirements_match(
 ) -> bool {
     fn norm(req: Option<&Requirement<CpuQuantity>>) -> (Option<u64>, Option<u64>) {
         (
-            req.and_then(|r| r.min.as_ref()).and_then(cpu_millis),
-            req.and_then(|r| r.max.as_ref()).and_then(cpu_millis),
+            req.and_then(|r| r.min.as_ref())
+                .and_then(CpuQuantity::to_millis),
+            req.and_then(|r| r.max.as_ref())
+                .and_then(CpuQuantity::to_millis),
         )
     }
     norm(a) == norm(b)
 }
 
-/// Normalize a CPU quantity to millicores so "1" and "1000m" compare equal.
-fn cpu_millis(quantity: &CpuQuantity) -> Option<u64> {
-    let repr = quantity.to_string();
-    let repr = repr.trim();
-    // A bare magnitude is cores.
-    if let Ok(value) = repr.parse::<f64>() {
-        return millis(value * 1000.0);
-    }
-    let split = repr.find(|c: char| c.is_ascii_alphabetic())?;
-    let value: f64 = repr[..split].parse().ok()?;
-    match repr[split..].parse::<CpuUnit>().ok()? {
-        CpuUnit::Core => millis(value * 1000.0),
-        CpuUnit::Milli => millis(value),
-    }
-}
-
-fn millis(value: f64) -> Option<u64> {
-    (value.is_finite() && value >= 0.0 && value < 2f64.powi(64)).then_some(value as u64)
-}
-
 /// Sidecars match when they are the same containers by (name, image),
 /// order-independent. Deliberately not a deep compare: the pool template's
 /// sidecars *replace* the runner's — they read their per-runner configuration
@@ -654,9 +713,9 @@ mod tests {
         let one: CpuQuantity = "1".parse().unwrap();
         let thousand_m: CpuQuantity = "1000m".parse().unwrap();
         let quarter: CpuQuantity = "250m".parse().unwrap();
-        assert_eq!(cpu_millis(&one), Some(1000));
-        assert_eq!(cpu_millis(&one), cpu_millis(&thousand_m));
-        assert_eq!(cpu_millis(&quarter), Some(250));
+        assert_eq!(one.to_millis(), Some(1000));
+        assert_eq!(one.to_millis(), thousand_m.to_millis());
+        assert_eq!(quarter.to_millis(), Some(250));
 
         let req = |min: &str, max: &str| {
             Some(Requirement::<CpuQuantity> {
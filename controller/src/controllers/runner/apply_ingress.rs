@@ -7,6 +7,7 @@ use kubimo::k8s_openapi::api::networking::v1::{
 use kubimo::kube::api::ObjectMeta;
 use kubimo::{Runner, prelude::*};
 
+use crate::config::ActivatorConfig;
 use crate::context::Context;
 use crate::controllers::ingress::effective_ingress_path;
 use crate::controllers::runner::apply_pod::runner_port;
@@ -18,6 +19,9 @@ impl RunnerReconciler {
         &self,
         ctx: &Context,
         runner: &Runner,
+        // Set while the runner is routed through the activator; see
+        // `apply_activator::activator`.
+        activator: Option<&ActivatorConfig>,
     ) -> Result<Ingress, kubimo::Error> {
         let namespace = runner.require_namespace()?;
         let ingress_class_name = runner
@@ -47,6 +51,20 @@ impl RunnerReconciler {
             "nginx.ingress.kubernetes.io/proxy-send-timeout".to_string(),
             proxy_timeout_secs,
         );
+        // A runner with nothing to serve it keeps its route, through the
+        // activator: the ingress controller sends it the runner's identity as
+        // the Host, the only part of the request the activator can tell
+        // runners apart by.
+        let backend_port = match activator {
+            Some(activator) => {
+                annotations.insert(
+                    "nginx.ingress.kubernetes.io/upstream-vhost".to_string(),
+                    super::apply_activator::activator_vhost(runner)?,
+                );
+                activator.port
+            }
+            None => runner_port(runner),
+        };
         if let Some(cluster_issuer) = spec_tls
             .and_then(|tls| tls.cluster_issuer.as_ref())
             .or(ctx.config.cluster_issuer.as_ref())
@@ -109,7 +127,7 @@ impl RunnerReconciler {
                                 service: Some(IngressServiceBackend {
                                     name: runner.name()?.to_string(),
                                     port: Some(ServiceBackendPort {
-                                        number: Some(runner_port(runner)),
+                                        number: Some(backend_port),
                                         ..Default::default()
                                     }),
                                 }),
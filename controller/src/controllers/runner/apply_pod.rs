@@ -1,10 +1,16 @@
 use kubimo::k8s_openapi::api::core::v1::Pod;
-use kubimo::{Runner, RunnerCommand, RunnerToken, Workspace, WorkspacePythonRuntime, prelude::*};
+use kubimo::k8s_openapi::apimachinery::pkg::api::resource::Quantity;
+use kubimo::{
+    EventType, Runner, RunnerCommand, RunnerToken, StorageQuantity, Workspace, WorkspaceMode,
+    WorkspacePythonRuntime, prelude::*,
+};
 
 use crate::Config;
 use crate::context::Context;
-use crate::controllers::ingress::ingress_path;
-use crate::controllers::runner_pod::{RunnerPodParams, TokenSource, build_runner_pod};
+use crate::controllers::ingress::effective_ingress_path;
+use crate::controllers::runner_pod::{
+    RunnerPodParams, TokenSource, build_runner_pod, runner_container,
+};
 use crate::controllers::slot_volume::{self, SLOT_CSI_DRIVER};
 use crate::controllers::workspace_affinity;
 
@@ -34,7 +40,17 @@ impl RunnerReconciler {
         let namespace = runner.require_namespace()?;
         let mode = workspace.effective_mode(ctx.config.default_workspace_mode);
         let sources = slot_volume::SlotSources::from_workspace(Some(workspace));
+        // A claim released by a suspension pins the URL and token the runner
+        // was served under; its own pod takes them over.
+        let released = runner
+            .status
+            .as_ref()
+            .and_then(|status| status.claim.as_ref())
+            .filter(|claim| claim.is_released());
         let token = match runner.spec.token.as_ref() {
+            _ if released.is_some() => released
+                .and_then(|claim| claim.token.as_deref())
+                .map_or(TokenSource::None, TokenSource::Value),
             Some(RunnerToken {
                 value: Some(token), ..
             }) => TokenSource::Value(token),
@@ -44,6 +60,23 @@ impl RunnerReconciler {
             }) => TokenSource::SecretEnv(secret_ref),
             _ => TokenSource::None,
         };
+        let pods = ctx.api_namespaced::<Pod>(namespace);
+        // Affinity is immutable on a live pod, so one that exists keeps what
+        // it was created with. A new one prefers the node still holding the
+        // workspace's slot — after a suspension, say — so it mounts the slot
+        // as it is instead of waiting on another node's hydration.
+        let affinity = match pods.get_opt(runner.name()?).await? {
+            Some(live) => live.spec.and_then(|spec| spec.affinity),
+            None => Some(workspace_affinity::workspace_affinity_preferring(
+                &runner.spec.workspace,
+                workspace
+                    .status
+                    .as_ref()
+                    .and_then(|status| status.slot.as_ref())
+                    .and_then(|slot| slot.node.as_deref())
+                    .filter(|_| mode == WorkspaceMode::Pooled),
+            )),
+        };
         let image = ctx.config.marimo_image(python_runtime).to_string();
         let pod = build_runner_pod(RunnerPodParams {
             name: runner.name()?.to_string(),
@@ -53,7 +86,7 @@ impl RunnerReconciler {
             owner_reference: runner.static_controller_owner_ref()?,
             asset_url: ctx.config.runner_asset_url(&image),
             image,
-            base_url: ingress_path(runner)?,
+            base_url: effective_ingress_path(runner)?,
             token,
             log_level: runner.spec.log_level,
             port: runner_port(runner),
@@ -61,13 +94,11 @@ impl RunnerReconciler {
             command: runner.spec.command,
             python_runtime,
             cpu: runner.spec.cpu.clone(),
-            memory: runner.spec.memory.clone(),
+            memory: runner.effective_memory(),
             env: runner.spec.env.clone().unwrap_or_default(),
             env_from: runner.spec.env_from.clone(),
             mode,
-            affinity: Some(workspace_affinity::workspace_affinity(
-                &runner.spec.workspace,
-            )),
+            affinity,
             slot_volume: slot_volume::workspace_volume(
                 &runner.spec.workspace,
                 mode,
@@ -80,7 +111,7 @@ impl RunnerReconciler {
             extra_volumes: Vec::new(),
             sidecars: runner.spec.sidecars.clone(),
         });
-        match ctx.api_namespaced::<Pod>(namespace).patch(&pod).await {
+        match pods.patch(&pod).await {
             Err(err) if super::is_invalid_request(&err) => {
                 // A live pod's spec is almost entirely immutable, so an apply that needs to
                 // change one of those fields — the sandbox runtimeClassName on a pod created
@@ -98,11 +129,20 @@ impl RunnerReconciler {
                     .api_namespaced::<Pod>(namespace)
                     .get_opt(runner.name()?)
                     .await;
-                if matches!(&live, Ok(Some(live)) if runtime_class_drifted(live, &pod) || volumes_drifted(live, &pod) || asset_env_drifted(live, &pod))
+                if matches!(&live, Ok(Some(live)) if runtime_class_drifted(live, &pod) || volumes_drifted(live, &pod) || asset_env_drifted(live, &pod) || memory_request_drifted(live, &pod))
                 {
                     ctx.api_namespaced::<Pod>(namespace)
                         .delete_opt(runner.name()?)
                         .await?;
+                    ctx.events
+                        .publish(
+                            runner,
+                            EventType::Normal,
+                            "PodReplaced",
+                            "Recycle",
+                            "the pod's immutable spec has drifted; deleting it to recreate",
+                        )
+                        .await;
                     return Ok(PodApply::Replaced);
                 }
                 Err(err)
@@ -152,10 +192,7 @@ fn volumes_drifted(live: &Pod, desired: &Pod) -> bool {
 /// container restart could not apply it either.
 fn asset_env_drifted(live: &Pod, desired: &Pod) -> bool {
     fn asset_url(pod: &Pod) -> Option<&str> {
-        pod.spec
-            .as_ref()?
-            .containers
-            .first()?
+        runner_container(pod)?
             .env
             .as_ref()?
             .iter()
@@ -166,6 +203,22 @@ fn asset_env_drifted(live: &Pod, desired: &Pod) -> bool {
     asset_url(live) != asset_url(desired)
 }
 
+/// Whether the live pod's memory request differs from the desired one, which
+/// is how `lifecycle.oomRetries` takes effect: the status loop raises
+/// `status.memoryRequest`, and a pod's resources cannot be patched in place.
+fn memory_request_drifted(live: &Pod, desired: &Pod) -> bool {
+    fn request(pod: &Pod) -> Option<&Quantity> {
+        runner_container(pod)?
+            .resources
+            .as_ref()?
+            .requests
+            .as_ref()?
+            .get("memory")
+    }
+    request(live).map(|q| StorageQuantity::from(q.clone()).to_bytes())
+        != request(desired).map(|q| StorageQuantity::from(q.clone()).to_bytes())
+}
+
 pub(crate) fn runner_port(runner: &Runner) -> i32 {
     match runner.spec.command {
         RunnerCommand::Render => 8080,
@@ -248,6 +301,7 @@ mod tests {
             Pod {
                 spec: Some(PodSpec {
                     containers: vec![Container {
+                        name: crate::controllers::runner_pod::RUNNER_CONTAINER.into(),
                         env: value.map(|value| {
                             vec![EnvVar {
                                 name: crate::controllers::runner_pod::ASSET_URL_ENV.into(),
@@ -298,4 +352,40 @@ mod tests {
             &desired
         ));
     }
+
+    /// A sidecar may come first in the pod; the request that `oomRetries`
+    /// raises is the runner container's, found by name as the status loop
+    /// finds it.
+    #[test]
+    fn memory_drift_is_read_from_the_runner_container() {
+        use kubimo::k8s_openapi::api::core::v1::{Container, ResourceRequirements};
+        fn pod_requesting(runner: &str) -> Pod {
+            let container = |name: &str, memory: &str| Container {
+                name: name.into(),
+                resources: Some(ResourceRequirements {
+                    requests: Some([("memory".to_string(), Quantity(memory.into()))].into()),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            };
+            Pod {
+                spec: Some(PodSpec {
+                    containers: vec![
+                        container("proxy", "64Mi"),
+                        container(crate::controllers::runner_pod::RUNNER_CONTAINER, runner),
+                    ],
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }
+        }
+        assert!(memory_request_drifted(
+            &pod_requesting("512Mi"),
+            &pod_requesting("1Gi")
+        ));
+        assert!(!memory_request_drifted(
+            &pod_requesting("1Gi"),
+            &pod_requesting("1024Mi")
+        ));
+    }
 }
@@ -22,6 +22,7 @@ impl RunnerReconciler {
                 ..Default::default()
             },
             spec: Some(ServiceSpec {
+                type_: Some("ClusterIP".to_string()),
                 selector: Some(self.pod_labels(runner)?),
                 ports: Some(vec![ServicePort {
                     name: Some("marimo".to_string()),
@@ -32,6 +33,30 @@ impl RunnerReconciler {
             }),
             ..Default::default()
         };
-        ctx.api_namespaced::<Service>(namespace).patch(&svc).await
+        let services = ctx.api_namespaced::<Service>(namespace);
+        delete_if_retyped(&services, runner.name()?, "ClusterIP").await?;
+        services.patch(&svc).await
     }
 }
+
+/// Delete the runner's Service when it has a type other than `type_`: the
+/// `ExternalName` one a suspended runner is routed to the activator through,
+/// or the `ClusterIP` one it had before. The apiserver refuses to turn one
+/// into the other in place — a `ClusterIP` keeps the address it was
+/// allocated — so the next apply creates it afresh; a 409 while the old one
+/// is still going is requeued like any other recreation.
+pub(super) async fn delete_if_retyped(
+    services: &kubimo::Api<Service>,
+    name: &str,
+    type_: &str,
+) -> Result<(), kubimo::Error> {
+    let live = services.get_opt(name).await?;
+    if live
+        .as_ref()
+        .and_then(|live| live.spec.as_ref())
+        .is_some_and(|spec| spec.type_.as_deref().unwrap_or("ClusterIP") != type_)
+    {
+        services.delete_opt(name).await?;
+    }
+    Ok(())
+}
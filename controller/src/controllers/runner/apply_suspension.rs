@@ -0,0 +1,107 @@
+//! Suspending a runner: everything it serves goes, the Runner stays — and,
+//! with an activator configured, so does its route, to the activator.
+//!
+//! The point is a cheap resume, so nothing that identifies the runner is
+//! touched — not its spec, not the claim it was served under. Under `Pooled`
+//! the workspace's slot also outlives the pod: the agent keeps an unmounted
+//! slot on its node until it needs the room, and the resumed pod prefers that
+//! node (see `apply_pod`).
+
+use kubimo::k8s_openapi::api::core::v1::{Pod, Service};
+use kubimo::k8s_openapi::api::networking::v1::Ingress;
+use kubimo::kube::runtime::controller::Action;
+use kubimo::{FilterParams, KubimoLabel, Runner, RunnerClaim, prelude::*};
+
+use futures::prelude::*;
+
+use crate::context::Context;
+
+use super::RunnerReconciler;
+
+/// The claim to keep across a suspension: the same one, marked released so
+/// that nothing waits on — or concedes to — the pod it names.
+pub(super) fn released_claim(runner: &Runner) -> Option<RunnerClaim> {
+    let claim = runner.status.as_ref()?.claim.as_ref()?;
+    (!claim.is_released()).then(|| RunnerClaim {
+        released: Some(true),
+        ..claim.clone()
+    })
+}
+
+impl RunnerReconciler {
+    pub(crate) async fn apply_suspension(
+        &self,
+        ctx: &Context,
+        runner: &Runner,
+    ) -> Result<Action, kubimo::Error> {
+        let namespace = runner.require_namespace()?;
+        let name = runner.name()?;
+
+        // Released before the claimed pod is deleted: the other way round, a
+        // reconcile that failed in between would find a claim whose pod is
+        // gone and clear it as stale, and the URL along with it.
+        if let Some(claim) = released_claim(runner) {
+            self.record_claim(ctx, runner, Some(claim)).await?;
+        }
+
+        // Every pod carrying the runner's name label: the cold pod named after
+        // it, and a claimed pod, which is not.
+        let pods = ctx.api_namespaced::<Pod>(namespace);
+        let name_label = KubimoLabel::borrow("name").to_string();
+        let mine = pods
+            .list(&FilterParams::new().with_labels((name_label.as_str(), name)))
+            .map_ok(|item| item.item)
+            .try_collect::<Vec<_>>()
+            .await?;
+        for pod in mine {
+            pods.delete_opt(pod.name()?).await?;
+        }
+        if let Some(activator) = super::apply_activator::activator(ctx, runner, None) {
+            return self.apply_activator_route(ctx, runner, activator).await;
+        }
+        ctx.api_namespaced::<Service>(namespace)
+            .delete_opt(name)
+            .await?;
+        ctx.api_namespaced::<Ingress>(namespace)
+            .delete_opt(name)
+            .await?;
+
+        // All three are owned, so one coming back (or a pod still finishing
+        // its grace period being recreated by someone else) wakes this again.
+        Ok(Action::await_change())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kubimo::{RunnerSpec, RunnerStatus};
+
+    #[test]
+    fn a_suspension_releases_the_claim_once_and_keeps_it_otherwise_intact() {
+        let mut runner = Runner::new("r", RunnerSpec::default());
+        assert_eq!(released_claim(&runner), None);
+        let claim = RunnerClaim {
+            pool: "editors".into(),
+            pod_name: "editors-a1b2c3d4".into(),
+            ingress_path: "/editors-a1b2c3d4".into(),
+            token: Some("secret".into()),
+            released: None,
+        };
+        runner.status = Some(RunnerStatus {
+            claim: Some(claim.clone()),
+            ..Default::default()
+        });
+        let released = released_claim(&runner).unwrap();
+        assert!(released.is_released());
+        assert_eq!(
+            RunnerClaim {
+                released: None,
+                ..released.clone()
+            },
+            claim
+        );
+        runner.status.as_mut().unwrap().claim = Some(released);
+        assert_eq!(released_claim(&runner), None);
+    }
+}
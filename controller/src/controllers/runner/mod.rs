@@ -1,8 +1,13 @@
+mod apply_activator;
+mod apply_budget;
 mod apply_claim;
 mod apply_ingress;
 mod apply_owner_reference;
 mod apply_pod;
 mod apply_service;
+mod apply_suspension;
+
+pub(crate) use apply_claim::may_hold_a_slot;
 
 use std::sync::Arc;
 use std::{collections::BTreeMap, time::Duration};
@@ -49,6 +54,12 @@ impl Reconciler for RunnerReconciler {
     type Error = kubimo::Error;
 
     async fn apply(&self, ctx: &Context, runner: &Runner) -> Result<Action, Self::Error> {
+        // Before the workspace is even read: a suspended runner has nothing
+        // to start, and must not wait on a workspace to stop.
+        if runner.spec.is_suspended() {
+            return self.apply_suspension(ctx, runner).await;
+        }
+
         let namespace = runner.require_namespace()?;
         let workspace = ctx
             .api_namespaced::<Workspace>(namespace)
@@ -70,6 +81,13 @@ impl Reconciler for RunnerReconciler {
             Some(workspace) => workspace,
         };
 
+        // After the workspace, so that a runner refused by its budget is one
+        // that could otherwise start; before the claim, so that it takes no
+        // warm pod either.
+        if let apply_budget::BudgetAdmission::Refused = self.apply_budget(ctx, runner).await? {
+            return Ok(Action::requeue(apply_budget::BUDGET_REQUEUE_INTERVAL));
+        }
+
         let python_runtime = get_workspace_python_runtime(&workspace)?;
 
         match self
@@ -90,10 +108,12 @@ impl Reconciler for RunnerReconciler {
                 // apply_pod is skipped wholesale: the claimed pod keeps its
                 // pool name and its warm-slot volume, and must never be
                 // measured against — or replaced by — the cold pod shape.
+                // Nor is it routed through the activator: a claimed pod that
+                // dies sends the runner down the cold path above.
                 futures::future::try_join_all([
                     self.apply_owner_reference(ctx, runner).boxed(),
                     self.apply_service(ctx, runner).map_ok(|_| ()).boxed(),
-                    self.apply_ingress(ctx, runner).map_ok(|_| ()).boxed(),
+                    self.apply_ingress(ctx, runner, None).map_ok(|_| ()).boxed(),
                 ])
                 .await?;
                 return Ok(Action::await_change());
@@ -110,6 +130,27 @@ impl Reconciler for RunnerReconciler {
             }
         }
 
+        // Until its pod is ready — on its first start, or after one lost to an
+        // eviction — the runner is routed through the activator, which holds
+        // its requests instead of letting them fail against a Service with no
+        // endpoints. The pod is owned, so turning ready brings this back to
+        // route to it.
+        let pod = ctx
+            .api_namespaced::<Pod>(namespace)
+            .get_opt(runner.name()?)
+            .await?;
+        let route = match apply_activator::activator(ctx, runner, pod.as_ref()) {
+            Some(activator) => self
+                .apply_activator_route(ctx, runner, activator)
+                .map_ok(|_| false)
+                .boxed(),
+            None => futures::future::try_join(
+                self.apply_service(ctx, runner),
+                self.apply_ingress(ctx, runner, None),
+            )
+            .map_ok(|_| false)
+            .boxed(),
+        };
         let applied = futures::future::try_join_all([
             self.apply_owner_reference(ctx, runner)
                 .map_ok(|_| false)
@@ -117,8 +158,7 @@ impl Reconciler for RunnerReconciler {
             self.apply_pod(ctx, runner, &workspace, python_runtime)
                 .map_ok(|applied| matches!(applied, apply_pod::PodApply::Replaced))
                 .boxed(),
-            self.apply_service(ctx, runner).map_ok(|_| false).boxed(),
-            self.apply_ingress(ctx, runner).map_ok(|_| false).boxed(),
+            route,
         ])
         .await;
 
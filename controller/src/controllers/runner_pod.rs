@@ -29,6 +29,20 @@ use crate::resources::Resources;
 /// argument (same contract as the claim marker).
 pub(crate) const ASSET_URL_ENV: &str = "KUBIMO_ASSET_URL";
 
+/// The marimo container's name, among any sidecars. Whatever reads the
+/// runner's own container back out of a live pod finds it by this name,
+/// never by position.
+pub(crate) const RUNNER_CONTAINER: &str = "runner";
+
+/// The marimo container of a runner pod.
+pub(crate) fn runner_container(pod: &Pod) -> Option<&Container> {
+    pod.spec
+        .as_ref()?
+        .containers
+        .iter()
+        .find(|container| container.name == RUNNER_CONTAINER)
+}
+
 /// How the marimo access token reaches start.sh.
 pub(crate) enum TokenSource<'a> {
     /// As a `--token` argument, from `spec.token.value` or a pool's minted
@@ -129,7 +143,7 @@ pub(crate) fn build_runner_pod(params: RunnerPodParams<'_>) -> Pod {
     };
     let volume_name = params.slot_volume.name.clone();
     let mut containers = vec![Container {
-        name: "runner".into(),
+        name: RUNNER_CONTAINER.into(),
         image: Some(params.image),
         resources: Resources::default()
             .cpu(params.cpu)
@@ -3,26 +3,34 @@ use kubimo::{Runner, RunnerStatus, Workspace, WorkspaceMode, prelude::*};
 
 use super::RunnerStatusReconciler;
 use super::conditions::{
-    claim_bound_condition, pod_ready_condition, pod_scheduled_condition, pvc_bound_condition,
-    slot_bound_condition, startup_complete, upsert_condition, workspace_ready_condition,
+    claim_bound_condition, failed_condition, hydrated_condition, pod_ready_condition,
+    pod_scheduled_condition, pvc_bound_condition, slot_bound_condition, startup_complete,
+    upsert_condition, upsert_progress_condition, workspace_ready_condition,
 };
 use crate::context::Context;
 
 impl RunnerStatusReconciler {
-    /// Updates the runner's startup progress conditions. Returns true once
-    /// all of them are True.
+    /// Updates the runner's startup progress conditions, and `Failed`.
+    /// Returns true once all of the former are True, with the pod they were
+    /// read from.
     pub(super) async fn apply_startup_conditions(
         &self,
         ctx: &Context,
         runner: &Runner,
         status: &mut RunnerStatus,
-    ) -> kubimo::Result<bool> {
+    ) -> kubimo::Result<(bool, Option<Pod>)> {
         let namespace = runner.require_namespace()?;
         let name = runner.name()?;
         let workspace_name = runner.spec.workspace.as_str();
         // A claimed runner's pod is the warm pod it adopted, which keeps its
         // pool name; only unclaimed runners have a pod named after themselves.
-        let claim = runner.status.as_ref().and_then(|s| s.claim.as_ref());
+        // A claim released by a suspension is no longer a pod: the runner's
+        // own pod serves it.
+        let claim = runner
+            .status
+            .as_ref()
+            .and_then(|s| s.claim.as_ref())
+            .filter(|claim| !claim.is_released());
         let pod_name = claim.map(|claim| claim.pod_name.as_str()).unwrap_or(name);
         let pods = ctx.api_namespaced::<Pod>(namespace);
         let pvcs = ctx.api_namespaced::<PersistentVolumeClaim>(namespace);
@@ -64,6 +72,17 @@ impl RunnerStatusReconciler {
             pod_scheduled_condition(pod.as_ref(), generation),
         );
         upsert_condition(conditions, pod_ready_condition(pod.as_ref(), generation));
-        Ok(startup_complete(conditions))
+        if let Some(failed) = failed_condition(pod.as_ref(), conditions, generation) {
+            upsert_condition(conditions, failed);
+        }
+        // Only a slot can be hydrated in the background; a PVC is the
+        // workspace's files, whole, from the moment it binds.
+        if mode == WorkspaceMode::Pooled {
+            upsert_progress_condition(
+                conditions,
+                hydrated_condition(workspace.as_ref(), generation),
+            );
+        }
+        Ok((startup_complete(conditions), pod))
     }
 }
@@ -1,9 +1,11 @@
 use std::time::Duration;
 
-use kubimo::Workspace;
-use kubimo::k8s_openapi::api::core::v1::{PersistentVolumeClaim, Pod};
+use kubimo::k8s_openapi::api::core::v1::{ContainerStateTerminated, PersistentVolumeClaim, Pod};
 use kubimo::k8s_openapi::apimachinery::pkg::apis::meta::v1::{Condition, Time};
 use kubimo::k8s_openapi::jiff::Timestamp;
+use kubimo::{Runner, RunnerPhase, Workspace};
+
+use crate::controllers::runner_pod::RUNNER_CONTAINER;
 
 // Re-exported from the api crate rather than defined here. These strings are
 // the contract consumers match on, and they treat a *missing* condition as
@@ -11,10 +13,11 @@ use kubimo::k8s_openapi::jiff::Timestamp;
 // runner at the previous phase, with no error raised anywhere. Sharing the
 // definition means a consumer can assert against it.
 pub(super) use kubimo::conditions::{
-    POD_READY, POD_SCHEDULED, PVC_BOUND, STARTUP_CONDITIONS, WORKSPACE_READY,
+    ACTIVITY_UNSUPPORTED, BUDGET_EXCEEDED, FAILED, HYDRATED, POD_READY, POD_SCHEDULED, PVC_BOUND,
+    STARTUP_CONDITIONS, SUSPENDED, WORKSPACE_READY,
 };
 
-fn condition(
+pub(super) fn condition(
     type_: &str,
     status: &str,
     reason: &str,
@@ -220,6 +223,49 @@ pub(super) fn workspace_ready_condition(
     }
 }
 
+/// Whether the slot's background restore has finished, from the agent's
+/// `status.hydration`.
+///
+/// A count left by some earlier slot says nothing about this one — a slot
+/// hydrated in full never writes one — so only a count naming the current
+/// `status.slot.id` can hold the condition `False`.
+pub(super) fn hydrated_condition(
+    workspace: Option<&Workspace>,
+    observed_generation: Option<i64>,
+) -> Condition {
+    let status = workspace.and_then(|workspace| workspace.status.as_ref());
+    let current_slot = status
+        .and_then(|status| status.slot.as_ref())
+        .and_then(|slot| slot.id.as_deref());
+    let hydration = status
+        .and_then(|status| status.hydration.as_ref())
+        .filter(|hydration| hydration.slot.is_some() && hydration.slot.as_deref() == current_slot);
+    let (status, reason, message) = match hydration {
+        None if workspace.is_none() => ("Unknown", "NotFound", "Workspace not found".to_string()),
+        Some(hydration) if !hydration.is_complete() => {
+            let pending = hydration.pending_files.unwrap_or_default();
+            let bytes = hydration.pending_bytes.unwrap_or_default();
+            match hydration.failed_files.unwrap_or_default() {
+                0 => (
+                    "False",
+                    "Hydrating",
+                    format!("{pending} files ({bytes} bytes) still to restore"),
+                ),
+                failed => (
+                    "False",
+                    "Retrying",
+                    format!(
+                        "{pending} files ({bytes} bytes) still to restore; {failed} failed and \
+                         are being retried"
+                    ),
+                ),
+            }
+        }
+        _ => ("True", "Complete", "Every file is restored".to_string()),
+    };
+    condition(HYDRATED, status, reason, message, observed_generation)
+}
+
 pub(super) fn pod_scheduled_condition(
     pod: Option<&Pod>,
     observed_generation: Option<i64>,
@@ -294,7 +340,7 @@ fn container_state_detail(pod: &Pod) -> Option<(String, String)> {
     let statuses = pod.status.as_ref()?.container_statuses.as_ref()?;
     let container = statuses
         .iter()
-        .find(|container| container.name == "runner" && !container.ready)
+        .find(|container| container.name == RUNNER_CONTAINER && !container.ready)
         .or_else(|| statuses.iter().find(|container| !container.ready))?;
     let state = container.state.as_ref()?;
     if let Some(waiting) = state.waiting.as_ref() {
@@ -322,6 +368,203 @@ fn container_state_detail(pod: &Pod) -> Option<(String, String)> {
     None
 }
 
+/// Written by `start.sh` to the runner container's termination message when
+/// the workspace's environment fails to install. A contract with the image,
+/// like the container name: keep the two in agreement.
+const DEPENDENCY_INSTALL_FAILED_MARKER: &str = "DependencyInstallFailed";
+
+/// The [`FAILED`] reason `lifecycle.oomRetries` acts on.
+pub(super) const OUT_OF_MEMORY: &str = "OutOfMemory";
+
+/// Why the runner container is failing, when it is failing for good rather
+/// than on its way up: a pull error, a container that cannot be created over
+/// its mount, or a crash kubelet is backing off from — classified by the
+/// termination it is backing off from. One crash that recovers on restart is
+/// not a failure, and neither is a claimed pod waiting on its warm image, nor
+/// a pod being deleted, whose container is stopped on purpose.
+fn classify_failure(pod: &Pod) -> Option<(&'static str, String)> {
+    if pod.metadata.deletion_timestamp.is_some() {
+        return None;
+    }
+    let statuses = pod.status.as_ref()?.container_statuses.as_ref()?;
+    let container = statuses
+        .iter()
+        .find(|container| container.name == RUNNER_CONTAINER)?;
+    let state = container.state.as_ref()?;
+    let waiting = state.waiting.as_ref();
+    let waiting_message = || {
+        waiting
+            .and_then(|waiting| waiting.message.clone())
+            .unwrap_or_default()
+    };
+    match waiting.and_then(|waiting| waiting.reason.as_deref()) {
+        Some("ImagePullBackOff" | "ErrImagePull" | "InvalidImageName") => {
+            return Some(("ImagePullFailed", waiting_message()));
+        }
+        Some("CreateContainerError" | "RunContainerError")
+            if waiting_message().to_lowercase().contains("mount") =>
+        {
+            return Some(("MountFailed", waiting_message()));
+        }
+        Some("CrashLoopBackOff") => {}
+        // Terminated now, on its way to a restart: only a termination that
+        // names its cause is classified ahead of the back-off, so an OOM
+        // retry need not wait it out. Any other exit may yet recover.
+        _ => {
+            let terminated = state
+                .terminated
+                .as_ref()
+                .filter(|terminated| terminated.exit_code != 0)?;
+            return named_termination(terminated);
+        }
+    }
+    let terminated = state.terminated.as_ref().or_else(|| {
+        container
+            .last_state
+            .as_ref()
+            .and_then(|state| state.terminated.as_ref())
+    })?;
+    named_termination(terminated).or_else(|| {
+        Some((
+            "CrashLoop",
+            format!(
+                "Container keeps exiting, last with exit code {}",
+                terminated.exit_code
+            ),
+        ))
+    })
+}
+
+/// A termination whose cause is known from the termination itself: an OOM
+/// kill, or `start.sh` reporting that the environment did not install.
+fn named_termination(terminated: &ContainerStateTerminated) -> Option<(&'static str, String)> {
+    let exit_code = terminated.exit_code;
+    if terminated.reason.as_deref() == Some("OOMKilled") {
+        return Some((
+            OUT_OF_MEMORY,
+            format!("Container was killed for running out of memory (exit code {exit_code})"),
+        ));
+    }
+    terminated
+        .message
+        .as_deref()
+        .filter(|message| message.contains(DEPENDENCY_INSTALL_FAILED_MARKER))
+        .map(|message| ("DependencyInstallFailed", message.trim().to_string()))
+}
+
+/// `True`, with [`classify_failure`]'s reason, while the pod is failing, then
+/// `False` once a pod that was is no longer. `None` for a runner whose pod
+/// never failed, and while there is no pod to judge — a failed pod being
+/// replaced has not recovered yet.
+pub(super) fn failed_condition(
+    pod: Option<&Pod>,
+    conditions: &[Condition],
+    observed_generation: Option<i64>,
+) -> Option<Condition> {
+    let pod = pod?;
+    match classify_failure(pod) {
+        Some((reason, message)) => Some(condition(
+            FAILED,
+            "True",
+            reason,
+            message,
+            observed_generation,
+        )),
+        None => conditions.iter().any(|cond| cond.type_ == FAILED).then(|| {
+            condition(
+                FAILED,
+                "False",
+                "Recovered",
+                "Recovered".to_string(),
+                observed_generation,
+            )
+        }),
+    }
+}
+
+/// `True` while `spec.suspended` is, then `False` for good once a runner that
+/// was suspended is resumed. `None` for a runner that never was: the
+/// condition's presence is what tells a resumed runner from a new one.
+pub(super) fn suspended_condition(
+    runner: &Runner,
+    conditions: &[Condition],
+    observed_generation: Option<i64>,
+) -> Option<Condition> {
+    if runner.spec.is_suspended() {
+        return Some(condition(
+            SUSPENDED,
+            "True",
+            "Suspended",
+            "Suspended; unset spec.suspended to resume".to_string(),
+            observed_generation,
+        ));
+    }
+    conditions
+        .iter()
+        .any(|cond| cond.type_ == SUSPENDED)
+        .then(|| {
+            condition(
+                SUSPENDED,
+                "False",
+                "Resumed",
+                "Resumed".to_string(),
+                observed_generation,
+            )
+        })
+}
+
+/// `True` while the runner asks for `lifecycle.inactiveAfterSecsWithoutExecution`
+/// and its marimo has no activity to report (`reported` is whether the last
+/// poll got a report). `None` for a runner that never was in that state.
+pub(super) fn activity_unsupported_condition(
+    runner: &Runner,
+    reported: bool,
+    conditions: &[Condition],
+    observed_generation: Option<i64>,
+) -> Option<Condition> {
+    let window = runner
+        .spec
+        .lifecycle
+        .as_ref()
+        .and_then(|lifecycle| lifecycle.inactive_after_secs_without_execution);
+    let (status, reason, message) = match window {
+        Some(_) if !reported => (
+            "True",
+            "Unsupported",
+            "lifecycle.inactiveAfterSecsWithoutExecution is ignored: this marimo does not \
+             serve status/activity, so open connections alone keep the runner active"
+                .to_string(),
+        ),
+        _ if !conditions
+            .iter()
+            .any(|cond| cond.type_ == ACTIVITY_UNSUPPORTED) =>
+        {
+            return None;
+        }
+        Some(_) => ("False", "Reported", "Activity is reported".to_string()),
+        None => (
+            "False",
+            "NotRequired",
+            "lifecycle.inactiveAfterSecsWithoutExecution is not set".to_string(),
+        ),
+    };
+    Some(condition(
+        ACTIVITY_UNSUPPORTED,
+        status,
+        reason,
+        message,
+        observed_generation,
+    ))
+}
+
+/// When the runner was last resumed, if it ever was.
+pub(super) fn resumed_at_secs(conditions: &[Condition]) -> Option<i64> {
+    conditions
+        .iter()
+        .find(|cond| cond.type_ == SUSPENDED && cond.status == "False")
+        .map(|cond| cond.last_transition_time.0.as_second())
+}
+
 /// Upserts by `type_`. Bumps `last_transition_time` only when `status`
 /// changes; a reason change updates reason/message/observed_generation but
 /// keeps the timestamp; message-only changes are ignored to avoid status
@@ -340,6 +583,20 @@ pub(super) fn upsert_condition(conditions: &mut Vec<Condition>, new: Condition)
     }
 }
 
+/// [`upsert_condition`], except that a new message is kept even when status
+/// and reason are unchanged. For conditions whose message *is* the progress
+/// being reported, and which only change as often as their source does.
+pub(super) fn upsert_progress_condition(conditions: &mut Vec<Condition>, new: Condition) {
+    let message = new.message.clone();
+    let observed_generation = new.observed_generation;
+    let type_ = new.type_.clone();
+    upsert_condition(conditions, new);
+    if let Some(current) = conditions.iter_mut().find(|cond| cond.type_ == type_) {
+        current.message = message;
+        current.observed_generation = observed_generation;
+    }
+}
+
 pub(super) fn startup_complete(conditions: &[Condition]) -> bool {
     STARTUP_CONDITIONS.iter().all(|type_| {
         conditions
@@ -348,6 +605,57 @@ pub(super) fn startup_complete(conditions: &[Condition]) -> bool {
     })
 }
 
+/// Reasons a startup condition can only be `False` for until someone acts:
+/// the pod crashes or cannot be created, the volume is gone, the workspace's
+/// init or budget refused it. Everything else is still on its way.
+const FAILED_REASONS: [&str; 10] = [
+    "CrashLoopBackOff",
+    "ImagePullBackOff",
+    "ErrImagePull",
+    "InvalidImageName",
+    "CreateContainerConfigError",
+    "CreateContainerError",
+    "Lost",
+    "ClaimFailed",
+    "JobFailed",
+    "BudgetExceeded",
+];
+
+pub(super) fn is_true(conditions: &[Condition], type_: &str) -> bool {
+    conditions
+        .iter()
+        .any(|cond| cond.type_ == type_ && cond.status == "True")
+}
+
+/// `status.phase`, from the startup conditions just computed. They are read
+/// in the order a runner fulfils them, so the phase names the first one still
+/// outstanding; `idle` only splits a serving runner in two.
+pub(super) fn runner_phase(conditions: &[Condition], suspended: bool, idle: bool) -> RunnerPhase {
+    if suspended {
+        return RunnerPhase::Suspended;
+    }
+    let failed = is_true(conditions, FAILED)
+        || is_true(conditions, BUDGET_EXCEEDED)
+        || conditions.iter().any(|cond| {
+            STARTUP_CONDITIONS.contains(&cond.type_.as_str())
+                && cond.status == "False"
+                && FAILED_REASONS.contains(&cond.reason.as_str())
+        });
+    if failed {
+        RunnerPhase::Failed
+    } else if !is_true(conditions, POD_SCHEDULED) {
+        RunnerPhase::Pending
+    } else if !is_true(conditions, PVC_BOUND) || !is_true(conditions, WORKSPACE_READY) {
+        RunnerPhase::Hydrating
+    } else if !is_true(conditions, POD_READY) {
+        RunnerPhase::Starting
+    } else if idle {
+        RunnerPhase::Idle
+    } else {
+        RunnerPhase::Ready
+    }
+}
+
 /// Whether the runner's volume (PVC, slot or claimed slot) is bound, as of
 /// the conditions computed earlier in this same reconcile.
 pub(super) fn volume_is_bound(conditions: &[Condition]) -> bool {
@@ -1008,4 +1316,348 @@ mod tests {
             "a pod un-ready for an hour is wedged, not starting"
         );
     }
+
+    fn workspace_on_slot(
+        slot: &str,
+        hydration: Option<kubimo::WorkspaceHydrationStatus>,
+    ) -> Workspace {
+        let mut workspace = Workspace::new("test", Default::default());
+        workspace.status = Some(WorkspaceStatus {
+            slot: Some(kubimo::WorkspaceSlotStatus {
+                id: Some(slot.to_string()),
+                ..Default::default()
+            }),
+            hydration,
+            ..Default::default()
+        });
+        workspace
+    }
+
+    /// Only the current slot's count can hold the condition down: a slot
+    /// restored in full never reports, so a count left by an earlier one
+    /// would otherwise read as a restore that never finishes.
+    #[test]
+    fn hydrated_follows_the_current_slot_only() {
+        let pending = kubimo::WorkspaceHydrationStatus {
+            slot: Some("slot-a".to_string()),
+            pending_files: Some(3),
+            pending_bytes: Some(300),
+            restored_files: Some(1),
+            restored_bytes: Some(100),
+            failed_files: Some(0),
+        };
+        let cond = hydrated_condition(
+            Some(&workspace_on_slot("slot-a", Some(pending.clone()))),
+            None,
+        );
+        assert_eq!(cond.type_, HYDRATED);
+        assert_eq!(cond.status, "False");
+        assert_eq!(cond.reason, "Hydrating");
+        assert!(cond.message.contains("3 files"));
+
+        let cond = hydrated_condition(
+            Some(&workspace_on_slot("slot-b", Some(pending.clone()))),
+            None,
+        );
+        assert_eq!(cond.status, "True", "the count is from another slot");
+
+        let done = kubimo::WorkspaceHydrationStatus {
+            pending_files: Some(0),
+            pending_bytes: Some(0),
+            ..pending
+        };
+        let cond = hydrated_condition(Some(&workspace_on_slot("slot-a", Some(done))), None);
+        assert_eq!(cond.status, "True");
+        assert_eq!(
+            hydrated_condition(Some(&workspace_on_slot("slot-a", None)), None).status,
+            "True"
+        );
+    }
+
+    /// Progress lives in the message, which [`upsert_condition`] leaves alone
+    /// while status and reason hold.
+    #[test]
+    fn a_progress_condition_keeps_its_newest_message() {
+        let mut conditions = Vec::new();
+        let progress =
+            |message: &str| condition(HYDRATED, "False", "Hydrating", message.to_string(), None);
+        upsert_progress_condition(&mut conditions, progress("3 files"));
+        upsert_progress_condition(&mut conditions, progress("2 files"));
+        assert_eq!(conditions.len(), 1);
+        assert_eq!(conditions[0].message, "2 files");
+    }
+
+    /// Absent until the first suspension, then kept for good: a resumed
+    /// runner must stay distinguishable from one that was never suspended.
+    #[test]
+    fn suspended_appears_with_the_first_suspension_and_stays() {
+        let mut runner = Runner::new("bmor-x", Default::default());
+        let mut conditions = Vec::new();
+        assert!(suspended_condition(&runner, &conditions, None).is_none());
+
+        runner.spec.suspended = Some(true);
+        let suspended = suspended_condition(&runner, &conditions, None).unwrap();
+        upsert_condition(&mut conditions, suspended);
+        assert_eq!(conditions[0].status, "True");
+        assert_eq!(resumed_at_secs(&conditions), None);
+
+        runner.spec.suspended = None;
+        let resumed = suspended_condition(&runner, &conditions, None).unwrap();
+        assert_eq!(resumed.status, "False");
+        upsert_condition(&mut conditions, resumed.clone());
+        assert_eq!(
+            resumed_at_secs(&conditions),
+            Some(resumed.last_transition_time.0.as_second())
+        );
+    }
+
+    fn startup(reasons: [(&str, &str); 4]) -> Vec<Condition> {
+        STARTUP_CONDITIONS
+            .iter()
+            .zip(reasons)
+            .map(|(type_, (status, reason))| condition(type_, status, reason, String::new(), None))
+            .collect()
+    }
+
+    const READY: (&str, &str) = ("True", "Ready");
+
+    #[test]
+    fn the_phase_names_the_first_outstanding_condition() {
+        let pending = ("False", "Pending");
+        let phase = |reasons, idle| runner_phase(&startup(reasons), false, idle);
+        assert_eq!(runner_phase(&[], false, false), RunnerPhase::Pending);
+        assert_eq!(
+            phase([pending, READY, ("False", "NotPresent"), pending], false),
+            RunnerPhase::Pending
+        );
+        // Pooled: scheduled, and the agent is still restoring the slot.
+        assert_eq!(
+            phase([pending, READY, READY, ("False", "NotStarted")], false),
+            RunnerPhase::Hydrating
+        );
+        // Dedicated: bound, and the init Job is still running.
+        assert_eq!(
+            phase([READY, ("False", "JobNotComplete"), READY, pending], false),
+            RunnerPhase::Hydrating
+        );
+        assert_eq!(
+            phase([READY, READY, READY, ("False", "Starting")], false),
+            RunnerPhase::Starting
+        );
+        assert_eq!(phase([READY; 4], false), RunnerPhase::Ready);
+        assert_eq!(phase([READY; 4], true), RunnerPhase::Idle);
+    }
+
+    #[test]
+    fn a_stuck_runner_fails_and_a_suspended_one_says_so() {
+        let phase = |reasons| runner_phase(&startup(reasons), false, false);
+        assert_eq!(
+            phase([READY, READY, READY, ("False", "CrashLoopBackOff")]),
+            RunnerPhase::Failed
+        );
+        // A crash that kept the slot from ever binding is still a crash.
+        assert_eq!(
+            phase([
+                ("False", "ImagePullBackOff"),
+                READY,
+                READY,
+                ("False", "ImagePullBackOff")
+            ]),
+            RunnerPhase::Failed
+        );
+        assert_eq!(
+            phase([
+                READY,
+                ("False", "BudgetExceeded"),
+                ("False", "NotPresent"),
+                READY
+            ]),
+            RunnerPhase::Failed
+        );
+        // Unschedulable may yet be fixed by a new node.
+        assert_eq!(
+            phase([READY, READY, ("False", "Unschedulable"), READY]),
+            RunnerPhase::Pending
+        );
+        assert_eq!(
+            runner_phase(&startup([READY; 4]), true, false),
+            RunnerPhase::Suspended
+        );
+    }
+
+    fn crash_looping_after(terminated: ContainerStateTerminated) -> Pod {
+        pod_with_status(PodStatus {
+            container_statuses: Some(vec![ContainerStatus {
+                last_state: Some(ContainerState {
+                    terminated: Some(terminated),
+                    ..Default::default()
+                }),
+                restart_count: 3,
+                ..container_status("runner", false, Some(waiting("CrashLoopBackOff", None)))
+            }]),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn a_crash_loop_is_classified_by_the_termination_behind_it() {
+        let oom = crash_looping_after(ContainerStateTerminated {
+            reason: Some("OOMKilled".to_string()),
+            exit_code: 137,
+            ..Default::default()
+        });
+        let failed = failed_condition(Some(&oom), &[], None).unwrap();
+        assert_condition(&failed, FAILED, "True", OUT_OF_MEMORY);
+
+        let install = crash_looping_after(ContainerStateTerminated {
+            reason: Some("Error".to_string()),
+            exit_code: 1,
+            message: Some(
+                "DependencyInstallFailed: pixi install exited with status 1\n".to_string(),
+            ),
+            ..Default::default()
+        });
+        let failed = failed_condition(Some(&install), &[], None).unwrap();
+        assert_condition(&failed, FAILED, "True", "DependencyInstallFailed");
+        assert!(failed.message.contains("pixi install"));
+
+        let crash = crash_looping_after(ContainerStateTerminated {
+            reason: Some("Error".to_string()),
+            exit_code: 2,
+            ..Default::default()
+        });
+        let failed = failed_condition(Some(&crash), &[], None).unwrap();
+        assert_condition(&failed, FAILED, "True", "CrashLoop");
+        assert!(failed.message.contains("exit code 2"));
+    }
+
+    fn terminated_now(terminated: ContainerStateTerminated) -> Pod {
+        pod_with_status(PodStatus {
+            container_statuses: Some(vec![container_status(
+                "runner",
+                false,
+                Some(ContainerState {
+                    terminated: Some(terminated),
+                    ..Default::default()
+                }),
+            )]),
+            ..Default::default()
+        })
+    }
+
+    /// A first crash is not a failure until kubelet backs off from it: the
+    /// restart may well recover.
+    #[test]
+    fn a_first_crash_that_restarts_is_not_a_failure() {
+        let crashed = terminated_now(ContainerStateTerminated {
+            reason: Some("Error".to_string()),
+            exit_code: 1,
+            ..Default::default()
+        });
+        assert!(failed_condition(Some(&crashed), &[], None).is_none());
+        let restarted = pod_with_status(PodStatus {
+            container_statuses: Some(vec![ContainerStatus {
+                last_state: Some(ContainerState {
+                    terminated: Some(ContainerStateTerminated {
+                        exit_code: 1,
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                }),
+                restart_count: 1,
+                ..container_status(
+                    "runner",
+                    true,
+                    Some(ContainerState {
+                        running: Some(ContainerStateRunning::default()),
+                        ..Default::default()
+                    }),
+                )
+            }]),
+            ..Default::default()
+        });
+        assert!(failed_condition(Some(&restarted), &[], None).is_none());
+        // An OOM kill says why at once, and is classified without the wait.
+        let oom = terminated_now(ContainerStateTerminated {
+            reason: Some("OOMKilled".to_string()),
+            exit_code: 137,
+            ..Default::default()
+        });
+        let failed = failed_condition(Some(&oom), &[], None).unwrap();
+        assert_condition(&failed, FAILED, "True", OUT_OF_MEMORY);
+    }
+
+    /// Suspending or replacing a runner deletes its pod, whose container then
+    /// exits to the SIGTERM it was sent — or cleanly.
+    #[test]
+    fn a_stop_is_not_a_failure() {
+        let mut stopping = terminated_now(ContainerStateTerminated {
+            reason: Some("Error".to_string()),
+            exit_code: 143,
+            ..Default::default()
+        });
+        stopping.metadata.deletion_timestamp = Some(Time(Timestamp::now()));
+        assert!(failed_condition(Some(&stopping), &[], None).is_none());
+        let exited = terminated_now(ContainerStateTerminated {
+            reason: Some("Completed".to_string()),
+            exit_code: 0,
+            ..Default::default()
+        });
+        assert!(failed_condition(Some(&exited), &[], None).is_none());
+    }
+
+    #[test]
+    fn pull_and_mount_errors_fail_but_a_starting_pod_does_not() {
+        let waiting_pod = |reason, message| {
+            pod_with_status(PodStatus {
+                container_statuses: Some(vec![container_status(
+                    "runner",
+                    false,
+                    Some(waiting(reason, message)),
+                )]),
+                ..Default::default()
+            })
+        };
+        let pull = waiting_pod("ImagePullBackOff", Some("Back-off pulling image"));
+        let failed = failed_condition(Some(&pull), &[], None).unwrap();
+        assert_condition(&failed, FAILED, "True", "ImagePullFailed");
+
+        let mount = waiting_pod(
+            "CreateContainerError",
+            Some("failed to generate spec: failed to mount /var/lib/kubelet/pods/x"),
+        );
+        let failed = failed_condition(Some(&mount), &[], None).unwrap();
+        assert_condition(&failed, FAILED, "True", "MountFailed");
+
+        let creating = waiting_pod("ContainerCreating", None);
+        assert!(failed_condition(Some(&creating), &[], None).is_none());
+    }
+
+    #[test]
+    fn a_failure_that_clears_is_recovered_and_a_missing_pod_changes_nothing() {
+        let failed = condition(FAILED, "True", OUT_OF_MEMORY, String::new(), None);
+        let running = pod_with_status(PodStatus {
+            container_statuses: Some(vec![container_status(
+                "runner",
+                true,
+                Some(ContainerState {
+                    running: Some(ContainerStateRunning::default()),
+                    ..Default::default()
+                }),
+            )]),
+            ..Default::default()
+        });
+        let recovered = failed_condition(Some(&running), std::slice::from_ref(&failed), None);
+        assert_condition(&recovered.unwrap(), FAILED, "False", "Recovered");
+        assert!(failed_condition(Some(&running), &[], None).is_none());
+        assert!(failed_condition(None, &[failed], None).is_none());
+        assert_eq!(
+            runner_phase(
+                &[condition(FAILED, "True", "CrashLoop", String::new(), None)],
+                false,
+                false
+            ),
+            RunnerPhase::Failed
+        );
+    }
 }
@@ -1,15 +1,18 @@
 mod apply_conditions;
 mod conditions;
+mod oom;
+mod shutdown;
 
 use std::sync::Arc;
 use std::time::Duration;
 
-use chrono::{TimeDelta, Utc};
+use chrono::{DateTime, TimeDelta, Utc};
 use futures::prelude::*;
 use kubimo::k8s_openapi::api::core::v1::Secret;
 use kubimo::k8s_openapi::apimachinery::pkg::apis::meta::v1::Condition as K8sCondition;
+use kubimo::k8s_openapi::jiff::Timestamp;
 use kubimo::kube::runtime::controller::Action;
-use kubimo::{Runner, RunnerCommand, RunnerStatus, prelude::*};
+use kubimo::{EventType, Runner, RunnerCommand, RunnerStatus, json_patch_macros::*, prelude::*};
 use serde::Deserialize;
 use thiserror::Error;
 use url::Url;
@@ -17,7 +20,7 @@ use url::Url;
 use crate::backoff::default_error_policy;
 use crate::config::StatusCheckResolution;
 use crate::context::Context;
-use crate::controllers::ingress::effective_ingress_path;
+use crate::controllers::ingress::{effective_ingress_path, runner_url};
 use crate::error::ControllerResult;
 use crate::reconciler::{ReconcileError, Reconciler, ReconcilerExt};
 
@@ -129,6 +132,22 @@ impl RunnerApi {
             .await?)
     }
 
+    /// When marimo last ran a cell and last heard from the editor. Only the
+    /// aqora build of marimo serves this; any other answers 404, which is
+    /// `None` rather than an error — open connections are then all there is
+    /// to go on, as before, and a runner that asked for more says so with an
+    /// `ActivityUnsupported` condition.
+    pub async fn activity(&self) -> Result<Option<Activity>, RunnerStatusError> {
+        let response = self
+            .get(self.api_endpoint.join("status/activity")?)
+            .send()
+            .await?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        Ok(Some(response.error_for_status()?.json::<Activity>().await?))
+    }
+
     pub async fn marimo_version(&self) -> Result<String, RunnerStatusError> {
         Ok(self
             .get(self.api_endpoint.join("version")?)
@@ -152,6 +171,40 @@ impl Connections {
     }
 }
 
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Activity {
+    last_execution: Option<DateTime<Utc>>,
+    last_input: Option<DateTime<Utc>>,
+}
+
+/// Does this poll count the runner as active?
+///
+/// An open connection is the baseline, but a forgotten tab holds one open
+/// indefinitely. With `inactive_after_secs_without_execution`, it also takes a
+/// cell run or an editor input within that window — either will do, since
+/// someone writing a cell they have not run yet is not idle. Without an
+/// activity report, the window cannot be measured and connections decide.
+fn is_active(
+    connections: Option<&Connections>,
+    activity: Option<&Activity>,
+    inactive_after_secs_without_execution: Option<u32>,
+    now: DateTime<Utc>,
+) -> bool {
+    if !connections.is_some_and(Connections::is_active) {
+        return false;
+    }
+    let (Some(window), Some(activity)) = (inactive_after_secs_without_execution, activity) else {
+        return true;
+    };
+    activity
+        .last_execution
+        .into_iter()
+        .chain(activity.last_input)
+        .max()
+        .is_some_and(|at| now - at < TimeDelta::seconds(window as i64))
+}
+
 /// Requeue interval while startup conditions are not all True. PVC and
 /// Workspace changes don't trigger this controller's watches, so a faster
 /// requeue is what surfaces startup progress promptly.
@@ -201,6 +254,45 @@ fn is_inactive_past_deadline(
     since + (delete_after_secs_inactive as i64) < now_secs
 }
 
+/// The `Failed` condition, while it is `True`.
+fn failure(conditions: &[K8sCondition]) -> Option<&K8sCondition> {
+    conditions
+        .iter()
+        .find(|cond| cond.type_ == conditions::FAILED && cond.status == "True")
+}
+
+/// Has this runner been idle long enough to suspend?
+///
+/// Measured like [`is_inactive_past_deadline`], except that a resume restarts
+/// the clock: a runner woken after a long idle has neither a recent
+/// `lastActive` nor a recent creation, and would otherwise be suspended again
+/// by the very next poll, before anyone had a chance to connect.
+fn is_idle_past_suspend_deadline(
+    runner: &Runner,
+    conditions: &[K8sCondition],
+    suspend_after_secs_inactive: u32,
+    now_secs: i64,
+) -> bool {
+    let Some(since) = [
+        runner
+            .status
+            .as_ref()
+            .and_then(|status| status.last_active.map(|dt| dt.timestamp())),
+        runner
+            .metadata
+            .creation_timestamp
+            .as_ref()
+            .map(|t| t.0.as_second()),
+        conditions::resumed_at_secs(conditions),
+    ]
+    .into_iter()
+    .flatten()
+    .max() else {
+        return false;
+    };
+    since + (suspend_after_secs_inactive as i64) < now_secs
+}
+
 #[derive(Debug, Clone, Default)]
 struct RunnerStatusReconciler {
     client: reqwest::Client,
@@ -246,7 +338,63 @@ impl RunnerStatusReconciler {
                 None
             }
         };
-        let is_active = connections.as_ref().is_some_and(Connections::is_active);
+        // Only worth asking a runner that answered at all. `Some(None)` is a
+        // marimo with no activity to report.
+        let activity = match connections {
+            Some(_) => match api.activity().await {
+                Ok(activity) => Some(activity),
+                Err(err) => {
+                    tracing::warn!(err = ?err, "Could not get runner activity: {}", err);
+                    None
+                }
+            },
+            None => None,
+        };
+        if let Some(activity) = &activity
+            && let Some(unsupported) = conditions::activity_unsupported_condition(
+                runner,
+                activity.is_some(),
+                status.conditions.as_deref().unwrap_or_default(),
+                runner.metadata.generation,
+            )
+        {
+            // Announced once: the lifecycle the user set is not the one the
+            // runner gets, and nothing else would tell them.
+            if unsupported.status == "True"
+                && !conditions::is_true(
+                    status.conditions.as_deref().unwrap_or_default(),
+                    conditions::ACTIVITY_UNSUPPORTED,
+                )
+            {
+                ctx.events
+                    .publish(
+                        runner,
+                        EventType::Warning,
+                        conditions::ACTIVITY_UNSUPPORTED,
+                        "Monitor",
+                        unsupported.message.clone(),
+                    )
+                    .await;
+            }
+            conditions::upsert_condition(
+                status.conditions.get_or_insert_with(Vec::new),
+                unsupported,
+            );
+        }
+        let activity = activity.flatten();
+        let is_active = is_active(
+            connections.as_ref(),
+            activity.as_ref(),
+            runner
+                .spec
+                .lifecycle
+                .as_ref()
+                .and_then(|l| l.inactive_after_secs_without_execution),
+            now,
+        );
+        if let Some(last_execution) = activity.as_ref().and_then(|a| a.last_execution) {
+            status.last_execution = Some(last_execution);
+        }
         let marimo_version = if connections.is_some()
             && runner
                 .status
@@ -307,6 +455,100 @@ impl RunnerStatusReconciler {
                 "Deleting an inactive runner",
             );
             ctx.api_for(runner)?.delete(name).await?;
+            ctx.events
+                .publish(
+                    runner,
+                    EventType::Normal,
+                    "DeletedInactive",
+                    "Delete",
+                    format!("deleted after {delete_after_secs_inactive}s without activity"),
+                )
+                .await;
+            return Ok(None);
+        }
+        if !is_active
+            && let Some(suspend_after_secs_inactive) = runner
+                .spec
+                .lifecycle
+                .as_ref()
+                .and_then(|l| l.suspend_after_secs_inactive)
+            // The same guard as the deletion above: a suspension is cheaper to
+            // undo, but an outage must not suspend every runner in the cluster
+            // from under its users either.
+            && (connections.is_some()
+                || unreachable_is_collectable(
+                    status.conditions.as_deref().unwrap_or_default(),
+                    now.timestamp(),
+                ))
+            && is_idle_past_suspend_deadline(
+                runner,
+                status.conditions.as_deref().unwrap_or_default(),
+                suspend_after_secs_inactive,
+                now.timestamp(),
+            )
+        {
+            let name = runner.name()?;
+            tracing::info!(
+                runner = %name,
+                suspend_after_secs_inactive,
+                reachable = connections.is_some(),
+                "Suspending an inactive runner",
+            );
+            // A patch of the one field rather than an apply of the spec: the
+            // spec belongs to whoever created the runner, and this must not
+            // take ownership of the rest of it.
+            ctx.api_for(runner)?
+                .patch_json(name, patch![add!(["spec", "suspended"] => true)])
+                .await?;
+            ctx.events
+                .publish(
+                    runner,
+                    EventType::Normal,
+                    "Suspended",
+                    "Suspend",
+                    format!("suspended after {suspend_after_secs_inactive}s without activity"),
+                )
+                .await;
+            return Ok(None);
+        }
+        Ok(Some(Action::requeue(interval)))
+    }
+
+    /// The idle GC of a suspended runner, which has nothing to poll: it has
+    /// been inactive since it was suspended, if not before, and is collected
+    /// once `deleteAfterSecsInactive` says so. Returns `None` once deleted.
+    async fn collect_suspended(
+        &self,
+        ctx: &Context,
+        runner: &Runner,
+    ) -> Result<Option<Action>, RunnerStatusError> {
+        let interval = Duration::from_secs(ctx.config.runner_status.interval_secs);
+        let now = Utc::now();
+        if let Some(delete_after_secs_inactive) = runner
+            .spec
+            .lifecycle
+            .as_ref()
+            .and_then(|l| l.delete_after_secs_inactive)
+            && is_inactive_past_deadline(runner, delete_after_secs_inactive, now.timestamp())
+        {
+            let name = runner.name()?;
+            tracing::info!(
+                runner = %name,
+                delete_after_secs_inactive,
+                "Deleting an inactive suspended runner",
+            );
+            ctx.api_for(runner)?.delete(name).await?;
+            ctx.events
+                .publish(
+                    runner,
+                    EventType::Normal,
+                    "DeletedInactive",
+                    "Delete",
+                    format!(
+                        "deleted while suspended, after {delete_after_secs_inactive}s without activity"
+                    ),
+                )
+                .await;
             return Ok(None);
         }
         Ok(Some(Action::requeue(interval)))
@@ -319,10 +561,114 @@ impl Reconciler for RunnerStatusReconciler {
     type Error = RunnerStatusError;
 
     async fn apply(&self, ctx: &Context, runner: &Runner) -> Result<Action, Self::Error> {
+        let shutdown = match runner.spec.lifecycle.as_ref() {
+            Some(lifecycle) => shutdown::scheduled_shutdown(
+                lifecycle,
+                runner.metadata.creation_timestamp.as_ref().map(|t| t.0),
+                Timestamp::now(),
+            ),
+            None => Ok(None),
+        };
+        if let Ok(Some(due)) = &shutdown
+            && due.at <= Timestamp::now()
+        {
+            let name = runner.name()?;
+            tracing::info!(
+                runner = %name,
+                reason = due.reason,
+                "Deleting a runner at its scheduled shutdown",
+            );
+            ctx.api_for(runner)?.delete(name).await?;
+            ctx.events
+                .publish(
+                    runner,
+                    EventType::Normal,
+                    "ShutdownReached",
+                    "Delete",
+                    format!("deleted at its scheduled shutdown ({})", due.reason),
+                )
+                .await;
+            return Ok(Action::await_change());
+        }
         let mut status = runner.status.clone().unwrap_or_default();
-        let startup_complete = self
+        let (startup_complete, pod) = self
             .apply_startup_conditions(ctx, runner, &mut status)
             .await?;
+        if let Some(failed) = failure(status.conditions.as_deref().unwrap_or_default()).cloned() {
+            let previous = runner
+                .status
+                .as_ref()
+                .and_then(|status| failure(status.conditions.as_deref().unwrap_or_default()));
+            if previous.map(|previous| previous.reason.as_str()) != Some(failed.reason.as_str()) {
+                ctx.events
+                    .publish(
+                        runner,
+                        EventType::Warning,
+                        &failed.reason,
+                        "Start",
+                        failed.message.clone(),
+                    )
+                    .await;
+            }
+            if failed.reason == conditions::OUT_OF_MEMORY
+                && let Some(pod) = pod.as_ref()
+                && let Some(request) = oom::next_memory_request(runner, pod)
+            {
+                let retries = status.oom_retries.unwrap_or(0) + 1;
+                tracing::info!(
+                    runner = runner.name()?,
+                    %request,
+                    retries,
+                    "Raising the memory request of an OOM-killed runner",
+                );
+                ctx.events
+                    .publish(
+                        runner,
+                        EventType::Normal,
+                        "OOMRetry",
+                        "Recycle",
+                        format!("replacing the pod with one requesting {request} of memory"),
+                    )
+                    .await;
+                status.memory_request = Some(request);
+                status.oom_retries = Some(retries);
+            }
+        }
+        let conditions = status.conditions.get_or_insert_with(Vec::new);
+        if let Some(suspended) =
+            conditions::suspended_condition(runner, conditions, runner.metadata.generation)
+        {
+            conditions::upsert_condition(conditions, suspended);
+        }
+        if let Some(scheduled) = shutdown::shutdown_scheduled_condition(
+            &shutdown,
+            conditions,
+            runner.metadata.generation,
+        ) {
+            // Announced once, when the warning starts: the condition is
+            // re-upserted on every poll, and the event is what reaches a user
+            // watching `kubectl get events` rather than the runner itself.
+            if scheduled.status == "True"
+                && !conditions::is_true(conditions, kubimo::conditions::SHUTDOWN_SCHEDULED)
+            {
+                ctx.events
+                    .publish(
+                        runner,
+                        EventType::Warning,
+                        kubimo::conditions::SHUTDOWN_SCHEDULED,
+                        "Schedule",
+                        scheduled.message.clone(),
+                    )
+                    .await;
+            }
+            conditions::upsert_progress_condition(conditions, scheduled);
+        }
+        status.shutdown_time = shutdown
+            .as_ref()
+            .ok()
+            .and_then(Option::as_ref)
+            .and_then(|shutdown| chrono::DateTime::from_timestamp(shutdown.at.as_second(), 0));
+        let suspended = runner.spec.is_suspended();
         // A claimed runner has no Service until the agent acks the claim, so
         // polling would only fail against a pod that is otherwise Ready and
         // warn-log every few seconds about an outage that isn't one.
@@ -330,30 +676,57 @@ impl Reconciler for RunnerStatusReconciler {
             .status
             .as_ref()
             .and_then(|s| s.claim.as_ref())
-            .is_some()
+            .is_some_and(|claim| !claim.is_released())
             && !conditions::volume_is_bound(status.conditions.as_deref().unwrap_or_default());
-        let action = if matches!(runner.spec.command, RunnerCommand::Render) || claim_pending {
-            Action::await_change()
+        let action = if suspended {
+            let Some(action) = self.collect_suspended(ctx, runner).await? else {
+                return Ok(Action::await_change());
+            };
+            action
+        } else if matches!(runner.spec.command, RunnerCommand::Render) || claim_pending {
+            // Nothing to poll, but a deadline still has to be kept.
+            if shutdown::has_deadline(runner.spec.lifecycle.as_ref()) {
+                Action::requeue(Duration::from_secs(ctx.config.runner_status.interval_secs))
+            } else {
+                Action::await_change()
+            }
         } else {
             let action = self.poll_api_status(ctx, runner, &mut status).await?;
             let Some(action) = action else {
-                // Runner was deleted for inactivity
+                // Runner was deleted or suspended for inactivity
                 return Ok(Action::await_change());
             };
             action
         };
+        // The poll above moves `lastActive` to now whenever it finds a
+        // connection, and skips polling while it is fresher than an interval,
+        // so anything older means no one was connected last time it looked.
+        // A `Render` runner is never polled, and has no connections to count.
+        let interval = Duration::from_secs(ctx.config.runner_status.interval_secs);
+        let idle = !matches!(runner.spec.command, RunnerCommand::Render)
+            && status.last_active.is_none_or(|last_active| {
+                Utc::now() - last_active >= TimeDelta::from_std(interval).unwrap_or(TimeDelta::MAX)
+            });
+        status.phase = Some(conditions::runner_phase(
+            status.conditions.as_deref().unwrap_or_default(),
+            suspended,
+            idle,
+        ));
+        status.url = runner_url(&ctx.config.runner_hosts, runner)?;
         // Computed before `status` is moved into the patch below.
         let startup_requeue = conditions::startup_requeue_interval(
             status.conditions.as_deref().unwrap_or_default(),
             Utc::now().timestamp(),
-            Duration::from_secs(ctx.config.runner_status.interval_secs),
+            interval,
         );
         if Some(&status) != runner.status.as_ref() {
             let mut patched = runner.clone();
             patched.status = Some(status);
             ctx.api_for(runner)?.patch_status(&patched).await?;
         }
-        if startup_complete {
+        // A suspended runner's startup conditions are all `False` on purpose;
+        // polling them fast would only watch it stay suspended.
+        if startup_complete || suspended {
             Ok(action)
         } else {
             Ok(Action::requeue(startup_requeue))
@@ -471,4 +844,159 @@ mod tests {
         let runner = runner_created_at(created, Some(now - 60));
         assert!(!is_inactive_past_deadline(&runner, day as u32, now));
     }
+
+    fn connections(active: usize) -> Connections {
+        Connections { active }
+    }
+
+    /// A forgotten tab is an open connection; only the execution window sees
+    /// through it.
+    #[test]
+    fn an_open_connection_without_execution_is_inactive_under_a_window() {
+        let now = chrono::DateTime::from_timestamp(100_000, 0).unwrap();
+        let minutes_ago = |minutes| Some(now - TimeDelta::minutes(minutes));
+        let stale = Activity {
+            last_execution: minutes_ago(90),
+            last_input: minutes_ago(120),
+        };
+        let open = connections(1);
+        assert!(is_active(Some(&open), Some(&stale), None, now));
+        assert!(!is_active(Some(&open), Some(&stale), Some(3_600), now));
+        // Typing counts as much as running.
+        let typing = Activity {
+            last_input: minutes_ago(5),
+            ..stale
+        };
+        assert!(is_active(Some(&open), Some(&typing), Some(3_600), now));
+        assert!(!is_active(
+            Some(&open),
+            Some(&Activity::default()),
+            Some(3_600),
+            now
+        ));
+        // An image that does not report activity keeps the connection rule.
+        assert!(is_active(Some(&open), None, Some(3_600), now));
+        assert!(!is_active(
+            Some(&connections(0)),
+            Some(&typing),
+            Some(3_600),
+            now
+        ));
+        assert!(!is_active(None, Some(&typing), None, now));
+    }
+
+    /// Any marimo other than the aqora build answers `status/activity` with a
+    /// 404. The poll goes on with connections alone, and the runner that set
+    /// an execution window is told it is ignored.
+    #[tokio::test]
+    async fn a_marimo_without_an_activity_report_falls_back_to_connections() {
+        use std::io::{BufRead, BufReader, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            while request.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+            stream
+                .write_all(
+                    b"HTTP/1.1 404 Not Found\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+                )
+                .unwrap();
+        });
+        let api = RunnerApi {
+            client: reqwest::Client::new(),
+            api_endpoint: format!("http://{addr}/api/").parse().unwrap(),
+            token: None,
+        };
+        let activity = api.activity().await.unwrap();
+        assert!(activity.is_none());
+        let now = Utc::now();
+        assert!(is_active(
+            Some(&connections(1)),
+            activity.as_ref(),
+            Some(3_600),
+            now
+        ));
+
+        let mut runner = Runner::new(
+            "bmor-test",
+            kubimo::RunnerSpec {
+                lifecycle: Some(kubimo::RunnerLifecycle {
+                    inactive_after_secs_without_execution: Some(3_600),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+        );
+        let unsupported =
+            conditions::activity_unsupported_condition(&runner, false, &[], None).unwrap();
+        assert_eq!(unsupported.type_, kubimo::conditions::ACTIVITY_UNSUPPORTED);
+        assert_eq!(
+            (unsupported.status.as_str(), unsupported.reason.as_str()),
+            ("True", "Unsupported")
+        );
+        assert!(conditions::activity_unsupported_condition(&runner, true, &[], None).is_none());
+        let reported = conditions::activity_unsupported_condition(
+            &runner,
+            true,
+            std::slice::from_ref(&unsupported),
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            (reported.status.as_str(), reported.reason.as_str()),
+            ("False", "Reported")
+        );
+        // Without a window there is nothing the report would have decided.
+        runner.spec.lifecycle = None;
+        assert!(conditions::activity_unsupported_condition(&runner, false, &[], None).is_none());
+        let unset =
+            conditions::activity_unsupported_condition(&runner, false, &[unsupported], None)
+                .unwrap();
+        assert_eq!(
+            (unset.status.as_str(), unset.reason.as_str()),
+            ("False", "NotRequired")
+        );
+    }
+
+    /// Woken after a long idle, a runner has neither a recent `lastActive`
+    /// nor a recent creation; only the resume keeps it from being suspended
+    /// again straight away.
+    #[test]
+    fn a_resume_restarts_the_suspension_deadline() {
+        let hour = 3_600;
+        let created = 1_000;
+        let now = created + 30 * hour;
+        let runner = runner_created_at(created, Some(created + hour));
+        assert!(is_idle_past_suspend_deadline(
+            &runner,
+            &[],
+            hour as u32,
+            now
+        ));
+        let resumed = vec![K8sCondition {
+            type_: kubimo::conditions::SUSPENDED.to_string(),
+            status: "False".to_string(),
+            reason: "Resumed".to_string(),
+            message: String::new(),
+            last_transition_time: Time(Timestamp::from_second(now - 60).unwrap()),
+            observed_generation: None,
+        }];
+        assert!(!is_idle_past_suspend_deadline(
+            &runner,
+            &resumed,
+            hour as u32,
+            now
+        ));
+        assert!(is_idle_past_suspend_deadline(
+            &runner,
+            &resumed,
+            hour as u32,
+            now + hour
+        ));
+    }
 }
@@ -0,0 +1,194 @@
+//! `lifecycle.oomRetries`: replacing a runner that keeps running out of
+//! memory with one that requests more of it.
+//!
+//! Only the request is raised, never the limit, so only OOM kills that a
+//! request can prevent are handled. `memory.max` is the pod's limit from the
+//! outset, so a container killed at its own limit gets no more room from a
+//! retry; what a larger request buys is a node with that much memory actually
+//! free, and a container the kernel reaches for last when the node runs short
+//! — which is how a runner well under its limit still gets OOM-killed. The
+//! request stops at `memory.max`, where it meets the limit; a runner whose
+//! request is already there, or that sets no `memory.max`, gets no retry.
+//!
+//! The controller only writes the new request into `status.memoryRequest`.
+//! The runner controller builds pods from it, and a live pod's resources
+//! cannot change, so it replaces the pod on the next apply.
+
+use kubimo::k8s_openapi::api::core::v1::Pod;
+use kubimo::{Runner, StorageQuantity, StorageUnit};
+
+use crate::controllers::runner_pod::runner_container;
+
+/// The memory request to retry an OOM-killed `runner` with, or `None` when
+/// it gets no retry: `lifecycle.oomRetries` is unset or spent, there is no
+/// `memory.max` to stop at, or the request already reaches it.
+///
+/// `pod` is the pod that was killed. Until the runner controller has
+/// replaced it, it still runs with the request before the last raise, and
+/// counting its kills again would spend every retry on one pod.
+pub(super) fn next_memory_request(runner: &Runner, pod: &Pod) -> Option<StorageQuantity> {
+    let limit = runner.spec.lifecycle.as_ref()?.oom_retries?;
+    let spent = runner
+        .status
+        .as_ref()
+        .and_then(|status| status.oom_retries)
+        .unwrap_or(0);
+    if spent >= limit {
+        return None;
+    }
+    // A claimed warm pod runs with the pool's resources, which a retry of
+    // the runner's own cannot change.
+    if runner
+        .status
+        .as_ref()
+        .and_then(|status| status.claim.as_ref())
+        .is_some_and(|claim| !claim.is_released())
+    {
+        return None;
+    }
+    let memory = runner.effective_memory()?;
+    let max = memory.max.as_ref().and_then(StorageQuantity::to_bytes)?;
+    let current = memory.min.as_ref().and_then(StorageQuantity::to_bytes);
+    if pod_memory_request(pod) != current {
+        return None;
+    }
+    let next = current.map_or(max, |current| current.saturating_mul(2).min(max));
+    (next > current.unwrap_or(0)).then(|| StorageQuantity::new(next as f64, StorageUnit::B))
+}
+
+/// The runner container's memory request, in bytes.
+fn pod_memory_request(pod: &Pod) -> Option<u64> {
+    runner_container(pod)?
+        .resources
+        .as_ref()?
+        .requests
+        .as_ref()?
+        .get("memory")
+        .cloned()
+        .map(StorageQuantity::from)
+        .and_then(|request| request.to_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kubimo::k8s_openapi::api::core::v1::{Container, PodSpec, ResourceRequirements};
+    use kubimo::k8s_openapi::apimachinery::pkg::api::resource::Quantity;
+    use kubimo::{Requirement, RunnerLifecycle, RunnerSpec, RunnerStatus};
+
+    const MI: u64 = 1 << 20;
+
+    fn runner(min: u64, max: u64, retries: u32, status: RunnerStatus) -> Runner {
+        let mut runner = Runner::new(
+            "test",
+            RunnerSpec {
+                memory: Some(Requirement {
+                    min: Some(StorageQuantity::new(min as f64, StorageUnit::B)),
+                    max: Some(StorageQuantity::new(max as f64, StorageUnit::B)),
+                }),
+                lifecycle: Some(RunnerLifecycle {
+                    oom_retries: Some(retries),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+        );
+        runner.status = Some(status);
+        runner
+    }
+
+    fn pod_requesting(bytes: u64) -> Pod {
+        Pod {
+            spec: Some(PodSpec {
+                containers: vec![Container {
+                    name: "runner".to_string(),
+                    resources: Some(ResourceRequirements {
+                        requests: Some(
+                            [("memory".to_string(), Quantity(bytes.to_string()))].into(),
+                        ),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn the_request_doubles_up_to_the_max() {
+        let first = runner(512 * MI, 1536 * MI, 3, RunnerStatus::default());
+        let next = next_memory_request(&first, &pod_requesting(512 * MI)).unwrap();
+        assert_eq!(next.to_bytes(), Some(1024 * MI));
+
+        let second = runner(
+            512 * MI,
+            1536 * MI,
+            3,
+            RunnerStatus {
+                memory_request: Some(next),
+                oom_retries: Some(1),
+                ..Default::default()
+            },
+        );
+        let next = next_memory_request(&second, &pod_requesting(1024 * MI)).unwrap();
+        assert_eq!(next.to_bytes(), Some(1536 * MI));
+
+        let at_max = runner(
+            512 * MI,
+            1536 * MI,
+            3,
+            RunnerStatus {
+                memory_request: Some(next),
+                oom_retries: Some(2),
+                ..Default::default()
+            },
+        );
+        assert!(next_memory_request(&at_max, &pod_requesting(1536 * MI)).is_none());
+    }
+
+    /// The pod the status loop sees OOM-killed right after a raise is still
+    /// the old one; raising again would spend a retry on a pod never retried.
+    #[test]
+    fn a_pod_not_yet_replaced_is_not_retried_again() {
+        let raised = runner(
+            512 * MI,
+            4096 * MI,
+            3,
+            RunnerStatus {
+                memory_request: Some(StorageQuantity::new((1024 * MI) as f64, StorageUnit::B)),
+                oom_retries: Some(1),
+                ..Default::default()
+            },
+        );
+        assert!(next_memory_request(&raised, &pod_requesting(512 * MI)).is_none());
+    }
+
+    #[test]
+    fn spent_retries_stop_the_raise() {
+        let spent = runner(
+            512 * MI,
+            4096 * MI,
+            1,
+            RunnerStatus {
+                oom_retries: Some(1),
+                ..Default::default()
+            },
+        );
+        assert!(next_memory_request(&spent, &pod_requesting(512 * MI)).is_none());
+    }
+
+    /// The limit is never raised: a runner already requesting its `memory.max`
+    /// was killed at its limit, which a retry cannot help, and one without a
+    /// `memory.max` has no limit for a request to grow into.
+    #[test]
+    fn a_kill_at_the_limit_is_not_retried() {
+        let at_limit = runner(1024 * MI, 1024 * MI, 3, RunnerStatus::default());
+        assert!(next_memory_request(&at_limit, &pod_requesting(1024 * MI)).is_none());
+
+        let mut unbounded = runner(512 * MI, 1024 * MI, 3, RunnerStatus::default());
+        unbounded.spec.memory.as_mut().unwrap().max = None;
+        assert!(next_memory_request(&unbounded, &pod_requesting(512 * MI)).is_none());
+    }
+}
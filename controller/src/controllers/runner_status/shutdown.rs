@@ -0,0 +1,210 @@
+//! `lifecycle.maxLifetimeSecs` and `lifecycle.activeHours`: deadlines that
+//! delete a runner whatever it is doing, announced by `ShutdownScheduled`
+//! for `lifecycle.shutdownWarningSecs` beforehand so that the frontend can
+//! warn whoever is using it.
+//!
+//! Only a shutdown inside the warning window is ever computed. Active hours
+//! could end at any minute, and looking further ahead than the warning would
+//! only be walking the expression for a date nobody is told about yet.
+
+use kubimo::k8s_openapi::apimachinery::pkg::apis::meta::v1::Condition;
+use kubimo::k8s_openapi::jiff::{SignedDuration, Timestamp};
+use kubimo::{RunnerLifecycle, conditions::SHUTDOWN_SCHEDULED};
+
+use crate::controllers::schedule::{self, Cron, ScheduleError};
+
+use super::conditions::condition;
+
+const REASON_MAX_LIFETIME: &str = "MaxLifetime";
+const REASON_OUTSIDE_ACTIVE_HOURS: &str = "OutsideActiveHours";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(super) struct Shutdown {
+    pub at: Timestamp,
+    pub reason: &'static str,
+}
+
+/// Whether `lifecycle` sets a deadline at all. A runner that has one must be
+/// looked at again on a timer, even when nothing else about it would wake
+/// this controller.
+pub(super) fn has_deadline(lifecycle: Option<&RunnerLifecycle>) -> bool {
+    lifecycle.is_some_and(|lifecycle| {
+        lifecycle.max_lifetime_secs.is_some() || lifecycle.active_hours.is_some()
+    })
+}
+
+/// The earliest shutdown `lifecycle` schedules no later than its warning
+/// window from `now`. Due already when `at` is not after `now`: a runner past
+/// its lifetime, or outside its hours.
+pub(super) fn scheduled_shutdown(
+    lifecycle: &RunnerLifecycle,
+    created: Option<Timestamp>,
+    now: Timestamp,
+) -> Result<Option<Shutdown>, ScheduleError> {
+    let warning_secs = i64::from(lifecycle.effective_shutdown_warning_secs());
+    let horizon = now
+        .checked_add(SignedDuration::from_secs(warning_secs))
+        .unwrap_or(Timestamp::MAX);
+    let lifetime = lifecycle
+        .max_lifetime_secs
+        .zip(created)
+        .map(|(secs, created)| Shutdown {
+            at: created
+                .checked_add(SignedDuration::from_secs(i64::from(secs)))
+                .unwrap_or(Timestamp::MAX),
+            reason: REASON_MAX_LIFETIME,
+        });
+    let hours = match lifecycle.active_hours.as_ref() {
+        None => None,
+        Some(hours) => {
+            let tz = schedule::time_zone(hours.time_zone.as_deref())?;
+            hours
+                .cron
+                .parse::<Cron>()?
+                .end_within(&now.to_zoned(tz), (warning_secs + 59) / 60)
+                .map(|end| Shutdown {
+                    at: end.timestamp(),
+                    reason: REASON_OUTSIDE_ACTIVE_HOURS,
+                })
+        }
+    };
+    Ok(lifetime
+        .into_iter()
+        .chain(hours)
+        .filter(|shutdown| shutdown.at <= horizon)
+        .min_by_key(|shutdown| shutdown.at))
+}
+
+/// `True` while a shutdown is scheduled, and `False` once none is for a
+/// runner that was warned before. Unreadable active hours are reported even
+/// to a runner that never was: nothing else would tell anyone they are being
+/// ignored.
+pub(super) fn shutdown_scheduled_condition(
+    shutdown: &Result<Option<Shutdown>, ScheduleError>,
+    conditions: &[Condition],
+    observed_generation: Option<i64>,
+) -> Option<Condition> {
+    let (status, reason, message) = match shutdown {
+        Ok(Some(shutdown)) => (
+            "True",
+            shutdown.reason,
+            format!("The runner will be deleted at {}", shutdown.at),
+        ),
+        Err(err) => (
+            "False",
+            "InvalidActiveHours",
+            format!("lifecycle.activeHours is ignored: {err}"),
+        ),
+        Ok(None)
+            if conditions
+                .iter()
+                .any(|cond| cond.type_ == SHUTDOWN_SCHEDULED) =>
+        {
+            (
+                "False",
+                "NotScheduled",
+                "No shutdown is scheduled".to_string(),
+            )
+        }
+        Ok(None) => return None,
+    };
+    Some(condition(
+        SHUTDOWN_SCHEDULED,
+        status,
+        reason,
+        message,
+        observed_generation,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kubimo::RunnerActiveHours;
+
+    fn at(datetime: &str) -> Timestamp {
+        format!("{datetime}Z").parse().unwrap()
+    }
+
+    #[test]
+    fn a_lifetime_is_only_announced_within_the_warning() {
+        let lifecycle = RunnerLifecycle {
+            max_lifetime_secs: Some(3_600),
+            shutdown_warning_secs: Some(300),
+            ..Default::default()
+        };
+        let created = Some(at("2025-06-02T10:00:00"));
+        let shutdown = |now| scheduled_shutdown(&lifecycle, created, at(now)).unwrap();
+        assert_eq!(shutdown("2025-06-02T10:50:00"), None);
+        assert_eq!(
+            shutdown("2025-06-02T10:56:00"),
+            Some(Shutdown {
+                at: at("2025-06-02T11:00:00"),
+                reason: REASON_MAX_LIFETIME,
+            })
+        );
+        // No creation time, nothing to count from.
+        assert_eq!(
+            scheduled_shutdown(&lifecycle, None, at("2025-06-03T00:00:00")).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn the_earliest_of_the_deadlines_wins() {
+        let lifecycle = RunnerLifecycle {
+            max_lifetime_secs: Some(8 * 3_600),
+            active_hours: Some(RunnerActiveHours {
+                cron: "* 8-18 * * *".into(),
+                time_zone: None,
+            }),
+            ..Default::default()
+        };
+        let created = Some(at("2025-06-02T16:00:00"));
+        let shutdown = |now| scheduled_shutdown(&lifecycle, created, at(now)).unwrap();
+        assert_eq!(shutdown("2025-06-02T17:00:00"), None);
+        assert_eq!(
+            shutdown("2025-06-02T18:52:00"),
+            Some(Shutdown {
+                at: at("2025-06-02T19:00:00"),
+                reason: REASON_OUTSIDE_ACTIVE_HOURS,
+            })
+        );
+        // Outside the hours, the shutdown is due at once.
+        assert_eq!(
+            shutdown("2025-06-02T23:00:00").map(|shutdown| shutdown.at),
+            Some(at("2025-06-02T23:00:00"))
+        );
+    }
+
+    #[test]
+    fn the_condition_appears_with_the_first_warning_and_stays() {
+        let invalid = RunnerLifecycle {
+            active_hours: Some(RunnerActiveHours {
+                cron: "* * *".into(),
+                time_zone: None,
+            }),
+            ..Default::default()
+        };
+        let shutdown = scheduled_shutdown(&invalid, None, at("2025-06-02T12:00:00"));
+        let cond = shutdown_scheduled_condition(&shutdown, &[], None).unwrap();
+        assert_eq!(
+            (cond.status.as_str(), cond.reason.as_str()),
+            ("False", "InvalidActiveHours")
+        );
+
+        assert_eq!(shutdown_scheduled_condition(&Ok(None), &[], None), None);
+        let warned = shutdown_scheduled_condition(
+            &Ok(Some(Shutdown {
+                at: at("2025-06-02T19:00:00"),
+                reason: REASON_OUTSIDE_ACTIVE_HOURS,
+            })),
+            &[],
+            None,
+        )
+        .unwrap();
+        assert_eq!(warned.status, "True");
+        let cleared = shutdown_scheduled_condition(&Ok(None), &[warned], None).unwrap();
+        assert_eq!(cleared.status, "False");
+    }
+}
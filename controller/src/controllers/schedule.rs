@@ -0,0 +1,485 @@
+//! The cron expressions of `CacheJobSpec::schedule` and
+//! `RunnerLifecycle::active_hours`.
+//!
+//! Hand-rolled rather than pulled in: the five classic fields are all either
+//! needs, and what they mean across a DST change is decided here, by
+//! the time zone database, rather than by whichever crate happened to be
+//! chosen. Seconds, years, `L`, `W`, `#` and `?` are refused, not ignored — an
+//! expression that parses must mean what it says. The `cron` crate wants a
+//! seconds field, and `croner` accepts every one of those extensions and reads
+//! zones from chrono-tz, a second copy of the database jiff already reads.
+
+use std::str::FromStr;
+
+use kubimo::k8s_openapi::jiff::civil::Date;
+use kubimo::k8s_openapi::jiff::tz::TimeZone;
+use kubimo::k8s_openapi::jiff::{RoundMode, Span, Timestamp, Unit, Zoned, ZonedRound};
+
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum ScheduleError {
+    #[error("expected 5 cron fields, found {0}")]
+    FieldCount(usize),
+    #[error("invalid cron field {field:?}: {reason}")]
+    Field { field: String, reason: &'static str },
+    #[error("unknown time zone {0:?}")]
+    TimeZone(String),
+}
+
+/// One field's allowed values, as a bit set over `min..=max`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Field {
+    bits: u64,
+    /// The field was `*` (or `*/1`). Matters for the days: cron ORs the day of
+    /// month and the day of week when both are restricted, and ANDs them
+    /// otherwise.
+    any: bool,
+}
+
+impl Field {
+    fn contains(&self, value: i8) -> bool {
+        self.bits & (1 << value) != 0
+    }
+
+    fn values(&self, min: i8, max: i8) -> impl Iterator<Item = i8> + '_ {
+        (min..=max).filter(|value| self.contains(*value))
+    }
+
+    fn parse(
+        field: &str,
+        min: i8,
+        max: i8,
+        names: &[&str],
+        name_base: i8,
+    ) -> Result<Self, ScheduleError> {
+        let err = |reason| ScheduleError::Field {
+            field: field.to_string(),
+            reason,
+        };
+        let value = |part: &str| -> Result<i8, ScheduleError> {
+            if let Some(index) = names
+                .iter()
+                .position(|name| name.eq_ignore_ascii_case(part))
+            {
+                return Ok(index as i8 + name_base);
+            }
+            let value = part.parse::<i8>().map_err(|_| err("not a number"))?;
+            if value < min || value > max {
+                return Err(err("value out of range"));
+            }
+            Ok(value)
+        };
+        let mut bits = 0u64;
+        let mut any = false;
+        for item in field.split(',') {
+            let (range, step) = match item.split_once('/') {
+                Some((range, step)) => {
+                    let step = step.parse::<i8>().map_err(|_| err("step not a number"))?;
+                    if step < 1 {
+                        return Err(err("step must be positive"));
+                    }
+                    (range, step)
+                }
+                None => (item, 1),
+            };
+            let (start, end) = if range == "*" {
+                any |= step == 1;
+                (min, max)
+            } else if let Some((start, end)) = range.split_once('-') {
+                (value(start)?, value(end)?)
+            } else {
+                // `5/15` means from 5 to the end, every 15.
+                let start = value(range)?;
+                (start, if step > 1 { max } else { start })
+            };
+            if start > end {
+                return Err(err("range start is after its end"));
+            }
+            for value in (start..=end).step_by(step as usize) {
+                bits |= 1 << value;
+            }
+        }
+        Ok(Self { bits, any })
+    }
+}
+
+const MONTHS: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const WEEKDAYS: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+/// A parsed cron expression.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Cron {
+    minutes: Field,
+    hours: Field,
+    days: Field,
+    months: Field,
+    /// Sunday is 0; a 7 in the expression is folded into it.
+    weekdays: Field,
+}
+
+impl FromStr for Cron {
+    type Err = ScheduleError;
+
+    fn from_str(expression: &str) -> Result<Self, Self::Err> {
+        let expression = match expression.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            expression => expression,
+        };
+        let fields = expression.split_whitespace().collect::<Vec<_>>();
+        let [minutes, hours, days, months, weekdays] = fields[..] else {
+            return Err(ScheduleError::FieldCount(fields.len()));
+        };
+        let mut weekdays = Field::parse(weekdays, 0, 7, &WEEKDAYS, 0)?;
+        if weekdays.contains(7) {
+            weekdays.bits = (weekdays.bits & !(1 << 7)) | 1;
+        }
+        Ok(Self {
+            minutes: Field::parse(minutes, 0, 59, &[], 0)?,
+            hours: Field::parse(hours, 0, 23, &[], 0)?,
+            days: Field::parse(days, 1, 31, &[], 0)?,
+            months: Field::parse(months, 1, 12, &MONTHS, 1)?,
+            weekdays,
+        })
+    }
+}
+
+/// How far ahead to look for a day the expression matches. Four years and a
+/// day reach the next 29 February; anything rarer never matches at all
+/// (`0 0 30 2 *`).
+const SEARCH_DAYS: usize = 4 * 366 + 1;
+
+impl Cron {
+    fn matches_day(&self, date: Date) -> bool {
+        if !self.months.contains(date.month()) {
+            return false;
+        }
+        let day = self.days.contains(date.day());
+        let weekday = self
+            .weekdays
+            .contains(date.weekday().to_sunday_zero_offset());
+        match (self.days.any, self.weekdays.any) {
+            (true, true) => true,
+            (true, false) => weekday,
+            (false, true) => day,
+            (false, false) => day || weekday,
+        }
+    }
+
+    /// The first time strictly after `after` the expression matches, read in
+    /// `after`'s time zone. `None` when it never does.
+    ///
+    /// Wall-clock times skipped by a DST change run at the instant the clock
+    /// jumps to, and a repeated hour runs once, at its first occurrence.
+    pub(crate) fn next_after(&self, after: &Zoned) -> Option<Zoned> {
+        let tz = after.time_zone();
+        let mut date = after.date();
+        for _ in 0..SEARCH_DAYS {
+            if self.matches_day(date) {
+                for hour in self.hours.values(0, 23) {
+                    for minute in self.minutes.values(0, 59) {
+                        let Ok(zoned) = date.at(hour, minute, 0, 0).to_zoned(tz.clone()) else {
+                            continue;
+                        };
+                        if zoned.timestamp() > after.timestamp() {
+                            return Some(zoned);
+                        }
+                    }
+                }
+            }
+            date = date.tomorrow().ok()?;
+        }
+        None
+    }
+
+    /// Whether the minute `at` falls in matches the expression, read in
+    /// `at`'s time zone.
+    pub(crate) fn matches(&self, at: &Zoned) -> bool {
+        self.matches_day(at.date())
+            && self.hours.contains(at.hour())
+            && self.minutes.contains(at.minute())
+    }
+
+    /// When the run of matching minutes `from` is in ends, looking no more
+    /// than `within` minutes ahead: `from` itself when its minute does not
+    /// match, the start of the first minute after it that does not otherwise.
+    /// `None` when every minute up to the horizon matches.
+    pub(crate) fn end_within(&self, from: &Zoned, within: i64) -> Option<Zoned> {
+        if !self.matches(from) {
+            return Some(from.clone());
+        }
+        let mut minute = from
+            .round(
+                ZonedRound::new()
+                    .smallest(Unit::Minute)
+                    .mode(RoundMode::Trunc),
+            )
+            .ok()?;
+        for _ in 0..within {
+            minute = minute.checked_add(Span::new().minutes(1)).ok()?;
+            if !self.matches(&minute) {
+                return Some(minute);
+            }
+        }
+        None
+    }
+
+    /// The latest time in `(since, now]` the expression matches, if any:
+    /// missed runs collapse into the most recent of them.
+    pub(crate) fn latest_due(&self, since: &Zoned, now: Timestamp) -> Option<Zoned> {
+        let mut due = None;
+        let mut cursor = since.clone();
+        while let Some(next) = self.next_after(&cursor) {
+            if next.timestamp() > now {
+                break;
+            }
+            cursor = next.clone();
+            due = Some(next);
+        }
+        due
+    }
+}
+
+/// The time zone a schedule is read in.
+pub(crate) fn time_zone(name: Option<&str>) -> Result<TimeZone, ScheduleError> {
+    match name {
+        None => Ok(TimeZone::UTC),
+        Some(name) => TimeZone::get(name).map_err(|_| ScheduleError::TimeZone(name.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kubimo::k8s_openapi::jiff::civil::DateTime;
+
+    fn at(tz: &TimeZone, datetime: &str) -> Zoned {
+        datetime
+            .parse::<DateTime>()
+            .unwrap()
+            .to_zoned(tz.clone())
+            .unwrap()
+    }
+
+    fn next(cron: &str, tz: &TimeZone, after: &str) -> String {
+        let next = cron
+            .parse::<Cron>()
+            .unwrap()
+            .next_after(&at(tz, after))
+            .unwrap();
+        format!("{} {}", next.datetime(), next.offset())
+    }
+
+    #[test]
+    fn fields_take_values_ranges_lists_steps_and_names() {
+        let utc = TimeZone::UTC;
+        assert_eq!(
+            next("30 3 * * *", &utc, "2026-01-01T03:30"),
+            "2026-01-02T03:30:00 +00"
+        );
+        assert_eq!(
+            next("*/15 9-17 * * mon-fri", &utc, "2026-01-02T17:50"),
+            // Friday evening to Monday morning.
+            "2026-01-05T09:00:00 +00"
+        );
+        assert_eq!(
+            next("5/20 * * * *", &utc, "2026-01-01T00:26"),
+            "2026-01-01T00:45:00 +00"
+        );
+        assert_eq!(
+            next("0 0 1 jan,jul *", &utc, "2026-01-01T00:00"),
+            "2026-07-01T00:00:00 +00"
+        );
+        assert_eq!(
+            next("0 0 * * 7", &utc, "2026-01-01T00:00"),
+            "2026-01-04T00:00:00 +00"
+        );
+        assert_eq!(
+            next("@monthly", &utc, "2026-01-15T12:00"),
+            "2026-02-01T00:00:00 +00"
+        );
+        assert_eq!(
+            next("0 0 29 2 *", &utc, "2026-03-01T00:00"),
+            "2028-02-29T00:00:00 +00"
+        );
+        assert!(
+            "0 0 30 2 *"
+                .parse::<Cron>()
+                .unwrap()
+                .next_after(&at(&utc, "2026-01-01T00:00"))
+                .is_none()
+        );
+    }
+
+    /// Restricting both day fields means either, as in every cron since V7.
+    #[test]
+    fn day_of_month_and_day_of_week_are_or_ed_when_both_are_set() {
+        let utc = TimeZone::UTC;
+        // 2026-01-02 is a Friday, before the 13th.
+        assert_eq!(
+            next("0 0 13 * fri", &utc, "2026-01-01T00:00"),
+            "2026-01-02T00:00:00 +00"
+        );
+        assert_eq!(
+            next("0 0 13 * *", &utc, "2026-01-01T00:00"),
+            "2026-01-13T00:00:00 +00"
+        );
+        // Only the weekday restricted: every Monday, whatever the date.
+        assert_eq!(
+            next("0 0 * * mon", &utc, "2026-01-01T00:00"),
+            "2026-01-05T00:00:00 +00"
+        );
+        // Not "the first Monday": any of the first seven days, and any Monday.
+        let first_week = "0 0 1-7 * mon".parse::<Cron>().unwrap();
+        assert!(first_week.matches(&at(&utc, "2026-01-03T00:00")));
+        assert!(first_week.matches(&at(&utc, "2026-01-26T00:00")));
+        assert!(!first_week.matches(&at(&utc, "2026-01-27T00:00")));
+        // `*/1` is `*`, so the date alone decides.
+        assert_eq!(
+            next("0 0 13 * */1", &utc, "2026-01-01T00:00"),
+            "2026-01-13T00:00:00 +00"
+        );
+    }
+
+    #[test]
+    fn malformed_expressions_are_refused() {
+        for cron in [
+            "0 0 * *",
+            "0 0 * * * *",
+            "60 * * * *",
+            "* 24 * * *",
+            "0 0 0 * *",
+            "0 0 * 13 *",
+            "0 0 * * 8",
+            "*/0 * * * *",
+            "10-5 * * * *",
+            "0 0 L * *",
+            "0 0 * * mon#2",
+            "0 0 ? * mon",
+            "0 0 1 * ?",
+            "0 0 15W * *",
+            "0 0 LW * *",
+            "0 0 * * 5L",
+            "0 0 1 1 * 2026",
+        ] {
+            assert!(cron.parse::<Cron>().is_err(), "{cron}");
+        }
+        assert!(time_zone(Some("Not/AZone")).is_err());
+    }
+
+    #[test]
+    fn schedules_follow_the_zone_across_dst_changes() {
+        let paris = time_zone(Some("Europe/Paris")).unwrap();
+        assert_eq!(
+            next("0 3 * * *", &paris, "2026-03-28T12:00"),
+            "2026-03-29T03:00:00 +02"
+        );
+        // 02:30 does not exist on 29 March: the run happens as the clock jumps.
+        assert_eq!(
+            next("30 2 * * *", &paris, "2026-03-28T12:00"),
+            "2026-03-29T03:30:00 +02"
+        );
+        // 02:30 happens twice on 25 October: the run happens once.
+        let cron = "30 2 * * *".parse::<Cron>().unwrap();
+        let first = cron.next_after(&at(&paris, "2026-10-24T12:00")).unwrap();
+        assert_eq!(first.offset().seconds(), 2 * 3600);
+        let second = cron.next_after(&first).unwrap();
+        assert_eq!(second.date().to_string(), "2026-10-26");
+    }
+
+    /// Every hour across a DST change: the hour the clock skips runs once,
+    /// as it jumps, and the hour it repeats runs only the first time round.
+    #[test]
+    fn an_hourly_schedule_neither_doubles_nor_skips_across_dst_changes() {
+        let paris = time_zone(Some("Europe/Paris")).unwrap();
+        let hourly = "0 * * * *".parse::<Cron>().unwrap();
+        let times = |from: &str, count| {
+            std::iter::successors(Some(at(&paris, from)), |after| hourly.next_after(after))
+                .skip(1)
+                .take(count)
+                .map(|next| format!("{} {}", next.datetime(), next.offset()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            times("2026-03-29T00:30", 3),
+            [
+                "2026-03-29T01:00:00 +01",
+                "2026-03-29T03:00:00 +02",
+                "2026-03-29T04:00:00 +02",
+            ]
+        );
+        assert_eq!(
+            times("2026-10-25T00:30", 3),
+            [
+                "2026-10-25T01:00:00 +02",
+                "2026-10-25T02:00:00 +02",
+                "2026-10-25T03:00:00 +01",
+            ]
+        );
+        // A run missed across the repeated hour is still due once.
+        let nightly = "30 2 * * *".parse::<Cron>().unwrap();
+        let due = nightly
+            .latest_due(
+                &at(&paris, "2026-10-24T12:00"),
+                at(&paris, "2026-10-25T04:00").timestamp(),
+            )
+            .unwrap();
+        assert_eq!(
+            format!("{} {}", due.datetime(), due.offset()),
+            "2026-10-25T02:30:00 +02"
+        );
+    }
+
+    /// Active hours are wall-clock minutes: a window on the hour the clock
+    /// skips is empty that day, and one on the hour it repeats lasts both.
+    #[test]
+    fn a_window_follows_the_wall_clock_across_dst_changes() {
+        let paris = time_zone(Some("Europe/Paris")).unwrap();
+        let two_am = "* 2 * * *".parse::<Cron>().unwrap();
+        // 02:30 on 29 March reads as 03:30, outside the window.
+        assert!(!two_am.matches(&at(&paris, "2026-03-29T02:30")));
+        let first = at(&paris, "2026-10-25T02:30");
+        assert_eq!(first.offset().seconds(), 2 * 3600);
+        let end = two_am.end_within(&first, 180).unwrap();
+        assert_eq!(
+            format!("{} {}", end.datetime(), end.offset()),
+            "2026-10-25T03:00:00 +01"
+        );
+    }
+
+    #[test]
+    fn a_window_ends_at_its_first_unmatched_minute() {
+        let tz = time_zone(Some("Europe/Paris")).unwrap();
+        let hours = "* 8-18 * * mon-fri".parse::<Cron>().unwrap();
+        assert!(hours.matches(&at(&tz, "2025-06-02T18:59:30")));
+        assert!(!hours.matches(&at(&tz, "2025-06-02T19:00")));
+        assert!(!hours.matches(&at(&tz, "2025-06-07T10:00")));
+        let end = hours
+            .end_within(&at(&tz, "2025-06-02T18:45:10"), 30)
+            .unwrap();
+        assert_eq!(end.datetime().to_string(), "2025-06-02T19:00:00");
+        assert_eq!(hours.end_within(&at(&tz, "2025-06-02T18:15"), 30), None);
+        let outside = at(&tz, "2025-06-02T22:00");
+        assert_eq!(hours.end_within(&outside, 30), Some(outside));
+    }
+
+    #[test]
+    fn missed_runs_collapse_into_the_latest() {
+        let utc = TimeZone::UTC;
+        let cron = "0 * * * *".parse::<Cron>().unwrap();
+        let since = at(&utc, "2026-01-01T00:00");
+        let now = at(&utc, "2026-01-01T05:30").timestamp();
+        assert_eq!(
+            cron.latest_due(&since, now).unwrap().datetime().to_string(),
+            "2026-01-01T05:00:00"
+        );
+        assert!(
+            cron.latest_due(&at(&utc, "2026-01-01T05:00"), now)
+                .is_none()
+        );
+    }
+}
@@ -7,6 +7,7 @@
 
 use std::collections::BTreeMap;
 
+use kubimo::chrono::{DateTime, Utc};
 use kubimo::k8s_openapi::api::core::v1::{
     CSIVolumeSource, LocalObjectReference, PersistentVolumeClaimVolumeSource, PodSecurityContext,
     Volume,
@@ -16,6 +17,18 @@ use kubimo::{Workspace, WorkspaceMode, WorkspacePythonRuntime, WorkspaceRestoreS
 /// Must match the `CSIDriver` object the agent registers under.
 pub(crate) const SLOT_CSI_DRIVER: &str = "kubimo.aqora.io";
 
+/// `spec.restoreFrom` as the agent consumes it.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct SlotSeed {
+    pub bucket: String,
+    pub key_prefix: Option<String>,
+    /// From `spec.restoreFrom.secrets`, defaulted here rather than by the agent.
+    pub secrets: WorkspaceRestoreSecrets,
+    /// Restore point within the seed's history, if one was asked for.
+    pub generation: Option<String>,
+    pub at: Option<DateTime<Utc>>,
+}
+
 /// Where the agent sources a slot's contents.
 #[derive(Debug, Default, Clone)]
 pub(crate) struct SlotSources {
@@ -29,9 +42,7 @@ pub(crate) struct SlotSources {
     /// `spec.restoreFrom`. Consumed by a restore init container under
     /// `Dedicated`; under `Pooled` there is no init Job, so it travels to the
     /// agent and is applied only when `archive` turns out to have no manifest.
-    /// Carries the secrets mode from `spec.restoreFrom.secrets` alongside the
-    /// location.
-    pub seed: Option<(String, Option<String>, WorkspaceRestoreSecrets)>,
+    pub seed: Option<SlotSeed>,
     /// Secret holding the workspace's S3 credentials, from
     /// `spec.indexer.pod.envFrom` — the same one the dedicated indexer pod
     /// mounts.
@@ -59,12 +70,12 @@ impl SlotSources {
                 .map(|indexer| (indexer.bucket.clone(), indexer.key_prefix.clone())),
             seed: workspace
                 .and_then(|workspace| workspace.spec.restore_from.as_ref())
-                .map(|restore| {
-                    (
-                        restore.bucket.clone(),
-                        restore.key_prefix.clone(),
-                        restore.secrets.unwrap_or_default(),
-                    )
+                .map(|restore| SlotSeed {
+                    bucket: restore.bucket.clone(),
+                    key_prefix: restore.key_prefix.clone(),
+                    secrets: restore.secrets.unwrap_or_default(),
+                    generation: restore.generation.clone(),
+                    at: restore.at,
                 }),
             credentials_secret: workspace
                 .and_then(|workspace| workspace.spec.indexer.as_ref())
@@ -156,16 +167,23 @@ fn slot_attributes(
             attributes.insert("keyPrefix".to_string(), key_prefix);
         }
     }
-    if let Some((bucket, key_prefix, secrets)) = sources.seed {
-        attributes.insert("seedBucket".to_string(), bucket);
-        if let Some(key_prefix) = key_prefix {
+    if let Some(seed) = sources.seed {
+        attributes.insert("seedBucket".to_string(), seed.bucket);
+        if let Some(key_prefix) = seed.key_prefix {
             attributes.insert("seedKeyPrefix".to_string(), key_prefix);
         }
+        // An older agent ignores these and seeds from the current manifest.
+        if let Some(generation) = seed.generation {
+            attributes.insert("seedGeneration".to_string(), generation);
+        }
+        if let Some(at) = seed.at {
+            attributes.insert("seedAt".to_string(), at.to_rfc3339());
+        }
         // Set explicitly even for the default, so the behavior is pinned by
         // this controller rather than by the agent's default. An older agent
         // ignores the attribute, which lands on the same safe default. A mode
         // name, never a credential, so it is safe on the Pod object.
-        attributes.insert("seedSecrets".to_string(), secrets.to_string());
+        attributes.insert("seedSecrets".to_string(), seed.secrets.to_string());
     }
     attributes
 }
@@ -238,11 +256,13 @@ mod tests {
         SlotSources {
             limit_bytes: Some(2_147_483_648),
             archive: Some((Some("bucket".into()), Some("workspace/abc/".into()))),
-            seed: Some((
-                "bucket".into(),
-                Some("workspace-template/v1/".into()),
-                WorkspaceRestoreSecrets::Values,
-            )),
+            seed: Some(SlotSeed {
+                bucket: "bucket".into(),
+                key_prefix: Some("workspace-template/v1/".into()),
+                secrets: WorkspaceRestoreSecrets::Values,
+                generation: Some("20260101T120000.000Z".into()),
+                at: None,
+            }),
             credentials_secret: Some("s3-credentials".into()),
         }
     }
@@ -289,6 +309,8 @@ mod tests {
             "workspace-template/v1/"
         );
         assert_eq!(attrs.get("seedSecrets").unwrap(), "values");
+        assert_eq!(attrs.get("seedGeneration").unwrap(), "20260101T120000.000Z");
+        assert!(!attrs.contains_key("seedAt"));
     }
 
     /// The safe default travels explicitly, and only alongside a seed — a
@@ -300,7 +322,10 @@ mod tests {
             WorkspaceMode::Pooled,
             false,
             SlotSources {
-                seed: Some(("bucket".into(), None, WorkspaceRestoreSecrets::default())),
+                seed: Some(SlotSeed {
+                    bucket: "bucket".into(),
+                    ..Default::default()
+                }),
                 ..Default::default()
             },
             Default::default(),
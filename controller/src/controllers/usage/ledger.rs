@@ -0,0 +1,160 @@
+//! Turning samples of what each account holds into usage over time.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use serde::Serialize;
+
+const SECS_PER_HOUR: f64 = 3600.0;
+const BYTES_PER_GIB: f64 = (1u64 << 30) as f64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum AccountKind {
+    Budget,
+    Workspace,
+}
+
+impl AccountKind {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Self::Budget => "budget",
+            Self::Workspace => "workspace",
+        }
+    }
+}
+
+/// Who usage is attributed to. A runner's time counts towards both its
+/// workspace and every Budget matching its labels, so accounts overlap and
+/// summing them across kinds counts twice.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct Account {
+    pub(crate) kind: AccountKind,
+    pub(crate) namespace: String,
+    pub(crate) name: String,
+}
+
+impl Account {
+    pub(crate) fn budget(namespace: &str, name: &str) -> Self {
+        Self {
+            kind: AccountKind::Budget,
+            namespace: namespace.to_string(),
+            name: name.to_string(),
+        }
+    }
+
+    pub(crate) fn workspace(namespace: &str, name: &str) -> Self {
+        Self {
+            kind: AccountKind::Workspace,
+            namespace: namespace.to_string(),
+            name: name.to_string(),
+        }
+    }
+}
+
+/// What an account holds at one instant.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct Sample {
+    pub(crate) storage_bytes: u64,
+    pub(crate) cpu_millis: u64,
+    pub(crate) memory_bytes: u64,
+}
+
+impl Sample {
+    pub(crate) fn add(&mut self, other: Sample) {
+        self.storage_bytes = self.storage_bytes.saturating_add(other.storage_bytes);
+        self.cpu_millis = self.cpu_millis.saturating_add(other.cpu_millis);
+        self.memory_bytes = self.memory_bytes.saturating_add(other.memory_bytes);
+    }
+}
+
+/// What an account held over a stretch of time.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Usage {
+    pub(crate) storage_byte_hours: f64,
+    pub(crate) cpu_core_hours: f64,
+    pub(crate) memory_gib_hours: f64,
+}
+
+impl Usage {
+    fn accrue(&mut self, sample: Sample, hours: f64) {
+        self.storage_byte_hours += sample.storage_bytes as f64 * hours;
+        self.cpu_core_hours += sample.cpu_millis as f64 / 1000.0 * hours;
+        self.memory_gib_hours += sample.memory_bytes as f64 / BYTES_PER_GIB * hours;
+    }
+}
+
+/// Usage per account, accrued from successive samples.
+#[derive(Debug, Default)]
+pub(crate) struct Ledger {
+    usage: BTreeMap<Account, Usage>,
+}
+
+impl Ledger {
+    /// Charge every account in `samples` for holding what it did for
+    /// `elapsed`. The sample is the one taken at the *start* of `elapsed`:
+    /// nothing is known about what happened after it, so it is assumed to
+    /// have held until now.
+    pub(crate) fn accrue(&mut self, samples: &BTreeMap<Account, Sample>, elapsed: Duration) {
+        let hours = elapsed.as_secs_f64() / SECS_PER_HOUR;
+        for (account, sample) in samples {
+            self.usage
+                .entry(account.clone())
+                .or_default()
+                .accrue(*sample, hours);
+        }
+    }
+
+    pub(crate) fn usage(&self) -> &BTreeMap<Account, Usage> {
+        &self.usage
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.usage.is_empty()
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.usage.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_sample_accrues_for_the_time_it_held() {
+        let account = Account::workspace("ns", "ws");
+        let samples = [(
+            account.clone(),
+            Sample {
+                storage_bytes: 1 << 30,
+                cpu_millis: 500,
+                memory_bytes: 2 << 30,
+            },
+        )]
+        .into();
+        let mut ledger = Ledger::default();
+        ledger.accrue(&samples, Duration::from_secs(1800));
+        ledger.accrue(&samples, Duration::from_secs(1800));
+
+        let usage = ledger.usage()[&account];
+        assert_eq!(usage.storage_byte_hours, BYTES_PER_GIB);
+        assert_eq!(usage.cpu_core_hours, 0.5);
+        assert_eq!(usage.memory_gib_hours, 2.0);
+    }
+
+    /// An account that held nothing still appears once sampled, so a report
+    /// lists every account it covers rather than only the busy ones.
+    #[test]
+    fn an_idle_account_is_listed_with_zero_usage() {
+        let account = Account::budget("ns", "team");
+        let mut ledger = Ledger::default();
+        ledger.accrue(
+            &[(account.clone(), Sample::default())].into(),
+            Duration::from_secs(60),
+        );
+        assert_eq!(ledger.usage()[&account], Usage::default());
+    }
+}
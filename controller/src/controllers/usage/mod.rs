@@ -0,0 +1,326 @@
+//! Usage accounting: byte-hours of workspace storage and core- and GiB-hours
+//! of runner time, per Budget and per Workspace.
+//!
+//! A Budget's status only says what is held *now*, every 30 seconds, and
+//! nothing remembers it; billing needs what was held over time. This samples
+//! what each account holds on a fixed interval and charges it for the time
+//! until the next sample, then publishes the totals as Prometheus counters
+//! and, when configured, writes each period's usage to a bucket as a report.
+//!
+//! Storage is charged as the budget counts it (see
+//! [`workspace_committed_bytes`]), and runner time as the CPU and memory a
+//! runner requests, for as long as it has a scheduled pod — what it keeps off
+//! every other tenant's nodes, whether or not it uses it.
+
+mod ledger;
+mod report;
+
+pub(crate) use ledger::{Account, Usage};
+
+use std::collections::BTreeMap;
+use std::pin::pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::prelude::*;
+use kubimo::k8s_openapi::api::core::v1::PersistentVolumeClaim;
+use kubimo::k8s_openapi::jiff::Timestamp;
+use kubimo::{Budget, FilterParams, Runner, Workspace, prelude::*};
+
+use crate::context::Context;
+use crate::controllers::budget::{BudgetTree, runner_demand, workspace_committed_bytes};
+use crate::controllers::workspace::pvc_storage_request;
+
+use ledger::{Ledger, Sample};
+use report::UsageReport;
+
+/// Run until `shutdown_signal`, then write the period in progress as a last
+/// report. Returns at once when there is nowhere to publish usage to.
+pub async fn run(ctx: Arc<Context>, shutdown_signal: impl Future<Output = ()> + Send + 'static) {
+    #[cfg(feature = "metrics")]
+    let metrics = ctx.config.metrics.enabled;
+    #[cfg(not(feature = "metrics"))]
+    let metrics = false;
+    let report_config = ctx.config.usage_report.clone();
+    if !metrics && report_config.is_none() {
+        return;
+    }
+
+    let interval = Duration::from_secs(ctx.config.usage_sample_interval_secs.max(1));
+    let report_interval = report_config
+        .as_ref()
+        .map(|config| Duration::from_secs(config.interval_secs.max(1)));
+    let mut shutdown = pin!(shutdown_signal);
+    let mut lifetime = Ledger::default();
+    let mut period = Ledger::default();
+    let mut period_start = Timestamp::now();
+    let mut last: Option<(Timestamp, BTreeMap<Account, Sample>)> = None;
+    let mut stopping = false;
+    loop {
+        let now = Timestamp::now();
+        let previous = last.take();
+        if let Some((at, samples)) = &previous {
+            let elapsed = Duration::try_from(now.duration_since(*at)).unwrap_or_default();
+            period.accrue(samples, elapsed);
+            if metrics {
+                lifetime.accrue(samples, elapsed);
+                #[cfg(feature = "metrics")]
+                for (account, usage) in lifetime.usage() {
+                    crate::metrics::record_usage(account, usage);
+                }
+            }
+        }
+
+        if let Some((config, report_interval)) = report_config.as_ref().zip(report_interval) {
+            let due = Duration::try_from(now.duration_since(period_start))
+                .is_ok_and(|elapsed| elapsed >= report_interval);
+            if (due || stopping) && !period.is_empty() {
+                let report = UsageReport {
+                    from: period_start,
+                    to: now,
+                    usage: period.usage(),
+                };
+                // A report that cannot be written keeps its period open: the
+                // next attempt covers it too, so nothing goes unreported.
+                match report.upload(config).await {
+                    Ok(key) => {
+                        tracing::info!(bucket = config.bucket, key, "wrote usage report");
+                        period.clear();
+                        period_start = now;
+                    }
+                    Err(err) => {
+                        tracing::warn!(%err, bucket = config.bucket, "could not write usage report")
+                    }
+                }
+            } else if period.is_empty() {
+                period_start = now;
+            }
+        }
+        if stopping {
+            return;
+        }
+
+        // A failed sample leaves the previous one standing: assuming nothing
+        // changed undercharges less than charging nothing for the interval.
+        last = match sample(&ctx).await {
+            Ok(samples) => Some((now, samples)),
+            Err(err) => {
+                tracing::warn!(%err, "could not sample usage");
+                previous.map(|(_, samples)| (now, samples))
+            }
+        };
+
+        tokio::select! {
+            _ = tokio::time::sleep(interval) => {}
+            _ = &mut shutdown => stopping = true,
+        }
+    }
+}
+
+/// What every account holds right now.
+async fn sample(ctx: &Context) -> Result<BTreeMap<Account, Sample>, kubimo::Error> {
+    let params = FilterParams::new();
+    let workspaces: Vec<Workspace> = ctx
+        .api_global::<Workspace>()
+        .list(&params)
+        .map_ok(|item| item.item)
+        .try_collect()
+        .await?;
+    let runners: Vec<Runner> = ctx
+        .api_global::<Runner>()
+        .list(&params)
+        .map_ok(|item| item.item)
+        .try_collect()
+        .await?;
+    let budgets: Vec<Budget> = ctx
+        .api_global::<Budget>()
+        .list(&params)
+        .map_ok(|item| item.item)
+        .try_collect()
+        .await?;
+    // PVCs are not labelled with the user label, but share the Workspace name.
+    let pvc_requests: BTreeMap<(String, String), u64> = ctx
+        .api_global::<PersistentVolumeClaim>()
+        .list(&params)
+        .map_ok(|item| item.item)
+        .try_filter_map(|pvc| async move {
+            let bytes = pvc_storage_request(&pvc).and_then(|q| q.to_bytes());
+            Ok(pvc.metadata.namespace.zip(pvc.metadata.name).zip(bytes))
+        })
+        .try_collect()
+        .await?;
+
+    let storage: Vec<(&Workspace, u64)> = workspaces
+        .iter()
+        .filter_map(|workspace| {
+            let key = (
+                workspace.require_namespace().ok()?.to_string(),
+                workspace.name().ok()?.to_string(),
+            );
+            let mode = workspace.effective_mode(ctx.config.default_workspace_mode);
+            let bytes = workspace_committed_bytes(workspace, mode, pvc_requests.get(&key).copied());
+            Some((workspace, bytes))
+        })
+        .collect();
+    Ok(attribute(&storage, &runners, &budgets))
+}
+
+/// Charge each workspace's storage and each running runner's requests to
+/// the workspace they belong to and to every Budget governing them, nested
+/// ones' ancestors included.
+/// Every Budget is listed, whether or not anything matched it.
+fn attribute(
+    workspaces: &[(&Workspace, u64)],
+    runners: &[Runner],
+    budgets: &[Budget],
+) -> BTreeMap<Account, Sample> {
+    let tree = BudgetTree::new(budgets.to_vec());
+    let mut samples: BTreeMap<Account, Sample> = BTreeMap::new();
+    for budget in budgets {
+        if let (Ok(namespace), Ok(name)) = (budget.require_namespace(), budget.name()) {
+            samples.entry(Account::budget(namespace, name)).or_default();
+        }
+    }
+    let mut charge = |namespace: &str, workspace: &str, labels, sample: Sample| {
+        samples
+            .entry(Account::workspace(namespace, workspace))
+            .or_default()
+            .add(sample);
+        for budget in tree.governing(namespace, labels) {
+            if let Ok(name) = budget.name() {
+                samples
+                    .entry(Account::budget(namespace, name))
+                    .or_default()
+                    .add(sample);
+            }
+        }
+    };
+    for (workspace, bytes) in workspaces {
+        let (Ok(namespace), Ok(name)) = (workspace.require_namespace(), workspace.name()) else {
+            continue;
+        };
+        let sample = Sample {
+            storage_bytes: *bytes,
+            ..Default::default()
+        };
+        charge(namespace, name, workspace.metadata.labels.as_ref(), sample);
+    }
+    for runner in runners.iter().filter(|runner| holds_a_pod(runner)) {
+        let Ok(namespace) = runner.require_namespace() else {
+            continue;
+        };
+        let demand = runner_demand(runner);
+        let sample = Sample {
+            cpu_millis: demand.cpu_millis,
+            memory_bytes: demand.memory_bytes,
+            ..Default::default()
+        };
+        charge(
+            namespace,
+            &runner.spec.workspace,
+            runner.metadata.labels.as_ref(),
+            sample,
+        );
+    }
+    samples
+}
+
+/// Whether `runner` has a pod scheduled onto a node, and so holds its
+/// requests there. A claimed warm pod counts the runner's own requests,
+/// which a claim only succeeds with when they match the pool's.
+fn holds_a_pod(runner: &Runner) -> bool {
+    !runner.spec.is_suspended()
+        && runner
+            .status
+            .as_ref()
+            .and_then(|status| status.conditions.as_ref())
+            .is_some_and(|conditions| {
+                conditions.iter().any(|cond| {
+                    cond.type_ == kubimo::conditions::POD_SCHEDULED && cond.status == "True"
+                })
+            })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kubimo::k8s_openapi::apimachinery::pkg::apis::meta::v1::{Condition, Time};
+    use kubimo::{BudgetSpec, Requirement, RunnerSpec, RunnerStatus, WorkspaceSpec};
+
+    fn labelled<K: Resource>(mut object: K, user: &str) -> K {
+        object.meta_mut().namespace = Some("ns".to_string());
+        object.meta_mut().labels = Some([("user".to_string(), user.to_string())].into());
+        object
+    }
+
+    fn runner(name: &str, user: &str, scheduled: bool) -> Runner {
+        let mut runner = labelled(
+            Runner::new(
+                name,
+                RunnerSpec {
+                    workspace: "ws".to_string(),
+                    cpu: Some(Requirement {
+                        min: Some("250m".parse().unwrap()),
+                        max: None,
+                    }),
+                    ..Default::default()
+                },
+            ),
+            user,
+        );
+        runner.status = Some(RunnerStatus {
+            conditions: Some(vec![Condition {
+                type_: kubimo::conditions::POD_SCHEDULED.to_string(),
+                status: if scheduled { "True" } else { "False" }.to_string(),
+                reason: String::new(),
+                message: String::new(),
+                observed_generation: None,
+                last_transition_time: Time(Timestamp::UNIX_EPOCH),
+            }]),
+            ..Default::default()
+        });
+        runner
+    }
+
+    #[test]
+    fn usage_is_charged_to_the_workspace_and_every_matching_budget() {
+        let workspace = labelled(Workspace::new("ws", WorkspaceSpec::default()), "alice");
+        let budgets = [
+            labelled(
+                Budget::new(
+                    "alice",
+                    BudgetSpec {
+                        selector: [("user".to_string(), "alice".to_string())].into(),
+                        ..Default::default()
+                    },
+                ),
+                "alice",
+            ),
+            labelled(
+                Budget::new(
+                    "bob",
+                    BudgetSpec {
+                        selector: [("user".to_string(), "bob".to_string())].into(),
+                        ..Default::default()
+                    },
+                ),
+                "bob",
+            ),
+        ];
+        let runners = [
+            runner("running", "alice", true),
+            runner("pending", "alice", false),
+        ];
+        let samples = attribute(&[(&workspace, 1024)], &runners, &budgets);
+
+        let expected = Sample {
+            storage_bytes: 1024,
+            cpu_millis: 250,
+            memory_bytes: 0,
+        };
+        assert_eq!(samples[&Account::workspace("ns", "ws")], expected);
+        assert_eq!(samples[&Account::budget("ns", "alice")], expected);
+        // Listed, with nothing charged to it.
+        assert_eq!(samples[&Account::budget("ns", "bob")], Sample::default());
+    }
+}
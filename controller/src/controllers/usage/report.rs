@@ -0,0 +1,215 @@
+//! The usage report billing ingests: one object per period, listing what
+//! each account used over it.
+//!
+//! Reports hold the usage *of their period*, not running totals, so that
+//! billing can sum them and a report that was never written (a controller
+//! killed outright) undercounts that period alone instead of every total
+//! after it.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use indexer::s3::{S3Client, UploadError};
+use kubimo::k8s_openapi::jiff::Timestamp;
+use serde::Serialize;
+use tokio::sync::Semaphore;
+
+use crate::config::{UsageReportConfig, UsageReportFormat};
+
+use super::ledger::{Account, AccountKind, Usage};
+
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum ReportError {
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error(transparent)]
+    Url(#[from] kubimo::url::ParseError),
+    #[error(transparent)]
+    Upload(#[from] UploadError),
+}
+
+pub(crate) struct UsageReport<'a> {
+    pub(crate) from: Timestamp,
+    pub(crate) to: Timestamp,
+    pub(crate) usage: &'a BTreeMap<Account, Usage>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Document<'a> {
+    from: String,
+    to: String,
+    entries: Vec<Entry<'a>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Entry<'a> {
+    kind: AccountKind,
+    namespace: &'a str,
+    name: &'a str,
+    #[serde(flatten)]
+    usage: Usage,
+}
+
+impl UsageReport<'_> {
+    /// `{key_prefix}{from}_{to}.{json,csv}`, in UTC with second precision:
+    /// lexical order is time order, and the name alone says what it covers.
+    pub(crate) fn key(&self, config: &UsageReportConfig) -> String {
+        let stamp = |at: Timestamp| at.strftime("%Y%m%dT%H%M%SZ").to_string();
+        let extension = match config.format {
+            UsageReportFormat::Json => "json",
+            UsageReportFormat::Csv => "csv",
+        };
+        format!(
+            "{prefix}{from}_{to}.{extension}",
+            prefix = config.key_prefix.as_deref().unwrap_or_default(),
+            from = stamp(self.from),
+            to = stamp(self.to),
+        )
+    }
+
+    pub(crate) fn render(&self, format: UsageReportFormat) -> Result<Vec<u8>, ReportError> {
+        match format {
+            UsageReportFormat::Json => Ok(serde_json::to_vec(&Document {
+                from: self.from.to_string(),
+                to: self.to.to_string(),
+                entries: self
+                    .usage
+                    .iter()
+                    .map(|(account, usage)| Entry {
+                        kind: account.kind,
+                        namespace: &account.namespace,
+                        name: &account.name,
+                        usage: *usage,
+                    })
+                    .collect(),
+            })?),
+            UsageReportFormat::Csv => Ok(self.to_csv().into_bytes()),
+        }
+    }
+
+    // Nothing here needs quoting: namespaces and object names are DNS labels
+    // or subdomains, and the rest are numbers and timestamps.
+    fn to_csv(&self) -> String {
+        let mut csv = String::from(
+            "from,to,kind,namespace,name,storage_byte_hours,cpu_core_hours,memory_gib_hours\n",
+        );
+        for (account, usage) in self.usage {
+            let _ = writeln!(
+                csv,
+                "{},{},{},{},{},{},{},{}",
+                self.from,
+                self.to,
+                account.kind.as_str(),
+                account.namespace,
+                account.name,
+                usage.storage_byte_hours,
+                usage.cpu_core_hours,
+                usage.memory_gib_hours,
+            );
+        }
+        csv
+    }
+
+    /// Write the report under `config.bucket`, with the same store client
+    /// the indexer uses: a bare bucket name is S3, and `gs://`, `az://` and
+    /// `file://` urls work too, each with its backend's own environment.
+    pub(crate) async fn upload(&self, config: &UsageReportConfig) -> Result<String, ReportError> {
+        let key = self.key(config);
+        let url = kubimo::bucket_url(&config.bucket)?.join(&key)?;
+        let body = self.render(config.format)?;
+        let size = body.len() as u64;
+        S3Client::from_env()
+            .upload(&url, std::io::Cursor::new(body), size, &Semaphore::new(1))
+            .await?;
+        Ok(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(format: UsageReportFormat) -> UsageReportConfig {
+        UsageReportConfig {
+            bucket: "billing".to_string(),
+            key_prefix: Some("kubimo/usage/".to_string()),
+            format,
+            interval_secs: 3600,
+        }
+    }
+
+    fn usage() -> BTreeMap<Account, Usage> {
+        [
+            (
+                Account::budget("ns", "team"),
+                Usage {
+                    storage_byte_hours: 1024.0,
+                    cpu_core_hours: 0.5,
+                    memory_gib_hours: 2.0,
+                },
+            ),
+            (Account::workspace("ns", "ws"), Usage::default()),
+        ]
+        .into()
+    }
+
+    fn report(usage: &BTreeMap<Account, Usage>) -> UsageReport<'_> {
+        UsageReport {
+            from: "2026-01-01T00:00:00Z".parse().unwrap(),
+            to: "2026-01-01T01:00:00Z".parse().unwrap(),
+            usage,
+        }
+    }
+
+    #[test]
+    fn the_key_names_the_period_it_covers() {
+        let usage = usage();
+        assert_eq!(
+            report(&usage).key(&config(UsageReportFormat::Csv)),
+            "kubimo/usage/20260101T000000Z_20260101T010000Z.csv"
+        );
+    }
+
+    #[test]
+    fn json_lists_one_entry_per_account() {
+        let usage = usage();
+        let json: serde_json::Value =
+            serde_json::from_slice(&report(&usage).render(UsageReportFormat::Json).unwrap())
+                .unwrap();
+        assert_eq!(json["from"], "2026-01-01T00:00:00Z");
+        assert_eq!(json["entries"][0]["kind"], "budget");
+        assert_eq!(json["entries"][0]["cpuCoreHours"], 0.5);
+        assert_eq!(json["entries"][1]["kind"], "workspace");
+        assert_eq!(json["entries"][1]["storageByteHours"], 0.0);
+    }
+
+    #[test]
+    fn csv_has_a_header_and_one_row_per_account() {
+        let usage = usage();
+        let csv =
+            String::from_utf8(report(&usage).render(UsageReportFormat::Csv).unwrap()).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("from,to,kind"));
+        assert_eq!(
+            lines[1],
+            "2026-01-01T00:00:00Z,2026-01-01T01:00:00Z,budget,ns,team,1024,0.5,2"
+        );
+    }
+
+    /// Not only S3: a `file://` bucket goes through the indexer's client too.
+    #[tokio::test]
+    async fn reports_upload_to_any_archive_backend() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = UsageReportConfig {
+            bucket: format!("file://{}", dir.path().display()),
+            ..config(UsageReportFormat::Csv)
+        };
+        let usage = usage();
+        let key = report(&usage).upload(&config).await.unwrap();
+        let written = std::fs::read_to_string(dir.path().join(&key)).unwrap();
+        assert!(written.starts_with("from,to,kind"));
+    }
+}
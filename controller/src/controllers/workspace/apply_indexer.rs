@@ -61,7 +61,7 @@ impl WorkspaceReconciler {
                     image: Some(ctx.config.marimo_image.clone()),
                     command: Some(cmd!["/app/indexer"]),
                     args: Some(indexer::upload_args(workspace, true)?),
-                    env: indexer::env(workspace),
+                    env: indexer::upload_env(workspace),
                     env_from: indexer::env_from(workspace),
                     volume_mounts: Some(vec![VolumeMount {
                         mount_path: indexer::MOUNT_DIR.to_string(),
@@ -49,7 +49,14 @@ cp -a /home/me/. /mnt
             image: Some(marimo_image.to_string()),
             command: Some(cmd!["/app/indexer"]),
             args: Some(indexer::download_args(restore)),
-            env: indexer::download_env(restore),
+            env: indexer::download_env(
+                restore,
+                workspace
+                    .spec
+                    .indexer
+                    .as_ref()
+                    .and_then(|indexer| indexer.limits.as_ref()),
+            ),
             env_from: indexer::pod_env_from(restore.pod.as_ref()),
             volume_mounts: Some(vec![VolumeMount {
                 mount_path: indexer::INIT_MOUNT_DIR.into(),
@@ -103,7 +103,27 @@ impl WorkspaceReconciler {
         let allowance =
             crate::controllers::budget::workspace_storage_allowance(ctx, workspace).await?;
 
-        let plan = apply_budget(desired, current_request.as_ref(), pvc.is_some(), allowance);
+        let mut plan = apply_budget(
+            desired,
+            current_request.as_ref(),
+            pvc.is_some(),
+            allowance.as_ref().map(|allowance| allowance.bytes),
+        );
+        // Named so a refusal under nested budgets says which level is full.
+        if let (Some(reason), Some(allowance)) = (plan.refuse.as_mut(), allowance) {
+            reason.push_str(&format!(" (Budget {})", allowance.budget));
+        }
+        plan.grown_from = current_request.filter(|current| {
+            let requested = plan
+                .request
+                .as_ref()
+                .and_then(|request| request.min.as_ref())
+                .and_then(StorageQuantity::to_bytes);
+            matches!(
+                (requested, current.to_bytes()),
+                (Some(requested), Some(current)) if requested > current
+            )
+        });
         Ok((plan, current_limit))
     }
 }
@@ -138,6 +158,9 @@ pub(crate) struct StoragePlan {
     pub(crate) request: Option<Requirement<StorageQuantity>>,
     /// When set, provisioning is refused with this reason (minimum can't fit budget).
     pub(crate) refuse: Option<String>,
+    /// The existing PVC's request, when `request` is larger than it — the
+    /// apply is a resize, which the reconciler reports as an event.
+    pub(crate) grown_from: Option<StorageQuantity>,
 }
 
 /// Clamp the desired storage request to the budget `allowance` (max total bytes
@@ -153,12 +176,14 @@ fn apply_budget(
         return StoragePlan {
             request: None,
             refuse: None,
+            grown_from: None,
         };
     };
     let Some(cap) = allowance else {
         return StoragePlan {
             request: Some(desired),
             refuse: None,
+            grown_from: None,
         };
     };
     let Some(desired_bytes) = desired.min.as_ref().and_then(StorageQuantity::to_bytes) else {
@@ -166,6 +191,7 @@ fn apply_budget(
         return StoragePlan {
             request: Some(desired),
             refuse: None,
+            grown_from: None,
         };
     };
 
@@ -175,6 +201,7 @@ fn apply_budget(
             refuse: Some(format!(
                 "requested storage {desired_bytes} bytes exceeds remaining budget {cap} bytes"
             )),
+            grown_from: None,
         };
     }
 
@@ -188,6 +215,7 @@ fn apply_budget(
             max: desired.max,
         }),
         refuse: None,
+        grown_from: None,
     }
 }
 
@@ -5,6 +5,7 @@ use kubimo::k8s_openapi::jiff::Timestamp;
 use kubimo::{Workspace, WorkspaceMode, WorkspaceStatus, prelude::*};
 
 use crate::context::Context;
+use crate::controllers::budget::{BudgetTree, read_only_condition, upsert_condition};
 use crate::controllers::workspace_python_runtime::fetch_workspace_python_runtime;
 
 use super::WorkspaceReconciler;
@@ -52,6 +53,12 @@ impl WorkspaceReconciler {
             // starting its first runner.
             let mut status = build_workspace_status(workspace, None, StatusKind::PooledReady, mode);
             status.python_runtime = Some(fetch_workspace_python_runtime(ctx, workspace).await?);
+            // Budgets freeze and thaw pooled workspaces themselves; this pass
+            // is what thaws one whose Budget was deleted while holding it.
+            let budgets = BudgetTree::load(ctx, namespace).await?;
+            if let Some(read_only) = read_only_condition(workspace, &budgets) {
+                upsert_condition(status.conditions.get_or_insert_default(), read_only);
+            }
             let mut workspace = workspace.clone();
             workspace.status = Some(status);
             ctx.api_namespaced::<Workspace>(namespace)
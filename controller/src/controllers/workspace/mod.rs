@@ -18,7 +18,7 @@ use kubimo::k8s_openapi::api::core::v1::{PersistentVolumeClaim, Pod, ServiceAcco
 use kubimo::k8s_openapi::api::rbac::v1::{Role, RoleBinding};
 use kubimo::kube::runtime::{Controller, controller::Action, reflector::ObjectRef, watcher};
 use kubimo::prelude::*;
-use kubimo::{Runner, Workspace, WorkspaceMode};
+use kubimo::{EventType, Runner, Workspace, WorkspaceMode};
 
 use crate::backoff::default_error_policy;
 use crate::context::Context;
@@ -52,8 +52,22 @@ impl Reconciler for WorkspaceReconciler {
         let (plan, current_limit) = self.plan_storage(ctx, workspace).await?;
         if let Some(reason) = plan.refuse {
             self.apply_budget_status(ctx, workspace, &reason).await?;
+            ctx.events
+                .publish(
+                    workspace,
+                    EventType::Warning,
+                    BUDGET_EXCEEDED_REASON,
+                    "Provision",
+                    reason,
+                )
+                .await;
             return Ok(Action::requeue(BUDGET_REQUEUE_INTERVAL));
         }
+        let resize = plan.grown_from.clone().zip(
+            plan.request
+                .as_ref()
+                .and_then(|request| request.min.clone()),
+        );
         let applied = futures::future::try_join_all([
             self.apply_pvc(ctx, workspace, plan.request, current_limit)
                 .map_ok(|_| false)
@@ -84,6 +98,17 @@ impl Reconciler for WorkspaceReconciler {
             }
             Err(err) => return Err(err),
         };
+        if let Some((from, to)) = resize {
+            ctx.events
+                .publish(
+                    workspace,
+                    EventType::Normal,
+                    "Resized",
+                    "Resize",
+                    format!("storage request grown from {from} to {to}"),
+                )
+                .await;
+        }
         if replaced {
             // apply_indexer deleted a pod whose immutable spec had drifted. Nothing has
             // recreated it, and its deletion is not a change this controller can wait on,
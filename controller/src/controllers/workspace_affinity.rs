@@ -1,7 +1,10 @@
 use std::collections::BTreeMap;
 
 use kubimo::KubimoLabel;
-use kubimo::k8s_openapi::api::core::v1::{Affinity, PodAffinity, PodAffinityTerm};
+use kubimo::k8s_openapi::api::core::v1::{
+    Affinity, NodeAffinity, NodeSelectorRequirement, NodeSelectorTerm, PodAffinity,
+    PodAffinityTerm, PreferredSchedulingTerm,
+};
 use kubimo::k8s_openapi::apimachinery::pkg::apis::meta::v1::LabelSelector;
 
 pub(crate) fn workspace_label(workspace_name: &str) -> (String, String) {
@@ -35,3 +38,66 @@ pub fn workspace_affinity(workspace_name: &str) -> Affinity {
         ..Default::default()
     }
 }
+
+/// [`workspace_affinity`], plus a preference for `slot_node`, the node whose
+/// data volume holds the workspace's slot.
+///
+/// Only a preference: the pod affinity still wins whenever another pod of the
+/// workspace runs elsewhere, and a node that is gone or full must not keep the
+/// pod Pending when hydrating a fresh slot somewhere else would do. Matched on
+/// the node's name, which is what the agent records, rather than on the
+/// hostname label, which need not equal it.
+pub fn workspace_affinity_preferring(workspace_name: &str, slot_node: Option<&str>) -> Affinity {
+    let mut affinity = workspace_affinity(workspace_name);
+    if let Some(node) = slot_node {
+        affinity.node_affinity = Some(NodeAffinity {
+            preferred_during_scheduling_ignored_during_execution: Some(vec![
+                PreferredSchedulingTerm {
+                    weight: 100,
+                    preference: NodeSelectorTerm {
+                        match_fields: Some(vec![NodeSelectorRequirement {
+                            key: "metadata.name".to_string(),
+                            operator: "In".to_string(),
+                            values: Some(vec![node.to_string()]),
+                        }]),
+                        ..Default::default()
+                    },
+                },
+            ]),
+            ..Default::default()
+        });
+    }
+    affinity
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_slot_node_is_preferred_on_top_of_the_workspace_affinity() {
+        assert_eq!(
+            workspace_affinity_preferring("bmow-x", None),
+            workspace_affinity("bmow-x")
+        );
+        let affinity = workspace_affinity_preferring("bmow-x", Some("node-a"));
+        assert_eq!(
+            affinity.pod_affinity,
+            workspace_affinity("bmow-x").pod_affinity
+        );
+        let node = affinity.node_affinity.unwrap();
+        assert!(
+            node.required_during_scheduling_ignored_during_execution
+                .is_none()
+        );
+        let preferred = node
+            .preferred_during_scheduling_ignored_during_execution
+            .unwrap();
+        let fields = preferred[0].preference.match_fields.as_ref().unwrap();
+        assert_eq!(fields[0].key, "metadata.name");
+        assert_eq!(
+            fields[0].values.as_deref(),
+            Some(&["node-a".to_string()][..])
+        );
+    }
+}
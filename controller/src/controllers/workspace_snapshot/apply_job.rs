@@ -0,0 +1,123 @@
+use kubimo::k8s_openapi::api::batch::v1::{Job, JobSpec};
+use kubimo::k8s_openapi::api::core::v1::{Container, PodSpec, PodTemplateSpec};
+use kubimo::kube::api::ObjectMeta;
+use kubimo::{Workspace, WorkspaceSnapshot, prelude::*};
+
+use crate::command::cmd;
+use crate::context::Context;
+use crate::controllers::indexer;
+
+use super::WorkspaceSnapshotReconciler;
+
+/// Where the container leaves its summary; kubelet's default
+/// `terminationMessagePath`, so the message needs no further wiring.
+const TERMINATION_LOG: &str = "/dev/termination-log";
+
+#[inline]
+pub(super) fn job_name(snapshot: &WorkspaceSnapshot) -> Result<String, kubimo::Error> {
+    Ok(format!("{}-snapshot", snapshot.name()?))
+}
+
+pub(super) fn dest_key_prefix(
+    snapshot: &WorkspaceSnapshot,
+    key_prefix: Option<&str>,
+) -> Result<String, kubimo::Error> {
+    let uid = snapshot
+        .metadata
+        .uid
+        .as_deref()
+        .ok_or_else(|| kubimo::Error::Custom("WorkspaceSnapshot has no uid".into()))?;
+    Ok(kubimo::snapshot_key_prefix(key_prefix, uid))
+}
+
+fn snapshot_args(bucket: &str, key_prefix: Option<&str>, dest_key_prefix: &str) -> Vec<String> {
+    let mut args = cmd!["snapshot", "--bucket", bucket];
+    if let Some(key_prefix) = key_prefix {
+        args.extend(cmd!["--key-prefix", key_prefix]);
+    }
+    args.extend(cmd!["--summary-path", TERMINATION_LOG]);
+    args.push(dest_key_prefix.to_string());
+    args
+}
+
+impl WorkspaceSnapshotReconciler {
+    pub(super) async fn apply_job(
+        &self,
+        ctx: &Context,
+        snapshot: &WorkspaceSnapshot,
+        workspace: &Workspace,
+        bucket: &str,
+        key_prefix: Option<&str>,
+        dest_key_prefix: &str,
+    ) -> Result<Job, kubimo::Error> {
+        let namespace = snapshot.require_namespace()?;
+        // No volume and no ServiceAccount: the copy is S3 to S3, so it needs the
+        // workspace's credentials and nothing else — in particular not its slot,
+        // which a pooled workspace may have on some other node or not at all.
+        let job = Job {
+            metadata: ObjectMeta {
+                name: Some(job_name(snapshot)?),
+                namespace: Some(namespace.to_string()),
+                owner_references: Some(vec![snapshot.static_controller_owner_ref()?]),
+                ..Default::default()
+            },
+            spec: Some(JobSpec {
+                template: PodTemplateSpec {
+                    spec: Some(PodSpec {
+                        restart_policy: Some("Never".into()),
+                        containers: vec![Container {
+                            name: "indexer".to_string(),
+                            image: Some(ctx.config.marimo_image.clone()),
+                            command: Some(cmd!["/app/indexer"]),
+                            args: Some(snapshot_args(bucket, key_prefix, dest_key_prefix)),
+                            env: indexer::env(workspace),
+                            env_from: indexer::env_from(workspace),
+                            ..Default::default()
+                        }],
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        ctx.api_namespaced::<Job>(namespace).patch(&job).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The bucket and prefix travel as flags, unlike the purge's: there is no
+    /// older indexer to stay compatible with, since one without the `snapshot`
+    /// subcommand fails the Job regardless.
+    #[test]
+    fn snapshot_args_name_source_summary_and_destination() {
+        assert_eq!(
+            snapshot_args("bucket", Some("ws/"), "ws/snapshots/uid/"),
+            vec![
+                "snapshot",
+                "--bucket",
+                "bucket",
+                "--key-prefix",
+                "ws/",
+                "--summary-path",
+                "/dev/termination-log",
+                "ws/snapshots/uid/",
+            ]
+        );
+        assert_eq!(
+            snapshot_args("bucket", None, "snapshots/uid/"),
+            vec![
+                "snapshot",
+                "--bucket",
+                "bucket",
+                "--summary-path",
+                "/dev/termination-log",
+                "snapshots/uid/",
+            ]
+        );
+    }
+}
@@ -0,0 +1,459 @@
+//! Copies a workspace's archive into a [`WorkspaceSnapshot`]'s own prefix.
+//!
+//! The copy itself is an indexer Job (`indexer snapshot`); this loop decides
+//! when it may start and carries its result into the snapshot's status. It may
+//! start once the archive reflects the workspace as of the snapshot's creation:
+//! either a sync finished after that moment, or no pod holds the workspace's
+//! slot at all, in which case the last flush on unpublish is the final word.
+//! Nothing is asked of the agent directly — its watcher re-syncs a live slot at
+//! least every poll interval, so waiting for the next sync *is* the flush.
+//!
+//! The copy lives under the source workspace's key prefix. The indexer's own
+//! purge of a deleted workspace leaves it alone, but a purge of the whole prefix
+//! by the platform does not; and deleting the snapshot leaves the copy behind,
+//! for the same reason pooled workspace cleanup leaves its archive — the
+//! credentials that could delete it belong to a workspace that may be gone.
+
+mod apply_job;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::prelude::*;
+use kubimo::k8s_openapi::api::batch::v1::Job;
+use kubimo::k8s_openapi::api::core::v1::Pod;
+use kubimo::k8s_openapi::apimachinery::pkg::apis::meta::v1::{Condition, Time};
+use kubimo::k8s_openapi::jiff::Timestamp;
+use kubimo::kube::runtime::{Controller, controller::Action};
+use kubimo::{
+    FilterParams, SnapshotSummary, Workspace, WorkspaceSnapshot, WorkspaceSnapshotStatus,
+    prelude::*,
+};
+
+use crate::backoff::default_error_policy;
+use crate::context::Context;
+use crate::controllers::runner::may_hold_a_slot;
+use crate::controllers::workspace_affinity;
+use crate::error::ControllerResult;
+use crate::reconciler::{ReconcileError, Reconciler, ReconcilerExt};
+
+/// How often to re-check a snapshot waiting on its workspace's next sync.
+/// Workspace status changes do not trigger a snapshot, so this polls; a fraction
+/// of the watcher's poll interval, so the wait is bounded by the sync itself.
+const FLUSH_POLL: Duration = Duration::from_secs(10);
+
+const READY: &str = "Ready";
+
+#[derive(Debug, Clone, Copy)]
+struct WorkspaceSnapshotReconciler;
+
+/// Reasons on the `Ready` condition, one per stage a snapshot passes through.
+const REASON_WORKSPACE_NOT_FOUND: &str = "WorkspaceNotFound";
+const REASON_NO_CONTENT: &str = "NoContent";
+const REASON_FLUSHING: &str = "Flushing";
+const REASON_COPYING: &str = "Copying";
+const REASON_FAILED: &str = "Failed";
+const REASON_READY: &str = "Ready";
+
+#[async_trait::async_trait]
+impl Reconciler for WorkspaceSnapshotReconciler {
+    type Resource = WorkspaceSnapshot;
+    type Error = kubimo::Error;
+
+    async fn apply(
+        &self,
+        ctx: &Context,
+        snapshot: &WorkspaceSnapshot,
+    ) -> Result<Action, Self::Error> {
+        // A ready snapshot is final: its copy is never rewritten.
+        if snapshot.restore_from().is_some() {
+            return Ok(Action::await_change());
+        }
+        let namespace = snapshot.require_namespace()?;
+
+        // Once the Job exists the workspace no longer matters: the copy reads
+        // only S3, so a workspace deleted mid-copy does not abort it.
+        if let Some(job) = ctx
+            .api_namespaced::<Job>(namespace)
+            .get_opt(&apply_job::job_name(snapshot)?)
+            .await?
+        {
+            return self.observe_job(ctx, snapshot, &job).await;
+        }
+
+        let Some(workspace) = ctx
+            .api_namespaced::<Workspace>(namespace)
+            .get_opt(&snapshot.spec.workspace)
+            .await?
+        else {
+            // Not terminal: the name may yet be created, and nothing else would
+            // wake this snapshot when it is.
+            let condition = ready_condition(
+                snapshot,
+                false,
+                REASON_WORKSPACE_NOT_FOUND,
+                "Workspace does not exist",
+            );
+            self.patch_status(ctx, snapshot, condition, |_| {}).await?;
+            return Ok(Action::requeue(FLUSH_POLL * 6));
+        };
+        let Some((bucket, key_prefix)) = archive_location(&workspace) else {
+            let condition = ready_condition(
+                snapshot,
+                false,
+                REASON_NO_CONTENT,
+                "Workspace does not archive its content (indexer.bucket and indexer.uploadContent)",
+            );
+            self.patch_status(ctx, snapshot, condition, |_| {}).await?;
+            return Ok(Action::await_change());
+        };
+
+        let workspace_pods = ctx
+            .api_namespaced::<Pod>(namespace)
+            .list(
+                &FilterParams::new().with_labels(workspace_affinity::workspace_label(
+                    &snapshot.spec.workspace,
+                )),
+            )
+            .map_ok(|item| item.item)
+            .try_collect::<Vec<_>>()
+            .await?;
+        let idle = !workspace_pods.iter().any(may_hold_a_slot);
+        if !is_flushed(snapshot, &workspace, idle) {
+            let condition = ready_condition(
+                snapshot,
+                false,
+                REASON_FLUSHING,
+                "Waiting for the workspace's next sync",
+            );
+            self.patch_status(ctx, snapshot, condition, |_| {}).await?;
+            return Ok(Action::requeue(FLUSH_POLL));
+        }
+
+        let dest_key_prefix = apply_job::dest_key_prefix(snapshot, key_prefix.as_deref())?;
+        // Recorded before the Job exists: the Job may outlive the workspace, so
+        // this is the last point the location is known for sure, and a Job
+        // that completed without it would leave a ready snapshot nobody can
+        // restore from.
+        let condition = ready_condition(snapshot, false, REASON_COPYING, "Copying the archive");
+        self.patch_status(ctx, snapshot, condition, |status| {
+            status.bucket = Some(bucket.to_string());
+            status.key_prefix = Some(dest_key_prefix.clone());
+        })
+        .await?;
+        self.apply_job(
+            ctx,
+            snapshot,
+            &workspace,
+            bucket,
+            key_prefix.as_deref(),
+            &dest_key_prefix,
+        )
+        .await?;
+        Ok(Action::await_change())
+    }
+}
+
+impl WorkspaceSnapshotReconciler {
+    async fn observe_job(
+        &self,
+        ctx: &Context,
+        snapshot: &WorkspaceSnapshot,
+        job: &Job,
+    ) -> Result<Action, kubimo::Error> {
+        let finished = job
+            .status
+            .as_ref()
+            .and_then(|status| status.conditions.as_ref())
+            .and_then(|conditions| {
+                conditions
+                    .iter()
+                    .filter(|cond| cond.status == "True")
+                    .find_map(|cond| match cond.type_.as_str() {
+                        "Complete" => Some(true),
+                        "Failed" => Some(false),
+                        _ => None,
+                    })
+            });
+        match finished {
+            None => Ok(Action::await_change()),
+            Some(false) => {
+                let condition = ready_condition(
+                    snapshot,
+                    false,
+                    REASON_FAILED,
+                    "The snapshot Job failed; see its pods' logs",
+                );
+                self.patch_status(ctx, snapshot, condition, |_| {}).await?;
+                Ok(Action::await_change())
+            }
+            Some(true) => {
+                let summary = self.job_summary(ctx, snapshot, job).await?;
+                let condition =
+                    ready_condition(snapshot, true, REASON_READY, "The archive was copied");
+                self.patch_status(ctx, snapshot, condition, |status| {
+                    status.files = summary.map(|summary| summary.files);
+                    status.total_content_bytes = summary.map(|summary| summary.total_content_bytes);
+                    status.ready_at = Some(kubimo::chrono::Utc::now());
+                })
+                .await?;
+                Ok(Action::await_change())
+            }
+        }
+    }
+
+    /// The summary the succeeded pod left as its termination message.
+    ///
+    /// `None` when it cannot be found — the pod was already collected — which
+    /// still makes the snapshot ready: the sizes are informational, the copy is
+    /// what matters and the Job only completes once its manifest is written.
+    async fn job_summary(
+        &self,
+        ctx: &Context,
+        snapshot: &WorkspaceSnapshot,
+        job: &Job,
+    ) -> Result<Option<SnapshotSummary>, kubimo::Error> {
+        let pods = ctx
+            .api_namespaced::<Pod>(snapshot.require_namespace()?)
+            .list(&FilterParams::new().with_labels(("batch.kubernetes.io/job-name", job.name()?)))
+            .map_ok(|item| item.item)
+            .try_collect::<Vec<_>>()
+            .await?;
+        Ok(pods.iter().find_map(termination_summary))
+    }
+
+    /// Replace the `Ready` condition and apply `update` on top of the current
+    /// status. A snapshot has one writer, so the read-modify-write is safe.
+    async fn patch_status(
+        &self,
+        ctx: &Context,
+        snapshot: &WorkspaceSnapshot,
+        condition: Condition,
+        update: impl FnOnce(&mut WorkspaceSnapshotStatus),
+    ) -> Result<(), kubimo::Error> {
+        let mut status = snapshot.status.clone().unwrap_or_default();
+        status.conditions = Some(vec![condition]);
+        update(&mut status);
+        let mut patched = snapshot.clone();
+        patched.status = Some(status);
+        ctx.api_namespaced::<WorkspaceSnapshot>(snapshot.require_namespace()?)
+            .patch_status(&patched)
+            .await?;
+        Ok(())
+    }
+}
+
+/// The archive a workspace's snapshot copies from: its indexer bucket and key
+/// prefix, provided it uploads content at all.
+fn archive_location(workspace: &Workspace) -> Option<(&str, Option<String>)> {
+    let indexer = workspace.spec.indexer.as_ref()?;
+    if indexer.upload_content != Some(true) {
+        return None;
+    }
+    Some((indexer.bucket.as_deref()?, indexer.key_prefix.clone()))
+}
+
+/// Whether the archive reflects the workspace as of the snapshot's creation.
+///
+/// Compared at whole seconds, and strictly: `creationTimestamp` is truncated to
+/// the second, so a sync finishing in that same second may have started before
+/// the snapshot was asked for.
+fn is_flushed(snapshot: &WorkspaceSnapshot, workspace: &Workspace, idle: bool) -> bool {
+    if idle {
+        return true;
+    }
+    let Some(created) = snapshot.metadata.creation_timestamp.as_ref() else {
+        return false;
+    };
+    workspace
+        .status
+        .as_ref()
+        .and_then(|status| status.archive.as_ref())
+        .and_then(|archive| archive.last_synced_at)
+        .is_some_and(|synced| synced.timestamp() > created.0.as_second())
+}
+
+fn termination_summary(pod: &Pod) -> Option<SnapshotSummary> {
+    pod.status
+        .as_ref()?
+        .container_statuses
+        .iter()
+        .flatten()
+        .filter_map(|status| status.state.as_ref()?.terminated.as_ref())
+        .filter(|terminated| terminated.exit_code == 0)
+        .find_map(|terminated| serde_json::from_str(terminated.message.as_deref()?).ok())
+}
+
+/// `Ready` condition, preserving the previous transition time when the status
+/// is unchanged (cf. `pool::ready_condition`).
+fn ready_condition(
+    snapshot: &WorkspaceSnapshot,
+    ready: bool,
+    reason: &str,
+    message: &str,
+) -> Condition {
+    let status = if ready { "True" } else { "False" };
+    let previous = snapshot
+        .status
+        .as_ref()
+        .and_then(|status| status.conditions.as_ref())
+        .and_then(|conditions| conditions.iter().find(|cond| cond.type_ == READY));
+    let last_transition_time = match previous {
+        Some(previous) if previous.status == status => previous.last_transition_time.clone(),
+        _ => Time(Timestamp::now()),
+    };
+    Condition {
+        last_transition_time,
+        observed_generation: snapshot.metadata.generation,
+        message: message.into(),
+        reason: reason.into(),
+        status: status.into(),
+        type_: READY.into(),
+    }
+}
+
+pub async fn run(
+    ctx: Arc<Context>,
+    shutdown_signal: impl Future<Output = ()> + Send + Sync + 'static,
+) -> Result<
+    impl Stream<Item = ControllerResult<WorkspaceSnapshot, ReconcileError<kubimo::Error>>>,
+    ReconcileError<kubimo::Error>,
+> {
+    let snapshots = ctx.api_global::<WorkspaceSnapshot>().kube().clone();
+    let jobs = ctx.api_global::<Job>().kube().clone();
+    Ok(Controller::new(snapshots, Default::default())
+        .owns(jobs, Default::default())
+        .graceful_shutdown_on(shutdown_signal)
+        .run(
+            WorkspaceSnapshotReconciler.reconcile("controller").await?,
+            default_error_policy,
+            ctx,
+        ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kubimo::k8s_openapi::api::core::v1::{
+        ContainerState, ContainerStateTerminated, ContainerStatus, PodStatus,
+    };
+    use kubimo::{WorkspaceArchiveStatus, WorkspaceSnapshotSpec, WorkspaceStatus};
+
+    fn snapshot_created_at(second: i64) -> WorkspaceSnapshot {
+        let mut snapshot = WorkspaceSnapshot::new(
+            "v1",
+            WorkspaceSnapshotSpec {
+                workspace: "bmow-x".into(),
+            },
+        );
+        snapshot.metadata.creation_timestamp = Some(Time(Timestamp::from_second(second).unwrap()));
+        snapshot
+    }
+
+    fn workspace_synced_at(millis: Option<i64>) -> Workspace {
+        let mut workspace = Workspace::new("bmow-x", Default::default());
+        workspace.status = Some(WorkspaceStatus {
+            archive: Some(WorkspaceArchiveStatus {
+                last_synced_at: millis
+                    .map(|millis| kubimo::chrono::DateTime::from_timestamp_millis(millis).unwrap()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+        workspace
+    }
+
+    #[test]
+    fn a_busy_workspace_is_flushed_by_the_first_sync_after_the_request() {
+        let snapshot = snapshot_created_at(1_000);
+        assert!(!is_flushed(&snapshot, &workspace_synced_at(None), false));
+        assert!(!is_flushed(
+            &snapshot,
+            &workspace_synced_at(Some(999_000)),
+            false
+        ));
+        // Same second as the request: may have started before it.
+        assert!(!is_flushed(
+            &snapshot,
+            &workspace_synced_at(Some(1_000_900)),
+            false
+        ));
+        assert!(is_flushed(
+            &snapshot,
+            &workspace_synced_at(Some(1_001_000)),
+            false
+        ));
+    }
+
+    /// No pod holds the slot, so nothing can have changed since its last
+    /// flush — waiting for a sync that no watcher will run would never end.
+    #[test]
+    fn an_idle_workspace_is_already_flushed() {
+        let snapshot = snapshot_created_at(1_000);
+        assert!(is_flushed(&snapshot, &workspace_synced_at(None), true));
+    }
+
+    #[test]
+    fn only_content_archives_can_be_snapshotted() {
+        let mut workspace = Workspace::new("bmow-x", Default::default());
+        assert!(archive_location(&workspace).is_none());
+        workspace.spec.indexer = Some(kubimo::WorkspaceIndexer {
+            bucket: Some("bucket".into()),
+            key_prefix: Some("ws/".into()),
+            ..Default::default()
+        });
+        assert!(archive_location(&workspace).is_none());
+        workspace.spec.indexer.as_mut().unwrap().upload_content = Some(true);
+        assert_eq!(
+            archive_location(&workspace),
+            Some(("bucket", Some("ws/".to_string())))
+        );
+    }
+
+    #[test]
+    fn the_summary_comes_from_the_succeeded_container() {
+        let terminated = |exit_code, message: &str| ContainerStatus {
+            state: Some(ContainerState {
+                terminated: Some(ContainerStateTerminated {
+                    exit_code,
+                    message: Some(message.into()),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let pod = |status| Pod {
+            status: Some(PodStatus {
+                container_statuses: Some(vec![status]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let summary = r#"{"files":2,"totalContentBytes":10}"#;
+        assert_eq!(
+            termination_summary(&pod(terminated(0, summary))),
+            Some(SnapshotSummary {
+                files: 2,
+                total_content_bytes: 10,
+            })
+        );
+        assert_eq!(termination_summary(&pod(terminated(1, summary))), None);
+        assert_eq!(termination_summary(&pod(terminated(0, "oops"))), None);
+    }
+
+    #[test]
+    fn a_ready_condition_keeps_its_transition_time() {
+        let mut snapshot = snapshot_created_at(1_000);
+        let first = ready_condition(&snapshot, false, REASON_FLUSHING, "waiting");
+        snapshot.status = Some(WorkspaceSnapshotStatus {
+            conditions: Some(vec![first.clone()]),
+            ..Default::default()
+        });
+        let copying = ready_condition(&snapshot, false, REASON_COPYING, "copying");
+        assert_eq!(copying.reason, "Copying");
+        assert_eq!(copying.last_transition_time, first.last_transition_time);
+        let ready = ready_condition(&snapshot, true, REASON_READY, "done");
+        assert_eq!(ready.status, "True");
+    }
+}
@@ -15,13 +15,40 @@ async fn ctrl_c() {
         .expect("Failed to listen for shutdown signal");
 }
 
-async fn shutdown_signal(service: &'static str) {
+/// Resolves when the process is asked to stop.
+///
+/// SIGTERM as well as SIGINT: kubelet stops pods with SIGTERM, so waiting on
+/// `ctrl_c()` alone meant every rollout or eviction ended in a SIGKILL at the
+/// end of the grace period, and the usage accountant never wrote the final
+/// report for the period in progress.
+async fn stop_requested() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+        let mut term = match signal(SignalKind::terminate()) {
+            Ok(term) => term,
+            Err(err) => {
+                tracing::error!(%err, "cannot listen for SIGTERM; falling back to SIGINT only");
+                ctrl_c().await;
+                return;
+            }
+        };
+        tokio::select! {
+            _ = term.recv() => {}
+            _ = ctrl_c() => {}
+        }
+    }
+    #[cfg(not(unix))]
     ctrl_c().await;
+}
+
+async fn shutdown_signal(service: &'static str) {
+    stop_requested().await;
     tracing::info!("Shutting down {service} controller...");
 }
 
 async fn shutdown_timeout(timeout: Duration) -> Result<ExitCode, BoxError> {
-    ctrl_c().await;
+    stop_requested().await;
     tracing::info!("Shutting down gracefully... (Ctrl+c to force)");
     match tokio::time::timeout(timeout, ctrl_c()).await {
         Ok(_) => {
@@ -62,6 +89,11 @@ async fn main() -> ExitCode {
         "Processing events in {} namespace...",
         client.kube().default_namespace()
     );
+    // Not fatal: the collector is housekeeping, and a controller that refused
+    // to start over it would stop every workspace instead.
+    if let Err(err) = controllers::archive_gc::apply(&ctx).await {
+        tracing::error!("Error applying archive gc CronJob: {err}");
+    }
     futures::future::try_select(
         futures::future::join_all([
             controllers::workspace::run(ctx.clone(), shutdown_signal("workspace"))
@@ -95,6 +127,14 @@ async fn main() -> ExitCode {
                 .await
                 .unwrap()
                 .wait(),
+            controllers::workspace_snapshot::run(
+                ctx.clone(),
+                shutdown_signal("workspace_snapshot"),
+            )
+            .await
+            .unwrap()
+            .wait(),
+            controllers::usage::run(ctx.clone(), shutdown_signal("usage")).boxed(),
         ])
         .map(|_| Ok(ExitCode::SUCCESS)),
         shutdown_timeout(Duration::from_secs(60)).boxed(),
@@ -4,6 +4,8 @@ use std::time::Instant;
 use futures::future::{BoxFuture, FutureExt};
 use tower::{Layer, Service};
 
+use crate::controllers::usage::{Account, Usage};
+
 pub fn install(bind_addr: SocketAddr) {
     metrics_exporter_prometheus::PrometheusBuilder::new()
         .with_http_listener(bind_addr)
@@ -12,6 +14,26 @@ pub fn install(bind_addr: SocketAddr) {
     tracing::info!("Serving Prometheus metrics on http://{bind_addr}/metrics");
 }
 
+/// Publish `account`'s usage since the controller started.
+///
+/// Counters only count whole units, so these are in the smallest unit that
+/// keeps a usage total meaningful — byte-hours of storage and memory,
+/// millicore-hours of CPU — and each is set to its running total rather than
+/// incremented, so a fraction carried across samples is never lost.
+pub(crate) fn record_usage(account: &Account, usage: &Usage) {
+    let labels = [
+        ("kind", account.kind.as_str().to_string()),
+        ("namespace", account.namespace.clone()),
+        ("name", account.name.clone()),
+    ];
+    metrics::counter!("kubimo_usage_storage_byte_hours_total", &labels)
+        .absolute(usage.storage_byte_hours as u64);
+    metrics::counter!("kubimo_usage_cpu_millicore_hours_total", &labels)
+        .absolute((usage.cpu_core_hours * 1000.0) as u64);
+    metrics::counter!("kubimo_usage_memory_byte_hours_total", &labels)
+        .absolute((usage.memory_gib_hours * (1u64 << 30) as f64) as u64);
+}
+
 pub(crate) fn controller_name<T>() -> &'static str {
     let full = std::any::type_name::<T>();
     full.rsplit("::").next().unwrap_or(full)
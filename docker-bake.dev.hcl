@@ -17,3 +17,7 @@ target "docker-metadata-conda-marimo" {
 target "docker-metadata-agent" {
   tags = ["ghcr.io/aqora-io/kubimo-agent:${TAG}"]
 }
+
+target "docker-metadata-activator" {
+  tags = ["ghcr.io/aqora-io/kubimo-activator:${TAG}"]
+}
@@ -16,7 +16,7 @@ variable "MARIMO_GIT" {
 }
 
 group "default" {
-  targets = ["marimo", "conda-marimo", "controller", "agent"]
+  targets = ["marimo", "conda-marimo", "controller", "agent", "activator"]
 }
 
 target "docker-metadata-controller" {}
@@ -54,6 +54,23 @@ target "agent" {
   ]
 }
 
+target "docker-metadata-activator" {}
+
+target "activator" {
+  inherits   = ["docker-metadata-activator"]
+  dockerfile = "docker/Dockerfile.activator"
+  context    = "."
+  args = {
+    SCCACHE_ENDPOINT = SCCACHE_ENDPOINT
+    SCCACHE_BUCKET   = SCCACHE_BUCKET
+    SCCACHE_REGION   = SCCACHE_REGION
+  }
+  secret = [
+    "id=SCCACHE_AWS_ACCESS_KEY_ID,env=SCCACHE_AWS_ACCESS_KEY_ID",
+    "id=SCCACHE_AWS_SECRET_ACCESS_KEY,env=SCCACHE_AWS_SECRET_ACCESS_KEY",
+  ]
+}
+
 target "docker-metadata-marimo" {}
 target "docker-metadata-conda-marimo" {}
 
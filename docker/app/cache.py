@@ -8,6 +8,7 @@ import itertools
 from dataclasses import dataclass
 import fnmatch
 from functools import lru_cache
+import json
 
 import marimo
 from marimo._server.export import export_as_md, run_app_then_export_as_html
@@ -16,6 +17,14 @@ from marimo._utils.marimo_path import MarimoPath
 logging.basicConfig(level=logging.INFO)
 logger = logging.getLogger(__name__)
 _LOG_LEVEL_CHOICES = ["debug", "info", "warning", "error", "critical"]
+# What each notebook can be exported as, under its `__marimo__` directory, and
+# what it is by default.
+_FORMAT_CHOICES = ["html", "md", "ipynb"]
+_DEFAULT_FORMATS = ("html", "md")
+# The report is the container's termination message, which kubelet truncates
+# past 4KiB — to bytes that are no longer JSON.
+_REPORT_MAX_BYTES = 4096
+_ERROR_MAX_CHARS = 200
 
 
 def _write_export(export_dir: Path, result):
@@ -25,20 +34,47 @@ def _write_export(export_dir: Path, result):
     (export_dir / result.download_filename).write_bytes(contents)
 
 
-async def _cache_app(path: Path, *, include_code: bool):
+def _describe_error(err: BaseException) -> str:
+    description = f"{type(err).__name__}: {err}"
+    if len(description) > _ERROR_MAX_CHARS:
+        description = description[: _ERROR_MAX_CHARS - 1] + "…"
+    return description
+
+
+async def _cache_app(
+    path: Path, *, include_code: bool, formats: tuple[str, ...]
+) -> dict[str, str | None]:
+    """Export `path` in each of `formats`, independently of one another.
+
+    Returns each format's error, or `None` where the export was written.
+    """
     logger.info(f"Caching {path}")
     marimo_path = MarimoPath(path)
-    html_result = await run_app_then_export_as_html(
-        marimo_path,
-        include_code=include_code,
-        cli_args={},
-        argv=[],
-    )
-    md_result = export_as_md(marimo_path)
     export_dir = path.parent / "__marimo__"
     export_dir.mkdir(parents=True, exist_ok=True)
-    _write_export(export_dir, html_result)
-    _write_export(export_dir, md_result)
+    errors: dict[str, str | None] = {}
+    for format in formats:
+        try:
+            if format == "html":
+                result = await run_app_then_export_as_html(
+                    marimo_path,
+                    include_code=include_code,
+                    cli_args={},
+                    argv=[],
+                )
+            elif format == "ipynb":
+                # Imported here: a marimo without it fails this format alone.
+                from marimo._server.export import export_as_ipynb
+
+                result = export_as_ipynb(marimo_path, sort_mode="top-down")
+            else:
+                result = export_as_md(marimo_path)
+            _write_export(export_dir, result)
+            errors[format] = None
+        except Exception as e:
+            logger.error(f"Failed to export {path} as {format}: {e}", exc_info=True)
+            errors[format] = _describe_error(e)
+    return errors
 
 
 def _is_gitignored(path: Path, git_root: Path) -> bool:
@@ -203,19 +239,80 @@ def _is_app(path: Path):
     return hasattr(module, "app") and isinstance(getattr(module, "app"), marimo.App)
 
 
-def _cache_app_sync(path: Path, include_code: bool, log_level: str):
+def _cache_app_sync(
+    path: Path, include_code: bool, log_level: str, formats: tuple[str, ...]
+) -> dict[str, str | None] | None:
+    """Each format's error, as `_cache_app`; `None` when `path` is no app."""
     logging.getLogger().setLevel(log_level.upper())
     try:
-        if _is_app(path):
-            asyncio.run(_cache_app(path, include_code=include_code))
-            logger.info(f"Cached {path}")
-            return True
-        else:
+        if not _is_app(path):
             logger.warning(f"Skipping {path}")
-            return False
+            return None
     except Exception as e:
+        # It may well have been a notebook, and nothing of it was rendered.
         logger.error(f"Failed to cache {path}: {e}", exc_info=True)
+        return {format: _describe_error(e) for format in formats}
+    errors = asyncio.run(_cache_app(path, include_code=include_code, formats=formats))
+    if not any(errors.values()):
+        logger.info(f"Cached {path}")
+    return errors
+
+
+def _is_selected(relative: str, include: list[str], exclude: list[str]) -> bool:
+    """Whether the CacheJob's globs select the notebook at `relative`."""
+    parts = relative.split("/")
+    if include and not any(_match_path_parts(glob, parts) for glob in include):
         return False
+    return not any(_match_path_parts(glob, parts) for glob in exclude)
+
+
+def _build_report(
+    results: list[tuple[str, dict[str, str | None]]],
+    max_bytes: int = _REPORT_MAX_BYTES,
+) -> dict:
+    """The report the controller reads back into the CacheJob's status.
+
+    Its shape is `CacheReport` in the kubimo API crate. The counts are always
+    whole; the per-notebook results are cut off at `max_bytes`, keeping the
+    failed notebooks, which are the ones worth reading about.
+    """
+    entries = [
+        {
+            "path": path,
+            "formats": [
+                {"format": format}
+                if error is None
+                else {"format": format, "error": error}
+                for format, error in formats.items()
+            ],
+        }
+        for path, formats in results
+    ]
+    failed = [
+        entry
+        for entry in entries
+        if any("error" in format for format in entry["formats"])
+    ]
+    succeeded = [entry for entry in entries if entry not in failed]
+    report = {
+        "notebooks": len(entries),
+        "failedNotebooks": len(failed),
+        "results": [],
+        "truncated": False,
+    }
+    for entry in failed + succeeded:
+        report["results"].append(entry)
+        if len(_encode_report(report)) > max_bytes:
+            report["results"].pop()
+            report["truncated"] = True
+            break
+    return report
+
+
+def _encode_report(report: dict) -> bytes:
+    return json.dumps(report, separators=(",", ":"), ensure_ascii=False).encode(
+        "utf-8"
+    )
 
 
 def _get_python_files(directory: str, include_gitignored: bool = False) -> list[Path]:
@@ -292,8 +389,19 @@ def _cache_all_apps(
     include_gitignored: bool = False,
     include_code: bool = False,
     log_level: str = "info",
+    report_path: str | None = None,
+    include: list[str] | None = None,
+    exclude: list[str] | None = None,
+    formats: tuple[str, ...] = _DEFAULT_FORMATS,
 ):
-    files = _get_python_files(directory, include_gitignored=include_gitignored)
+    root = Path(directory).resolve()
+    # Selected before `_is_app` imports anything: leaving a notebook out must
+    # also keep its code from running.
+    files = [
+        path
+        for path in _get_python_files(directory, include_gitignored=include_gitignored)
+        if _is_selected(path.relative_to(root).as_posix(), include or [], exclude or [])
+    ]
 
     # Run _cache_app in parallel with process workers
     with ProcessPoolExecutor() as executor:
@@ -303,14 +411,26 @@ def _cache_all_apps(
                 files,
                 itertools.repeat(include_code),
                 itertools.repeat(log_level),
+                itertools.repeat(formats),
             )
         )
 
-    successful = sum(results)
-    failed = len(results) - successful
+    notebooks = [
+        (path.relative_to(root).as_posix(), errors)
+        for path, errors in zip(files, results)
+        if errors is not None
+    ]
+    report = _build_report(notebooks)
     logger.info(
-        f"Caching complete: {successful} apps cached successfully, {failed} failed or skipped"
+        f"Caching complete: {report['notebooks'] - report['failedNotebooks']} apps "
+        f"cached successfully, {report['failedNotebooks']} failed, "
+        f"{len(files) - len(notebooks)} skipped"
     )
+    if report_path is not None:
+        try:
+            Path(report_path).write_bytes(_encode_report(report))
+        except OSError as e:
+            logger.warning(f"Failed to write the report to {report_path}: {e}")
 
 
 if __name__ == "__main__":
@@ -331,6 +451,26 @@ if __name__ == "__main__":
         choices=_LOG_LEVEL_CHOICES,
         help="Log level.",
     )
+    parser.add_argument(
+        "--report-path",
+        help="Also write what each notebook came to here, as JSON.",
+    )
+    parser.add_argument(
+        "--include",
+        action="append",
+        help="Only cache notebooks whose relative path matches this glob. Repeatable.",
+    )
+    parser.add_argument(
+        "--exclude",
+        action="append",
+        help="Skip notebooks whose relative path matches this glob. Repeatable.",
+    )
+    parser.add_argument(
+        "--format",
+        action="append",
+        choices=_FORMAT_CHOICES,
+        help="Export as this format. Repeatable; html and md when absent.",
+    )
     parser.add_argument("directory", nargs="?", default=".", help="Directory to cache")
     args = parser.parse_args()
     logging.getLogger().setLevel(args.log_level.upper())
@@ -339,4 +479,8 @@ if __name__ == "__main__":
         include_gitignored=args.include_gitignored,
         include_code=args.include_code,
         log_level=args.log_level,
+        report_path=args.report_path,
+        include=args.include,
+        exclude=args.exclude,
+        formats=tuple(dict.fromkeys(args.format)) if args.format else _DEFAULT_FORMATS,
     )
@@ -1,3 +1,4 @@
+import json
 import subprocess
 import sys
 from pathlib import Path
@@ -57,7 +58,13 @@ def test_cache_exports_html_and_markdown(tmp_path):
     notebook.write_text(NOTEBOOK)
 
     result = subprocess.run(
-        [sys.executable, str(CACHE_SCRIPT), "--include-code", str(tmp_path)],
+        [
+            sys.executable,
+            str(CACHE_SCRIPT),
+            "--include-code",
+            f"--report-path={tmp_path / 'report.json'}",
+            str(tmp_path),
+        ],
         capture_output=True,
         text=True,
     )
@@ -73,3 +80,49 @@ def test_cache_exports_html_and_markdown(tmp_path):
     md_text = md.read_text()
     assert "# Hello" in md_text
     assert "x = 21 * 2" in md_text
+
+    report = json.loads((tmp_path / "report.json").read_text())
+    assert report == {
+        "notebooks": 1,
+        "failedNotebooks": 0,
+        "results": [
+            {"path": "nb.py", "formats": [{"format": "html"}, {"format": "md"}]}
+        ],
+        "truncated": False,
+    }
+
+
+def test_report_keeps_failed_notebooks_when_cut_short():
+    # The report is a termination message: past the cap kubelet truncates it
+    # into invalid JSON, and the status would show nothing at all.
+    import cache
+
+    results = [(f"ok_{i}.py", {"html": None, "md": None}) for i in range(50)]
+    results.append(("broken.py", {"html": "ValueError: boom", "md": None}))
+
+    report = cache._build_report(results, max_bytes=1024)
+    assert report["notebooks"] == 51
+    assert report["failedNotebooks"] == 1
+    assert report["truncated"] is True
+    assert report["results"][0]["path"] == "broken.py"
+    assert report["results"][0]["formats"][0] == {
+        "format": "html",
+        "error": "ValueError: boom",
+    }
+    assert len(cache._encode_report(report)) <= 1024
+
+    untouched = cache._build_report(results[-2:])
+    assert untouched["truncated"] is False
+    assert len(untouched["results"]) == 2
+
+
+def test_globs_select_notebooks_before_they_run():
+    import cache
+
+    assert cache._is_selected("a/b/nb.py", [], [])
+    assert cache._is_selected("a/b/nb.py", ["a/**/*.py"], [])
+    assert cache._is_selected("nb.py", ["**/nb.py"], [])
+    assert not cache._is_selected("a/b/nb.py", ["a/*.py"], [])
+    assert not cache._is_selected("a/b/nb.py", ["a/**"], ["**/b/*"])
+    # Exclusion wins over an explicit include.
+    assert not cache._is_selected("nb.py", ["nb.py"], ["nb.py"])
@@ -2,6 +2,7 @@
 
 set -xe
 
+cache_flags=()
 while [[ $# -gt 0 ]]; do
   case $1 in
   --base-url)
@@ -34,6 +35,12 @@ while [[ $# -gt 0 ]]; do
     shift
     shift
     ;;
+  # `cache` only; repeatable, and forwarded to cache.py as given.
+  --include | --exclude | --format)
+    cache_flags+=("$1=$2")
+    shift
+    shift
+    ;;
   -* | --*)
     echo "Unknown option $1"
     exit 1
@@ -113,6 +120,22 @@ pixi_install_workspace() {
   /usr/local/bin/pixi install
 }
 
+# Leave the failure where the controller reads it — the termination message —
+# so a runner whose environment does not install is reported as that rather
+# than as just another crash loop. The marker is matched by the runner_status
+# controller (DEPENDENCY_INSTALL_FAILED_MARKER); keep the two in agreement.
+#
+# From a backgrounded sync the exit alone would end only the subshell. By then
+# $$ is marimo — the script exec'd it — so stop that too: the message is only
+# read once the container terminates.
+dependency_install_failed() {
+  echo "DependencyInstallFailed: $1 exited with status $2" >/dev/termination-log || true
+  if [[ "$BASHPID" != "$$" ]]; then
+    kill -TERM "$$" || true
+  fi
+  exit 1
+}
+
 is_marimo_venv_configured() {
   /usr/local/bin/python3 -c '
 import sys, tomllib
@@ -138,11 +161,12 @@ sync_workspace_env() {
   # The trade: a workspace that has added dependencies has a window where they
   # are not yet installed, and opening a notebook in it shows marimo's
   # "Install packages" banner. Recoverable, and the same banner users already
-  # get for genuinely missing packages.
+  # get for genuinely missing packages. A sync that fails outright is not, and
+  # still fails the runner, just later.
   if [[ -n "$UV_PROJECT" ]]; then
-    uv_sync_workspace &
+    (uv_sync_workspace || dependency_install_failed "uv sync" $?) &
   elif [[ -x /usr/local/bin/pixi ]]; then
-    pixi_install_workspace
+    pixi_install_workspace || dependency_install_failed "pixi install" $?
   fi
   ensure_marimo_venv_config
 }
@@ -238,8 +262,13 @@ elif [[ "$CMD" == "cache" ]]; then
     pixi_install_workspace
     export VIRTUAL_ENV="$CONDA_PREFIX"
   fi
+  # Where to leave the per-notebook report the controller copies into the
+  # CacheJob's status: the container's termination log.
+  if [ -n "$KUBIMO_CACHE_REPORT" ]; then
+    cache_flags+=("--report-path=$KUBIMO_CACHE_REPORT")
+  fi
   exec "$VIRTUAL_ENV/bin/python3" /app/cache.py \
-    --include-code "${common_flags[@]}"
+    --include-code "${common_flags[@]}" "${cache_flags[@]}"
 
 else
   echo "Unknown command $CMD"
@@ -0,0 +1,241 @@
+//! Content-defined chunking of large files.
+//!
+//! A file at or over the threshold is stored as a list of chunks instead of
+//! one object. Boundaries are cut where a rolling hash of the last 64 bytes
+//! hits a pattern, so they follow the bytes rather than their offsets: an
+//! append changes the last chunk, an insertion the one or two around it, and
+//! every other chunk keeps both its bytes and its name. A 2 GB table with one
+//! row appended uploads a few MiB.
+//!
+//! The hash is a gear hash, as in FastCDC: one shift and one table lookup per
+//! byte, cheap next to the sha256 every chunk gets for its name anyway.
+//!
+//! Chunks are named by that digest, so the object at a chunk url only ever
+//! holds one thing. In a sealed archive the name is keyed by the data key
+//! ([`DataKey::object_name`]): a plain digest would tell anyone who can list
+//! the bucket which well-known files the archive holds, and a chunk sealed
+//! under an earlier key must not be taken for one sealed under this one.
+
+use kubimo::WorkspaceArchiveChunking;
+use kubimo::url::Url;
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use crate::encryption::DataKey;
+
+/// No cut is made closer than this to the previous one. Keeps the request
+/// count of a large file in check whatever its bytes look like.
+pub const MIN_CHUNK_LEN: usize = 1024 * 1024;
+
+/// A cut is forced here when the content offers none, so a run of zeros
+/// still chunks. Also the most an upload holds in memory per file.
+pub const MAX_CHUNK_LEN: usize = 16 * 1024 * 1024;
+
+/// Cut where the top bits of the hash are all zero: one position in 2^22,
+/// so chunks average 4 MiB past the minimum.
+const MASK_BITS: u32 = 22;
+
+/// One pseudo-random word per byte value. Any fixed table works; what matters
+/// is that every indexer uses the same one, or two uploads of the same file
+/// would cut it differently and share nothing.
+const GEAR: [u64; 256] = gear_table();
+
+const fn gear_table() -> [u64; 256] {
+    // splitmix64, seeded arbitrarily.
+    let mut table = [0u64; 256];
+    let mut state = 0x6b75_6269_6d6f_6364u64;
+    let mut i = 0;
+    while i < table.len() {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+}
+
+/// What `spec.indexer.chunking` asks of an upload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkingPolicy {
+    pub min_size: u64,
+    pub max_file_size: u64,
+}
+
+impl From<&WorkspaceArchiveChunking> for ChunkingPolicy {
+    fn from(chunking: &WorkspaceArchiveChunking) -> Self {
+        Self {
+            min_size: chunking.effective_min_size(),
+            max_file_size: chunking.effective_max_file_size(),
+        }
+    }
+}
+
+impl ChunkingPolicy {
+    /// Is a file of `size` bytes stored as chunks?
+    pub fn chunks(&self, size: u64) -> bool {
+        size >= self.min_size
+    }
+}
+
+/// An upload's chunking: the policy, and the archive its chunks go to.
+#[derive(Debug, Clone)]
+pub struct Chunking {
+    pub policy: ChunkingPolicy,
+    bucket: String,
+    key_prefix: Option<String>,
+}
+
+impl Chunking {
+    pub fn new(
+        policy: ChunkingPolicy,
+        bucket: impl Into<String>,
+        key_prefix: Option<String>,
+    ) -> Self {
+        Self {
+            policy,
+            bucket: bucket.into(),
+            key_prefix,
+        }
+    }
+
+    pub fn url(&self, name: &str) -> Result<Url, kubimo::url::ParseError> {
+        kubimo::chunk_url(&self.bucket, self.key_prefix.as_deref(), name)
+    }
+}
+
+/// The name of the chunk holding `bytes`: its sha256, keyed by `data_key`
+/// when the archive is sealed.
+pub fn chunk_name(bytes: &[u8], data_key: Option<&DataKey>) -> String {
+    let digest = Sha256::digest(bytes);
+    match data_key {
+        Some(data_key) => data_key.object_name(&digest),
+        None => format!("{digest:x}"),
+    }
+}
+
+/// Where the chunk at the start of `data` ends. `data` must run to the end of
+/// the input or hold at least [`MAX_CHUNK_LEN`] bytes, so that finding no cut
+/// means the chunk really does run to the end of what is there.
+fn cut(data: &[u8]) -> usize {
+    if data.len() <= MIN_CHUNK_LEN {
+        return data.len();
+    }
+    let end = data.len().min(MAX_CHUNK_LEN);
+    let mut hash = 0u64;
+    for (i, byte) in data[MIN_CHUNK_LEN..end].iter().enumerate() {
+        hash = (hash << 1).wrapping_add(GEAR[*byte as usize]);
+        if hash >> (u64::BITS - MASK_BITS) == 0 {
+            return MIN_CHUNK_LEN + i + 1;
+        }
+    }
+    end
+}
+
+/// Splits a stream into chunks, holding at most [`MAX_CHUNK_LEN`] of it.
+pub struct Chunker<R> {
+    input: R,
+    buffer: Vec<u8>,
+    eof: bool,
+}
+
+impl<R: AsyncRead + Unpin> Chunker<R> {
+    pub fn new(input: R) -> Self {
+        Self {
+            input,
+            buffer: Vec::new(),
+            eof: false,
+        }
+    }
+
+    pub async fn next_chunk(&mut self) -> std::io::Result<Option<Vec<u8>>> {
+        while !self.eof && self.buffer.len() < MAX_CHUNK_LEN {
+            let filled = self.buffer.len();
+            self.buffer.resize(MAX_CHUNK_LEN, 0);
+            let read = self.input.read(&mut self.buffer[filled..]).await?;
+            self.buffer.truncate(filled + read);
+            self.eof = read == 0;
+        }
+        if self.buffer.is_empty() {
+            return Ok(None);
+        }
+        let rest = self.buffer.split_off(cut(&self.buffer));
+        Ok(Some(std::mem::replace(&mut self.buffer, rest)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn noise(len: usize, mut state: u64) -> Vec<u8> {
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                (state >> 32) as u8
+            })
+            .collect()
+    }
+
+    async fn chunks(input: &[u8]) -> Vec<Vec<u8>> {
+        let mut chunker = Chunker::new(input);
+        let mut chunks = Vec::new();
+        while let Some(chunk) = chunker.next_chunk().await.unwrap() {
+            chunks.push(chunk);
+        }
+        chunks
+    }
+
+    #[tokio::test]
+    async fn chunks_reassemble_and_stay_within_bounds() {
+        let input = noise(40 * 1024 * 1024, 0x2545_f491_4f6c_dd1d);
+        let chunks = chunks(&input).await;
+        assert_eq!(chunks.concat(), input);
+        assert!(chunks.len() > 2, "expected content-defined cuts");
+        let (last, rest) = chunks.split_last().unwrap();
+        assert!(!last.is_empty());
+        for chunk in rest {
+            assert!((MIN_CHUNK_LEN..=MAX_CHUNK_LEN).contains(&chunk.len()));
+        }
+    }
+
+    #[tokio::test]
+    async fn content_with_no_cut_points_is_cut_at_the_maximum() {
+        let chunks = chunks(&vec![0u8; MAX_CHUNK_LEN + 10]).await;
+        assert_eq!(
+            chunks.iter().map(Vec::len).collect::<Vec<_>>(),
+            vec![MAX_CHUNK_LEN, 10]
+        );
+    }
+
+    /// The point of the exercise: bytes inserted near the start of a file
+    /// leave every chunk after the next cut as it was.
+    #[tokio::test]
+    async fn an_insertion_only_changes_the_chunks_around_it() {
+        let original = noise(40 * 1024 * 1024, 0x9e37_79b9_7f4a_7c15);
+        let mut edited = original[..100].to_vec();
+        edited.extend_from_slice(b"one more row\n");
+        edited.extend_from_slice(&original[100..]);
+        let names = |chunks: Vec<Vec<u8>>| {
+            chunks
+                .iter()
+                .map(|chunk| chunk_name(chunk, None))
+                .collect::<Vec<_>>()
+        };
+        let before = names(chunks(&original).await);
+        let after = names(chunks(&edited).await);
+        assert_ne!(before[0], after[0]);
+        assert_eq!(before[1..], after[1..]);
+    }
+
+    #[test]
+    fn the_policy_chunks_files_from_the_threshold_up() {
+        let policy = ChunkingPolicy::from(&WorkspaceArchiveChunking::default());
+        let min = WorkspaceArchiveChunking::DEFAULT_MIN_SIZE;
+        assert!(!policy.chunks(min - 1));
+        assert!(policy.chunks(min));
+    }
+}
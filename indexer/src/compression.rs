@@ -0,0 +1,249 @@
+//! Compression of archive content.
+//!
+//! Applied to file content only, below sealing: an object is compressed, then
+//! sealed, and a restore opens it before inflating it. The other way round
+//! would compress ciphertext, which does not compress.
+//!
+//! Whether a file is compressed at all is decided here, from its name and
+//! size, before any of it is read. The small-object path of an upload holds
+//! the whole file anyway and also keeps it raw when zstd would not shrink it;
+//! a multipart upload streams, so it commits to the codec up front and relies
+//! on the name to have ruled out formats that are compressed already.
+
+use std::path::Path;
+
+use kubimo::{ContentCodec, WorkspaceArchiveCompression};
+use zstd::stream::raw::{Decoder, Encoder, InBuffer, Operation, OutBuffer};
+
+/// How much output room each step of a stream is given.
+const STEP_LEN: usize = 128 * 1024;
+
+/// Extensions of formats that carry their own compression. Another pass
+/// spends CPU on both ends of every sync for a saving of a few bytes.
+const COMPRESSED_EXTENSIONS: &[&str] = &[
+    "7z", "avif", "br", "bz2", "gif", "gz", "heic", "jpeg", "jpg", "lz4", "mkv", "mov", "mp3",
+    "mp4", "npz", "ogg", "parquet", "pdf", "png", "rar", "tgz", "webm", "webp", "whl", "xlsx",
+    "xz", "zip", "zst",
+];
+
+/// What `spec.indexer.compression` asks of an upload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompressionPolicy {
+    pub min_size: u64,
+    pub level: i32,
+}
+
+impl From<&WorkspaceArchiveCompression> for CompressionPolicy {
+    fn from(compression: &WorkspaceArchiveCompression) -> Self {
+        Self {
+            min_size: compression.effective_min_size(),
+            level: compression.effective_level(),
+        }
+    }
+}
+
+impl CompressionPolicy {
+    /// The codec to store `path` with, if it is worth compressing.
+    pub fn codec_for(&self, path: &Path, size: u64) -> Option<ContentCodec> {
+        if size < self.min_size {
+            return None;
+        }
+        let name = path.file_name()?.to_str()?.to_ascii_lowercase();
+        let precompressed = COMPRESSED_EXTENSIONS.iter().any(|extension| {
+            name.rsplit_once('.')
+                .is_some_and(|(_, ext)| ext == *extension)
+        });
+        (!precompressed).then_some(ContentCodec::Zstd)
+    }
+}
+
+/// `bytes` compressed with `codec`, or `None` if that would not make them
+/// smaller.
+pub fn compress_if_smaller(
+    codec: ContentCodec,
+    level: i32,
+    bytes: &[u8],
+) -> std::io::Result<Option<Vec<u8>>> {
+    match codec {
+        ContentCodec::Zstd => {
+            let compressed = zstd::bulk::compress(bytes, level)?;
+            Ok((compressed.len() < bytes.len()).then_some(compressed))
+        }
+    }
+}
+
+/// Streaming compression, shaped like [`crate::encryption::Sealer`] so an
+/// upload can feed one into the other chunk by chunk.
+pub struct Compressor {
+    encoder: Encoder<'static>,
+}
+
+impl Compressor {
+    pub fn new(codec: ContentCodec, level: i32) -> std::io::Result<Self> {
+        match codec {
+            ContentCodec::Zstd => Ok(Self {
+                encoder: Encoder::new(level)?,
+            }),
+        }
+    }
+
+    pub fn update(&mut self, input: &[u8], out: &mut Vec<u8>) -> std::io::Result<()> {
+        run(&mut self.encoder, input, out).map(|_| ())
+    }
+
+    pub fn finish(mut self, out: &mut Vec<u8>) -> std::io::Result<()> {
+        loop {
+            out.reserve(STEP_LEN);
+            let pos = out.len();
+            let mut output = OutBuffer::around_pos(out, pos);
+            if self.encoder.finish(&mut output, true)? == 0 {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Streaming half of a download's decompression.
+pub struct Decompressor {
+    decoder: Decoder<'static>,
+    /// zstd's hint from the last step: zero exactly when the input so far
+    /// ends on a frame boundary.
+    pending: usize,
+}
+
+impl Decompressor {
+    pub fn new(codec: ContentCodec) -> std::io::Result<Self> {
+        match codec {
+            ContentCodec::Zstd => Ok(Self {
+                decoder: Decoder::new()?,
+                pending: 0,
+            }),
+        }
+    }
+
+    pub fn update(&mut self, input: &[u8], out: &mut Vec<u8>) -> std::io::Result<()> {
+        if !input.is_empty() {
+            self.pending = run(&mut self.decoder, input, out)?;
+        }
+        Ok(())
+    }
+
+    /// Fails if the input stopped mid-frame, so a truncated object is an
+    /// error rather than a short file. The crc32 would catch it too, but only
+    /// when the manifest recorded one.
+    pub fn finish(self) -> std::io::Result<()> {
+        if self.pending != 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "compressed object ends mid-frame",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Feed all of `input` through `operation`, appending what it produces to
+/// `out`. Keeps stepping while the output fills up, since zstd may hold back
+/// output it had no room for even once the input is consumed.
+fn run(operation: &mut impl Operation, input: &[u8], out: &mut Vec<u8>) -> std::io::Result<usize> {
+    let mut input = InBuffer::around(input);
+    loop {
+        out.reserve(STEP_LEN);
+        let pos = out.len();
+        let mut output = OutBuffer::around_pos(out, pos);
+        let hint = operation.run(&mut input, &mut output)?;
+        let filled = output.pos() == output.capacity();
+        if input.pos() == input.src.len() && !filled {
+            return Ok(hint);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> CompressionPolicy {
+        CompressionPolicy::from(&WorkspaceArchiveCompression::default())
+    }
+
+    fn stream(input: &[u8], chunk: usize) -> Vec<u8> {
+        let mut compressor = Compressor::new(ContentCodec::Zstd, 3).unwrap();
+        let mut compressed = Vec::new();
+        for piece in input.chunks(chunk) {
+            compressor.update(piece, &mut compressed).unwrap();
+        }
+        compressor.finish(&mut compressed).unwrap();
+        compressed
+    }
+
+    fn inflate(compressed: &[u8], chunk: usize) -> std::io::Result<Vec<u8>> {
+        let mut decompressor = Decompressor::new(ContentCodec::Zstd).unwrap();
+        let mut plain = Vec::new();
+        for piece in compressed.chunks(chunk) {
+            decompressor.update(piece, &mut plain)?;
+        }
+        decompressor.finish()?;
+        Ok(plain)
+    }
+
+    #[test]
+    fn streams_round_trip_whatever_the_chunking() {
+        let plain: Vec<u8> = (0..1_000_000u32)
+            .flat_map(|i| format!("{},{}\n", i, i % 97).into_bytes())
+            .collect();
+        let compressed = stream(&plain, 10_000);
+        assert!(compressed.len() < plain.len() / 4);
+        for chunk in [7, 4096, compressed.len()] {
+            assert_eq!(inflate(&compressed, chunk).unwrap(), plain);
+        }
+        // The bulk form is the same format, so either side reads the other.
+        let bulk = compress_if_smaller(ContentCodec::Zstd, 3, &plain)
+            .unwrap()
+            .unwrap();
+        assert_eq!(inflate(&bulk, 4096).unwrap(), plain);
+    }
+
+    #[test]
+    fn a_truncated_object_fails_to_inflate() {
+        let plain = b"import marimo\n".repeat(10_000);
+        let compressed = stream(&plain, 4096);
+        let err = inflate(&compressed[..compressed.len() - 3], 4096).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn incompressible_bytes_stay_raw() {
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let noise: Vec<u8> = (0..64 * 1024)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                (state >> 32) as u8
+            })
+            .collect();
+        assert_eq!(
+            compress_if_smaller(ContentCodec::Zstd, 3, &noise).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn the_policy_skips_small_files_and_compressed_formats() {
+        let policy = policy();
+        let big = WorkspaceArchiveCompression::DEFAULT_MIN_SIZE;
+        assert_eq!(
+            policy.codec_for(Path::new("data/sales.csv"), big),
+            Some(ContentCodec::Zstd)
+        );
+        assert_eq!(policy.codec_for(Path::new("notebook.py"), big - 1), None);
+        assert_eq!(policy.codec_for(Path::new("plot.PNG"), big), None);
+        assert_eq!(policy.codec_for(Path::new("data.parquet"), big), None);
+        // No extension is not a reason to skip.
+        assert_eq!(
+            policy.codec_for(Path::new("Makefile"), big),
+            Some(ContentCodec::Zstd)
+        );
+    }
+}
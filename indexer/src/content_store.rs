@@ -0,0 +1,89 @@
+//! The bucket-wide content-addressed store.
+//!
+//! Per-workspace keys are random, so two workspaces holding the same file —
+//! a fork and its source, say — hold two objects. A workspace that opts in
+//! with `spec.indexer.contentAddressed` instead names its content by sha256
+//! under [`kubimo::CONTENT_STORE_DIR_NAME`], and a file already there is not
+//! uploaded again by anyone.
+//!
+//! The price is ownership: no archive owns a store object, so nothing that
+//! cleans up after one archive may delete it. Objects leave the store only
+//! through [`crate::gc`], which deletes what no manifest in the bucket names.
+
+use futures::StreamExt;
+use kubimo::chrono::{DateTime, Utc};
+use kubimo::url::Url;
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncRead, AsyncSeek, AsyncSeekExt};
+use tokio_util::io::ReaderStream;
+
+/// Where a workspace's content goes when it opts in.
+#[derive(Clone, Debug)]
+pub struct ContentStore {
+    bucket: String,
+}
+
+impl ContentStore {
+    pub fn new(bucket: impl Into<String>) -> Self {
+        Self {
+            bucket: bucket.into(),
+        }
+    }
+
+    pub fn url(&self, digest: &str) -> Result<Url, kubimo::url::ParseError> {
+        kubimo::content_store_url(&self.bucket, digest)
+    }
+}
+
+/// The sha256 (lowercase hex) and crc32 of everything `input` yields, leaving
+/// it rewound for the upload that may follow. One read for both: the digest
+/// names the object and the crc32 is what the manifest verifies against.
+pub async fn digest_of(
+    input: &mut (impl AsyncRead + AsyncSeek + Unpin),
+) -> std::io::Result<(String, u32)> {
+    let mut sha256 = Sha256::new();
+    let mut crc32 = crc32fast::Hasher::new();
+    let mut stream = ReaderStream::new(&mut *input);
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
+        sha256.update(&chunk);
+        crc32.update(&chunk);
+    }
+    input.rewind().await?;
+    Ok((format!("{:x}", sha256.finalize()), crc32.finalize()))
+}
+
+/// Must an existing object be written again before it is reused? See
+/// [`kubimo::CONTENT_STORE_REFRESH_SECS`] for the race this closes.
+pub fn needs_refresh(last_modified: DateTime<Utc>, now: DateTime<Utc>) -> bool {
+    (now - last_modified).num_seconds() >= kubimo::CONTENT_STORE_REFRESH_SECS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kubimo::chrono::TimeDelta;
+
+    #[tokio::test]
+    async fn digest_names_the_bytes_and_rewinds() {
+        let mut input = std::io::Cursor::new(b"hello world".to_vec());
+        let (digest, crc32) = digest_of(&mut input).await.unwrap();
+        assert_eq!(
+            digest,
+            "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9"
+        );
+        assert_eq!(crc32, crc32fast::hash(b"hello world"));
+        assert_eq!(input.position(), 0);
+        let url = ContentStore::new("bucket").url(&digest).unwrap();
+        assert!(kubimo::is_content_store_url(&url));
+    }
+
+    #[test]
+    fn only_old_objects_are_refreshed() {
+        let now = Utc::now();
+        let refresh = TimeDelta::seconds(kubimo::CONTENT_STORE_REFRESH_SECS);
+        assert!(!needs_refresh(now, now));
+        assert!(!needs_refresh(now - refresh + TimeDelta::seconds(1), now));
+        assert!(needs_refresh(now - refresh, now));
+    }
+}
@@ -0,0 +1,543 @@
+//! Client-side envelope encryption of archive objects.
+//!
+//! Each archive has one data key, minted by whichever upload first finds it
+//! without one and reused for as long as it can be opened. The manifest
+//! carries it wrapped under a key-encryption key; the key-encryption keys
+//! themselves travel with the workspace's S3 credentials as
+//! [`KEYS_OPTION`], so the bucket only ever holds ciphertext and a wrapped
+//! key it cannot unwrap.
+//!
+//! Objects are sealed in fixed-size segments rather than whole, so an upload
+//! or a restore streams through a file instead of holding it in memory. The
+//! layout is [`MAGIC`], a random salt, then each segment's ciphertext and tag.
+//! Every object gets its own AES-256-GCM key, derived from the data key and
+//! the salt, so nonces can be a plain segment counter: no two objects ever
+//! share a key for a counter to repeat under. The final segment's nonce is
+//! marked, which makes a truncated object fail to open rather than open short.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use base64::Engine as _;
+use kubimo::ManifestEncryption;
+use ring::aead::{AES_256_GCM, Aad, LessSafeKey, NONCE_LEN, Nonce, UnboundKey};
+use ring::hkdf::{HKDF_SHA256, Salt};
+use ring::hmac;
+use ring::rand::{SecureRandom, SystemRandom};
+use thiserror::Error;
+
+/// The credential entry holding the key-encryption keys: comma-separated
+/// `{id}={base64 of 32 bytes}`, the first of which wraps new data keys. The
+/// rest only unwrap, which is how a key is rotated out: list the new one
+/// first, and drop the old one once every archive has been rewritten.
+pub const KEYS_OPTION: &str = "KUBIMO_ARCHIVE_KEYS";
+
+/// Leads every sealed object. Content is told apart by the manifest, not by
+/// this — a tenant's file may begin with anything — but the secrets object is
+/// always JSON, so there it is unambiguous.
+pub const MAGIC: &[u8; 4] = b"KBE1";
+
+const KEY_LEN: usize = 32;
+const SALT_LEN: usize = 16;
+const TAG_LEN: usize = 16;
+const HEADER_LEN: usize = MAGIC.len() + SALT_LEN;
+const SEGMENT_LEN: usize = 64 * 1024;
+const SEALED_SEGMENT_LEN: usize = SEGMENT_LEN + TAG_LEN;
+const OBJECT_KEY_INFO: &[u8] = b"kubimo archive object";
+const OBJECT_NAME_INFO: &[u8] = b"kubimo archive object name";
+
+#[derive(Debug, Error)]
+pub enum EncryptionError {
+    #[error("malformed {KEYS_OPTION}: {0}")]
+    MalformedKeys(String),
+    #[error("archive data key is wrapped with key {0:?}, which the keyring does not hold")]
+    UnknownKey(String),
+    #[error("archive data key could not be unwrapped")]
+    Unwrap,
+    #[error("object is sealed but the archive names no data key")]
+    NoDataKey,
+    #[error("object is not sealed")]
+    NotSealed,
+    #[error("sealed object failed to open: corrupt, truncated or sealed with another key")]
+    Open,
+    #[error("could not gather randomness")]
+    Random,
+    #[error("object is too large to seal")]
+    TooLarge,
+}
+
+/// The key-encryption keys, by id.
+pub struct Keyring {
+    primary: String,
+    keys: BTreeMap<String, LessSafeKey>,
+}
+
+impl std::fmt::Debug for Keyring {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Keyring")
+            .field("primary", &self.primary)
+            .field("ids", &self.keys.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl Keyring {
+    /// Parse a [`KEYS_OPTION`] value. Anything malformed is an error rather
+    /// than a smaller keyring: a key quietly dropped is an archive that stops
+    /// being encrypted, or one that no longer restores.
+    pub fn parse(value: &str) -> Result<Self, EncryptionError> {
+        let malformed = |reason: String| EncryptionError::MalformedKeys(reason);
+        let mut primary = None;
+        let mut keys = BTreeMap::new();
+        for entry in value.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (id, key) = entry
+                .split_once('=')
+                .ok_or_else(|| malformed("expected {id}={base64 key}".to_string()))?;
+            let id = id.trim();
+            if id.is_empty()
+                || !id
+                    .bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.'))
+            {
+                return Err(malformed(format!("bad key id {id:?}")));
+            }
+            let bytes = base64::engine::general_purpose::STANDARD
+                .decode(key.trim())
+                .map_err(|err| malformed(format!("key {id}: {err}")))?;
+            if bytes.len() != KEY_LEN {
+                return Err(malformed(format!(
+                    "key {id} is {} bytes, not {KEY_LEN}",
+                    bytes.len()
+                )));
+            }
+            let key = UnboundKey::new(&AES_256_GCM, &bytes)
+                .map_err(|_| malformed(format!("key {id} is unusable")))?;
+            if keys.insert(id.to_string(), LessSafeKey::new(key)).is_some() {
+                return Err(malformed(format!("key {id} is listed twice")));
+            }
+            primary.get_or_insert_with(|| id.to_string());
+        }
+        let primary = primary.ok_or_else(|| malformed("no keys".to_string()))?;
+        Ok(Self { primary, keys })
+    }
+
+    /// A fresh data key, wrapped under the primary key.
+    pub fn mint(&self) -> Result<DataKey, EncryptionError> {
+        let mut bytes = [0u8; KEY_LEN];
+        SystemRandom::new()
+            .fill(&mut bytes)
+            .map_err(|_| EncryptionError::Random)?;
+        self.wrap(bytes)
+    }
+
+    /// Unwrap an archive's data key. It comes back wrapped under the primary
+    /// key whichever key it arrived under, so an archive written with it
+    /// migrates to the current key without a byte of content being rewritten.
+    pub fn unwrap(&self, envelope: &ManifestEncryption) -> Result<DataKey, EncryptionError> {
+        let kek = self
+            .keys
+            .get(&envelope.key_id)
+            .ok_or_else(|| EncryptionError::UnknownKey(envelope.key_id.clone()))?;
+        let mut wrapped = base64::engine::general_purpose::STANDARD
+            .decode(&envelope.wrapped_key)
+            .map_err(|_| EncryptionError::Unwrap)?;
+        if wrapped.len() != NONCE_LEN + KEY_LEN + TAG_LEN {
+            return Err(EncryptionError::Unwrap);
+        }
+        let (nonce, sealed) = wrapped.split_at_mut(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(nonce).map_err(|_| EncryptionError::Unwrap)?;
+        let bytes = kek
+            .open_in_place(nonce, Aad::from(envelope.key_id.as_bytes()), sealed)
+            .map_err(|_| EncryptionError::Unwrap)?;
+        let bytes: [u8; KEY_LEN] = (&*bytes).try_into().map_err(|_| EncryptionError::Unwrap)?;
+        self.wrap(bytes)
+    }
+
+    fn wrap(&self, bytes: [u8; KEY_LEN]) -> Result<DataKey, EncryptionError> {
+        let kek = &self.keys[&self.primary];
+        // Random nonces are fine here: a key-encryption key wraps one data key
+        // per archive, nowhere near enough for two to collide.
+        let mut nonce = [0u8; NONCE_LEN];
+        SystemRandom::new()
+            .fill(&mut nonce)
+            .map_err(|_| EncryptionError::Random)?;
+        let mut sealed = bytes.to_vec();
+        kek.seal_in_place_append_tag(
+            Nonce::assume_unique_for_key(nonce),
+            Aad::from(self.primary.as_bytes()),
+            &mut sealed,
+        )
+        .map_err(|_| EncryptionError::Random)?;
+        let mut wrapped = nonce.to_vec();
+        wrapped.extend(sealed);
+        Ok(DataKey {
+            bytes: Arc::new(bytes),
+            envelope: ManifestEncryption {
+                key_id: self.primary.clone(),
+                wrapped_key: base64::engine::general_purpose::STANDARD.encode(wrapped),
+            },
+        })
+    }
+}
+
+/// An archive's data key, with the envelope its manifest records.
+#[derive(Clone)]
+pub struct DataKey {
+    bytes: Arc<[u8; KEY_LEN]>,
+    envelope: ManifestEncryption,
+}
+
+impl std::fmt::Debug for DataKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DataKey")
+            .field("key_id", &self.envelope.key_id)
+            .finish_non_exhaustive()
+    }
+}
+
+impl DataKey {
+    pub fn envelope(&self) -> &ManifestEncryption {
+        &self.envelope
+    }
+
+    fn object_key(&self, salt: &[u8]) -> LessSafeKey {
+        let prk = Salt::new(HKDF_SHA256, salt).extract(self.bytes.as_slice());
+        let okm = prk
+            .expand(&[OBJECT_KEY_INFO], &AES_256_GCM)
+            .expect("an AES-256 key is a valid HKDF output length");
+        LessSafeKey::new(UnboundKey::from(okm))
+    }
+
+    /// The name to store content with `digest` under. A keyed hash rather
+    /// than the digest itself, which would let anyone listing the bucket test
+    /// it for files they already have; and it differs between data keys, so
+    /// content sealed under an earlier key is never mistaken for this one's.
+    pub fn object_name(&self, digest: &[u8]) -> String {
+        let prk = Salt::new(HKDF_SHA256, &[]).extract(self.bytes.as_slice());
+        let okm = prk
+            .expand(&[OBJECT_NAME_INFO], hmac::HMAC_SHA256)
+            .expect("an HMAC-SHA256 key is a valid HKDF output length");
+        hmac::sign(&hmac::Key::from(okm), digest)
+            .as_ref()
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect()
+    }
+
+    pub fn sealer(&self) -> Result<Sealer, EncryptionError> {
+        let mut salt = [0u8; SALT_LEN];
+        SystemRandom::new()
+            .fill(&mut salt)
+            .map_err(|_| EncryptionError::Random)?;
+        Ok(Sealer {
+            key: self.object_key(&salt),
+            header: Some(salt),
+            segment: 0,
+            buffer: Vec::with_capacity(SEGMENT_LEN),
+        })
+    }
+
+    pub fn opener(&self) -> Opener {
+        Opener {
+            data_key: self.clone(),
+            key: None,
+            segment: 0,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Seal `plain` whole. For small objects; content goes through
+    /// [`Self::sealer`].
+    pub fn seal(&self, plain: &[u8]) -> Result<Vec<u8>, EncryptionError> {
+        let mut sealer = self.sealer()?;
+        let mut out = Vec::with_capacity(sealed_len(plain.len() as u64) as usize);
+        sealer.update(plain, &mut out)?;
+        sealer.finish(&mut out)?;
+        Ok(out)
+    }
+
+    /// Open a whole sealed object.
+    pub fn open(&self, sealed: &[u8]) -> Result<Vec<u8>, EncryptionError> {
+        let mut opener = self.opener();
+        let mut out = Vec::with_capacity(sealed.len());
+        opener.update(sealed, &mut out)?;
+        opener.finish(&mut out)?;
+        Ok(out)
+    }
+}
+
+/// Does `bytes` begin like a sealed object?
+pub fn is_sealed(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+/// How many bytes sealing `plain_len` bytes produces, for sizing uploads.
+pub fn sealed_len(plain_len: u64) -> u64 {
+    let segments = plain_len.div_ceil(SEGMENT_LEN as u64).max(1);
+    HEADER_LEN as u64 + segments * TAG_LEN as u64 + plain_len
+}
+
+fn nonce(segment: u32, last: bool) -> Nonce {
+    let mut nonce = [0u8; NONCE_LEN];
+    nonce[NONCE_LEN - 5..NONCE_LEN - 1].copy_from_slice(&segment.to_be_bytes());
+    nonce[NONCE_LEN - 1] = last.into();
+    Nonce::assume_unique_for_key(nonce)
+}
+
+/// Streaming half of [`DataKey::seal`]. A full segment is held back until
+/// more input arrives, since only [`Self::finish`] knows which one is last.
+pub struct Sealer {
+    key: LessSafeKey,
+    header: Option<[u8; SALT_LEN]>,
+    segment: u32,
+    buffer: Vec<u8>,
+}
+
+impl Sealer {
+    pub fn update(&mut self, mut input: &[u8], out: &mut Vec<u8>) -> Result<(), EncryptionError> {
+        self.write_header(out);
+        while !input.is_empty() {
+            if self.buffer.len() == SEGMENT_LEN {
+                self.seal_segment(false, out)?;
+            }
+            let take = (SEGMENT_LEN - self.buffer.len()).min(input.len());
+            self.buffer.extend_from_slice(&input[..take]);
+            input = &input[take..];
+        }
+        Ok(())
+    }
+
+    pub fn finish(mut self, out: &mut Vec<u8>) -> Result<(), EncryptionError> {
+        self.write_header(out);
+        self.seal_segment(true, out)
+    }
+
+    fn write_header(&mut self, out: &mut Vec<u8>) {
+        if let Some(salt) = self.header.take() {
+            out.extend_from_slice(MAGIC);
+            out.extend_from_slice(&salt);
+        }
+    }
+
+    fn seal_segment(&mut self, last: bool, out: &mut Vec<u8>) -> Result<(), EncryptionError> {
+        let tag = self
+            .key
+            .seal_in_place_separate_tag(nonce(self.segment, last), Aad::empty(), &mut self.buffer)
+            .map_err(|_| EncryptionError::TooLarge)?;
+        out.append(&mut self.buffer);
+        out.extend_from_slice(tag.as_ref());
+        self.segment = self
+            .segment
+            .checked_add(1)
+            .ok_or(EncryptionError::TooLarge)?;
+        Ok(())
+    }
+}
+
+/// Streaming half of [`DataKey::open`], with the same hold-back as
+/// [`Sealer`]: a segment is only opened once it is known not to be the last.
+pub struct Opener {
+    data_key: DataKey,
+    key: Option<LessSafeKey>,
+    segment: u32,
+    buffer: Vec<u8>,
+}
+
+impl Opener {
+    pub fn update(&mut self, input: &[u8], out: &mut Vec<u8>) -> Result<(), EncryptionError> {
+        self.buffer.extend_from_slice(input);
+        if self.key.is_none() {
+            if self.buffer.len() < HEADER_LEN {
+                return Ok(());
+            }
+            if !is_sealed(&self.buffer) {
+                return Err(EncryptionError::NotSealed);
+            }
+            self.key = Some(
+                self.data_key
+                    .object_key(&self.buffer[MAGIC.len()..HEADER_LEN]),
+            );
+            self.buffer.drain(..HEADER_LEN);
+        }
+        let mut start = 0;
+        while self.buffer.len() - start > SEALED_SEGMENT_LEN {
+            let end = start + SEALED_SEGMENT_LEN;
+            self.open_segment(start..end, false, out)?;
+            start = end;
+        }
+        self.buffer.drain(..start);
+        Ok(())
+    }
+
+    pub fn finish(mut self, out: &mut Vec<u8>) -> Result<(), EncryptionError> {
+        if self.key.is_none() {
+            return Err(if is_sealed(&self.buffer) {
+                EncryptionError::Open
+            } else {
+                EncryptionError::NotSealed
+            });
+        }
+        if self.buffer.len() < TAG_LEN {
+            return Err(EncryptionError::Open);
+        }
+        let len = self.buffer.len();
+        self.open_segment(0..len, true, out)
+    }
+
+    fn open_segment(
+        &mut self,
+        range: std::ops::Range<usize>,
+        last: bool,
+        out: &mut Vec<u8>,
+    ) -> Result<(), EncryptionError> {
+        let key = self.key.as_ref().ok_or(EncryptionError::Open)?;
+        let plain = key
+            .open_in_place(
+                nonce(self.segment, last),
+                Aad::empty(),
+                &mut self.buffer[range],
+            )
+            .map_err(|_| EncryptionError::Open)?;
+        out.extend_from_slice(plain);
+        self.segment = self.segment.checked_add(1).ok_or(EncryptionError::Open)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(byte: u8) -> String {
+        base64::engine::general_purpose::STANDARD.encode([byte; KEY_LEN])
+    }
+
+    fn keyring(value: &str) -> Keyring {
+        Keyring::parse(value).unwrap()
+    }
+
+    #[test]
+    fn objects_round_trip_across_segment_boundaries() {
+        let data_key = keyring(&format!("k1={}", key(1))).mint().unwrap();
+        for len in [
+            0,
+            1,
+            SEGMENT_LEN - 1,
+            SEGMENT_LEN,
+            SEGMENT_LEN + 1,
+            3 * SEGMENT_LEN,
+        ] {
+            let plain = (0..len).map(|i| i as u8).collect::<Vec<_>>();
+            let sealed = data_key.seal(&plain).unwrap();
+            assert_eq!(sealed.len() as u64, sealed_len(len as u64), "{len}");
+            assert!(is_sealed(&sealed));
+            // Fed in awkward pieces, as a download stream would.
+            let mut opener = data_key.opener();
+            let mut opened = Vec::new();
+            for chunk in sealed.chunks(7919) {
+                opener.update(chunk, &mut opened).unwrap();
+            }
+            opener.finish(&mut opened).unwrap();
+            assert_eq!(opened, plain, "{len}");
+        }
+    }
+
+    /// Dropping whole trailing segments leaves every remaining tag valid, so
+    /// only the last-segment marker catches it.
+    #[test]
+    fn truncated_or_tampered_objects_fail_to_open() {
+        let data_key = keyring(&format!("k1={}", key(1))).mint().unwrap();
+        let sealed = data_key.seal(&vec![7u8; 2 * SEGMENT_LEN + 10]).unwrap();
+        let truncated = &sealed[..HEADER_LEN + SEALED_SEGMENT_LEN];
+        assert!(matches!(
+            data_key.open(truncated),
+            Err(EncryptionError::Open)
+        ));
+        let mut tampered = sealed.clone();
+        tampered[HEADER_LEN + 3] ^= 1;
+        assert!(matches!(
+            data_key.open(&tampered),
+            Err(EncryptionError::Open)
+        ));
+        assert!(matches!(
+            data_key.open(b"{\"plain\": true}"),
+            Err(EncryptionError::NotSealed)
+        ));
+        let other = keyring(&format!("k1={}", key(1))).mint().unwrap();
+        assert!(matches!(other.open(&sealed), Err(EncryptionError::Open)));
+    }
+
+    /// Rotation: a keyring that lists a new primary ahead of the old key still
+    /// opens the old envelope, and hands the same data key back wrapped under
+    /// the new one.
+    #[test]
+    fn an_old_envelope_unwraps_and_rewraps_under_the_primary() {
+        let old = keyring(&format!("old={}", key(1)));
+        let data_key = old.mint().unwrap();
+        let sealed = data_key.seal(b"hello").unwrap();
+
+        let rotated = keyring(&format!("new={}, old={}", key(2), key(1)));
+        let unwrapped = rotated.unwrap(data_key.envelope()).unwrap();
+        assert_eq!(unwrapped.envelope().key_id, "new");
+        assert_eq!(unwrapped.open(&sealed).unwrap(), b"hello");
+        assert_eq!(
+            keyring(&format!("new={}", key(2)))
+                .unwrap(unwrapped.envelope())
+                .unwrap()
+                .open(&sealed)
+                .unwrap(),
+            b"hello"
+        );
+
+        assert!(matches!(
+            keyring(&format!("new={}", key(2))).unwrap(data_key.envelope()),
+            Err(EncryptionError::UnknownKey(id)) if id == "old"
+        ));
+        // The right id over the wrong key material must not unwrap to garbage.
+        assert!(matches!(
+            keyring(&format!("old={}", key(3))).unwrap(data_key.envelope()),
+            Err(EncryptionError::Unwrap)
+        ));
+    }
+
+    /// Names survive a key-encryption key rotation, which keeps the data key,
+    /// and differ under a new data key.
+    #[test]
+    fn object_names_follow_the_data_key() {
+        let old = keyring(&format!("old={}", key(1)));
+        let data_key = old.mint().unwrap();
+        let digest = [7u8; 32];
+        let name = data_key.object_name(&digest);
+        assert_eq!(name.len(), 64);
+        assert_ne!(name, data_key.object_name(&[8u8; 32]));
+
+        let rotated = keyring(&format!("new={}, old={}", key(2), key(1)));
+        let unwrapped = rotated.unwrap(data_key.envelope()).unwrap();
+        assert_eq!(unwrapped.object_name(&digest), name);
+        assert_ne!(old.mint().unwrap().object_name(&digest), name);
+    }
+
+    #[test]
+    fn malformed_keyrings_are_rejected_whole() {
+        for value in [
+            "",
+            "k1",
+            &format!("={}", key(1)),
+            "k1=not base64",
+            &format!(
+                "k1={}",
+                base64::engine::general_purpose::STANDARD.encode([1u8; 16])
+            ),
+            &format!("k1={},k1={}", key(1), key(2)),
+            &format!("k 1={}", key(1)),
+        ] {
+            assert!(
+                matches!(
+                    Keyring::parse(value),
+                    Err(EncryptionError::MalformedKeys(_))
+                ),
+                "{value:?} parsed"
+            );
+        }
+    }
+}
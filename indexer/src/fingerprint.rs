@@ -18,7 +18,7 @@ use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::SystemTime;
 
-use kubimo::WorkspaceDirContentUrl;
+use kubimo::{ContentCodec, WorkspaceDirContentChunk, WorkspaceDirContentUrl};
 use tokio::sync::RwLock;
 
 /// What was true about a file the last time it was successfully uploaded.
@@ -35,6 +35,9 @@ struct WorkspaceDirContentUrlKey {
     url: String,
     crc32: Option<u32>,
     e_tag: Option<String>,
+    encrypted: Option<bool>,
+    codec: Option<ContentCodec>,
+    chunks: Option<Vec<WorkspaceDirContentChunk>>,
 }
 
 /// Remembers which files are already in S3 unchanged.
@@ -67,6 +70,9 @@ impl ContentCache {
             url: found.content.url.parse().ok()?,
             crc32: found.content.crc32,
             e_tag: found.content.e_tag.clone(),
+            encrypted: found.content.encrypted,
+            codec: found.content.codec,
+            chunks: found.content.chunks.clone(),
         })
     }
 
@@ -86,6 +92,9 @@ impl ContentCache {
                     url: content.url.to_string(),
                     crc32: content.crc32,
                     e_tag: content.e_tag.clone(),
+                    encrypted: content.encrypted,
+                    codec: content.codec,
+                    chunks: content.chunks.clone(),
                 },
             },
         );
@@ -116,6 +125,9 @@ mod tests {
             url: url.parse().unwrap(),
             crc32: Some(7),
             e_tag: Some("etag".into()),
+            encrypted: None,
+            codec: None,
+            chunks: None,
         }
     }
 
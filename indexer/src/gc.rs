@@ -0,0 +1,397 @@
+//! Mark-and-sweep collection of archive objects nothing names any more.
+//!
+//! Two kinds of object end up unreachable. Per-archive content is orphaned
+//! whenever `upload::run` dies between writing its manifest and sweeping what
+//! the manifest stopped naming: the next cycle starts from the CRs, which may
+//! already have moved on, and never learns those objects existed. And the
+//! shared content store (see [`crate::content_store`]) has no owner at all, so
+//! whether one of its objects is still needed can only be answered by reading
+//! everything that might name it.
+//!
+//! Reachable means named by a current manifest, a retained generation, a
+//! snapshot copy's manifest or a `WorkspaceDirectory` CR, anywhere in the
+//! bucket. The CRs count because they are written after the manifest, and an
+//! archive written by an indexer that predates manifests has nothing else.
+//!
+//! There is no refcount to keep consistent with the archives, and so nothing
+//! to repair when a writer dies halfway. The cost is that collection reads
+//! every manifest in the bucket, which is why it runs as its own command
+//! rather than as part of a sync.
+
+use std::collections::BTreeSet;
+use std::time::Duration;
+
+use futures::stream::{self, StreamExt, TryStreamExt};
+use kubimo::chrono::{DateTime, TimeDelta, Utc};
+use kubimo::url::Url;
+use kubimo::{FilterParams, WorkspaceDir, WorkspaceManifest};
+use thiserror::Error;
+
+use crate::s3::{DownloadError, ListError, ListedObject, S3Client};
+
+#[derive(Debug, Error)]
+pub enum GcError {
+    #[error(transparent)]
+    List(#[from] ListError),
+    #[error(transparent)]
+    Url(#[from] kubimo::url::ParseError),
+    #[error("could not read manifest {url}: {source}")]
+    Read { url: Url, source: DownloadError },
+    #[error("could not parse manifest {url}: {source}")]
+    Parse { url: Url, source: serde_json::Error },
+    #[error("grace period of {0}s is shorter than the minimum of {1}s")]
+    GraceTooShort(u64, u64),
+}
+
+#[derive(Clone, Debug)]
+pub struct GcOptions {
+    pub bucket: String,
+    /// Only objects whose key starts with this may be deleted. Matched as a
+    /// raw string, like the archives' own `{prefix}{name}` keys. Manifests
+    /// are read from the whole bucket either way: one outside the prefix may
+    /// still name objects inside it.
+    pub key_prefix: Option<String>,
+    /// Unreachable objects younger than this are kept. They may belong to a
+    /// sync whose manifest has not landed yet.
+    pub grace: Duration,
+    /// Report what would be deleted without deleting it.
+    pub dry_run: bool,
+    pub max_concurrency: usize,
+}
+
+impl GcOptions {
+    /// Twice the content store's refresh interval: a reused store object is
+    /// never older than that interval, and the second half covers the sync
+    /// that reuses it. Per-archive content needs far less, but one floor for
+    /// both keeps the flag from being safe for one and not the other.
+    pub const MIN_GRACE_SECS: u64 = 2 * kubimo::CONTENT_STORE_REFRESH_SECS as u64;
+    pub const DEFAULT_GRACE_SECS: u64 = 24 * 60 * 60;
+}
+
+/// What a collection found, and did or would do.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct GcSummary {
+    /// Objects under the prefix, manifests and the like included.
+    pub scanned: usize,
+    /// Manifests and generations read.
+    pub manifests: usize,
+    /// Distinct urls the manifests and CRs name.
+    pub reachable: usize,
+    /// Unreachable objects kept because they are younger than the grace.
+    pub young: usize,
+    /// Unreachable objects deleted — or, on a dry run, that would be.
+    pub collected: usize,
+    pub collected_bytes: u64,
+    /// Deletes that failed. The next collection retries them.
+    pub failed: usize,
+}
+
+fn file_name(object: &ListedObject) -> Option<&str> {
+    object.url.path_segments()?.next_back()
+}
+
+/// Is `object` something whose urls keep other objects alive: a manifest at
+/// any archive root, or one of its generations?
+///
+/// By suffix rather than by name, because a key prefix without a trailing
+/// slash is concatenated raw: the archive at `ws1-` keeps its manifest at
+/// `ws1-manifest.json` and its history under `ws1-history/`.
+fn names_content(object: &ListedObject) -> bool {
+    let Some(mut segments) = object.url.path_segments() else {
+        return false;
+    };
+    let Some(file_name) = segments.next_back() else {
+        return false;
+    };
+    if file_name.ends_with(kubimo::MANIFEST_FILE_NAME) {
+        return true;
+    }
+    segments
+        .next_back()
+        .is_some_and(|dir| dir.ends_with(kubimo::HISTORY_DIR_NAME))
+        && file_name
+            .strip_suffix(".json")
+            .and_then(kubimo::parse_generation_name)
+            .is_some()
+}
+
+/// Objects an archive keeps by construction rather than by naming them, and
+/// which are therefore never collected however unreachable they look.
+fn is_structural(object: &ListedObject) -> bool {
+    names_content(object)
+        || file_name(object).is_some_and(|name| name.ends_with(kubimo::SECRETS_FILE_NAME))
+}
+
+/// Keys are relative to `root` rather than to the url's path, which for a
+/// `file://` archive starts with the directory the archive lives in.
+fn in_scope(object: &ListedObject, root: &Url, key_prefix: Option<&str>) -> bool {
+    object
+        .url
+        .as_str()
+        .strip_prefix(root.as_str())
+        .is_some_and(|key| key.starts_with(key_prefix.unwrap_or("")))
+}
+
+/// Every url a manifest or generation in `objects` names.
+///
+/// Fails rather than skipping anything it cannot read: a manifest left out of
+/// the mark is a set of objects the sweep deletes from under a live archive.
+async fn mark(
+    s3: &S3Client,
+    objects: &[ListedObject],
+    summary: &mut GcSummary,
+) -> Result<BTreeSet<Url>, GcError> {
+    let mut reachable = BTreeSet::new();
+    for object in objects.iter().filter(|object| names_content(object)) {
+        let url = &object.url;
+        let bytes = s3.get_bytes(url).await.map_err(|source| GcError::Read {
+            url: url.clone(),
+            source,
+        })?;
+        let manifest: WorkspaceManifest =
+            serde_json::from_slice(&bytes).map_err(|source| GcError::Parse {
+                url: url.clone(),
+                source,
+            })?;
+        summary.manifests += 1;
+        reachable.extend(manifest.urls().cloned());
+    }
+    Ok(reachable)
+}
+
+/// Every url a `WorkspaceDirectory` CR in the cluster names. Fails on the
+/// first error for the same reason [`mark`] does.
+pub async fn workspace_dir_urls(client: &kubimo::Client) -> Result<BTreeSet<Url>, kubimo::Error> {
+    let mut urls = BTreeSet::new();
+    let mut workspace_dirs = client
+        .api_global::<WorkspaceDir>()
+        .list(&FilterParams::new());
+    while let Some(workspace_dir) = workspace_dirs.try_next().await? {
+        for file in workspace_dir
+            .item
+            .spec
+            .entries
+            .iter()
+            .flatten()
+            .filter_map(|entry| entry.file.as_ref())
+        {
+            urls.extend(file.urls().cloned());
+        }
+    }
+    Ok(urls)
+}
+
+/// The objects in `objects` that may be deleted: in scope, not structural,
+/// named by nothing in `reachable`, and last written at least `grace` before
+/// `now`.
+fn unreachable<'a>(
+    objects: &'a [ListedObject],
+    root: &Url,
+    key_prefix: Option<&str>,
+    reachable: &BTreeSet<Url>,
+    grace: TimeDelta,
+    now: DateTime<Utc>,
+    summary: &mut GcSummary,
+) -> Vec<&'a ListedObject> {
+    let mut collect = Vec::new();
+    for object in objects
+        .iter()
+        .filter(|object| in_scope(object, root, key_prefix))
+    {
+        summary.scanned += 1;
+        if is_structural(object) || reachable.contains(&object.url) {
+            continue;
+        }
+        if now - object.last_modified < grace {
+            summary.young += 1;
+            continue;
+        }
+        summary.collected += 1;
+        summary.collected_bytes += object.size;
+        collect.push(object);
+    }
+    collect
+}
+
+/// Collect `opts.bucket`, given the urls the cluster's CRs name in `live`.
+///
+/// The bucket is listed once, before any manifest is read. An object written
+/// after the listing is not a candidate at all, and one written before it is
+/// either named by a manifest the mark then reads at its newest, or young
+/// enough for the grace to spare.
+pub async fn gc(
+    opts: &GcOptions,
+    s3: &S3Client,
+    live: &BTreeSet<Url>,
+) -> Result<GcSummary, GcError> {
+    if opts.grace.as_secs() < GcOptions::MIN_GRACE_SECS {
+        return Err(GcError::GraceTooShort(
+            opts.grace.as_secs(),
+            GcOptions::MIN_GRACE_SECS,
+        ));
+    }
+    let grace = TimeDelta::from_std(opts.grace).unwrap_or(TimeDelta::MAX);
+    let root = kubimo::bucket_url(&opts.bucket)?;
+    let mut summary = GcSummary::default();
+    let objects = s3.list(&root).await?;
+    let mut reachable = mark(s3, &objects, &mut summary).await?;
+    reachable.extend(live.iter().cloned());
+    summary.reachable = reachable.len();
+    let now = Utc::now();
+    let collect = unreachable(
+        &objects,
+        &root,
+        opts.key_prefix.as_deref(),
+        &reachable,
+        grace,
+        now,
+        &mut summary,
+    );
+    if opts.dry_run {
+        for object in collect {
+            tracing::info!(
+                "Would delete {} ({} bytes, last written {})",
+                object.url,
+                object.size,
+                object.last_modified
+            );
+        }
+        return Ok(summary);
+    }
+    summary.failed = stream::iter(collect)
+        .map(|object| async move {
+            match s3.delete(&object.url).await {
+                Ok(()) => {
+                    tracing::info!("Deleted {} ({} bytes)", object.url, object.size);
+                    0
+                }
+                Err(err) => {
+                    tracing::error!("Error deleting {}: {err}", object.url);
+                    1
+                }
+            }
+        })
+        .buffer_unordered(opts.max_concurrency.max(1))
+        .fold(0, |failed, result| async move { failed + result })
+        .await;
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIGEST_A: &str = "a000000000000000000000000000000000000000000000000000000000000000";
+    const DIGEST_B: &str = "b000000000000000000000000000000000000000000000000000000000000000";
+    const DIGEST_C: &str = "c000000000000000000000000000000000000000000000000000000000000000";
+
+    fn listed(url: &str, age_hours: i64, now: DateTime<Utc>) -> ListedObject {
+        ListedObject {
+            url: url.parse().unwrap(),
+            size: 10,
+            last_modified: now - TimeDelta::hours(age_hours),
+        }
+    }
+
+    fn collected(
+        objects: &[ListedObject],
+        key_prefix: Option<&str>,
+        reachable: &[&str],
+        now: DateTime<Utc>,
+    ) -> (Vec<String>, GcSummary) {
+        let reachable = reachable
+            .iter()
+            .map(|url| url.parse().unwrap())
+            .collect::<BTreeSet<Url>>();
+        let mut summary = GcSummary::default();
+        let collect = unreachable(
+            objects,
+            &"s3://bucket/".parse().unwrap(),
+            key_prefix,
+            &reachable,
+            TimeDelta::hours(24),
+            now,
+            &mut summary,
+        );
+        (
+            collect
+                .iter()
+                .map(|object| object.url.to_string())
+                .collect(),
+            summary,
+        )
+    }
+
+    #[test]
+    fn manifests_generations_and_snapshots_are_marked() {
+        let now = Utc::now();
+        let marked = |url: &str| names_content(&listed(url, 0, now));
+        assert!(marked("s3://bucket/manifest.json"));
+        assert!(marked("s3://bucket/ws/manifest.json"));
+        assert!(marked("s3://bucket/ws1-manifest.json"));
+        assert!(marked("s3://bucket/ws/snapshots/uid/manifest.json"));
+        assert!(marked("s3://bucket/ws/history/20260101T000000.000Z.json"));
+        assert!(marked("s3://bucket/ws1-history/20260101T000000.000Z.json"));
+        assert!(!marked("s3://bucket/ws/secrets.json"));
+        assert!(!marked("s3://bucket/ws/history/latest.json"));
+        assert!(!marked("s3://bucket/ws/abcdefghijklm.json"));
+    }
+
+    /// Only unreachable store objects past the grace go.
+    #[test]
+    fn only_old_unreachable_store_objects_are_collected() {
+        let now = Utc::now();
+        let store = |digest: &str| format!("s3://bucket/cas/sha256/{digest}");
+        let objects = vec![
+            listed(&store(DIGEST_A), 48, now),
+            listed(&store(DIGEST_B), 48, now),
+            listed(&store(DIGEST_C), 1, now),
+        ];
+        let (collect, summary) = collected(&objects, None, &[&store(DIGEST_A)], now);
+        assert_eq!(collect, vec![store(DIGEST_B)]);
+        assert_eq!(
+            summary,
+            GcSummary {
+                scanned: 3,
+                young: 1,
+                collected: 1,
+                collected_bytes: 10,
+                ..Default::default()
+            }
+        );
+    }
+
+    /// An orphan left by a cycle that died before its sweep goes; what the
+    /// archive keeps by construction stays, reachable or not.
+    #[test]
+    fn archive_orphans_go_and_structural_objects_stay() {
+        let now = Utc::now();
+        let objects = vec![
+            listed("s3://bucket/ws/manifest.json", 48, now),
+            listed("s3://bucket/ws/secrets.json", 48, now),
+            listed("s3://bucket/ws/history/20260101T000000.000Z.json", 48, now),
+            listed("s3://bucket/ws/aaaaaaaaaaaaa.py", 48, now),
+            listed("s3://bucket/ws/bbbbbbbbbbbbb.py", 48, now),
+            listed("s3://bucket/ws/ccccccccccccc.py", 1, now),
+        ];
+        let (collect, _) = collected(&objects, None, &["s3://bucket/ws/aaaaaaaaaaaaa.py"], now);
+        assert_eq!(collect, vec!["s3://bucket/ws/bbbbbbbbbbbbb.py"]);
+    }
+
+    /// The prefix narrows what may be deleted, as a raw string like the keys
+    /// themselves: `ws` would take `ws-other` with it, `ws/` does not.
+    #[test]
+    fn only_objects_under_the_prefix_are_collected() {
+        let now = Utc::now();
+        let objects = vec![
+            listed("s3://bucket/ws/aaaaaaaaaaaaa.py", 48, now),
+            listed("s3://bucket/ws-other/aaaaaaaaaaaaa.py", 48, now),
+            listed(&format!("s3://bucket/cas/sha256/{DIGEST_A}"), 48, now),
+        ];
+        let (collect, summary) = collected(&objects, Some("ws/"), &[], now);
+        assert_eq!(collect, vec!["s3://bucket/ws/aaaaaaaaaaaaa.py"]);
+        assert_eq!(summary.scanned, 1);
+        let (collect, _) = collected(&objects, Some("ws"), &[], now);
+        assert_eq!(collect.len(), 2);
+    }
+}
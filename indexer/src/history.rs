@@ -0,0 +1,512 @@
+//! Point-in-time copies of an archive's manifest.
+//!
+//! `manifest.json` is rewritten on every sync, so on its own the archive only
+//! ever describes the tree as it is now: a notebook deleted by mistake is gone
+//! from S3 one debounce window later. With history enabled the upload pipeline
+//! also copies each manifest it writes to `{prefix}history/{generation}.json`,
+//! at most once per interval, and prunes the copies retention no longer covers.
+//!
+//! A copy of the manifest is only worth something while the objects it names
+//! still hold what it says they hold. Two things keep that true: a file whose
+//! content changed is uploaded under a fresh key instead of over its old one
+//! (see `EntryWorker::upload_rotating`), and the stale-object sweep spares
+//! anything a retained generation still names. Objects are released once the
+//! last generation naming them is pruned.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use kubimo::chrono::{DateTime, Utc};
+use kubimo::{WorkspaceArchiveGeneration, WorkspaceArchiveHistory, WorkspaceManifest, url::Url};
+use thiserror::Error;
+use tokio::sync::{Mutex, Semaphore};
+
+use crate::s3::{DownloadError, ListError, ListedObject, S3Client, UploadError};
+
+/// How many generations `status.archive.generations` lists. Retention may
+/// keep more; the status is for picking a recent one, not an inventory.
+pub const STATUS_GENERATIONS: usize = 20;
+
+#[derive(Debug, Error)]
+pub enum HistoryError {
+    #[error(transparent)]
+    List(#[from] ListError),
+    #[error(transparent)]
+    Download(#[from] DownloadError),
+    #[error(transparent)]
+    Upload(#[from] UploadError),
+    #[error("error parsing manifest generation: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error(transparent)]
+    Url(#[from] kubimo::url::ParseError),
+}
+
+/// How many generations to keep and how often to cut one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryRetention {
+    pub generations: Option<usize>,
+    pub max_age: Option<Duration>,
+    pub interval: Duration,
+}
+
+impl From<&WorkspaceArchiveHistory> for HistoryRetention {
+    fn from(history: &WorkspaceArchiveHistory) -> Self {
+        Self {
+            generations: history.effective_generations().map(|n| n as usize),
+            max_age: history.max_age_secs.map(Duration::from_secs),
+            interval: Duration::from_secs(history.effective_interval_secs()),
+        }
+    }
+}
+
+impl HistoryRetention {
+    /// Whether enough time has passed since `latest` to cut another one.
+    pub fn is_due(&self, latest: Option<&Generation>, now: DateTime<Utc>) -> bool {
+        let Some(latest) = latest else {
+            return true;
+        };
+        (now - latest.created_at)
+            .to_std()
+            .is_ok_and(|elapsed| elapsed >= self.interval)
+    }
+
+    /// Split chronologically sorted `generations` into `(kept, pruned)`.
+    ///
+    /// The newest generation is always kept, whatever its age and even with a
+    /// count of zero: it is the one a restore-to-now would pick, and pruning
+    /// it would leave an archive with history enabled and none to show.
+    pub fn split(
+        &self,
+        mut generations: Vec<Generation>,
+        now: DateTime<Utc>,
+    ) -> (Vec<Generation>, Vec<Generation>) {
+        let Some(newest) = generations.pop() else {
+            return (Vec::new(), Vec::new());
+        };
+        let mut pruned = Vec::new();
+        if let Some(max_age) = self.max_age {
+            let (old, young): (Vec<_>, Vec<_>) = generations
+                .into_iter()
+                .partition(|g| (now - g.created_at).to_std().is_ok_and(|age| age > max_age));
+            pruned.extend(old);
+            generations = young;
+        }
+        if let Some(count) = self.generations {
+            // Minus one for the newest, which is kept regardless.
+            let excess = generations.len().saturating_sub(count.saturating_sub(1));
+            pruned.extend(generations.drain(..excess));
+        }
+        generations.push(newest);
+        pruned.sort_by_key(|g| g.created_at);
+        (generations, pruned)
+    }
+}
+
+/// One manifest copy under the history directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Generation {
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub url: Url,
+}
+
+impl Generation {
+    /// `None` for anything under the history directory that is not a
+    /// generation — nothing else writes there, but a listing is not a
+    /// promise.
+    fn from_listed(object: &ListedObject) -> Option<Self> {
+        let file_name = object.url.path_segments()?.next_back()?;
+        let name = file_name.strip_suffix(".json")?;
+        let created_at = kubimo::parse_generation_name(name)?;
+        Some(Self {
+            name: name.to_string(),
+            created_at,
+            url: object.url.clone(),
+        })
+    }
+}
+
+impl From<&Generation> for WorkspaceArchiveGeneration {
+    fn from(generation: &Generation) -> Self {
+        Self {
+            name: generation.name.clone(),
+            created_at: generation.created_at,
+        }
+    }
+}
+
+/// Newest first, capped at [`STATUS_GENERATIONS`].
+pub fn status_generations(generations: &[Generation]) -> Vec<WorkspaceArchiveGeneration> {
+    generations
+        .iter()
+        .rev()
+        .take(STATUS_GENERATIONS)
+        .map(Into::into)
+        .collect()
+}
+
+/// Which manifest a restore reads instead of the current one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RestorePoint {
+    /// A generation by name, as listed in `status.archive.generations`.
+    Generation(String),
+    /// The newest generation cut at or before this instant.
+    At(DateTime<Utc>),
+}
+
+impl fmt::Display for RestorePoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Generation(name) => write!(f, "generation {name}"),
+            Self::At(at) => write!(f, "the archive as of {}", at.to_rfc3339()),
+        }
+    }
+}
+
+impl RestorePoint {
+    /// The point `restoreFrom.generation`/`restoreFrom.at` select, if either
+    /// is set. `generation` wins if both are, though admission refuses that.
+    pub fn from_parts(generation: Option<String>, at: Option<DateTime<Utc>>) -> Option<Self> {
+        generation.map(Self::Generation).or(at.map(Self::At))
+    }
+}
+
+/// Pick the generation `point` names out of chronologically sorted
+/// `generations`.
+pub fn resolve<'a>(generations: &'a [Generation], point: &RestorePoint) -> Option<&'a Generation> {
+    match point {
+        RestorePoint::Generation(name) => generations.iter().find(|g| &g.name == name),
+        RestorePoint::At(at) => generations.iter().rev().find(|g| g.created_at <= *at),
+    }
+}
+
+/// Every generation of the archive at `bucket`/`key_prefix`, oldest first.
+pub async fn list_generations(
+    s3: &S3Client,
+    bucket: &str,
+    key_prefix: Option<&str>,
+) -> Result<Vec<Generation>, ListError> {
+    let url = kubimo::history_url(bucket, key_prefix)?;
+    let mut generations = s3
+        .list(&url)
+        .await?
+        .iter()
+        .filter_map(Generation::from_listed)
+        .collect::<Vec<_>>();
+    generations.sort_by_key(|g| g.created_at);
+    Ok(generations)
+}
+
+/// Content keys each generation names, remembered across sync cycles.
+///
+/// Generations are immutable once written, so a generation read once never
+/// needs reading again; without this every cycle that retires an object
+/// would fetch every retained generation to learn whether it may be deleted.
+#[derive(Clone, Default)]
+pub struct GenerationUrls {
+    by_generation: Arc<Mutex<BTreeMap<Url, BTreeSet<Url>>>>,
+}
+
+impl GenerationUrls {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    async fn fetch(&self, s3: &S3Client, generation: &Url) -> Result<BTreeSet<Url>, HistoryError> {
+        if let Some(urls) = self.by_generation.lock().await.get(generation) {
+            return Ok(urls.clone());
+        }
+        let manifest: WorkspaceManifest = serde_json::from_slice(&s3.get_bytes(generation).await?)?;
+        let urls = manifest.content_urls().cloned().collect::<BTreeSet<_>>();
+        self.by_generation
+            .lock()
+            .await
+            .insert(generation.clone(), urls.clone());
+        Ok(urls)
+    }
+
+    async fn insert(&self, generation: Url, manifest: &WorkspaceManifest) {
+        self.by_generation
+            .lock()
+            .await
+            .insert(generation, manifest.content_urls().cloned().collect());
+    }
+
+    async fn forget(&self, generation: &Url) {
+        self.by_generation.lock().await.remove(generation);
+    }
+
+    /// Every content url the given generations name. Fails if any of them
+    /// cannot be read: an incomplete answer would let the sweep delete
+    /// something a generation still needs.
+    pub async fn protected(
+        &self,
+        s3: &S3Client,
+        generations: &[Generation],
+    ) -> Result<BTreeSet<Url>, HistoryError> {
+        let mut protected = BTreeSet::new();
+        for generation in generations {
+            protected.extend(self.fetch(s3, &generation.url).await?);
+        }
+        Ok(protected)
+    }
+}
+
+/// What [`record`] left behind.
+#[derive(Debug, Default)]
+pub struct Recorded {
+    /// The retained generations, oldest first.
+    pub generations: Vec<Generation>,
+    /// Content urls the pruned generations named. Candidates for deletion,
+    /// not verdicts: a retained generation or the current tree may still
+    /// name them.
+    pub released: BTreeSet<Url>,
+}
+
+/// Cut a generation from `manifest` if one is due, then prune what retention
+/// no longer covers.
+///
+/// `manifest` is `None` when this cycle's manifest did not reach the bucket:
+/// nothing is cut — a generation must describe an archive that was actually
+/// written — but the listing is still needed to know what the sweep must
+/// spare.
+///
+/// A generation is only cut when the manifest differs from the newest one,
+/// judged by the crc32 the upload recorded for it. After a restart that
+/// marker is gone and one redundant generation may be cut; the alternative,
+/// fetching the newest generation every cycle to compare, costs a GET per
+/// save for the lifetime of the watcher.
+#[allow(clippy::too_many_arguments)]
+pub async fn record(
+    s3: &S3Client,
+    bucket: &str,
+    key_prefix: Option<&str>,
+    retention: &HistoryRetention,
+    manifest: Option<&WorkspaceManifest>,
+    generation_urls: &GenerationUrls,
+    upload_permits: &Semaphore,
+    now: DateTime<Utc>,
+) -> Result<Recorded, HistoryError> {
+    let mut generations = list_generations(s3, bucket, key_prefix).await?;
+    if let Some(manifest) = manifest {
+        let bytes = serde_json::to_vec(manifest)?;
+        let latest = generations.last();
+        let changed = match latest {
+            None => true,
+            Some(latest) => {
+                s3.cached_crc32(&latest.url, false).await != Some(crc32fast::hash(&bytes))
+            }
+        };
+        if changed && retention.is_due(latest, now) {
+            let url = kubimo::generation_url(bucket, key_prefix, now)?;
+            let size = bytes.len() as u64;
+            s3.upload(&url, std::io::Cursor::new(bytes), size, upload_permits)
+                .await?;
+            tracing::info!("Cut archive generation {url}");
+            generation_urls.insert(url.clone(), manifest).await;
+            generations.push(Generation {
+                name: kubimo::generation_name(now),
+                created_at: now,
+                url,
+            });
+        }
+    }
+    let (mut kept, pruned) = retention.split(generations, now);
+    let mut released = BTreeSet::new();
+    for generation in pruned {
+        // Read before deleting: once the object is gone, nothing records which
+        // content keys only it was holding on to.
+        match generation_urls.fetch(s3, &generation.url).await {
+            Ok(urls) => released.extend(urls),
+            Err(err) => {
+                tracing::error!(
+                    "Could not read generation {} before pruning it; keeping it: {err}",
+                    generation.name
+                );
+                kept.push(generation);
+                continue;
+            }
+        }
+        match s3.delete(&generation.url).await {
+            Ok(()) => {
+                tracing::info!("Pruned archive generation {}", generation.name);
+                generation_urls.forget(&generation.url).await;
+            }
+            Err(err) => {
+                tracing::error!("Error pruning generation {}: {err}", generation.name);
+                kept.push(generation);
+            }
+        }
+    }
+    kept.sort_by_key(|g| g.created_at);
+    Ok(Recorded {
+        generations: kept,
+        released,
+    })
+}
+
+/// Delete every generation of an archive and every content object they name.
+/// Part of purging a deleted workspace: nothing but the listing records these.
+pub async fn purge(s3: &S3Client, bucket: &str, key_prefix: Option<&str>) {
+    let generations = match list_generations(s3, bucket, key_prefix).await {
+        Ok(generations) => generations,
+        Err(err) => {
+            tracing::error!("Error listing archive generations: {err}");
+            return;
+        }
+    };
+    let generation_urls = GenerationUrls::new();
+    for generation in generations {
+        match generation_urls.fetch(s3, &generation.url).await {
+            Ok(urls) => {
+                // Store objects are shared with other archives; the collector
+                // deletes them once nothing names them.
+                for url in urls
+                    .into_iter()
+                    .filter(|url| !kubimo::is_content_store_url(url))
+                {
+                    if let Err(err) = s3.delete(&url).await {
+                        tracing::error!("Error deleting object at {url}: {err}");
+                    }
+                }
+            }
+            Err(err) => {
+                tracing::error!("Could not read generation {}: {err}", generation.name);
+            }
+        }
+        match s3.delete(&generation.url).await {
+            Ok(()) => tracing::info!("Deleted archive generation {}", generation.name),
+            Err(err) => tracing::error!("Error deleting generation {}: {err}", generation.name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINUTE: i64 = 60_000;
+
+    fn generation(minutes: i64) -> Generation {
+        let created_at =
+            DateTime::from_timestamp_millis(1_767_225_600_000 + minutes * MINUTE).unwrap();
+        Generation {
+            name: kubimo::generation_name(created_at),
+            created_at,
+            url: kubimo::generation_url("bucket", Some("ws/"), created_at).unwrap(),
+        }
+    }
+
+    fn at(minutes: i64) -> DateTime<Utc> {
+        generation(minutes).created_at
+    }
+
+    fn names(generations: &[Generation]) -> Vec<i64> {
+        generations
+            .iter()
+            .map(|g| (g.created_at - at(0)).num_minutes())
+            .collect()
+    }
+
+    fn retention(generations: Option<usize>, max_age_minutes: Option<u64>) -> HistoryRetention {
+        HistoryRetention {
+            generations,
+            max_age: max_age_minutes.map(|m| Duration::from_secs(m * 60)),
+            interval: Duration::from_secs(5 * 60),
+        }
+    }
+
+    #[test]
+    fn retention_by_count_keeps_the_newest() {
+        let all = (0..5).map(generation).collect::<Vec<_>>();
+        let (kept, pruned) = retention(Some(2), None).split(all, at(10));
+        assert_eq!(names(&kept), vec![3, 4]);
+        assert_eq!(names(&pruned), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn retention_by_age_drops_only_the_old() {
+        let all = (0..5).map(|i| generation(i * 10)).collect::<Vec<_>>();
+        let (kept, pruned) = retention(None, Some(25)).split(all, at(45));
+        assert_eq!(names(&kept), vec![20, 30, 40]);
+        assert_eq!(names(&pruned), vec![0, 10]);
+    }
+
+    #[test]
+    fn both_limits_apply_together() {
+        let all = (0..5).map(|i| generation(i * 10)).collect::<Vec<_>>();
+        let (kept, _) = retention(Some(2), Some(25)).split(all.clone(), at(45));
+        assert_eq!(names(&kept), vec![30, 40]);
+        let (kept, _) = retention(Some(4), Some(15)).split(all, at(45));
+        assert_eq!(names(&kept), vec![30, 40]);
+    }
+
+    /// An idle workspace's only generation ages past `maxAgeSecs`; pruning it
+    /// would leave history enabled and nothing to restore.
+    #[test]
+    fn the_newest_generation_survives_any_limit() {
+        let (kept, pruned) = retention(Some(0), Some(1)).split(vec![generation(0)], at(600));
+        assert_eq!(names(&kept), vec![0]);
+        assert!(pruned.is_empty());
+        let (kept, pruned) = retention(Some(1), None).split(Vec::new(), at(0));
+        assert!(kept.is_empty() && pruned.is_empty());
+    }
+
+    #[test]
+    fn a_generation_is_due_once_the_interval_has_passed() {
+        let policy = retention(None, None);
+        assert!(policy.is_due(None, at(0)));
+        assert!(!policy.is_due(Some(&generation(0)), at(4)));
+        assert!(policy.is_due(Some(&generation(0)), at(5)));
+        // A clock that went backwards is not a reason to cut one.
+        assert!(!policy.is_due(Some(&generation(10)), at(5)));
+    }
+
+    #[test]
+    fn restore_to_a_timestamp_picks_the_newest_at_or_before_it() {
+        let all = vec![generation(0), generation(10), generation(20)];
+        let pick = |point| resolve(&all, &point).map(|g| names(std::slice::from_ref(g))[0]);
+        assert_eq!(pick(RestorePoint::At(at(15))), Some(10));
+        assert_eq!(pick(RestorePoint::At(at(20))), Some(20));
+        assert_eq!(pick(RestorePoint::At(at(99))), Some(20));
+        // Before the first generation there is nothing to restore.
+        assert_eq!(pick(RestorePoint::At(at(-1))), None);
+        assert_eq!(
+            pick(RestorePoint::Generation(generation(10).name)),
+            Some(10)
+        );
+        assert_eq!(pick(RestorePoint::Generation("nope".to_string())), None);
+    }
+
+    #[test]
+    fn only_generation_objects_are_read_from_a_listing() {
+        let listed = |url: &str| ListedObject {
+            url: url.parse().unwrap(),
+            size: 1,
+            last_modified: at(0),
+        };
+        let good = generation(0);
+        assert_eq!(
+            Generation::from_listed(&listed(good.url.as_str())),
+            Some(good)
+        );
+        for url in [
+            "s3://bucket/ws/history/notes.txt",
+            "s3://bucket/ws/history/latest.json",
+            "s3://bucket/ws/history/",
+        ] {
+            assert_eq!(Generation::from_listed(&listed(url)), None, "{url}");
+        }
+    }
+
+    #[test]
+    fn status_lists_recent_generations_newest_first() {
+        let all = (0..30).map(generation).collect::<Vec<_>>();
+        let status = status_generations(&all);
+        assert_eq!(status.len(), STATUS_GENERATIONS);
+        assert_eq!(status[0].name, generation(29).name);
+        assert_eq!(status[STATUS_GENERATIONS - 1].name, generation(10).name);
+    }
+}
@@ -67,6 +67,15 @@ impl WorkspaceFileUrlSet {
         let key = self.set.get_or_insert(item);
         self.key.format(key, ext.as_deref())
     }
+
+    /// Move `item` to a freshly minted url. The one it had stays reserved:
+    /// with archive history on, a retained generation may still name it, and
+    /// handing it to another path would overwrite what that generation
+    /// restores.
+    pub fn rotate(&mut self, item: PathBuf) -> Result<Url, UrlParseError> {
+        self.set.keys.remove(&item);
+        self.get_or_insert(item)
+    }
 }
 
 struct KeySet<T, K> {
@@ -168,7 +177,7 @@ struct WorkspaceFileKey {
 
 impl WorkspaceFileKey {
     pub fn new(bucket: String, prefix: Option<String>) -> Result<Self, UrlParseError> {
-        let base = Url::parse(&format!("s3://{bucket}/"))?;
+        let base = kubimo::bucket_url(&bucket)?;
         if let Some(prefix) = prefix.as_ref() {
             let _ = base.join(prefix)?;
         }
@@ -188,7 +197,11 @@ impl WorkspaceFileKey {
     fn parse(&self, url: &Url) -> Result<(u64, Option<String>), InvalidKey> {
         let relative_path = url
             .path()
-            .strip_prefix(&format!("/{}", self.prefix.as_deref().unwrap_or("")))
+            .strip_prefix(&format!(
+                "{}{}",
+                self.base.path(),
+                self.prefix.as_deref().unwrap_or("")
+            ))
             .ok_or(InvalidKey::BadExt)?;
         let (name_part, format_part) = relative_path
             .rsplit_once('.')
@@ -257,6 +270,23 @@ mod tests {
         assert_eq!(names.get_or_insert(path), fresh);
     }
 
+    /// The old key must stay taken, not merely unmapped: a generation that
+    /// still names it would restore whatever a later mint wrote there.
+    #[test]
+    fn a_rotated_path_never_reuses_its_old_key() {
+        let path = PathBuf::from("notebook.py");
+        let mut urls = url_set();
+        let old = urls.get_or_insert(path.clone()).unwrap();
+        let new = urls.rotate(path.clone()).unwrap();
+        assert_ne!(old, new);
+        assert_eq!(urls.get_or_insert(path).unwrap(), new);
+        assert!(
+            urls.set
+                .values
+                .contains_key(&urls.key.parse(&old).unwrap().0)
+        );
+    }
+
     /// The root's name is stable even unseeded, because key 0 is reserved for
     /// it. This is why the defect stayed invisible in a flat test workspace —
     /// only subdirectories churned.
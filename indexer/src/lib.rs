@@ -5,13 +5,20 @@
 //! Rather than reimplement manifest parsing, path safety and CRC verification a
 //! second time, both share the modules here.
 
+pub mod chunking;
+pub mod compression;
+pub mod content_store;
 pub mod disk;
+pub mod encryption;
 pub mod fingerprint;
+pub mod gc;
+pub mod history;
 pub mod keys;
 pub mod python;
 pub mod restore;
 pub mod s3;
 pub mod secrets;
+pub mod snapshot;
 pub mod upload;
 pub mod watcher;
 
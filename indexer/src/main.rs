@@ -1,10 +1,16 @@
 use std::collections::BTreeSet;
 use std::path::PathBuf;
+use std::time::Duration;
 
 use clap::{Args, Parser, Subcommand};
+use indexer::chunking::ChunkingPolicy;
+use indexer::compression::CompressionPolicy;
+use indexer::gc;
+use indexer::history::{HistoryRetention, RestorePoint};
 use indexer::keys::{WorkspaceDirNameSet, WorkspaceFileUrlSet};
 use indexer::restore;
-use indexer::s3::{CacheMarkers, S3Client};
+use indexer::s3::{CacheMarkers, RangedDownload, S3Client};
+use indexer::snapshot;
 use indexer::upload::{self, WorkspaceKeys};
 use tracing_subscriber::{EnvFilter, fmt, layer::SubscriberExt, prelude::*};
 
@@ -20,6 +26,8 @@ enum Command {
     Upload(UploadArgs),
     Clean(CleanArgs),
     Download(DownloadArgs),
+    Snapshot(SnapshotArgs),
+    Gc(GcArgs),
 }
 
 #[derive(Args, Debug)]
@@ -28,7 +36,13 @@ struct UploadArgs {
     include_gitignored: bool,
     #[arg(long, short = 'k')]
     exclude_hidden: bool,
-    #[arg(long, default_value_t = 100 * 1024 * 1024)] // 100 MB
+    /// Files larger than this are left out of the archive, unless chunking
+    /// takes them. Env-backed like the history options.
+    #[arg(
+        long,
+        env = "KUBIMO_MAX_FILE_SIZE",
+        default_value_t = kubimo::WorkspaceArchiveLimits::DEFAULT_MAX_FILE_SIZE
+    )]
     max_file_size: u64,
     #[arg(long, default_value_t = 10)]
     max_upload_concurrency: usize,
@@ -54,6 +68,57 @@ struct UploadArgs {
     watch_max_wait_millis: u64,
     #[arg(long, default_value_t = 60 * 1000)]
     watch_poll_millis: u64,
+    /// Keep this many point-in-time generations of the manifest. History is
+    /// on when this or `--history-max-age-secs` is given.
+    ///
+    /// The history options are env-backed for the same reason as
+    /// `download --secrets`: the controller only ever sets the env vars, which
+    /// an older indexer ignores.
+    #[arg(long, env = "KUBIMO_HISTORY_GENERATIONS")]
+    history_generations: Option<u32>,
+    /// Prune generations older than this, bar the newest.
+    #[arg(long, env = "KUBIMO_HISTORY_MAX_AGE_SECS")]
+    history_max_age_secs: Option<u64>,
+    /// Cut at most one generation per this many seconds.
+    #[arg(
+        long,
+        env = "KUBIMO_HISTORY_INTERVAL_SECS",
+        default_value_t = kubimo::WorkspaceArchiveHistory::DEFAULT_INTERVAL_SECS
+    )]
+    history_interval_secs: u64,
+    /// Upload content to the bucket's shared content-addressed store, skipping
+    /// files some workspace already uploaded. Env-backed like the history
+    /// options.
+    #[arg(long, env = "KUBIMO_CONTENT_ADDRESSED")]
+    content_addressed: bool,
+    /// Compress files of at least this many bytes, bar formats that are
+    /// compressed already. Compression is on when this is given; env-backed
+    /// like the history options.
+    #[arg(long, env = "KUBIMO_COMPRESSION_MIN_SIZE")]
+    compression_min_size: Option<u64>,
+    /// zstd level to compress with.
+    #[arg(
+        long,
+        env = "KUBIMO_COMPRESSION_LEVEL",
+        default_value_t = kubimo::WorkspaceArchiveCompression::DEFAULT_LEVEL
+    )]
+    compression_level: i32,
+    /// Upload files of at least this many bytes as content-defined chunks,
+    /// so a change re-uploads only the chunks around it. Chunking is on when
+    /// this is given; env-backed like the history options.
+    #[arg(long, env = "KUBIMO_CHUNKING_MIN_SIZE")]
+    chunking_min_size: Option<u64>,
+    /// Size cap for chunked files, in place of `--max-file-size`.
+    #[arg(
+        long,
+        env = "KUBIMO_CHUNKING_MAX_FILE_SIZE",
+        default_value_t = kubimo::WorkspaceArchiveChunking::DEFAULT_MAX_FILE_SIZE
+    )]
+    chunking_max_file_size: u64,
+    /// Keep the archived cache of any notebook and format with no cache file
+    /// on disk. Env-backed like the history options.
+    #[arg(long, env = "KUBIMO_KEEP_CACHES")]
+    keep_caches: bool,
     name: String,
     #[arg(default_value = ".")]
     directory: PathBuf,
@@ -74,6 +139,22 @@ impl UploadArgs {
             watch_debounce_millis: self.watch_debounce_millis,
             watch_max_wait_millis: self.watch_max_wait_millis,
             watch_poll_millis: self.watch_poll_millis,
+            history: (self.history_generations.is_some() || self.history_max_age_secs.is_some())
+                .then(|| HistoryRetention {
+                    generations: self.history_generations.map(|n| n as usize),
+                    max_age: self.history_max_age_secs.map(Duration::from_secs),
+                    interval: Duration::from_secs(self.history_interval_secs),
+                }),
+            content_addressed: self.content_addressed,
+            compression: self.compression_min_size.map(|min_size| CompressionPolicy {
+                min_size,
+                level: self.compression_level,
+            }),
+            chunking: self.chunking_min_size.map(|min_size| ChunkingPolicy {
+                min_size,
+                max_file_size: self.chunking_max_file_size,
+            }),
+            keep_caches: self.keep_caches,
             name: self.name.clone(),
             directory: self.directory.clone(),
         }
@@ -99,6 +180,21 @@ pub(crate) struct DownloadArgs {
     pub(crate) key_prefix: Option<String>,
     #[arg(long, default_value_t = 10)]
     pub(crate) max_download_concurrency: usize,
+    /// Restore files stored plain and larger than this as ranges of this
+    /// size, fetched side by side. Env-backed like `--secrets`.
+    #[arg(
+        long,
+        env = "KUBIMO_DOWNLOAD_PART_SIZE",
+        default_value_t = kubimo::WorkspaceArchiveLimits::DEFAULT_DOWNLOAD_PART_SIZE
+    )]
+    pub(crate) download_part_size: u64,
+    /// Ranges of one file fetched at once.
+    #[arg(
+        long,
+        env = "KUBIMO_DOWNLOAD_PART_CONCURRENCY",
+        default_value_t = kubimo::WorkspaceArchiveLimits::DEFAULT_DOWNLOAD_PART_CONCURRENCY as usize
+    )]
+    pub(crate) download_part_concurrency: usize,
     /// Continue on per-file download errors instead of failing.
     #[arg(long)]
     pub(crate) best_effort: bool,
@@ -109,6 +205,12 @@ pub(crate) struct DownloadArgs {
     /// environment variable, so the controller only ever sets the env var.
     #[arg(long, env = "KUBIMO_RESTORE_SECRETS", default_value_t)]
     pub(crate) secrets: kubimo::WorkspaceRestoreSecrets,
+    /// Restore this archive generation instead of the current manifest.
+    #[arg(long, env = "KUBIMO_RESTORE_GENERATION", conflicts_with = "at")]
+    pub(crate) generation: Option<String>,
+    /// Restore the newest generation cut at or before this RFC 3339 instant.
+    #[arg(long, env = "KUBIMO_RESTORE_AT")]
+    pub(crate) at: Option<kubimo::chrono::DateTime<kubimo::chrono::Utc>>,
     #[arg(default_value = ".")]
     pub(crate) directory: PathBuf,
 }
@@ -120,11 +222,79 @@ impl DownloadArgs {
             key_prefix: self.key_prefix.clone(),
             directory: self.directory.clone(),
             max_download_concurrency: self.max_download_concurrency,
+            ranged: RangedDownload {
+                part_size: self.download_part_size,
+                concurrency: self.download_part_concurrency,
+            },
             best_effort: self.best_effort,
             secrets: self.secrets,
+            point: RestorePoint::from_parts(self.generation.clone(), self.at),
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+struct SnapshotArgs {
+    #[arg(long, short, env = "AWS_BUCKET")]
+    bucket: String,
+    /// Key prefix of the archive to copy.
+    #[arg(long, short = 'p', env = "AWS_KEY_PREFIX")]
+    key_prefix: Option<String>,
+    #[arg(long, default_value_t = 10)]
+    max_copy_concurrency: usize,
+    /// Also write the summary of the copy here as JSON. The controller points
+    /// this at the container's termination log, which is how the sizes reach
+    /// the snapshot's status without this binary needing API access.
+    #[arg(long)]
+    summary_path: Option<PathBuf>,
+    /// Key prefix to write the copy under.
+    dest_key_prefix: String,
+}
+
+impl SnapshotArgs {
+    fn to_options(&self) -> snapshot::SnapshotOptions {
+        snapshot::SnapshotOptions {
+            bucket: self.bucket.clone(),
+            key_prefix: self.key_prefix.clone(),
+            dest_key_prefix: self.dest_key_prefix.clone(),
+            max_concurrency: self.max_copy_concurrency,
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+struct GcArgs {
+    #[arg(long, short, env = "AWS_BUCKET")]
+    bucket: String,
+    /// Only delete objects whose key starts with this. Manifests are still
+    /// read from the whole bucket.
+    ///
+    /// Without it every unreachable object in the bucket is a candidate, so
+    /// leave it unset only on a bucket that holds nothing but archives.
+    #[arg(long, short = 'p', env = "AWS_KEY_PREFIX")]
+    key_prefix: Option<String>,
+    /// Keep unreachable objects written more recently than this.
+    #[arg(long, default_value_t = gc::GcOptions::DEFAULT_GRACE_SECS)]
+    grace_secs: u64,
+    /// Log what would be deleted, and delete nothing.
+    #[arg(long)]
+    dry_run: bool,
+    #[arg(long, default_value_t = 10)]
+    max_delete_concurrency: usize,
+}
+
+impl GcArgs {
+    fn to_options(&self) -> gc::GcOptions {
+        gc::GcOptions {
+            bucket: self.bucket.clone(),
+            key_prefix: self.key_prefix.clone(),
+            grace: Duration::from_secs(self.grace_secs),
+            dry_run: self.dry_run,
+            max_concurrency: self.max_delete_concurrency,
         }
     }
 }
+
 #[tokio::main]
 async fn main() {
     tracing_subscriber::registry()
@@ -136,14 +306,64 @@ async fn main() {
     let s3 = S3Client::from_env();
 
     match cli.command {
-        // Download needs no Kubernetes API access; only the other commands
-        // build a client.
+        // Download and snapshot need no Kubernetes API access; only the other
+        // commands build a client.
         Command::Download(args) => {
             if let Err(err) = restore::restore(&args.to_options(), &s3).await {
                 tracing::error!("Error restoring workspace: {err}");
                 std::process::exit(1);
             }
         }
+        Command::Snapshot(args) => {
+            let summary = match snapshot::snapshot(&args.to_options(), &s3).await {
+                Ok(summary) => summary,
+                Err(err) => {
+                    tracing::error!("Error snapshotting archive: {err}");
+                    std::process::exit(1);
+                }
+            };
+            if let Some(path) = args.summary_path.as_ref() {
+                let json = serde_json::to_vec(&summary).expect("summary serializes");
+                if let Err(err) = std::fs::write(path, json) {
+                    tracing::error!("Could not write summary to {}: {err}", path.display());
+                    std::process::exit(1);
+                }
+            }
+        }
+        Command::Gc(args) => {
+            let client = kube_client().await;
+            // The CRs are half of what is reachable; collecting without them
+            // would delete what a pre-manifest archive still restores from.
+            let live = match gc::workspace_dir_urls(&client).await {
+                Ok(live) => live,
+                Err(err) => {
+                    tracing::error!("Error listing workspace dirs: {err}");
+                    std::process::exit(1);
+                }
+            };
+            match gc::gc(&args.to_options(), &s3, &live).await {
+                Ok(summary) => {
+                    tracing::info!(
+                        scanned = summary.scanned,
+                        manifests = summary.manifests,
+                        reachable = summary.reachable,
+                        young = summary.young,
+                        collected = summary.collected,
+                        collected_bytes = summary.collected_bytes,
+                        failed = summary.failed,
+                        dry_run = args.dry_run,
+                        "Collected unreachable archive objects"
+                    );
+                    if summary.failed > 0 {
+                        std::process::exit(1);
+                    }
+                }
+                Err(err) => {
+                    tracing::error!("Error collecting archive objects: {err}");
+                    std::process::exit(1);
+                }
+            }
+        }
         Command::Upload(args) => {
             let client = kube_client().await;
             let mut previous_names = BTreeSet::new();
@@ -165,7 +385,21 @@ async fn main() {
                 &mut previous_urls,
             )
             .await;
-            let keys = WorkspaceKeys::new(names, urls);
+            let data_key = match upload::resolve_data_key(
+                &s3,
+                args.bucket.as_deref(),
+                args.key_prefix.as_deref(),
+                &mut cache_markers,
+            )
+            .await
+            {
+                Ok(data_key) => data_key,
+                Err(err) => {
+                    tracing::error!("Error resolving the archive's data key: {err}");
+                    std::process::exit(1);
+                }
+            };
+            let keys = WorkspaceKeys::new(names, urls, data_key);
             s3.set_cache(cache_markers).await;
 
             if args.watch {
@@ -290,4 +524,69 @@ mod tests {
             .is_err()
         );
     }
+
+    #[test]
+    fn a_restore_point_is_a_generation_or_an_instant_not_both() {
+        let args = download(Cli::parse_from([
+            "indexer",
+            "download",
+            "--bucket",
+            "b",
+            "--at",
+            "2026-01-01T12:00:00Z",
+        ]));
+        assert_eq!(
+            args.to_options().point,
+            Some(RestorePoint::At("2026-01-01T12:00:00Z".parse().unwrap()))
+        );
+        let args = download(Cli::parse_from([
+            "indexer",
+            "download",
+            "--bucket",
+            "b",
+            "--generation",
+            "20260101T120000.000Z",
+        ]));
+        assert_eq!(
+            args.to_options().point,
+            Some(RestorePoint::Generation("20260101T120000.000Z".into()))
+        );
+        assert!(
+            Cli::try_parse_from([
+                "indexer",
+                "download",
+                "--bucket",
+                "b",
+                "--generation",
+                "g",
+                "--at",
+                "2026-01-01T12:00:00Z",
+            ])
+            .is_err()
+        );
+    }
+
+    /// Only a count or an age turns history on; the interval alone has a
+    /// default and says nothing about whether history is wanted.
+    #[test]
+    fn history_is_on_only_with_a_retention_limit() {
+        let upload = |extra: &[&str]| {
+            let mut argv = vec!["indexer", "upload"];
+            argv.extend_from_slice(extra);
+            argv.push("bmow-abc");
+            match Cli::parse_from(argv).command {
+                Command::Upload(args) => args.to_options().history,
+                other => panic!("expected upload, got {other:?}"),
+            }
+        };
+        assert_eq!(upload(&["--history-interval-secs", "60"]), None);
+        let history = upload(&["--history-max-age-secs", "3600"]).unwrap();
+        assert_eq!(history.generations, None);
+        assert_eq!(history.max_age, Some(Duration::from_secs(3600)));
+        assert_eq!(history.interval, Duration::from_secs(300));
+        assert_eq!(
+            upload(&["--history-generations", "3"]).unwrap().generations,
+            Some(3)
+        );
+    }
 }
@@ -4,20 +4,53 @@ use std::time::SystemTime;
 
 use base64::Engine as _;
 use ignore::gitignore::{Gitignore, GitignoreBuilder};
-use kubimo::{ManifestSecrets, WorkspaceManifest, WorkspaceRestoreSecrets, url::Url};
+use kubimo::{
+    ContentCodec, ManifestSecrets, WorkspaceArchiveHydration, WorkspaceDirContentChunk,
+    WorkspaceManifest, WorkspaceRestoreSecrets, url::Url,
+};
+use sha2::{Digest, Sha256};
 use thiserror::Error;
 use tokio::{io::AsyncWriteExt, sync::Semaphore, task::JoinSet};
 
 use crate::disk;
-use crate::s3::{DownloadError, S3Client};
+use crate::encryption::{self, DataKey, EncryptionError};
+use crate::history::{self, RestorePoint};
+use crate::s3::{DownloadError, Encoding, ListError, RangedDownload, S3Client};
 use crate::secrets;
 
 #[derive(Debug, PartialEq)]
 pub struct RestoreFile {
     pub path: PathBuf,
     pub url: Url,
+    /// As the manifest records it. Only used to decide whether the object is
+    /// worth fetching in ranges, never trusted as the length of what arrives.
+    pub size: Option<u64>,
     pub crc32: Option<u32>,
     pub modified: Option<SystemTime>,
+    /// Sealed with the archive's data key; see [`kubimo::ManifestEncryption`].
+    pub encrypted: bool,
+    pub codec: Option<ContentCodec>,
+    /// The file's content in pieces, to be written one after another; `url`
+    /// then names no object. See [`kubimo::WorkspaceDirContentChunk`].
+    pub chunks: Option<Vec<WorkspaceDirContentChunk>>,
+    /// A marimo notebook, by the manifest's account.
+    pub notebook: bool,
+}
+
+impl RestoreFile {
+    fn with_path(&self, path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            url: self.url.clone(),
+            size: self.size,
+            crc32: self.crc32,
+            modified: self.modified,
+            encrypted: self.encrypted,
+            codec: self.codec,
+            chunks: self.chunks.clone(),
+            notebook: self.notebook,
+        }
+    }
 }
 
 #[derive(Debug, Default)]
@@ -68,19 +101,24 @@ pub struct ArchiveOrigin {
 }
 
 impl ArchiveOrigin {
-    fn base(&self) -> String {
+    pub(crate) fn base(&self) -> String {
         let mut prefix = self.key_prefix.as_deref().unwrap_or("").to_string();
         // Anchor at a path boundary: without the trailing slash, `mine` would
         // also admit `mine-other/…` as its own content.
         if !prefix.is_empty() && !prefix.ends_with('/') {
             prefix.push('/');
         }
-        format!("s3://{}/{}", self.bucket, prefix)
+        // Only ever built for a bucket whose manifest url already parsed, so
+        // the fallback is never reached; it admits nothing if it is.
+        match kubimo::bucket_url(&self.bucket) {
+            Ok(root) => format!("{root}{prefix}"),
+            Err(_) => format!("{}:{prefix}", self.bucket),
+        }
     }
 
-    fn check(&self, url: &Url) -> Result<(), PlanError> {
+    pub(crate) fn check(&self, url: &Url) -> Result<(), PlanError> {
         let base = self.base();
-        if url.as_str().starts_with(&base) {
+        if url.as_str().starts_with(&base) || self.shares(url) {
             return Ok(());
         }
         Err(PlanError::ForeignContent {
@@ -88,6 +126,22 @@ impl ArchiveOrigin {
             expected: base,
         })
     }
+
+    /// Is `url` in the content store of this archive's own bucket? Store
+    /// objects belong to no prefix, so the prefix check cannot admit them;
+    /// the bucket check still can, and a manifest can only name one by the
+    /// digest of its bytes.
+    ///
+    /// Compared against the store url this bucket would give the digest, not
+    /// by host: a `file://` archive has no host to compare.
+    pub(crate) fn shares(&self, url: &Url) -> bool {
+        kubimo::is_content_store_url(url)
+            && url
+                .path_segments()
+                .and_then(|mut segments| segments.next_back())
+                .and_then(|digest| kubimo::content_store_url(&self.bucket, digest).ok())
+                .is_some_and(|own| own == *url)
+    }
 }
 
 fn safe_relative_path(path: &str) -> Result<PathBuf, PlanError> {
@@ -146,12 +200,19 @@ pub fn plan_restore(
                 }
             } else if let Some(file) = &entry.file {
                 if let Some(content) = &file.content {
-                    origin.check(&content.url)?;
+                    for url in content.object_urls() {
+                        origin.check(url)?;
+                    }
                     let file = RestoreFile {
                         path: entry_path,
                         url: content.url.clone(),
+                        size: file.size,
                         crc32: content.crc32,
                         modified: entry.modified.map(Into::into),
+                        encrypted: content.encrypted.unwrap_or(false),
+                        codec: content.codec,
+                        chunks: content.chunks.clone(),
+                        notebook: file.marimo.is_some(),
                     };
                     if secrets::is_secret(matcher, &file.path, false) {
                         plan.secret_files.push(file);
@@ -191,6 +252,15 @@ pub enum RestoreError {
     Io(#[from] std::io::Error),
     #[error("{0} of {1} files failed to download")]
     Failed(usize, usize),
+    #[error("could not list archive generations: {0}")]
+    List(#[from] ListError),
+    /// Deliberately not `Download(NotFound)`: callers read that as "never
+    /// indexed" and hydrate an empty workspace, which is the wrong answer to
+    /// "restore this point" when the point is simply not there.
+    #[error("archive has no {0}")]
+    NoGeneration(RestorePoint),
+    #[error(transparent)]
+    Encryption(#[from] EncryptionError),
 }
 
 /// Restore an archive into `args.directory` from its manifest in S3.
@@ -204,16 +274,48 @@ pub struct RestoreOptions {
     /// Where to write the restored tree.
     pub directory: PathBuf,
     pub max_download_concurrency: usize,
+    /// How a large file is split across requests. Comes on top of
+    /// `max_download_concurrency`: that many files, each up to this many
+    /// ranges at once.
+    pub ranged: RangedDownload,
     /// Continue past per-file download errors instead of failing the restore.
     pub best_effort: bool,
     /// How to treat the archive's secrets. `NamesOnly` is the fail-safe
     /// default; only a caller that decided the restorer may see the source's
     /// values passes `Values`.
     pub secrets: WorkspaceRestoreSecrets,
+    /// Restore an archive generation instead of the current manifest. Secret
+    /// values are not versioned; a `Values` restore of a generation writes the
+    /// archive's current ones.
+    pub point: Option<RestorePoint>,
 }
 
-pub async fn restore(args: &RestoreOptions, s3: &S3Client) -> Result<(), RestoreError> {
-    let manifest_url = kubimo::manifest_url(&args.bucket, args.key_prefix.as_deref())?;
+/// The manifest `args` asks for: the current one, or the generation its
+/// restore point resolves to.
+async fn manifest_url(args: &RestoreOptions, s3: &S3Client) -> Result<Url, RestoreError> {
+    let Some(point) = &args.point else {
+        return Ok(kubimo::manifest_url(
+            &args.bucket,
+            args.key_prefix.as_deref(),
+        )?);
+    };
+    let generations =
+        history::list_generations(s3, &args.bucket, args.key_prefix.as_deref()).await?;
+    let generation = history::resolve(&generations, point)
+        .ok_or_else(|| RestoreError::NoGeneration(point.clone()))?;
+    tracing::info!("Restoring archive generation {}", generation.name);
+    Ok(generation.url.clone())
+}
+
+/// A manifest, read and planned, with the key to its content.
+struct LoadedArchive {
+    manifest: WorkspaceManifest,
+    plan: RestorePlan,
+    data_key: Option<DataKey>,
+}
+
+async fn load(args: &RestoreOptions, s3: &S3Client) -> Result<LoadedArchive, RestoreError> {
+    let manifest_url = manifest_url(args, s3).await?;
     let bytes = s3.get_bytes(&manifest_url).await?;
     let manifest: WorkspaceManifest = serde_json::from_slice(&bytes)?;
     if !manifest.upload_content {
@@ -225,7 +327,22 @@ pub async fn restore(args: &RestoreOptions, s3: &S3Client) -> Result<(), Restore
     };
     let matcher = legacy_matcher(args, s3, &manifest, &origin).await?;
     let plan = plan_restore(&manifest, &origin, &matcher)?;
+    // Before touching the directory: an archive whose key is not in the
+    // keyring fails here, not one file at a time after the tree is laid out.
+    let data_key = s3.reading_key(manifest.encryption.as_ref())?;
+    Ok(LoadedArchive {
+        manifest,
+        plan,
+        data_key,
+    })
+}
 
+/// Everything but file content: the space check, directories and symlinks.
+async fn lay_out(
+    args: &RestoreOptions,
+    manifest: &WorkspaceManifest,
+    plan: &RestorePlan,
+) -> Result<(), RestoreError> {
     tokio::fs::create_dir_all(&args.directory).await?;
     let usage = disk::disk_usage(&args.directory)?;
     if usage.available < manifest.total_content_bytes {
@@ -246,14 +363,24 @@ pub async fn restore(args: &RestoreOptions, s3: &S3Client) -> Result<(), Restore
     for path in &plan.skipped {
         tracing::warn!("Skipping {}: no content in archive", path.display());
     }
+    Ok(())
+}
 
-    let total = plan.files.len();
+/// Download `files` side by side, returning how many failed.
+async fn download_files(
+    args: &RestoreOptions,
+    s3: &S3Client,
+    files: Vec<RestoreFile>,
+    data_key: Option<&DataKey>,
+) -> usize {
     let permits = Arc::new(Semaphore::new(args.max_download_concurrency));
     let mut join_set = JoinSet::new();
-    for file in plan.files {
+    for file in files {
         let s3 = s3.clone();
         let permits = permits.clone();
         let directory = args.directory.clone();
+        let data_key = data_key.cloned();
+        let ranged = args.ranged;
         join_set.spawn(async move {
             let _permit = match permits.acquire().await {
                 Ok(permit) => permit,
@@ -262,7 +389,7 @@ pub async fn restore(args: &RestoreOptions, s3: &S3Client) -> Result<(), Restore
                     return 1;
                 }
             };
-            match download_file(&s3, &directory, &file).await {
+            match download_file(&s3, &directory, &file, data_key.as_ref(), ranged).await {
                 Ok(()) => {
                     tracing::info!("Restored {}", file.path.display());
                     0
@@ -278,12 +405,27 @@ pub async fn restore(args: &RestoreOptions, s3: &S3Client) -> Result<(), Restore
     while let Some(res) = join_set.join_next().await {
         failed += res.unwrap_or(1);
     }
+    failed
+}
+
+pub async fn restore(args: &RestoreOptions, s3: &S3Client) -> Result<(), RestoreError> {
+    let LoadedArchive {
+        manifest,
+        mut plan,
+        data_key,
+    } = load(args, s3).await?;
+    lay_out(args, &manifest, &plan).await?;
+
+    let files = std::mem::take(&mut plan.files);
+    let total = files.len();
+    let mut failed = download_files(args, s3, files, data_key.as_ref()).await;
     tracing::info!(
         "Restored {} of {total} files ({} skipped)",
         total - failed,
         plan.skipped.len()
     );
-    let outcome = restore_secrets(args, s3, &manifest, plan.secret_files).await?;
+    let outcome =
+        restore_secrets(args, s3, &manifest, plan.secret_files, data_key.as_ref()).await?;
     failed += outcome.failed;
     let total = total + outcome.total;
     if failed > 0 && !args.best_effort {
@@ -292,6 +434,116 @@ pub async fn restore(args: &RestoreOptions, s3: &S3Client) -> Result<(), Restore
     Ok(())
 }
 
+/// Read by `uv sync` before marimo starts, so a runner cannot start without
+/// them whatever their size.
+const PROJECT_FILES: [&str; 2] = ["pyproject.toml", "uv.lock"];
+
+/// The files [`restore_eager`] writes before returning: what a runner reads
+/// as it starts, and whatever is too small to be worth deferring.
+#[derive(Debug, Clone, Copy)]
+pub struct EagerFiles {
+    pub max_size: u64,
+}
+
+impl From<&WorkspaceArchiveHydration> for EagerFiles {
+    fn from(hydration: &WorkspaceArchiveHydration) -> Self {
+        Self {
+            max_size: hydration.effective_eager_max_file_size(),
+        }
+    }
+}
+
+impl EagerFiles {
+    /// A file whose manifest entry records no size is not known to be large,
+    /// and is restored up front like any other small file.
+    pub fn wants(&self, file: &RestoreFile) -> bool {
+        file.notebook
+            || file
+                .path
+                .file_name()
+                .is_some_and(|name| PROJECT_FILES.iter().any(|project| name == *project))
+            || file.size.is_none_or(|size| size <= self.max_size)
+    }
+}
+
+/// Files still to restore, and the key to read them with.
+pub struct PendingFiles {
+    pub files: Vec<RestoreFile>,
+    pub data_key: Option<DataKey>,
+}
+
+/// [`restore`], except that only the files `eager` wants are downloaded; the
+/// rest are returned, to be written with [`stage_file`]. Directories, symlinks and
+/// secrets are all in place when this returns.
+pub async fn restore_eager(
+    args: &RestoreOptions,
+    s3: &S3Client,
+    eager: EagerFiles,
+) -> Result<PendingFiles, RestoreError> {
+    let LoadedArchive {
+        manifest,
+        mut plan,
+        data_key,
+    } = load(args, s3).await?;
+    lay_out(args, &manifest, &plan).await?;
+
+    let (files, deferred) = std::mem::take(&mut plan.files)
+        .into_iter()
+        .partition::<Vec<_>, _>(|file| eager.wants(file));
+    let total = files.len();
+    let mut failed = download_files(args, s3, files, data_key.as_ref()).await;
+    tracing::info!(
+        "Restored {} of {total} files, deferred {} ({} skipped)",
+        total - failed,
+        deferred.len(),
+        plan.skipped.len()
+    );
+    let outcome =
+        restore_secrets(args, s3, &manifest, plan.secret_files, data_key.as_ref()).await?;
+    failed += outcome.failed;
+    let total = total + outcome.total;
+    if failed > 0 && !args.best_effort {
+        return Err(RestoreError::Failed(failed, total));
+    }
+    Ok(PendingFiles {
+        files: deferred,
+        data_key,
+    })
+}
+
+/// Every file a restore of `args` would write, without writing anything.
+/// For picking a deferred restore back up after the process that began it is
+/// gone; secrets are not among them, since [`restore_eager`] never defers
+/// those.
+pub async fn plan_files(
+    args: &RestoreOptions,
+    s3: &S3Client,
+) -> Result<PendingFiles, RestoreError> {
+    let LoadedArchive { plan, data_key, .. } = load(args, s3).await?;
+    Ok(PendingFiles {
+        files: plan.files,
+        data_key,
+    })
+}
+
+/// Download `file` into `staging` rather than to its own path, and return
+/// where it landed: complete and verified, or, on error, nowhere. Moving it
+/// into place is the caller's, who knows what else may be writing the tree.
+pub async fn stage_file(
+    s3: &S3Client,
+    staging: &Path,
+    file: &RestoreFile,
+    data_key: Option<&DataKey>,
+    ranged: RangedDownload,
+) -> Result<PathBuf, RestoreError> {
+    let name = format!(
+        "{:x}",
+        Sha256::digest(file.path.as_os_str().as_encoded_bytes())
+    );
+    download_file(s3, staging, &file.with_path(name.clone()), data_key, ranged).await?;
+    Ok(staging.join(name))
+}
+
 /// The matcher [`plan_restore`] diverts with. For an archive written by a
 /// secrets-aware indexer nothing needs matching — its secret paths never
 /// reached the manifest, and the always-secret `.env` file name is caught by
@@ -386,10 +638,11 @@ async fn restore_secrets(
     s3: &S3Client,
     manifest: &WorkspaceManifest,
     secret_files: Vec<RestoreFile>,
+    data_key: Option<&DataKey>,
 ) -> Result<SecretRestoreOutcome, RestoreError> {
     match (args.secrets, manifest.secrets.as_ref()) {
         (WorkspaceRestoreSecrets::Values, Some(names)) => {
-            restore_secret_values(args, s3, names).await
+            restore_secret_values(args, s3, names, data_key).await
         }
         (WorkspaceRestoreSecrets::Values, None) => {
             let mut outcome = SecretRestoreOutcome {
@@ -397,7 +650,7 @@ async fn restore_secrets(
                 ..Default::default()
             };
             for file in &secret_files {
-                match download_file(s3, &args.directory, file).await {
+                match download_file(s3, &args.directory, file, data_key, args.ranged).await {
                     Ok(()) => tracing::info!("Restored secret file {}", file.path.display()),
                     Err(err) => {
                         outcome.failed += 1;
@@ -468,6 +721,7 @@ async fn restore_secret_values(
     args: &RestoreOptions,
     s3: &S3Client,
     names: &ManifestSecrets,
+    data_key: Option<&DataKey>,
 ) -> Result<SecretRestoreOutcome, RestoreError> {
     let url = kubimo::secrets_url(&args.bucket, args.key_prefix.as_deref())?;
     let bytes = match s3.get_bytes(&url).await {
@@ -493,6 +747,17 @@ async fn restore_secret_values(
         }
         Err(err) => return Err(err.into()),
     };
+    // Told apart by the object rather than the manifest: the secrets object
+    // is written before the manifest, so after a cycle that switched keys on
+    // or off the two can briefly disagree.
+    let bytes = if encryption::is_sealed(&bytes) {
+        data_key
+            .ok_or(EncryptionError::NoDataKey)?
+            .open(&bytes)?
+            .into()
+    } else {
+        bytes
+    };
     let workspace_secrets: kubimo::WorkspaceSecrets = serde_json::from_slice(&bytes)?;
     write_secret_values(args, &workspace_secrets).await
 }
@@ -599,12 +864,37 @@ async fn download_file(
     s3: &S3Client,
     directory: &Path,
     file: &RestoreFile,
+    data_key: Option<&DataKey>,
+    ranged: RangedDownload,
 ) -> Result<(), RestoreError> {
+    let data_key = match (file.encrypted, data_key) {
+        (false, _) => None,
+        (true, Some(data_key)) => Some(data_key),
+        (true, None) => return Err(EncryptionError::NoDataKey.into()),
+    };
     let full_path = directory.join(&file.path);
     let output = create_output_file(&full_path).await?;
     // `download` takes the handle by value, so it is closed by the time an
     // error returns here.
-    if let Err(err) = s3.download(&file.url, output, file.crc32).await {
+    let downloaded = match (&file.chunks, file.size) {
+        (Some(chunks), _) => download_chunks(s3, output, chunks, file.crc32, data_key).await,
+        // The object is the file's bytes as they are, so any range of it is
+        // the same range of the file.
+        (None, Some(size)) if data_key.is_none() && file.codec.is_none() && ranged.splits(size) => {
+            drop(output);
+            s3.download_ranged(&file.url, &full_path, size, file.crc32, ranged)
+                .await
+        }
+        (None, _) => {
+            let encoding = Encoding {
+                codec: file.codec,
+                data_key,
+                ..Default::default()
+            };
+            s3.download(&file.url, output, file.crc32, encoding).await
+        }
+    };
+    if let Err(err) = downloaded {
         // Don't leave a partial or corrupt file behind — with --best-effort
         // the restore continues and the file would otherwise look restored.
         if let Err(remove_err) = remove_if_exists(&full_path).await {
@@ -623,6 +913,38 @@ async fn download_file(
     Ok(())
 }
 
+/// Write `chunks` to `output` in order, each checked against its own crc32.
+/// The whole file's crc32 is combined from theirs rather than hashed again,
+/// and still catches a chunk list that is complete in every piece but put
+/// together in the wrong order.
+async fn download_chunks(
+    s3: &S3Client,
+    mut output: tokio::fs::File,
+    chunks: &[WorkspaceDirContentChunk],
+    expected_crc32: Option<u32>,
+    data_key: Option<&DataKey>,
+) -> Result<u32, DownloadError> {
+    let mut hasher = crc32fast::Hasher::new();
+    for chunk in chunks {
+        let encoding = Encoding {
+            codec: chunk.codec,
+            data_key,
+            ..Default::default()
+        };
+        let crc32 = s3
+            .download(&chunk.url, &mut output, Some(chunk.crc32), encoding)
+            .await?;
+        hasher.combine(&crc32fast::Hasher::new_with_initial_len(crc32, chunk.size));
+    }
+    let actual = hasher.finalize();
+    if let Some(expected) = expected_crc32
+        && expected != actual
+    {
+        return Err(DownloadError::Crc32Mismatch { expected, actual });
+    }
+    Ok(actual)
+}
+
 fn set_modified(path: &Path, modified: SystemTime) -> std::io::Result<()> {
     std::fs::File::options()
         .write(true)
@@ -648,6 +970,7 @@ mod tests {
             // Legacy shape: most of these tests predate secrets, and `None`
             // exercises the diversion path a filtered archive never takes.
             secrets: None,
+            encryption: None,
         }
     }
 
@@ -672,6 +995,9 @@ mod tests {
                     url: "s3://bucket/0123456789abc".parse().unwrap(),
                     crc32: Some(7),
                     e_tag: None,
+                    encrypted: None,
+                    codec: None,
+                    chunks: None,
                 }),
                 marimo: None,
             }),
@@ -781,6 +1107,234 @@ mod tests {
         }
     }
 
+    /// Every chunk is held to the origin, not just the file's own key: that
+    /// key names no object, so checking it alone would check nothing.
+    #[test]
+    fn plan_restore_rejects_a_chunk_outside_the_archive() {
+        let mut manifest = manifest_pointing_at("s3://bucket/0123456789abc");
+        let content = manifest.directories[0].entries[0]
+            .file
+            .as_mut()
+            .and_then(|file| file.content.as_mut())
+            .unwrap();
+        content.chunks = Some(vec![WorkspaceDirContentChunk {
+            url: "s3://other-bucket/chunks/one".parse().unwrap(),
+            size: 1,
+            crc32: 7,
+            e_tag: None,
+            codec: None,
+        }]);
+        assert!(matches!(
+            plan(&manifest),
+            Err(PlanError::ForeignContent { .. })
+        ));
+    }
+
+    /// Chunks are written back to back, and a chunk list whose pieces are
+    /// each intact still fails when it does not add up to the file.
+    #[tokio::test]
+    async fn a_chunked_file_downloads_in_order_and_checks_the_whole() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = kubimo::bucket_url(&format!("file://{}", dir.path().display())).unwrap();
+        let s3 = S3Client::from_options(Vec::<(String, String)>::new());
+        let mut chunks = Vec::new();
+        for (name, bytes) in [("one", &b"hello, "[..]), ("two", &b"world"[..])] {
+            let url = root.join(&format!("ws/chunks/{name}")).unwrap();
+            let uploaded = s3
+                .upload(
+                    &url,
+                    std::io::Cursor::new(bytes.to_vec()),
+                    bytes.len() as u64,
+                    &Semaphore::new(1),
+                )
+                .await
+                .unwrap();
+            chunks.push(WorkspaceDirContentChunk {
+                url,
+                size: bytes.len() as u64,
+                crc32: uploaded.crc32,
+                e_tag: uploaded.e_tag,
+                codec: None,
+            });
+        }
+        let target = dir.path().join("restored");
+        std::fs::create_dir(&target).unwrap();
+        let mut file = RestoreFile {
+            path: PathBuf::from("greeting.txt"),
+            url: root.join("ws/0123456789abc").unwrap(),
+            size: Some(12),
+            crc32: Some(crc32fast::hash(b"hello, world")),
+            modified: None,
+            encrypted: false,
+            codec: None,
+            chunks: Some(chunks),
+            notebook: false,
+        };
+        download_file(&s3, &target, &file, None, RangedDownload::default())
+            .await
+            .unwrap();
+        assert_eq!(
+            std::fs::read(target.join("greeting.txt")).unwrap(),
+            b"hello, world"
+        );
+
+        file.chunks.as_mut().unwrap().reverse();
+        assert!(matches!(
+            download_file(&s3, &target, &file, None, RangedDownload::default()).await,
+            Err(RestoreError::Download(DownloadError::Crc32Mismatch { .. }))
+        ));
+        assert!(!target.join("greeting.txt").exists());
+    }
+
+    /// Ranges land at their own offsets whatever order they finish in, and an
+    /// object shorter than the manifest claims is an error, not a short file.
+    #[tokio::test]
+    async fn a_large_plain_file_downloads_in_ranges() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = kubimo::bucket_url(&format!("file://{}", dir.path().display())).unwrap();
+        let s3 = S3Client::from_options(Vec::<(String, String)>::new());
+        let bytes = (0..1000u32).flat_map(u32::to_le_bytes).collect::<Vec<_>>();
+        let url = root.join("ws/0123456789abc").unwrap();
+        s3.upload(
+            &url,
+            std::io::Cursor::new(bytes.clone()),
+            bytes.len() as u64,
+            &Semaphore::new(1),
+        )
+        .await
+        .unwrap();
+        let target = dir.path().join("restored");
+        std::fs::create_dir(&target).unwrap();
+        let mut file = RestoreFile {
+            path: PathBuf::from("weights.bin"),
+            url,
+            size: Some(bytes.len() as u64),
+            crc32: Some(crc32fast::hash(&bytes)),
+            modified: None,
+            encrypted: false,
+            codec: None,
+            chunks: None,
+            notebook: false,
+        };
+        let ranged = RangedDownload {
+            part_size: 300,
+            concurrency: 4,
+        };
+        assert!(ranged.splits(bytes.len() as u64));
+        download_file(&s3, &target, &file, None, ranged)
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read(target.join("weights.bin")).unwrap(), bytes);
+
+        file.size = Some(bytes.len() as u64 + 500);
+        assert!(
+            download_file(&s3, &target, &file, None, ranged)
+                .await
+                .is_err()
+        );
+        assert!(!target.join("weights.bin").exists());
+    }
+
+    /// A staged file is complete and verified or not there at all: a failed
+    /// download leaves nothing behind in staging for a caller to move.
+    #[tokio::test]
+    async fn a_staged_file_is_complete_or_absent() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = kubimo::bucket_url(&format!("file://{}", dir.path().display())).unwrap();
+        let s3 = S3Client::from_options(Vec::<(String, String)>::new());
+        let url = root.join("ws/0123456789abc").unwrap();
+        s3.upload(
+            &url,
+            std::io::Cursor::new(b"a,b\n1,2\n".to_vec()),
+            8,
+            &Semaphore::new(1),
+        )
+        .await
+        .unwrap();
+        let staging = dir.path().join("staging");
+        std::fs::create_dir(&staging).unwrap();
+        let mut file = RestoreFile {
+            path: PathBuf::from("data/table.csv"),
+            url,
+            size: Some(8),
+            crc32: Some(crc32fast::hash(b"a,b\n1,2\n")),
+            modified: None,
+            encrypted: false,
+            codec: None,
+            chunks: None,
+            notebook: false,
+        };
+        let staged = stage_file(&s3, &staging, &file, None, RangedDownload::default())
+            .await
+            .unwrap();
+        assert_eq!(staged.parent(), Some(staging.as_path()));
+        assert_eq!(std::fs::read(&staged).unwrap(), b"a,b\n1,2\n");
+        std::fs::remove_file(staged).unwrap();
+
+        file.path = PathBuf::from("data/other.csv");
+        file.crc32 = Some(0);
+        assert!(
+            stage_file(&s3, &staging, &file, None, RangedDownload::default())
+                .await
+                .is_err()
+        );
+        assert_eq!(std::fs::read_dir(&staging).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn notebooks_and_project_files_are_eager_whatever_their_size() {
+        let eager = EagerFiles { max_size: 10 };
+        let file = |path: &str, size: u64, notebook: bool| RestoreFile {
+            path: PathBuf::from(path),
+            url: "s3://bucket/0123456789abc".parse().unwrap(),
+            size: Some(size),
+            crc32: None,
+            modified: None,
+            encrypted: false,
+            codec: None,
+            chunks: None,
+            notebook,
+        };
+        assert!(eager.wants(&file("notebook.py", 1000, true)));
+        assert!(eager.wants(&file("uv.lock", 1000, false)));
+        assert!(eager.wants(&file("sub/pyproject.toml", 1000, false)));
+        assert!(eager.wants(&file("small.csv", 10, false)));
+        assert!(!eager.wants(&file("large.csv", 11, false)));
+        assert!(!eager.wants(&file("helpers.py", 1000, false)));
+    }
+
+    /// A content-addressed archive names objects outside its prefix by
+    /// design, but never outside its bucket.
+    #[test]
+    fn plan_restore_allows_the_content_store_of_its_own_bucket() {
+        let origin = ArchiveOrigin {
+            bucket: "bucket".to_string(),
+            key_prefix: Some("workspace/mine/".to_string()),
+        };
+        let digest = "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9";
+        let shared = kubimo::content_store_url("bucket", digest).unwrap();
+        assert!(
+            plan_restore(
+                &manifest_pointing_at(shared.as_str()),
+                &origin,
+                &Gitignore::empty()
+            )
+            .is_ok()
+        );
+        // Same bucket name on another backend is another bucket.
+        for other in ["other-bucket", "gs://bucket"] {
+            let elsewhere = kubimo::content_store_url(other, digest).unwrap();
+            assert!(matches!(
+                plan_restore(
+                    &manifest_pointing_at(elsewhere.as_str()),
+                    &origin,
+                    &Gitignore::empty()
+                ),
+                Err(PlanError::ForeignContent { .. })
+            ));
+        }
+    }
+
     /// The shape a seeded workspace's *own* manifest has: it sits at
     /// `workspace/{uuid}/` and points into `workspace/{uuid}/seed/`. This is
     /// what makes a never-opened pooled workspace cloneable, so the check must
@@ -929,8 +1483,10 @@ mod tests {
             key_prefix: None,
             directory: directory.to_path_buf(),
             max_download_concurrency: 1,
+            ranged: RangedDownload::default(),
             best_effort: false,
             secrets,
+            point: None,
         }
     }
 
@@ -948,7 +1504,7 @@ mod tests {
         });
         // The S3 client is never touched on this path: names come from the
         // manifest alone.
-        let outcome = restore_secrets(&args, &S3Client::from_env(), &manifest, Vec::new())
+        let outcome = restore_secrets(&args, &S3Client::from_env(), &manifest, Vec::new(), None)
             .await
             .unwrap();
         assert_eq!(outcome.failed, 0);
@@ -971,7 +1527,7 @@ mod tests {
         let args = restore_options(dir.path(), WorkspaceRestoreSecrets::NamesOnly);
         let mut manifest = manifest(vec![]);
         manifest.secrets = Some(kubimo::ManifestSecrets::default());
-        restore_secrets(&args, &S3Client::from_env(), &manifest, Vec::new())
+        restore_secrets(&args, &S3Client::from_env(), &manifest, Vec::new(), None)
             .await
             .unwrap();
         assert!(!dir.path().join(".env").exists());
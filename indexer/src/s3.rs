@@ -1,13 +1,20 @@
 use std::collections::BTreeMap;
+use std::fmt;
 use std::sync::Arc;
 
+use bytes::Bytes;
 use crc32fast::Hasher as Crc32Hasher;
 use futures::StreamExt;
+use kubimo::chrono::{DateTime, Utc};
 use kubimo::url::Url;
+use kubimo::{ContentCodec, WorkspaceArchiveLimits};
 use object_store::{
-    Attribute, AttributeValue, Attributes, ObjectStore, ObjectStoreExt, PutMultipartOptions,
-    PutOptions, PutPayloadMut, WriteMultipart,
-    aws::{AmazonS3, AmazonS3Builder},
+    Attribute, AttributeValue, Attributes, GetOptions, ObjectStore, ObjectStoreExt,
+    PutMultipartOptions, PutOptions, PutPayloadMut, WriteMultipart,
+    aws::{AmazonS3Builder, AmazonS3ConfigKey},
+    azure::{AzureConfigKey, MicrosoftAzureBuilder},
+    gcp::{GoogleCloudStorageBuilder, GoogleConfigKey},
+    local::LocalFileSystem,
     path::Path as Key,
 };
 use thiserror::Error;
@@ -17,48 +24,154 @@ use tokio::{
 };
 use tokio_util::io::ReaderStream;
 
+use crate::compression::{self, Compressor, Decompressor};
+use crate::encryption::{self, DataKey, EncryptionError, Keyring, Sealer};
+
 pub struct UploadResult {
     pub crc32: u32,
     pub e_tag: Option<String>,
+    /// How the stored bytes are compressed, which need not be as asked: a
+    /// small object that would not shrink is stored raw, and one left in
+    /// place because it was unchanged keeps whatever form it was written in.
+    pub codec: Option<ContentCodec>,
+}
+
+/// How an object's bytes are stored: compressed with `codec`, then sealed
+/// with `data_key`. The default is the bytes as they are.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Encoding<'a> {
+    pub codec: Option<ContentCodec>,
+    /// Only read when compressing; 0 is zstd's own default.
+    pub level: i32,
+    pub data_key: Option<&'a DataKey>,
+}
+
+/// How [`S3Client::download_ranged`] splits an object.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RangedDownload {
+    pub part_size: u64,
+    pub concurrency: usize,
 }
 
+impl Default for RangedDownload {
+    fn default() -> Self {
+        Self::from(&WorkspaceArchiveLimits::default())
+    }
+}
+
+impl From<&WorkspaceArchiveLimits> for RangedDownload {
+    fn from(limits: &WorkspaceArchiveLimits) -> Self {
+        Self {
+            part_size: limits.effective_download_part_size(),
+            concurrency: limits.effective_download_part_concurrency() as usize,
+        }
+    }
+}
+
+impl RangedDownload {
+    /// Is an object of `size` bytes worth splitting?
+    pub fn splits(&self, size: u64) -> bool {
+        size > self.part_size
+    }
+}
+
+/// One object found by [`S3Client::list`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListedObject {
+    pub url: Url,
+    pub size: u64,
+    pub last_modified: DateTime<Utc>,
+}
+
+/// What [`S3Client::head`] found at a url.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjectHead {
+    pub size: u64,
+    pub e_tag: Option<String>,
+    pub last_modified: DateTime<Utc>,
+}
+
+/// The archive's object store, whichever backend a url's scheme selects.
+///
+/// Named for the backend it started out with. `s3://` is still what every
+/// bare bucket name resolves to (see [`kubimo::bucket_url`]), but `gs://`,
+/// `az://` and `file://` urls are served the same way, each by a store built
+/// on first use and kept per bucket.
 #[derive(Clone)]
 pub struct S3Client {
-    builder: Arc<AmazonS3Builder>,
-    clients: Arc<RwLock<BTreeMap<String, AmazonS3>>>,
+    builders: Arc<StoreBuilders>,
+    clients: Arc<RwLock<BTreeMap<StoreId, Arc<dyn ObjectStore>>>>,
     cache_markers: Arc<RwLock<CacheMarkers>>,
+    /// The key-encryption keys the credentials carried, if any. A malformed
+    /// entry is kept as its error rather than dropped, so that every use fails
+    /// instead of the archive quietly going unencrypted.
+    keyring: Option<Result<Arc<Keyring>, String>>,
 }
 
 #[derive(Error, Debug)]
 pub enum UploadError {
     #[error(transparent)]
-    Url(#[from] ParseS3UrlError),
+    Url(#[from] ParseStoreUrlError),
     #[error(transparent)]
     Io(#[from] std::io::Error),
     #[error(transparent)]
     Upload(#[from] object_store::Error),
     #[error(transparent)]
     Semaphore(#[from] AcquireError),
+    #[error(transparent)]
+    Encryption(#[from] EncryptionError),
 }
 
 #[derive(Debug, Error)]
 pub enum DeleteError {
     #[error(transparent)]
-    Url(#[from] ParseS3UrlError),
+    Url(#[from] ParseStoreUrlError),
+    #[error(transparent)]
+    S3(#[from] object_store::Error),
+}
+
+#[derive(Debug, Error)]
+pub enum ListError {
+    #[error(transparent)]
+    Url(#[from] ParseStoreUrlError),
     #[error(transparent)]
     S3(#[from] object_store::Error),
+    #[error(transparent)]
+    ObjectUrl(#[from] kubimo::url::ParseError),
+}
+
+#[derive(Debug, Error)]
+pub enum HeadError {
+    #[error(transparent)]
+    Url(#[from] ParseStoreUrlError),
+    #[error(transparent)]
+    S3(#[from] object_store::Error),
+}
+
+#[derive(Debug, Error)]
+pub enum CopyError {
+    #[error(transparent)]
+    Url(#[from] ParseStoreUrlError),
+    #[error(transparent)]
+    S3(#[from] object_store::Error),
+    #[error("cannot copy between buckets: {from} to {to}")]
+    CrossBucket { from: String, to: String },
 }
 
 #[derive(Debug, Error)]
 pub enum DownloadError {
     #[error(transparent)]
-    Url(#[from] ParseS3UrlError),
+    Url(#[from] ParseStoreUrlError),
     #[error(transparent)]
     Io(#[from] std::io::Error),
     #[error(transparent)]
     S3(#[from] object_store::Error),
     #[error("crc32 mismatch: expected {expected:08x}, got {actual:08x}")]
     Crc32Mismatch { expected: u32, actual: u32 },
+    #[error(transparent)]
+    Encryption(#[from] EncryptionError),
+    #[error("object ends at byte {actual}, expected {expected}")]
+    Truncated { expected: u64, actual: u64 },
 }
 
 #[derive(Debug, Error)]
@@ -71,16 +184,27 @@ pub enum CacheMarkerCheckError {
     NoRemoteETag,
     #[error("ETag does not match")]
     ETagMismatch,
+    #[error("Object is sealed differently")]
+    SealMismatch,
     #[error(transparent)]
     S3(#[from] object_store::Error),
 }
 
 impl S3Client {
     pub fn from_env() -> Self {
-        Self::from_builder(AmazonS3Builder::from_env())
+        let keyring = std::env::var(encryption::KEYS_OPTION)
+            .ok()
+            .map(|value| Keyring::parse(&value));
+        let builders = StoreBuilders {
+            s3: AmazonS3Builder::from_env(),
+            gcs: GoogleCloudStorageBuilder::from_env(),
+            azure: MicrosoftAzureBuilder::from_env(),
+        };
+        Self::from_builders(builders, keyring)
     }
 
-    /// Build a client from an explicit set of `AWS_*` options.
+    /// Build a client from an explicit set of `AWS_*` options (or `GOOGLE_*`,
+    /// or `AZURE_*`, for archives on those backends).
     ///
     /// The node agent serves workspaces from more than one S3 account at once —
     /// on a shared cluster each environment has its own bucket *and* its own
@@ -89,29 +213,117 @@ impl S3Client {
     /// `NodePublishVolume`, and this turns those into a client.
     ///
     /// Keys are matched case-insensitively against `object_store`'s config
-    /// names, so a Kubernetes Secret's `AWS_ACCESS_KEY_ID` works as-is.
-    /// Anything unrecognised is ignored rather than rejected: a Secret shared
-    /// with other consumers may legitimately carry keys that mean nothing here.
+    /// names, so a Kubernetes Secret's `AWS_ACCESS_KEY_ID` works as-is. Each
+    /// backend takes whichever keys it recognises, since nothing here knows
+    /// which backend the workspace's bucket is on until a url is opened.
+    /// Anything no backend recognises is ignored rather than rejected: a
+    /// Secret shared with other consumers may legitimately carry keys that
+    /// mean nothing here.
+    ///
+    /// The archive's key-encryption keys ([`encryption::KEYS_OPTION`]) ride in
+    /// the same Secret. They are credentials for the same archive, and this
+    /// way they reach the indexer's `envFrom` and the agent's publish alike.
     pub fn from_options<K, V>(options: impl IntoIterator<Item = (K, V)>) -> Self
     where
         K: AsRef<str>,
         V: Into<String>,
     {
-        let mut builder = AmazonS3Builder::new();
+        let mut builders = StoreBuilders {
+            s3: AmazonS3Builder::new(),
+            gcs: GoogleCloudStorageBuilder::new(),
+            azure: MicrosoftAzureBuilder::new(),
+        };
+        let mut keyring = None;
         for (key, value) in options {
-            if let Ok(key) = key.as_ref().to_ascii_lowercase().parse() {
-                builder = builder.with_config(key, value);
+            let key = key.as_ref();
+            let value = value.into();
+            if key.eq_ignore_ascii_case(encryption::KEYS_OPTION) {
+                keyring = Some(Keyring::parse(&value));
+                continue;
+            }
+            let key = key.to_ascii_lowercase();
+            if let Ok(key) = key.parse::<AmazonS3ConfigKey>() {
+                builders.s3 = builders.s3.with_config(key, value.clone());
+            }
+            if let Ok(key) = key.parse::<GoogleConfigKey>() {
+                builders.gcs = builders.gcs.with_config(key, value.clone());
+            }
+            if let Ok(key) = key.parse::<AzureConfigKey>() {
+                builders.azure = builders.azure.with_config(key, value);
             }
         }
-        Self::from_builder(builder)
+        Self::from_builders(builders, keyring)
     }
 
-    fn from_builder(builder: AmazonS3Builder) -> Self {
+    fn from_builders(
+        builders: StoreBuilders,
+        keyring: Option<Result<Keyring, EncryptionError>>,
+    ) -> Self {
         Self {
-            builder: Arc::new(builder),
+            builders: Arc::new(builders),
             clients: Arc::new(RwLock::new(BTreeMap::new())),
             cache_markers: Arc::new(RwLock::new(CacheMarkers::new())),
+            keyring: keyring.map(|keyring| keyring.map(Arc::new).map_err(|err| err.to_string())),
+        }
+    }
+
+    fn keyring(&self) -> Result<Option<&Keyring>, EncryptionError> {
+        match &self.keyring {
+            None => Ok(None),
+            Some(Ok(keyring)) => Ok(Some(keyring)),
+            Some(Err(err)) => Err(EncryptionError::MalformedKeys(err.clone())),
+        }
+    }
+
+    /// The data key an upload should seal with, given the envelope the
+    /// archive's current manifest carries. `None` when this client holds no
+    /// keys: the archive is written plain.
+    ///
+    /// The archive's own key is reused whenever it can be opened, since every
+    /// object it sealed stays readable only for as long as the manifest names
+    /// it. One that cannot be — no envelope yet, or one wrapped with a key
+    /// since dropped from the keyring — is replaced by a fresh key, and the
+    /// second value says so: the caller must then stop trusting markers for
+    /// sealed objects, which were sealed with a key the new manifest will not
+    /// name.
+    pub fn writing_key(
+        &self,
+        envelope: Option<&kubimo::ManifestEncryption>,
+    ) -> Result<Option<(DataKey, bool)>, EncryptionError> {
+        let Some(keyring) = self.keyring()? else {
+            if envelope.is_some() {
+                tracing::warn!(
+                    "Archive is encrypted but no {} are configured; writing it plain",
+                    encryption::KEYS_OPTION
+                );
+            }
+            return Ok(None);
+        };
+        if let Some(envelope) = envelope {
+            match keyring.unwrap(envelope) {
+                Ok(key) => return Ok(Some((key, false))),
+                Err(err) => {
+                    tracing::warn!("Replacing the archive's data key: {err}");
+                }
+            }
         }
+        Ok(Some((keyring.mint()?, true)))
+    }
+
+    /// The data key a reader opens an archive with. Unlike [`Self::writing_key`]
+    /// there is no fallback: an envelope this client cannot open is an archive
+    /// it cannot restore.
+    pub fn reading_key(
+        &self,
+        envelope: Option<&kubimo::ManifestEncryption>,
+    ) -> Result<Option<DataKey>, EncryptionError> {
+        let Some(envelope) = envelope else {
+            return Ok(None);
+        };
+        let keyring = self
+            .keyring()?
+            .ok_or_else(|| EncryptionError::UnknownKey(envelope.key_id.clone()))?;
+        keyring.unwrap(envelope).map(Some)
     }
 
     pub async fn set_cache(&self, cache_markers: CacheMarkers) {
@@ -132,30 +344,33 @@ impl S3Client {
         markers.extend(cache_markers);
     }
 
-    async fn bucket(&self, bucket: &str) -> object_store::Result<AmazonS3> {
+    async fn bucket(&self, bucket: &StoreId) -> object_store::Result<Arc<dyn ObjectStore>> {
         if let Some(client) = self.clients.read().await.get(bucket) {
             return Ok(client.clone());
         }
-        let client = self
-            .builder
-            .as_ref()
-            .clone()
-            .with_bucket_name(bucket.to_string())
-            .build()?;
+        let builders = self.builders.as_ref().clone();
+        let name = bucket.name.clone();
+        let client: Arc<dyn ObjectStore> = match bucket.backend {
+            Backend::S3 => Arc::new(builders.s3.with_bucket_name(name).build()?),
+            Backend::Gcs => Arc::new(builders.gcs.with_bucket_name(name).build()?),
+            Backend::Azure => Arc::new(builders.azure.with_container_name(name).build()?),
+            Backend::Local => Arc::new(LocalFileSystem::new()),
+        };
         self.clients
             .write()
             .await
-            .insert(bucket.to_string(), client.clone());
+            .insert(bucket.clone(), client.clone());
         Ok(client)
     }
 
     async fn get_cached(
         &self,
-        s3: &AmazonS3,
-        bucket: String,
+        s3: &Arc<dyn ObjectStore>,
+        bucket: StoreId,
         key: Key,
         crc32: u32,
-    ) -> Result<String, CacheMarkerCheckError> {
+        sealed: bool,
+    ) -> Result<(String, Option<ContentCodec>), CacheMarkerCheckError> {
         let Some(marker) = self
             .cache_markers
             .read()
@@ -166,60 +381,121 @@ impl S3Client {
         else {
             return Err(CacheMarkerCheckError::NotFound);
         };
-        if marker.0 != crc32 {
+        if marker.crc32 != crc32 {
             return Err(CacheMarkerCheckError::Crc32Mismatch);
         }
+        // Same bytes, but not the same object: turning encryption on must
+        // rewrite every file, changed or not.
+        if marker.sealed != sealed {
+            return Err(CacheMarkerCheckError::SealMismatch);
+        }
         let Some(e_tag) = s3.head(&key).await?.e_tag else {
             return Err(CacheMarkerCheckError::NoRemoteETag);
         };
-        if marker.1 != e_tag {
+        if marker.e_tag != e_tag {
             return Err(CacheMarkerCheckError::ETagMismatch);
         }
-        Ok(e_tag)
+        // Unlike the seal, a codec change is no reason to rewrite: the object
+        // still restores to the same bytes, as long as the manifest keeps
+        // naming the codec it was written with.
+        Ok((e_tag, marker.codec))
     }
 
     #[tracing::instrument(skip(self, input))]
     pub async fn upload(
+        &self,
+        url: &Url,
+        input: impl AsyncRead + AsyncSeek + Unpin,
+        size: u64,
+        upload_permits: &Semaphore,
+    ) -> Result<UploadResult, UploadError> {
+        self.upload_encoded(url, input, size, upload_permits, Encoding::default())
+            .await
+    }
+
+    /// [`Self::upload`], encoding the bytes on their way out. The result's
+    /// crc32 is still of the bytes `input` holds.
+    #[tracing::instrument(skip(self, input, encoding))]
+    pub async fn upload_encoded(
         &self,
         url: &Url,
         mut input: impl AsyncRead + AsyncSeek + Unpin,
         size: u64,
         upload_permits: &Semaphore,
+        encoding: Encoding<'_>,
     ) -> Result<UploadResult, UploadError> {
-        let (bucket, key) = parse_s3_url(url)?;
+        let (bucket, key) = parse_store_url(url)?;
         let s3 = self.bucket(&bucket).await?;
-        let part_size = std::cmp::max(MIN_PART_SIZE, size.div_ceil(MAX_PARTS));
-        let res = if size < part_size {
-            let mut payload = PutPayloadMut::new();
+        let Encoding {
+            codec,
+            level,
+            data_key,
+        } = encoding;
+        let sealed = data_key.is_some();
+        let stored_size = if sealed {
+            encryption::sealed_len(size)
+        } else {
+            size
+        };
+        let part_size = std::cmp::max(MIN_PART_SIZE, stored_size.div_ceil(MAX_PARTS));
+        let res = if stored_size < part_size {
+            let mut chunks = Vec::new();
             let mut hasher = Crc32Hasher::new();
             let mut stream = ReaderStream::new(input);
             while let Some(chunk) = stream.next().await {
                 let bytes = chunk?;
                 hasher.update(&bytes);
-                payload.push(bytes);
+                chunks.push(bytes);
             }
             let crc32 = hasher.finalize();
-            if let Ok(e_tag) = self
-                .get_cached(&s3, bucket.clone(), key.clone(), crc32)
+            if let Ok((e_tag, codec)) = self
+                .get_cached(&s3, bucket.clone(), key.clone(), crc32, sealed)
                 .await
             {
                 return Ok(UploadResult {
                     crc32,
                     e_tag: Some(e_tag),
+                    codec,
                 });
             }
+            // Compressed and sealed only once the marker has missed: most
+            // uploads of an unchanged file stop there.
+            let mut stored_codec = None;
+            if let Some(codec) = codec
+                && let Some(compressed) =
+                    compression::compress_if_smaller(codec, level, &chunks.concat())?
+            {
+                chunks = vec![compressed.into()];
+                stored_codec = Some(codec);
+            }
+            let mut payload = PutPayloadMut::new();
+            let mut sealer = data_key.map(DataKey::sealer).transpose()?;
+            for chunk in chunks {
+                payload.push(seal_chunk(sealer.as_mut(), chunk)?);
+            }
+            if let Some(sealer) = sealer {
+                payload.push(finish_sealing(sealer)?);
+            }
             let e_tag = s3
                 .put_opts(
                     &key,
                     payload.freeze(),
                     PutOptions {
-                        attributes: get_attributes(&key),
+                        attributes: get_attributes(
+                            bucket.backend,
+                            &key,
+                            sealed || stored_codec.is_some(),
+                        ),
                         ..Default::default()
                     },
                 )
                 .await?
                 .e_tag;
-            UploadResult { crc32, e_tag }
+            UploadResult {
+                crc32,
+                e_tag,
+                codec: stored_codec,
+            }
         } else {
             let mut hasher = Crc32Hasher::new();
             let mut stream = ReaderStream::new(&mut input);
@@ -227,13 +503,14 @@ impl S3Client {
                 hasher.update(&chunk?);
             }
             let crc32 = hasher.finalize();
-            if let Ok(e_tag) = self
-                .get_cached(&s3, bucket.clone(), key.clone(), crc32)
+            if let Ok((e_tag, codec)) = self
+                .get_cached(&s3, bucket.clone(), key.clone(), crc32, sealed)
                 .await
             {
                 return Ok(UploadResult {
                     crc32,
                     e_tag: Some(e_tag),
+                    codec,
                 });
             }
             input.rewind().await?;
@@ -242,60 +519,331 @@ impl S3Client {
                 s3.put_multipart_opts(
                     &key,
                     PutMultipartOptions {
-                        attributes: get_attributes(&key),
+                        attributes: get_attributes(bucket.backend, &key, sealed || codec.is_some()),
                         ..Default::default()
                     },
                 )
                 .await?,
             );
+            // Too big to try first, so the codec is used as asked.
+            let mut compressor = codec
+                .map(|codec| Compressor::new(codec, level))
+                .transpose()?;
+            let mut sealer = data_key.map(DataKey::sealer).transpose()?;
             while let Some(chunk) = stream.next().await {
                 let permits = acquire_permit(upload_permits).await?;
                 multipart.wait_for_capacity(permits.len()).await?;
-                multipart.put(chunk?);
+                let chunk = compress_chunk(compressor.as_mut(), chunk?)?;
+                multipart.put(seal_chunk(sealer.as_mut(), chunk)?);
+            }
+            if let Some(compressor) = compressor {
+                let mut tail = Vec::new();
+                compressor.finish(&mut tail)?;
+                multipart.put(seal_chunk(sealer.as_mut(), tail.into())?);
+            }
+            if let Some(sealer) = sealer {
+                multipart.put(finish_sealing(sealer)?);
             }
             let e_tag = multipart.finish().await?.e_tag;
-            UploadResult { crc32, e_tag }
+            UploadResult {
+                crc32,
+                e_tag,
+                codec,
+            }
         };
         if let Some(e_tag) = &res.e_tag {
-            self.cache_markers
-                .write()
-                .await
-                .insert(url.clone(), res.crc32, e_tag.clone());
+            self.cache_markers.write().await.insert(
+                url.clone(),
+                res.crc32,
+                e_tag.clone(),
+                sealed,
+                res.codec,
+            );
         }
         Ok(res)
     }
 
+    /// The crc32 this process last saw `url` uploaded with, if any, and if it
+    /// was sealed as `sealed` says: an object holding the right bytes in the
+    /// wrong form is no more reusable than one holding the wrong bytes. Seeded
+    /// from the `WorkspaceDirectory` CRs at startup like the rest of the
+    /// markers, so it survives a restart for everything the archive already
+    /// describes.
+    pub async fn cached_crc32(&self, url: &Url, sealed: bool) -> Option<u32> {
+        let (bucket, key) = parse_store_url(url).ok()?;
+        self.cache_markers
+            .read()
+            .await
+            .items
+            .get(&(bucket, key))
+            .filter(|marker| marker.sealed == sealed)
+            .map(|marker| marker.crc32)
+    }
+
+    /// Drop the cache marker for `url`, so the next [`Self::upload`] to it
+    /// writes the bytes even if they match what the object already holds.
+    /// Only worth doing when the write itself is the point, such as renewing
+    /// an object's age.
+    pub async fn forget(&self, url: &Url) {
+        let Ok((bucket, key)) = parse_store_url(url) else {
+            return;
+        };
+        self.cache_markers
+            .write()
+            .await
+            .items
+            .remove(&(bucket, key));
+    }
+
+    /// Drop every marker `stale` holds for the same key. For a client shared
+    /// between archives, where [`Self::extend_cache`] would otherwise leave
+    /// behind markers a caller has just found stale.
+    pub async fn forget_cached(&self, stale: &CacheMarkers) {
+        let mut markers = self.cache_markers.write().await;
+        for key in stale.items.keys() {
+            markers.items.remove(key);
+        }
+    }
+
+    /// Metadata of the object at `url`, or `None` if there is none.
+    #[tracing::instrument(skip(self))]
+    pub async fn head(&self, url: &Url) -> Result<Option<ObjectHead>, HeadError> {
+        let (bucket, key) = parse_store_url(url)?;
+        let s3 = self.bucket(&bucket).await?;
+        head_from_store(&s3, &key).await
+    }
+
+    /// Every object under the directory `url` names, sorted by key.
+    ///
+    /// Listing is by path segment, not by raw string prefix: `s3://b/ws/`
+    /// lists `ws/a` but not `ws-other/a`. Callers holding a raw key prefix
+    /// list its parent and filter.
+    #[tracing::instrument(skip(self))]
+    pub async fn list(&self, url: &Url) -> Result<Vec<ListedObject>, ListError> {
+        let (bucket, key) = parse_store_url(url)?;
+        let s3 = self.bucket(&bucket).await?;
+        list_from_store(&s3, &bucket.root()?, &key).await
+    }
+
     #[tracing::instrument(skip(self))]
     pub async fn delete(&self, url: &Url) -> Result<(), DeleteError> {
-        let (bucket, key) = parse_s3_url(url)?;
+        let (bucket, key) = parse_store_url(url)?;
         let s3 = self.bucket(&bucket).await?;
         s3.delete(&key).await?;
         Ok(())
     }
 
+    /// Server-side copy of one object to another key in the same bucket. The
+    /// bytes never pass through this process, which is what makes copying a
+    /// whole archive affordable.
+    #[tracing::instrument(skip(self))]
+    pub async fn copy(&self, from: &Url, to: &Url) -> Result<(), CopyError> {
+        let (bucket, from_key) = parse_store_url(from)?;
+        let (to_bucket, to_key) = parse_store_url(to)?;
+        if bucket != to_bucket {
+            return Err(CopyError::CrossBucket {
+                from: bucket.to_string(),
+                to: to_bucket.to_string(),
+            });
+        }
+        let s3 = self.bucket(&bucket).await?;
+        s3.copy(&from_key, &to_key).await?;
+        Ok(())
+    }
+
     /// GET a small object fully into memory.
     #[tracing::instrument(skip(self))]
     pub async fn get_bytes(&self, url: &Url) -> Result<bytes::Bytes, DownloadError> {
-        let (bucket, key) = parse_s3_url(url)?;
+        let (bucket, key) = parse_store_url(url)?;
         let s3 = self.bucket(&bucket).await?;
         get_bytes_from_store(&s3, &key).await
     }
 
-    /// Stream a GET to `output`, verifying against `expected_crc32` when
-    /// given. Returns the crc32 of the downloaded bytes.
-    #[tracing::instrument(skip(self, output))]
+    /// Stream a GET to `output`, undoing `encoding` and verifying against
+    /// `expected_crc32` when given. Returns the crc32 of the bytes written,
+    /// which are the decoded ones.
+    #[tracing::instrument(skip(self, output, encoding))]
     pub async fn download(
         &self,
         url: &Url,
         output: impl AsyncWrite + Unpin,
         expected_crc32: Option<u32>,
+        encoding: Encoding<'_>,
+    ) -> Result<u32, DownloadError> {
+        let (bucket, key) = parse_store_url(url)?;
+        let s3 = self.bucket(&bucket).await?;
+        download_from_store(&s3, &key, output, expected_crc32, encoding).await
+    }
+
+    /// Download the `size` bytes of a plain object into the file at `path`,
+    /// as ranges fetched side by side and written in place. Returns the crc32
+    /// of the whole, combined from the ranges' own, and fails on a mismatch
+    /// with `expected_crc32` like [`Self::download`].
+    ///
+    /// Only for objects stored as the file's bytes: a sealed or compressed
+    /// object cannot be decoded from the middle. A range that fails part way
+    /// is requested again from the first byte it had not yet written, so a
+    /// dropped connection late in a large file costs the rest of one range
+    /// rather than the file.
+    #[tracing::instrument(skip(self))]
+    pub async fn download_ranged(
+        &self,
+        url: &Url,
+        path: &std::path::Path,
+        size: u64,
+        expected_crc32: Option<u32>,
+        ranged: RangedDownload,
     ) -> Result<u32, DownloadError> {
-        let (bucket, key) = parse_s3_url(url)?;
+        let (bucket, key) = parse_store_url(url)?;
         let s3 = self.bucket(&bucket).await?;
-        download_from_store(&s3, &key, output, expected_crc32).await
+        tokio::fs::OpenOptions::new()
+            .write(true)
+            .open(path)
+            .await?
+            .set_len(size)
+            .await?;
+        let part_size = ranged.part_size.max(1);
+        let parts = (0..size.div_ceil(part_size)).map(|index| {
+            let start = index * part_size;
+            start..size.min(start + part_size)
+        });
+        // `buffered` yields in order, so the crcs combine in file order.
+        let mut crcs = futures::stream::iter(parts)
+            .map(|range| download_range(&s3, &key, path, range))
+            .buffered(ranged.concurrency.max(1));
+        let mut hasher = Crc32Hasher::new();
+        while let Some(part) = crcs.next().await {
+            let (crc32, len) = part?;
+            hasher.combine(&Crc32Hasher::new_with_initial_len(crc32, len));
+        }
+        let actual = hasher.finalize();
+        if let Some(expected) = expected_crc32
+            && expected != actual
+        {
+            return Err(DownloadError::Crc32Mismatch { expected, actual });
+        }
+        Ok(actual)
+    }
+}
+
+/// How many times one range is requested before its download gives up.
+const RANGE_ATTEMPTS: usize = 3;
+
+/// Write `range` of the object at `key` to the same offsets of `path`,
+/// returning its crc32 and length.
+async fn download_range(
+    store: &Arc<dyn ObjectStore>,
+    key: &Key,
+    path: &std::path::Path,
+    range: std::ops::Range<u64>,
+) -> Result<(u32, u64), DownloadError> {
+    let mut output = tokio::fs::OpenOptions::new().write(true).open(path).await?;
+    output.seek(std::io::SeekFrom::Start(range.start)).await?;
+    let mut hasher = Crc32Hasher::new();
+    let mut written = 0;
+    let mut attempt = 1;
+    while range.start + written < range.end {
+        let options = GetOptions {
+            range: Some((range.start + written..range.end).into()),
+            ..Default::default()
+        };
+        let failed = match store.get_opts(key, options).await {
+            Ok(result) => {
+                let before = written;
+                let mut stream = result.into_stream();
+                let mut failed = None;
+                while let Some(chunk) = stream.next().await {
+                    match chunk {
+                        Ok(bytes) => {
+                            output.write_all(&bytes).await?;
+                            hasher.update(&bytes);
+                            written += bytes.len() as u64;
+                        }
+                        Err(err) => {
+                            failed = Some(err);
+                            break;
+                        }
+                    }
+                }
+                match failed {
+                    Some(err) => err,
+                    // Clean end of a response that made no progress: the
+                    // object is shorter than the manifest says.
+                    None if written == before => {
+                        return Err(DownloadError::Truncated {
+                            expected: range.end,
+                            actual: range.start + written,
+                        });
+                    }
+                    None => continue,
+                }
+            }
+            Err(err @ object_store::Error::NotFound { .. }) => return Err(err.into()),
+            Err(err) => err,
+        };
+        if attempt == RANGE_ATTEMPTS {
+            return Err(failed.into());
+        }
+        tracing::warn!(
+            "Range {}..{} of {key} failed at byte {}, resuming: {failed}",
+            range.start,
+            range.end,
+            range.start + written
+        );
+        attempt += 1;
+    }
+    output.flush().await?;
+    Ok((hasher.finalize(), written))
+}
+
+async fn list_from_store(
+    store: &impl ObjectStore,
+    base: &Url,
+    prefix: &Key,
+) -> Result<Vec<ListedObject>, ListError> {
+    let prefix = (!prefix.as_ref().is_empty()).then_some(prefix);
+    let mut stream = store.list(prefix);
+    let mut objects = Vec::new();
+    while let Some(meta) = stream.next().await {
+        let meta = meta?;
+        objects.push(ListedObject {
+            url: base.join(meta.location.as_ref())?,
+            size: meta.size,
+            last_modified: meta.last_modified,
+        });
+    }
+    // Stores do not promise an order; callers reading generations rely on one.
+    objects.sort_by(|a, b| a.url.as_str().cmp(b.url.as_str()));
+    Ok(objects)
+}
+
+async fn head_from_store(
+    store: &impl ObjectStore,
+    key: &Key,
+) -> Result<Option<ObjectHead>, HeadError> {
+    match store.head(key).await {
+        Ok(meta) => Ok(Some(ObjectHead {
+            size: meta.size,
+            e_tag: meta.e_tag,
+            last_modified: meta.last_modified,
+        })),
+        Err(object_store::Error::NotFound { .. }) => Ok(None),
+        Err(err) => Err(err.into()),
     }
 }
 
+/// The crc32 of everything `input` yields from its current position, leaving
+/// it rewound to the start for the upload that follows.
+pub async fn crc32_of(input: &mut (impl AsyncRead + AsyncSeek + Unpin)) -> std::io::Result<u32> {
+    let mut hasher = Crc32Hasher::new();
+    let mut stream = ReaderStream::new(&mut *input);
+    while let Some(chunk) = stream.next().await {
+        hasher.update(&chunk?);
+    }
+    input.rewind().await?;
+    Ok(hasher.finalize())
+}
+
 async fn get_bytes_from_store(
     store: &impl ObjectStore,
     key: &Key,
@@ -308,13 +856,36 @@ async fn download_from_store(
     key: &Key,
     mut output: impl AsyncWrite + Unpin,
     expected_crc32: Option<u32>,
+    encoding: Encoding<'_>,
 ) -> Result<u32, DownloadError> {
     let mut stream = store.get(key).await?.into_stream();
     let mut hasher = Crc32Hasher::new();
+    let mut opener = encoding.data_key.map(DataKey::opener);
+    let mut decompressor = encoding.codec.map(Decompressor::new).transpose()?;
+    let (mut opened, mut inflated) = (Vec::new(), Vec::new());
     while let Some(chunk) = stream.next().await {
         let bytes = chunk?;
-        hasher.update(&bytes);
-        output.write_all(&bytes).await?;
+        let bytes = match opener.as_mut() {
+            Some(opener) => {
+                opened.clear();
+                opener.update(&bytes, &mut opened)?;
+                opened.as_slice()
+            }
+            None => &bytes,
+        };
+        let bytes = inflate_chunk(decompressor.as_mut(), bytes, &mut inflated)?;
+        hasher.update(bytes);
+        output.write_all(bytes).await?;
+    }
+    if let Some(opener) = opener {
+        opened.clear();
+        opener.finish(&mut opened)?;
+        let bytes = inflate_chunk(decompressor.as_mut(), &opened, &mut inflated)?;
+        hasher.update(bytes);
+        output.write_all(bytes).await?;
+    }
+    if let Some(decompressor) = decompressor {
+        decompressor.finish()?;
     }
     output.flush().await?;
     let actual = hasher.finalize();
@@ -326,6 +897,45 @@ async fn download_from_store(
     Ok(actual)
 }
 
+fn compress_chunk(compressor: Option<&mut Compressor>, chunk: Bytes) -> std::io::Result<Bytes> {
+    let Some(compressor) = compressor else {
+        return Ok(chunk);
+    };
+    let mut out = Vec::new();
+    compressor.update(&chunk, &mut out)?;
+    Ok(out.into())
+}
+
+/// `input` inflated into `scratch`, or `input` itself when there is nothing
+/// to inflate.
+fn inflate_chunk<'a>(
+    decompressor: Option<&mut Decompressor>,
+    input: &'a [u8],
+    scratch: &'a mut Vec<u8>,
+) -> std::io::Result<&'a [u8]> {
+    let Some(decompressor) = decompressor else {
+        return Ok(input);
+    };
+    scratch.clear();
+    decompressor.update(input, scratch)?;
+    Ok(scratch)
+}
+
+fn seal_chunk(sealer: Option<&mut Sealer>, chunk: Bytes) -> Result<Bytes, EncryptionError> {
+    let Some(sealer) = sealer else {
+        return Ok(chunk);
+    };
+    let mut out = Vec::with_capacity(chunk.len());
+    sealer.update(&chunk, &mut out)?;
+    Ok(out.into())
+}
+
+fn finish_sealing(sealer: Sealer) -> Result<Bytes, EncryptionError> {
+    let mut out = Vec::new();
+    sealer.finish(&mut out)?;
+    Ok(out.into())
+}
+
 const MIN_PART_SIZE: u64 = 10 * 1024 * 1024; // 10 MB
 const MAX_PARTS: u64 = 10_000;
 
@@ -340,8 +950,15 @@ async fn acquire_permit(
     Ok(permits)
 }
 
-fn get_attributes(key: &Key) -> Attributes {
+fn get_attributes(backend: Backend, key: &Key, encoded: bool) -> Attributes {
     let mut attributes = Attributes::new();
+    // A sealed or compressed object is no longer what its extension says,
+    // and a browser handed one as `text/html` would only render noise. A
+    // local file has nowhere to keep a content type, and `LocalFileSystem`
+    // fails the whole write rather than drop one.
+    if encoded || backend == Backend::Local {
+        return attributes;
+    }
     if let Some(extension) = key.extension() {
         match extension {
             "json" => {
@@ -371,31 +988,90 @@ fn get_attributes(key: &Key) -> Attributes {
     attributes
 }
 
+/// The `object_store` implementation a url's scheme selects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Backend {
+    S3,
+    Gcs,
+    Azure,
+    Local,
+}
+
+impl Backend {
+    fn scheme(self) -> &'static str {
+        match self {
+            Self::S3 => "s3",
+            Self::Gcs => "gs",
+            Self::Azure => "az",
+            Self::Local => "file",
+        }
+    }
+}
+
+/// One bucket (or container) on one backend: what a single store serves.
+/// The local filesystem is one store with an empty name, and its keys are
+/// absolute paths.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct StoreId {
+    backend: Backend,
+    name: String,
+}
+
+impl StoreId {
+    /// The url every key in this store is relative to.
+    fn root(&self) -> Result<Url, kubimo::url::ParseError> {
+        Url::parse(&format!("{}://{}/", self.backend.scheme(), self.name))
+    }
+}
+
+impl fmt::Display for StoreId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}://{}", self.backend.scheme(), self.name)
+    }
+}
+
+/// One builder per backend, all configured from the same options. Kept
+/// unbuilt because a store is per bucket, and the bucket only comes with the
+/// url.
+#[derive(Clone)]
+struct StoreBuilders {
+    s3: AmazonS3Builder,
+    gcs: GoogleCloudStorageBuilder,
+    azure: MicrosoftAzureBuilder,
+}
+
 #[derive(Debug, Error)]
-pub enum ParseS3UrlError {
-    #[error("URL scheme is not supported")]
-    BadScheme,
+pub enum ParseStoreUrlError {
+    #[error("URL scheme is not supported: {0}")]
+    BadScheme(String),
     #[error("URL does not contain bucket name")]
     NoBucket,
     #[error(transparent)]
     Path(#[from] object_store::path::Error),
 }
 
-fn parse_s3_url(url: &Url) -> Result<(String, Key), ParseS3UrlError> {
-    if url.scheme() != "s3" {
-        return Err(ParseS3UrlError::BadScheme);
-    }
-    let Some(bucket) = url.host_str() else {
-        return Err(ParseS3UrlError::NoBucket);
+fn parse_store_url(url: &Url) -> Result<(StoreId, Key), ParseStoreUrlError> {
+    let backend = match url.scheme() {
+        "s3" => Backend::S3,
+        "gs" => Backend::Gcs,
+        "az" => Backend::Azure,
+        "file" => Backend::Local,
+        scheme => return Err(ParseStoreUrlError::BadScheme(scheme.to_string())),
+    };
+    let name = match backend {
+        Backend::Local => String::new(),
+        _ => match url.host_str() {
+            Some(bucket) if !bucket.is_empty() => bucket.to_string(),
+            _ => return Err(ParseStoreUrlError::NoBucket),
+        },
     };
     let key = Key::parse(url.path())?;
-    Ok((bucket.to_string(), key))
+    Ok((StoreId { backend, name }, key))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use object_store::aws::AmazonS3ConfigKey;
     use object_store::memory::InMemory;
 
     /// The exact key set every environment's S3 Secret carries.
@@ -422,7 +1098,7 @@ mod tests {
             ("AWS_BUCKET", "aqora-pr-1036-private"),
         ]);
 
-        let value = |key| client.builder.get_config_value(&key);
+        let value = |key| client.builders.s3.get_config_value(&key);
         assert_eq!(
             value(AmazonS3ConfigKey::Endpoint).as_deref(),
             Some("https://minio-pr-1036.aqora-internal.io"),
@@ -453,7 +1129,8 @@ mod tests {
         ]);
         assert_eq!(
             client
-                .builder
+                .builders
+                .s3
                 .get_config_value(&AmazonS3ConfigKey::Endpoint)
                 .as_deref(),
             Some("https://example.invalid"),
@@ -469,11 +1146,23 @@ mod tests {
         let client = S3Client::from_env();
 
         let mut first = CacheMarkers::new();
-        first.insert("s3://bucket/slot-a".parse().unwrap(), 1, "etag-a".into());
+        first.insert(
+            "s3://bucket/slot-a".parse().unwrap(),
+            1,
+            "etag-a".into(),
+            false,
+            None,
+        );
         client.extend_cache(first).await;
 
         let mut second = CacheMarkers::new();
-        second.insert("s3://bucket/slot-b".parse().unwrap(), 2, "etag-b".into());
+        second.insert(
+            "s3://bucket/slot-b".parse().unwrap(),
+            2,
+            "etag-b".into(),
+            false,
+            None,
+        );
         client.extend_cache(second).await;
 
         let markers = client.cache_markers.read().await;
@@ -504,6 +1193,7 @@ mod tests {
             &Key::parse("data.csv").unwrap(),
             &mut output,
             Some(crc32fast::hash(b"hello world")),
+            Encoding::default(),
         )
         .await
         .unwrap();
@@ -511,13 +1201,159 @@ mod tests {
         assert_eq!(crc32, crc32fast::hash(b"hello world"));
     }
 
+    /// A missing object is an answer, not an error: the content store asks
+    /// before every upload whether the bytes are already there.
     #[tokio::test]
-    async fn test_download_without_expected_crc32() {
+    async fn test_head_reports_absent_objects_as_none() {
         let store = store_with("data.csv", b"hello world").await;
+        let head = head_from_store(&store, &Key::parse("data.csv").unwrap())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(head.size, 11);
+        assert!(
+            head_from_store(&store, &Key::parse("missing.csv").unwrap())
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    /// The checksum a manifest records is of the plain bytes, so it must hold
+    /// of what the download writes, not of what the bucket stores.
+    #[tokio::test]
+    async fn test_download_opens_sealed_objects_and_checks_the_plain_crc32() {
+        let client = S3Client::from_options([(encryption::KEYS_OPTION, test_keys())]);
+        let data_key = client.writing_key(None).unwrap().unwrap().0;
+        let plain = vec![42u8; 200_000];
+        let store = InMemory::new();
+        let key = Key::parse("data.csv").unwrap();
+        store
+            .put(&key, data_key.seal(&plain).unwrap().into())
+            .await
+            .unwrap();
         let mut output = std::io::Cursor::new(Vec::new());
-        download_from_store(&store, &Key::parse("data.csv").unwrap(), &mut output, None)
+        download_from_store(
+            &store,
+            &key,
+            &mut output,
+            Some(crc32fast::hash(&plain)),
+            Encoding {
+                data_key: Some(&data_key),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(output.into_inner(), plain);
+
+        // Read back without the key, it is neither the file nor its checksum.
+        let err = download_from_store(
+            &store,
+            &key,
+            std::io::Cursor::new(Vec::new()),
+            Some(crc32fast::hash(&plain)),
+            Encoding::default(),
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(err, DownloadError::Crc32Mismatch { .. }));
+    }
+
+    /// Compressed, then sealed: the download has to undo both, in the other
+    /// order, before the checksum means anything.
+    #[tokio::test]
+    async fn test_download_inflates_compressed_objects_under_the_seal() {
+        let client = S3Client::from_options([(encryption::KEYS_OPTION, test_keys())]);
+        let data_key = client.writing_key(None).unwrap().unwrap().0;
+        let plain = b"name,value\nalpha,1\n".repeat(20_000);
+        let compressed = compression::compress_if_smaller(ContentCodec::Zstd, 3, &plain)
+            .unwrap()
+            .unwrap();
+        let store = InMemory::new();
+        let key = Key::parse("data.csv").unwrap();
+        store
+            .put(&key, data_key.seal(&compressed).unwrap().into())
             .await
             .unwrap();
+        let mut output = std::io::Cursor::new(Vec::new());
+        let crc32 = download_from_store(
+            &store,
+            &key,
+            &mut output,
+            Some(crc32fast::hash(&plain)),
+            Encoding {
+                codec: Some(ContentCodec::Zstd),
+                data_key: Some(&data_key),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(output.into_inner(), plain);
+        assert_eq!(crc32, crc32fast::hash(&plain));
+    }
+
+    fn test_keys() -> String {
+        use base64::Engine as _;
+        format!(
+            "k1={}",
+            base64::engine::general_purpose::STANDARD.encode([1u8; 32])
+        )
+    }
+
+    /// The archive keys arrive in the same Secret as the S3 credentials. One
+    /// that is present but malformed must fail every use, not be read as "no
+    /// encryption".
+    #[test]
+    fn archive_keys_ride_with_the_credentials_and_fail_closed() {
+        let client = S3Client::from_options([
+            ("AWS_ENDPOINT", "https://example.invalid".to_string()),
+            ("kubimo_archive_keys", test_keys()),
+        ]);
+        let (data_key, fresh) = client.writing_key(None).unwrap().unwrap();
+        assert!(fresh);
+        let (reused, fresh) = client
+            .writing_key(Some(data_key.envelope()))
+            .unwrap()
+            .unwrap();
+        assert!(!fresh);
+        assert_eq!(reused.envelope().key_id, "k1");
+        assert!(client.reading_key(Some(data_key.envelope())).is_ok());
+
+        let plain = S3Client::from_options([("AWS_ENDPOINT", "https://example.invalid")]);
+        assert!(
+            plain
+                .writing_key(Some(data_key.envelope()))
+                .unwrap()
+                .is_none()
+        );
+        assert!(matches!(
+            plain.reading_key(Some(data_key.envelope())),
+            Err(EncryptionError::UnknownKey(_))
+        ));
+        assert!(plain.reading_key(None).unwrap().is_none());
+
+        let broken = S3Client::from_options([(encryption::KEYS_OPTION, "k1=short")]);
+        assert!(matches!(
+            broken.writing_key(None),
+            Err(EncryptionError::MalformedKeys(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_download_without_expected_crc32() {
+        let store = store_with("data.csv", b"hello world").await;
+        let mut output = std::io::Cursor::new(Vec::new());
+        download_from_store(
+            &store,
+            &Key::parse("data.csv").unwrap(),
+            &mut output,
+            None,
+            Encoding::default(),
+        )
+        .await
+        .unwrap();
         assert_eq!(output.into_inner(), b"hello world");
     }
 
@@ -530,12 +1366,106 @@ mod tests {
             &Key::parse("data.csv").unwrap(),
             &mut output,
             Some(crc32fast::hash(b"something else")),
+            Encoding::default(),
         )
         .await
         .unwrap_err();
         assert!(matches!(err, DownloadError::Crc32Mismatch { .. }));
     }
 
+    /// Listing is by segment: a sibling prefix sharing the same leading
+    /// characters is a different workspace and must not show up.
+    #[tokio::test]
+    async fn test_list_from_store_stays_under_the_directory() {
+        let store = InMemory::new();
+        for key in [
+            "ws/history/b.json",
+            "ws/history/a.json",
+            "ws/0123456789abc.py",
+            "ws-other/history/c.json",
+        ] {
+            store
+                .put(&Key::parse(key).unwrap(), b"{}".as_slice().into())
+                .await
+                .unwrap();
+        }
+        let base = Url::parse("s3://bucket/").unwrap();
+        let listed = list_from_store(&store, &base, &Key::parse("ws/history").unwrap())
+            .await
+            .unwrap();
+        assert_eq!(
+            listed.iter().map(|o| o.url.as_str()).collect::<Vec<_>>(),
+            vec![
+                "s3://bucket/ws/history/a.json",
+                "s3://bucket/ws/history/b.json"
+            ]
+        );
+        assert_eq!(listed[0].size, 2);
+    }
+
+    /// A `file://` archive goes through the same client as an S3 one, with
+    /// absolute paths for keys. An `.html` key is the case that would fail if
+    /// content types reached `LocalFileSystem`.
+    #[tokio::test]
+    async fn test_file_urls_round_trip_through_the_local_filesystem() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = kubimo::bucket_url(&format!("file://{}", dir.path().display())).unwrap();
+        let url = root.join("ws/index.html").unwrap();
+        let client = S3Client::from_options(Vec::<(String, String)>::new());
+        let bytes = b"<html></html>";
+        let uploaded = client
+            .upload(
+                &url,
+                std::io::Cursor::new(bytes.to_vec()),
+                bytes.len() as u64,
+                &Semaphore::new(1),
+            )
+            .await
+            .unwrap();
+        assert_eq!(uploaded.crc32, crc32fast::hash(bytes));
+        assert_eq!(
+            std::fs::read(dir.path().join("ws/index.html")).unwrap(),
+            bytes
+        );
+
+        let listed = client.list(&root.join("ws/").unwrap()).await.unwrap();
+        assert_eq!(
+            listed.iter().map(|o| &o.url).collect::<Vec<_>>(),
+            vec![&url]
+        );
+        let mut output = Vec::new();
+        client
+            .download(&url, &mut output, Some(uploaded.crc32), Encoding::default())
+            .await
+            .unwrap();
+        assert_eq!(output, bytes);
+    }
+
+    #[test]
+    fn test_store_urls_select_the_backend() {
+        let store = |url: &str| parse_store_url(&url.parse().unwrap()).unwrap();
+        let (id, key) = store("gs://bucket/ws/manifest.json");
+        assert_eq!((id.backend, id.name.as_str()), (Backend::Gcs, "bucket"));
+        assert_eq!(key.as_ref(), "ws/manifest.json");
+        let (id, _) = store("az://container/ws/manifest.json");
+        assert_eq!(id.to_string(), "az://container");
+        let (id, key) = store("file:///srv/archive/ws/manifest.json");
+        assert_eq!(id.backend, Backend::Local);
+        assert_eq!(key.as_ref(), "srv/archive/ws/manifest.json");
+        assert!(matches!(
+            parse_store_url(&"https://bucket/ws".parse().unwrap()),
+            Err(ParseStoreUrlError::BadScheme(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_crc32_of_rewinds_the_input() {
+        let mut input = std::io::Cursor::new(b"hello world".to_vec());
+        let crc32 = crc32_of(&mut input).await.unwrap();
+        assert_eq!(crc32, crc32fast::hash(b"hello world"));
+        assert_eq!(input.position(), 0);
+    }
+
     #[tokio::test]
     async fn test_get_bytes_from_store() {
         let store = store_with("manifest.json", b"{}").await;
@@ -546,9 +1476,18 @@ mod tests {
     }
 }
 
+/// What an upload last left at a key.
+#[derive(Debug, Clone)]
+struct CacheMarker {
+    crc32: u32,
+    e_tag: String,
+    sealed: bool,
+    codec: Option<ContentCodec>,
+}
+
 #[derive(Debug, Default)]
 pub struct CacheMarkers {
-    items: BTreeMap<(String, Key), (u32, String)>,
+    items: BTreeMap<(StoreId, Key), CacheMarker>,
 }
 
 impl CacheMarkers {
@@ -556,13 +1495,28 @@ impl CacheMarkers {
         Self::default()
     }
 
-    pub fn insert(&mut self, url: Url, crc32: u32, e_tag: String) {
-        match parse_s3_url(&url) {
+    pub fn insert(
+        &mut self,
+        url: Url,
+        crc32: u32,
+        e_tag: String,
+        sealed: bool,
+        codec: Option<ContentCodec>,
+    ) {
+        match parse_store_url(&url) {
             Ok((bucket, key)) => {
-                self.items.insert((bucket, key), (crc32, e_tag));
+                self.items.insert(
+                    (bucket, key),
+                    CacheMarker {
+                        crc32,
+                        e_tag,
+                        sealed,
+                        codec,
+                    },
+                );
             }
             Err(err) => {
-                tracing::warn!("Failed to parse S3 URL for cache marker: {url}: {err}");
+                tracing::warn!("Failed to parse store URL for cache marker: {url}: {err}");
             }
         }
     }
@@ -575,4 +1529,15 @@ impl CacheMarkers {
     pub fn extend(&mut self, other: CacheMarkers) {
         self.items.extend(other.items);
     }
+
+    /// Drop the markers of every sealed object, returning them. A sealed
+    /// marker only says the object holds these bytes under *some* data key;
+    /// once the archive has a new one, that is no reason to keep naming it.
+    pub fn forget_sealed(&mut self) -> CacheMarkers {
+        let (sealed, plain) = std::mem::take(&mut self.items)
+            .into_iter()
+            .partition(|(_, marker)| marker.sealed);
+        self.items = plain;
+        CacheMarkers { items: sealed }
+    }
 }
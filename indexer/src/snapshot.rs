@@ -0,0 +1,548 @@
+//! Named, immutable copies of an archive.
+//!
+//! A snapshot is an archive root of its own: a manifest, the content objects it
+//! names and the secrets object, all under a prefix nothing else writes to. The
+//! live archive cannot serve for this. Without history its content keys are
+//! overwritten in place; with it a changed file moves to a fresh key, and the
+//! old one, though never reused, is swept once no retained generation names it.
+//! So the content is copied, not referenced.
+//!
+//! The exception is content in the shared store, which is never overwritten
+//! and never swept while a manifest names it. The copy's manifest is one such,
+//! so those objects are referenced as they are.
+//!
+//! Chunks of large files are never overwritten either, but they are swept, so
+//! they are copied like any other content.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use kubimo::{ContentCodec, SnapshotSummary, WorkspaceManifest, url::Url};
+use thiserror::Error;
+use tokio::{sync::Semaphore, task::JoinSet};
+
+use crate::encryption::{DataKey, EncryptionError};
+use crate::restore::{ArchiveOrigin, PlanError};
+use crate::s3::{CopyError, DownloadError, Encoding, S3Client, UploadError};
+
+#[derive(Debug, Error)]
+pub enum SnapshotError {
+    #[error(transparent)]
+    Download(#[from] DownloadError),
+    #[error(transparent)]
+    Upload(#[from] UploadError),
+    #[error(transparent)]
+    Copy(#[from] CopyError),
+    #[error("error parsing manifest: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error(transparent)]
+    Url(#[from] kubimo::url::ParseError),
+    #[error(transparent)]
+    Plan(#[from] PlanError),
+    #[error("archive was written without --upload-content and cannot be snapshotted")]
+    NoContent,
+    #[error("archive has no manifest to snapshot")]
+    NoManifest,
+    #[error("{0} of {1} objects failed to copy")]
+    Failed(usize, usize),
+    #[error(transparent)]
+    Encryption(#[from] EncryptionError),
+}
+
+/// Everything [`snapshot`] needs, without the binary's clap types.
+#[derive(Debug, Clone)]
+pub struct SnapshotOptions {
+    /// Bucket holding the source archive; the copy goes to the same bucket.
+    pub bucket: String,
+    pub key_prefix: Option<String>,
+    /// Where the copy is written, normally [`kubimo::snapshot_key_prefix`].
+    pub dest_key_prefix: String,
+    pub max_concurrency: usize,
+}
+
+/// One content object to copy, and the checksum the source manifest recorded
+/// for it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SnapshotCopy {
+    pub from: Url,
+    pub to: Url,
+    pub crc32: Option<u32>,
+    /// Copied sealed and compressed as it is; only the read-back has to
+    /// decode it.
+    pub encrypted: bool,
+    pub codec: Option<ContentCodec>,
+}
+
+#[derive(Debug)]
+pub struct SnapshotPlan {
+    /// The source manifest with every content url moved under the copy.
+    pub manifest: WorkspaceManifest,
+    pub copies: Vec<SnapshotCopy>,
+}
+
+/// Rewrite `manifest` to describe a copy under `dest`, and list the objects
+/// that copy needs.
+///
+/// Content urls are checked against `origin` exactly as a restore would: the
+/// copy is made with credentials for the whole bucket, so a manifest naming
+/// another prefix must not have that prefix's objects pulled into a snapshot
+/// that anyone with access to this one could then restore.
+///
+/// Content-store urls are kept as they are and not copied: see the module
+/// docs.
+///
+/// Marimo meta and cache urls are dropped rather than copied. They are derived
+/// artifacts a restore ignores, and left in place they would point back into
+/// the live archive, which is free to overwrite them.
+pub fn plan_snapshot(
+    manifest: &WorkspaceManifest,
+    origin: &ArchiveOrigin,
+    dest: &ArchiveOrigin,
+) -> Result<SnapshotPlan, SnapshotError> {
+    let origin_base = origin.base();
+    let dest_base = Url::parse(&dest.base())?;
+    let mut manifest = manifest.clone();
+    // Keyed by source url so an object two entries share is copied once.
+    let mut copies = BTreeMap::new();
+    for entry in manifest
+        .directories
+        .iter_mut()
+        .flat_map(|dir| dir.entries.iter_mut())
+    {
+        let Some(file) = entry.file.as_mut() else {
+            continue;
+        };
+        file.marimo = None;
+        let Some(content) = file.content.as_mut() else {
+            continue;
+        };
+        origin.check(&content.url)?;
+        let rebase = |url: &Url| dest_base.join(&url.as_str()[origin_base.len()..]);
+        let encrypted = content.encrypted.unwrap_or(false);
+        if let Some(chunks) = content.chunks.as_mut() {
+            // Copied like whole files, not left in place: the source's sweep
+            // deletes a chunk as soon as no file there holds it any more.
+            for chunk in chunks {
+                origin.check(&chunk.url)?;
+                if origin.shares(&chunk.url) {
+                    continue;
+                }
+                let to = rebase(&chunk.url)?;
+                copies.entry(chunk.url.clone()).or_insert(SnapshotCopy {
+                    from: chunk.url.clone(),
+                    to: to.clone(),
+                    crc32: Some(chunk.crc32),
+                    encrypted,
+                    codec: chunk.codec,
+                });
+                chunk.url = to;
+                chunk.e_tag = None;
+            }
+            // Names no object, so there is nothing to copy.
+            content.url = rebase(&content.url)?;
+            continue;
+        }
+        if origin.shares(&content.url) {
+            continue;
+        }
+        let to = rebase(&content.url)?;
+        copies.entry(content.url.clone()).or_insert(SnapshotCopy {
+            from: content.url.clone(),
+            to: to.clone(),
+            crc32: content.crc32,
+            encrypted,
+            codec: content.codec,
+        });
+        content.url = to;
+        // The e-tag is the source object's; a copy gets its own.
+        content.e_tag = None;
+    }
+    Ok(SnapshotPlan {
+        manifest,
+        copies: copies.into_values().collect(),
+    })
+}
+
+/// Copy the archive at `opts.key_prefix` to `opts.dest_key_prefix`.
+///
+/// Idempotent: the copy's manifest is written last, so its presence means the
+/// copy is complete, and a rerun — the snapshot Job retrying after a lost pod —
+/// returns what is already there instead of copying again. Without it a rerun
+/// after the source moved on would silently replace the checkpoint with a
+/// newer state.
+pub async fn snapshot(
+    opts: &SnapshotOptions,
+    s3: &S3Client,
+) -> Result<SnapshotSummary, SnapshotError> {
+    let dest_manifest_url = kubimo::manifest_url(&opts.bucket, Some(&opts.dest_key_prefix))?;
+    match s3.get_bytes(&dest_manifest_url).await {
+        Ok(bytes) => {
+            let manifest: WorkspaceManifest = serde_json::from_slice(&bytes)?;
+            tracing::info!("Snapshot already complete at {dest_manifest_url}");
+            return Ok(SnapshotSummary::from(&manifest));
+        }
+        Err(DownloadError::S3(object_store::Error::NotFound { .. })) => {}
+        Err(err) => return Err(err.into()),
+    }
+
+    let manifest_url = kubimo::manifest_url(&opts.bucket, opts.key_prefix.as_deref())?;
+    let manifest: WorkspaceManifest = match s3.get_bytes(&manifest_url).await {
+        Ok(bytes) => serde_json::from_slice(&bytes)?,
+        Err(DownloadError::S3(object_store::Error::NotFound { .. })) => {
+            return Err(SnapshotError::NoManifest);
+        }
+        Err(err) => return Err(err.into()),
+    };
+    if !manifest.upload_content {
+        return Err(SnapshotError::NoContent);
+    }
+    let origin = ArchiveOrigin {
+        bucket: opts.bucket.clone(),
+        key_prefix: opts.key_prefix.clone(),
+    };
+    let dest = ArchiveOrigin {
+        bucket: opts.bucket.clone(),
+        key_prefix: Some(opts.dest_key_prefix.clone()),
+    };
+    let mut plan = plan_snapshot(&manifest, &origin, &dest)?;
+    // The copies keep the source's data key, so the copy's manifest carries
+    // it too — wrapped under the current primary, so a checkpoint taken after
+    // a rotation no longer depends on the retired key.
+    let data_key = s3.reading_key(manifest.encryption.as_ref())?;
+    if let Some(data_key) = &data_key {
+        plan.manifest.encryption = Some(data_key.envelope().clone());
+    }
+
+    let total = plan.copies.len();
+    let permits = Arc::new(Semaphore::new(opts.max_concurrency));
+    let mut join_set = JoinSet::new();
+    for copy in plan.copies {
+        let s3 = s3.clone();
+        let permits = permits.clone();
+        let data_key = data_key.clone();
+        join_set.spawn(async move {
+            let _permit = match permits.acquire().await {
+                Ok(permit) => permit,
+                Err(err) => {
+                    tracing::error!("Error acquiring permit: {err}");
+                    return 1;
+                }
+            };
+            match copy_object(&s3, &copy, data_key.as_ref()).await {
+                Ok(()) => {
+                    tracing::info!("Copied {} to {}", copy.from, copy.to);
+                    0
+                }
+                Err(err) => {
+                    tracing::error!("Error copying {}: {err}", copy.from);
+                    1
+                }
+            }
+        });
+    }
+    let mut failed = 0;
+    while let Some(res) = join_set.join_next().await {
+        failed += res.unwrap_or(1);
+    }
+    if failed > 0 {
+        // No manifest, so the partial copy is invisible to a restore and the
+        // retry starts over.
+        return Err(SnapshotError::Failed(failed, total));
+    }
+
+    // The secrets object is keyed like the manifest and named nowhere, so it
+    // is copied by construction. A legacy archive has none.
+    let secrets_from = kubimo::secrets_url(&opts.bucket, opts.key_prefix.as_deref())?;
+    let secrets_to = kubimo::secrets_url(&opts.bucket, Some(&opts.dest_key_prefix))?;
+    match s3.copy(&secrets_from, &secrets_to).await {
+        Ok(()) => {}
+        Err(CopyError::S3(object_store::Error::NotFound { .. })) => {}
+        Err(err) => return Err(err.into()),
+    }
+
+    let bytes = serde_json::to_vec(&plan.manifest)?;
+    let size = bytes.len() as u64;
+    s3.upload(
+        &dest_manifest_url,
+        std::io::Cursor::new(bytes),
+        size,
+        &Semaphore::new(1),
+    )
+    .await?;
+    tracing::info!("Snapshot of {manifest_url} complete at {dest_manifest_url}");
+    Ok(SnapshotSummary::from(&plan.manifest))
+}
+
+/// Copy one object, then read the copy back against the manifest's checksum.
+///
+/// The live archive overwrites a modified file's object in place, so a copy
+/// taken while the workspace is being edited can capture newer bytes than the
+/// manifest describes. Restore would refuse such an object on its checksum,
+/// but only long after the checkpoint was reported ready; failing here lets
+/// the Job retry against a fresh manifest instead.
+///
+/// The checksum is of the file's bytes, so a sealed or compressed copy is
+/// decoded to check it.
+async fn copy_object(
+    s3: &S3Client,
+    copy: &SnapshotCopy,
+    data_key: Option<&DataKey>,
+) -> Result<(), SnapshotError> {
+    let data_key = match (copy.encrypted, data_key) {
+        (false, _) => None,
+        (true, Some(data_key)) => Some(data_key),
+        (true, None) => return Err(EncryptionError::NoDataKey.into()),
+    };
+    s3.copy(&copy.from, &copy.to).await?;
+    if copy.crc32.is_some() {
+        let encoding = Encoding {
+            codec: copy.codec,
+            data_key,
+            ..Default::default()
+        };
+        s3.download(&copy.to, tokio::io::sink(), copy.crc32, encoding)
+            .await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kubimo::{
+        ManifestDirectory, ManifestVersion, WorkspaceDirContentChunk, WorkspaceDirContentUrl,
+        WorkspaceDirEntry, WorkspaceDirFile, WorkspaceDirMarimo,
+    };
+
+    fn file(name: &str, url: &str, crc32: u32) -> WorkspaceDirEntry {
+        WorkspaceDirEntry {
+            name: name.to_string(),
+            file: Some(WorkspaceDirFile {
+                size: Some(3),
+                content: Some(WorkspaceDirContentUrl {
+                    url: url.parse().unwrap(),
+                    crc32: Some(crc32),
+                    e_tag: Some("etag".to_string()),
+                    encrypted: None,
+                    codec: None,
+                    chunks: None,
+                }),
+                marimo: Some(WorkspaceDirMarimo {
+                    meta_json: Some(WorkspaceDirContentUrl {
+                        url: "s3://bucket/ws/META".parse().unwrap(),
+                        crc32: None,
+                        e_tag: None,
+                        encrypted: None,
+                        codec: None,
+                        chunks: None,
+                    }),
+                    caches: None,
+                }),
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn manifest(entries: Vec<WorkspaceDirEntry>) -> WorkspaceManifest {
+        WorkspaceManifest {
+            version: ManifestVersion::V1,
+            workspace: "bmow-x".to_string(),
+            upload_content: true,
+            total_content_bytes: 3 * entries.len() as u64,
+            directories: vec![ManifestDirectory {
+                path: String::new(),
+                entries,
+            }],
+            secrets: Some(Default::default()),
+            encryption: None,
+        }
+    }
+
+    fn origin(key_prefix: &str) -> ArchiveOrigin {
+        ArchiveOrigin {
+            bucket: "bucket".to_string(),
+            key_prefix: Some(key_prefix.to_string()),
+        }
+    }
+
+    #[test]
+    fn a_snapshot_moves_every_content_url_under_its_own_prefix() {
+        let source = manifest(vec![
+            file("a.py", "s3://bucket/ws/AAAAAAAAAAAAA", 1),
+            file("b.py", "s3://bucket/ws/BBBBBBBBBBBBB", 2),
+        ]);
+        let plan = plan_snapshot(
+            &source,
+            &origin("ws/"),
+            &origin(&kubimo::snapshot_key_prefix(Some("ws/"), "uid")),
+        )
+        .unwrap();
+        assert_eq!(
+            plan.copies,
+            vec![
+                SnapshotCopy {
+                    from: "s3://bucket/ws/AAAAAAAAAAAAA".parse().unwrap(),
+                    to: "s3://bucket/ws/snapshots/uid/AAAAAAAAAAAAA"
+                        .parse()
+                        .unwrap(),
+                    crc32: Some(1),
+                    encrypted: false,
+                    codec: None,
+                },
+                SnapshotCopy {
+                    from: "s3://bucket/ws/BBBBBBBBBBBBB".parse().unwrap(),
+                    to: "s3://bucket/ws/snapshots/uid/BBBBBBBBBBBBB"
+                        .parse()
+                        .unwrap(),
+                    crc32: Some(2),
+                    encrypted: false,
+                    codec: None,
+                },
+            ]
+        );
+        let entry = &plan.manifest.directories[0].entries[0];
+        let file = entry.file.as_ref().unwrap();
+        let content = file.content.as_ref().unwrap();
+        assert_eq!(
+            content.url.as_str(),
+            "s3://bucket/ws/snapshots/uid/AAAAAAAAAAAAA"
+        );
+        assert_eq!(content.crc32, Some(1));
+        assert_eq!(content.e_tag, None);
+        // Nothing in the copy may point back into the live archive.
+        assert!(file.marimo.is_none());
+        assert_eq!(
+            SnapshotSummary::from(&plan.manifest),
+            SnapshotSummary {
+                files: 2,
+                total_content_bytes: 6,
+            }
+        );
+    }
+
+    /// The copy restores like any archive, so it must pass the same origin
+    /// check a restore pointed at it applies.
+    #[test]
+    fn a_snapshot_restores_from_its_own_prefix() {
+        let dest = origin(&kubimo::snapshot_key_prefix(Some("ws/"), "uid"));
+        let plan = plan_snapshot(
+            &manifest(vec![file("a.py", "s3://bucket/ws/AAAAAAAAAAAAA", 1)]),
+            &origin("ws/"),
+            &dest,
+        )
+        .unwrap();
+        crate::restore::plan_restore(
+            &plan.manifest,
+            &dest,
+            &ignore::gitignore::Gitignore::empty(),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn a_snapshot_references_the_content_store_instead_of_copying_it() {
+        let shared = kubimo::content_store_url(
+            "bucket",
+            "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9",
+        )
+        .unwrap();
+        let plan = plan_snapshot(
+            &manifest(vec![
+                file("a.py", "s3://bucket/ws/AAAAAAAAAAAAA", 1),
+                file("data.csv", shared.as_str(), 2),
+            ]),
+            &origin("ws/"),
+            &origin("ws/snapshots/uid/"),
+        )
+        .unwrap();
+        assert_eq!(plan.copies.len(), 1);
+        let content = plan.manifest.directories[0].entries[1]
+            .file
+            .as_ref()
+            .and_then(|file| file.content.as_ref())
+            .unwrap();
+        assert_eq!(content.url, shared);
+        assert_eq!(content.e_tag.as_deref(), Some("etag"));
+    }
+
+    #[test]
+    fn a_snapshot_copies_chunks_and_copies_a_shared_one_once() {
+        let chunk = |name: &str, crc32| WorkspaceDirContentChunk {
+            url: format!("s3://bucket/ws/chunks/{name}").parse().unwrap(),
+            size: 3,
+            crc32,
+            e_tag: Some("etag".to_string()),
+            codec: Some(ContentCodec::Zstd),
+        };
+        let mut a = file("a.bin", "s3://bucket/ws/AAAAAAAAAAAAA", 1);
+        let mut b = file("b.bin", "s3://bucket/ws/BBBBBBBBBBBBB", 2);
+        for (entry, chunks) in [
+            (&mut a, vec![chunk("one", 10), chunk("two", 20)]),
+            (&mut b, vec![chunk("two", 20)]),
+        ] {
+            entry
+                .file
+                .as_mut()
+                .unwrap()
+                .content
+                .as_mut()
+                .unwrap()
+                .chunks = Some(chunks);
+        }
+        let dest = origin("ws/snapshots/uid/");
+        let plan = plan_snapshot(&manifest(vec![a, b]), &origin("ws/"), &dest).unwrap();
+        assert_eq!(
+            plan.copies
+                .iter()
+                .map(|copy| (copy.to.as_str(), copy.crc32, copy.codec))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    "s3://bucket/ws/snapshots/uid/chunks/one",
+                    Some(10),
+                    Some(ContentCodec::Zstd)
+                ),
+                (
+                    "s3://bucket/ws/snapshots/uid/chunks/two",
+                    Some(20),
+                    Some(ContentCodec::Zstd)
+                ),
+            ]
+        );
+        let content = plan.manifest.directories[0].entries[0]
+            .file
+            .as_ref()
+            .and_then(|file| file.content.as_ref())
+            .unwrap();
+        assert_eq!(
+            content.url.as_str(),
+            "s3://bucket/ws/snapshots/uid/AAAAAAAAAAAAA"
+        );
+        let chunks = content.chunks.as_ref().unwrap();
+        assert_eq!(
+            chunks[1].url.as_str(),
+            "s3://bucket/ws/snapshots/uid/chunks/two"
+        );
+        assert_eq!(chunks[1].e_tag, None);
+        crate::restore::plan_restore(
+            &plan.manifest,
+            &dest,
+            &ignore::gitignore::Gitignore::empty(),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn a_snapshot_refuses_content_outside_the_archive() {
+        let err = plan_snapshot(
+            &manifest(vec![file("a.py", "s3://bucket/other/AAAAAAAAAAAAA", 1)]),
+            &origin("ws/"),
+            &origin("ws/snapshots/uid/"),
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            SnapshotError::Plan(PlanError::ForeignContent { .. })
+        ));
+    }
+}
@@ -20,10 +20,10 @@ use futures::{
 use kubimo::FilterParams;
 use kubimo::{
     ManifestSecrets, ResourceNameExt, SecretEnvEntry, SecretFileEntry, Workspace,
-    WorkspaceArchiveStatus, WorkspaceDir, WorkspaceDirContentUrl, WorkspaceDirDirectory,
-    WorkspaceDirEntry, WorkspaceDirField, WorkspaceDirFile, WorkspaceDirMarimo,
-    WorkspaceDirMarimoCache, WorkspaceDirSpec, WorkspaceDirSymlink, WorkspaceSecrets,
-    WorkspaceSecretsVersion, WorkspaceStatus, WorkspaceStorageStatus, url::Url,
+    WorkspaceArchiveStatus, WorkspaceDir, WorkspaceDirContentChunk, WorkspaceDirContentUrl,
+    WorkspaceDirDirectory, WorkspaceDirEntry, WorkspaceDirField, WorkspaceDirFile,
+    WorkspaceDirMarimo, WorkspaceDirMarimoCache, WorkspaceDirSpec, WorkspaceDirSymlink,
+    WorkspaceSecrets, WorkspaceSecretsVersion, WorkspaceStatus, WorkspaceStorageStatus, url::Url,
 };
 use thiserror::Error;
 use tokio::{
@@ -36,11 +36,16 @@ use tokio::{
     task::JoinSet,
 };
 
+use crate::chunking::{self, Chunker, Chunking, ChunkingPolicy};
+use crate::compression::CompressionPolicy;
+use crate::content_store::{self, ContentStore};
 use crate::disk;
+use crate::encryption::{DataKey, EncryptionError};
 use crate::fingerprint::ContentCache;
+use crate::history::{self, GenerationUrls, HistoryRetention, Recorded};
 use crate::keys::{WorkspaceDirNameSet, WorkspaceFileUrlSet};
 use crate::python::{Notebook, get_marimo_notebook};
-use crate::s3::{CacheMarkers, DownloadError, S3Client, UploadError};
+use crate::s3::{self, CacheMarkers, DownloadError, HeadError, S3Client, UploadError};
 use crate::secrets;
 use crate::watcher::{WaitError, Watcher};
 
@@ -64,6 +69,25 @@ pub struct UploadOptions {
     /// Ceiling on how long a burst of events may defer a sync.
     pub watch_max_wait_millis: u64,
     pub watch_poll_millis: u64,
+    /// Keep point-in-time generations of the manifest. `None` leaves the
+    /// archive describing only the current tree, as it always has.
+    pub history: Option<HistoryRetention>,
+    /// Upload content to the bucket's shared content-addressed store instead
+    /// of under this workspace's own keys.
+    pub content_addressed: bool,
+    /// Compress content worth compressing. `None` stores every file's bytes
+    /// as they are.
+    pub compression: Option<CompressionPolicy>,
+    /// Store files from the threshold up as content-defined chunks, so a
+    /// change re-uploads only the chunks around it. `None` uploads every file
+    /// whole, up to `max_file_size`.
+    pub chunking: Option<ChunkingPolicy>,
+    /// Keep the archived cache of any notebook and format that has no cache
+    /// file on disk. A CacheJob regenerates only the notebooks and formats it
+    /// selects, and a freshly hydrated slot holds no caches at all (they are
+    /// never restored), so without this every cache it did not touch would
+    /// drop out of the manifest and its object be swept.
+    pub keep_caches: bool,
     /// Name of the Workspace this directory belongs to.
     pub name: String,
     pub directory: PathBuf,
@@ -75,13 +99,26 @@ const CACHE_FORMATS: &[&str] = &["md", "html", "ipynb"];
 pub struct WorkspaceKeys {
     dir_names: Arc<Mutex<WorkspaceDirNameSet>>,
     file_urls: Arc<Mutex<WorkspaceFileUrlSet>>,
+    /// Which content keys each archive generation names. Lives here because
+    /// it has the same lifetime as the key sets: the whole watch.
+    generation_urls: GenerationUrls,
+    /// What content and secrets are sealed with, from [`resolve_data_key`].
+    /// Fixed for the watch like the keys: markers for sealed objects are only
+    /// good for as long as the key that sealed them.
+    data_key: Option<DataKey>,
 }
 
 impl WorkspaceKeys {
-    pub fn new(dir_names: WorkspaceDirNameSet, file_urls: WorkspaceFileUrlSet) -> Self {
+    pub fn new(
+        dir_names: WorkspaceDirNameSet,
+        file_urls: WorkspaceFileUrlSet,
+        data_key: Option<DataKey>,
+    ) -> Self {
         Self {
             dir_names: Arc::new(Mutex::new(dir_names)),
             file_urls: Arc::new(Mutex::new(file_urls)),
+            generation_urls: GenerationUrls::new(),
+            data_key,
         }
     }
 
@@ -92,6 +129,10 @@ impl WorkspaceKeys {
     pub async fn file_url(&self, path: PathBuf) -> Result<Url, kubimo::url::ParseError> {
         self.file_urls.lock().await.get_or_insert(path)
     }
+
+    pub async fn rotate_file_url(&self, path: PathBuf) -> Result<Url, kubimo::url::ParseError> {
+        self.file_urls.lock().await.rotate(path)
+    }
 }
 
 fn marimo_cache_path(path: impl AsRef<Path>, format: &str) -> Option<PathBuf> {
@@ -120,9 +161,76 @@ pub struct WorkerOptions {
     keys: WorkspaceKeys,
     /// Lets an unchanged file skip being read and HEADed entirely.
     content_cache: ContentCache,
+    /// Upload changed content under a fresh key rather than over the object a
+    /// generation may still restore. Only content: marimo meta and caches are
+    /// derived from it and no generation restores them.
+    rotate_modified: bool,
+    /// Name content by its digest in the shared store. Takes precedence over
+    /// rotation, which it makes unnecessary: a store object never changes.
+    /// Never set alongside a data key: see [`run`].
+    content_store: Option<ContentStore>,
+    /// Which content to compress. Only content under the workspace's own
+    /// keys: see [`EntryWorker::upload_addressed`].
+    compression: Option<CompressionPolicy>,
+    /// Where and from what size content is chunked. Raises the size cap for
+    /// content only: a marimo notebook past `max_file_size` is still skipped.
+    chunking: Option<Chunking>,
     /// Shared with the run that spawned these workers: what they could not
     /// upload is what the archive is missing, and only the run can report it.
     failures: Arc<AtomicUsize>,
+    /// Caches the previous manifest named, by notebook path, when the run
+    /// keeps them (see [`UploadOptions::keep_caches`]).
+    previous_caches: Option<Arc<PreviousCaches>>,
+}
+
+/// The caches an archive's manifest names, by the path of their notebook.
+type PreviousCaches = BTreeMap<PathBuf, Vec<WorkspaceDirMarimoCache>>;
+
+fn manifest_caches(manifest: &kubimo::WorkspaceManifest) -> PreviousCaches {
+    let mut caches = PreviousCaches::new();
+    for directory in &manifest.directories {
+        for entry in &directory.entries {
+            if let Some(found) = entry
+                .file
+                .as_ref()
+                .and_then(|file| file.marimo.as_ref())
+                .and_then(|marimo| marimo.caches.clone())
+            {
+                caches.insert(Path::new(&directory.path).join(&entry.name), found);
+            }
+        }
+    }
+    caches
+}
+
+/// Read the caches the current manifest names. No manifest yet means no
+/// caches to keep.
+async fn previous_caches(
+    bucket: &str,
+    key_prefix: Option<&str>,
+    s3: &S3Client,
+) -> Result<PreviousCaches, DataKeyError> {
+    let url = kubimo::manifest_url(bucket, key_prefix)?;
+    match s3.get_bytes(&url).await {
+        Ok(bytes) => Ok(manifest_caches(&serde_json::from_slice(&bytes)?)),
+        Err(DownloadError::S3(object_store::Error::NotFound { .. })) => Ok(PreviousCaches::new()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Add to `caches`, found on disk for the notebook at `path`, the previously
+/// archived ones for every format it lacks. Only those with an object: one
+/// that was too large to upload has nothing to keep.
+fn keep_previous_caches(
+    caches: &mut Vec<WorkspaceDirMarimoCache>,
+    path: &Path,
+    previous: &PreviousCaches,
+) {
+    for cache in previous.get(path).into_iter().flatten() {
+        if cache.url.is_some() && !caches.iter().any(|found| found.format == cache.format) {
+            caches.push(cache.clone());
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -145,11 +253,65 @@ pub enum WorkerError {
     #[error(transparent)]
     S3(#[from] object_store::Error),
     #[error(transparent)]
+    Head(#[from] HeadError),
+    #[error(transparent)]
     S3Key(#[from] object_store::path::Error),
     #[error(transparent)]
     Url(#[from] kubimo::url::ParseError),
 }
 
+/// Why an upload could not settle on a data key. Every case stops the upload:
+/// guessing wrong either writes plain what should be sealed, or seals with a
+/// key the manifest does not name.
+#[derive(Error, Debug)]
+pub enum DataKeyError {
+    #[error(transparent)]
+    Encryption(#[from] EncryptionError),
+    #[error(transparent)]
+    Url(#[from] kubimo::url::ParseError),
+    #[error("could not read the archive manifest: {0}")]
+    Download(#[from] DownloadError),
+    #[error("could not parse the archive manifest: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// The data key this archive's uploads seal with, if the client holds
+/// archive keys.
+///
+/// Read from the current manifest so that successive uploads keep sealing
+/// with the key their objects already use (see [`S3Client::writing_key`]).
+/// When a new key has to be minted, the markers of sealed objects go, from
+/// `cache_markers` and from any `s3` already holds: they would otherwise let
+/// an unchanged file keep its object, sealed with a key no manifest will name
+/// again.
+pub async fn resolve_data_key(
+    s3: &S3Client,
+    bucket: Option<&str>,
+    key_prefix: Option<&str>,
+    cache_markers: &mut CacheMarkers,
+) -> Result<Option<DataKey>, DataKeyError> {
+    let Some(bucket) = bucket else {
+        return Ok(None);
+    };
+    let url = kubimo::manifest_url(bucket, key_prefix)?;
+    let envelope = match s3.get_bytes(&url).await {
+        Ok(bytes) => serde_json::from_slice::<kubimo::WorkspaceManifest>(&bytes)?.encryption,
+        Err(DownloadError::S3(object_store::Error::NotFound { .. })) => None,
+        Err(err) => return Err(err.into()),
+    };
+    let Some((data_key, fresh)) = s3.writing_key(envelope.as_ref())? else {
+        return Ok(None);
+    };
+    if fresh {
+        tracing::info!(
+            "Sealing the archive with a new data key under {}",
+            data_key.envelope().key_id
+        );
+        s3.forget_cached(&cache_markers.forget_sealed()).await;
+    }
+    Ok(Some(data_key))
+}
+
 impl EntryWorker {
     async fn run(&self) {
         while let Some(path) = self.rx.lock().await.recv().await {
@@ -175,6 +337,10 @@ impl EntryWorker {
         }
     }
 
+    /// Upload a derived artifact — marimo meta or a cache — to its key. These
+    /// stay plain even in a sealed archive: they are read straight from the
+    /// bucket by what renders notebooks, which holds no archive keys, and no
+    /// restore ever reads them.
     async fn upload(
         &self,
         path: impl AsRef<Path>,
@@ -191,6 +357,168 @@ impl EntryWorker {
             url,
             crc32: Some(result.crc32),
             e_tag: result.e_tag,
+            encrypted: None,
+            codec: None,
+            chunks: None,
+        })
+    }
+
+    /// Upload the content of `path` to `url`, compressed when the policy says
+    /// it is worth it and sealed when the archive has a data key.
+    async fn upload_content(
+        &self,
+        path: &Path,
+        url: Url,
+        size: u64,
+        input: impl AsyncRead + AsyncSeek + Unpin,
+    ) -> Result<WorkspaceDirContentUrl, WorkerError> {
+        let compression = self.opts.compression.as_ref();
+        let encoding = s3::Encoding {
+            codec: compression.and_then(|policy| policy.codec_for(path, size)),
+            level: compression.map_or(0, |policy| policy.level),
+            data_key: self.opts.keys.data_key.as_ref(),
+        };
+        let result = self
+            .opts
+            .s3
+            .upload_encoded(&url, input, size, &self.opts.upload_permits, encoding)
+            .await?;
+        Ok(WorkspaceDirContentUrl {
+            url,
+            crc32: Some(result.crc32),
+            e_tag: result.e_tag,
+            encrypted: self.opts.keys.data_key.is_some().then_some(true),
+            codec: result.codec,
+            chunks: None,
+        })
+    }
+
+    /// [`Self::upload`], except that content which differs from what its
+    /// current object holds goes to a fresh key instead of over it.
+    ///
+    /// "Differs" is judged against the crc32 the cache markers hold for the
+    /// object. A url with no marker — freshly minted, or one the markers lost
+    /// — counts as different, since nothing proves overwriting it is safe.
+    async fn upload_rotating(
+        &self,
+        path: impl AsRef<Path>,
+        size: u64,
+        mut input: impl AsyncRead + AsyncSeek + Unpin,
+    ) -> Result<WorkspaceDirContentUrl, WorkerError> {
+        let path = path.as_ref().to_path_buf();
+        let mut url = self.opts.keys.file_url(path.clone()).await?;
+        let crc32 = s3::crc32_of(&mut input).await?;
+        let sealed = self.opts.keys.data_key.is_some();
+        if self.opts.s3.cached_crc32(&url, sealed).await != Some(crc32) {
+            url = self.opts.keys.rotate_file_url(path.clone()).await?;
+        }
+        self.upload_content(&path, url, size, input).await
+    }
+
+    /// Upload content to the shared store under its digest, unless some
+    /// workspace already has.
+    ///
+    /// An object found there is reused without reading it back: the digest is
+    /// the proof of what it holds. One old enough to be near collection is
+    /// written again instead, so it cannot be collected between here and the
+    /// manifest that names it. Its marker is dropped first, or `upload` would
+    /// see matching bytes and skip the very write that renews it.
+    ///
+    /// Store objects are never compressed. A workspace that finds one already
+    /// there names it without reading it, so it could not tell which codec
+    /// whoever wrote it used.
+    async fn upload_addressed(
+        &self,
+        store: &ContentStore,
+        size: u64,
+        mut input: impl AsyncRead + AsyncSeek + Unpin,
+    ) -> Result<WorkspaceDirContentUrl, WorkerError> {
+        let (digest, crc32) = content_store::digest_of(&mut input).await?;
+        let url = store.url(&digest)?;
+        match self.opts.s3.head(&url).await? {
+            Some(head)
+                if !content_store::needs_refresh(
+                    head.last_modified,
+                    kubimo::chrono::Utc::now(),
+                ) =>
+            {
+                return Ok(WorkspaceDirContentUrl {
+                    url,
+                    crc32: Some(crc32),
+                    e_tag: head.e_tag,
+                    encrypted: None,
+                    codec: None,
+                    chunks: None,
+                });
+            }
+            Some(_) => self.opts.s3.forget(&url).await,
+            None => {}
+        }
+        let result = self
+            .opts
+            .s3
+            .upload(&url, input, size, &self.opts.upload_permits)
+            .await?;
+        Ok(WorkspaceDirContentUrl {
+            url,
+            crc32: Some(result.crc32),
+            e_tag: result.e_tag,
+            encrypted: None,
+            codec: None,
+            chunks: None,
+        })
+    }
+
+    /// Upload `path` as content-defined chunks (see [`crate::chunking`]).
+    ///
+    /// Each chunk goes through the same paths a whole file would: the shared
+    /// store when the workspace uses it, otherwise its own object, compressed
+    /// and sealed as the archive asks. A chunk is named by what it holds, so
+    /// one this archive already has leaves its cache marker to skip the
+    /// upload, and no chunk object ever needs rotating: changed bytes are a
+    /// new name. The file still takes a key of its own, which names no object
+    /// but keeps the path's key stable should it shrink back under the
+    /// threshold.
+    ///
+    /// Chunks go up one at a time, so a file holds at most one chunk in
+    /// memory; the upload permits still bound how many files do at once.
+    async fn upload_chunked(
+        &self,
+        chunking: &Chunking,
+        path: &Path,
+        file: tokio::fs::File,
+    ) -> Result<WorkspaceDirContentUrl, WorkerError> {
+        let url = self.opts.keys.file_url(path.to_path_buf()).await?;
+        let data_key = self.opts.keys.data_key.as_ref();
+        let mut chunker = Chunker::new(file);
+        let mut chunks = Vec::new();
+        let mut crc32 = crc32fast::Hasher::new();
+        while let Some(bytes) = chunker.next_chunk().await? {
+            crc32.update(&bytes);
+            let size = bytes.len() as u64;
+            let input = std::io::Cursor::new(bytes);
+            let uploaded = if let Some(store) = &self.opts.content_store {
+                self.upload_addressed(store, size, input).await?
+            } else {
+                let name = chunking::chunk_name(input.get_ref(), data_key);
+                self.upload_content(path, chunking.url(&name)?, size, input)
+                    .await?
+            };
+            chunks.push(WorkspaceDirContentChunk {
+                url: uploaded.url,
+                size,
+                crc32: uploaded.crc32.unwrap_or_default(),
+                e_tag: uploaded.e_tag,
+                codec: uploaded.codec,
+            });
+        }
+        Ok(WorkspaceDirContentUrl {
+            url,
+            crc32: Some(crc32.finalize()),
+            e_tag: None,
+            encrypted: data_key.is_some().then_some(true),
+            codec: None,
+            chunks: Some(chunks),
         })
     }
 
@@ -302,6 +630,9 @@ impl EntryWorker {
                 }
             }
         }
+        if let Some(previous) = &self.opts.previous_caches {
+            keep_previous_caches(&mut caches, path, previous);
+        }
         caches.sort_by_key(|cache| cache.format.clone());
         let meta_json = match meta_upload.await {
             Ok(Ok(url)) => Some(url),
@@ -341,7 +672,16 @@ impl EntryWorker {
             return Ok(None);
         }
         let path = path.as_ref();
-        if size > self.opts.max_file_size {
+        let chunking = self
+            .opts
+            .chunking
+            .as_ref()
+            .filter(|chunking| chunking.policy.chunks(size));
+        let max_file_size = match chunking {
+            Some(chunking) => chunking.policy.max_file_size,
+            None => self.opts.max_file_size,
+        };
+        if size > max_file_size {
             return Ok(None);
         }
         let full_path = self.opts.directory.join(path);
@@ -360,9 +700,17 @@ impl EntryWorker {
         if let Some(cached) = self.opts.content_cache.get(path, modified, size).await {
             return Ok(Some(cached));
         }
-        let uploaded = self
-            .upload(path, size, tokio::fs::File::open(full_path).await?)
-            .await?;
+        let file = tokio::fs::File::open(full_path).await?;
+        let uploaded = if let Some(chunking) = chunking {
+            self.upload_chunked(chunking, path, file).await?
+        } else if let Some(store) = &self.opts.content_store {
+            self.upload_addressed(store, size, file).await?
+        } else if self.opts.rotate_modified {
+            self.upload_rotating(path, size, file).await?
+        } else {
+            let url = self.opts.keys.file_url(path.to_path_buf()).await?;
+            self.upload_content(path, url, size, file).await?
+        };
         self.opts
             .content_cache
             .insert(path.to_path_buf(), modified, size, &uploaded)
@@ -638,8 +986,12 @@ pub async fn process_existing_dirs(
             // key for the same path, re-uploads the content under it, and
             // orphans the old object forever: nothing else ever deletes it.
             if let Some(content) = &file.content {
-                previous_urls.insert(content.url.clone());
-                if let Err(err) = urls.insert(path.clone(), &content.url) {
+                previous_urls.extend(content.object_urls().cloned());
+                // A store url is named by its digest, not by the path, so it
+                // has no key to re-seed; it would only fail to parse as one.
+                if !kubimo::is_content_store_url(&content.url)
+                    && let Err(err) = urls.insert(path.clone(), &content.url)
+                {
                     tracing::warn!(
                         "Error inserting workspace content url for {}: {}",
                         path.display(),
@@ -649,7 +1001,24 @@ pub async fn process_existing_dirs(
                 if let Some(e_tag) = &content.e_tag
                     && let Some(crc32) = &content.crc32
                 {
-                    cache_markers.insert(content.url.clone(), *crc32, e_tag.clone());
+                    cache_markers.insert(
+                        content.url.clone(),
+                        *crc32,
+                        e_tag.clone(),
+                        content.encrypted.unwrap_or(false),
+                        content.codec,
+                    );
+                }
+                for chunk in content.chunks.iter().flatten() {
+                    if let Some(e_tag) = &chunk.e_tag {
+                        cache_markers.insert(
+                            chunk.url.clone(),
+                            chunk.crc32,
+                            e_tag.clone(),
+                            content.encrypted.unwrap_or(false),
+                            chunk.codec,
+                        );
+                    }
                 }
             }
             let Some(marimo) = &file.marimo else {
@@ -668,7 +1037,7 @@ pub async fn process_existing_dirs(
                 if let Some(e_tag) = &url.e_tag
                     && let Some(crc32) = &url.crc32
                 {
-                    cache_markers.insert(url.url.clone(), *crc32, e_tag.clone());
+                    cache_markers.insert(url.url.clone(), *crc32, e_tag.clone(), false, None);
                 }
             }
             let Some(caches) = &marimo.caches else {
@@ -698,7 +1067,7 @@ pub async fn process_existing_dirs(
                     if let Some(e_tag) = &url.e_tag
                         && let Some(crc32) = &url.crc32
                     {
-                        cache_markers.insert(url.url.clone(), *crc32, e_tag.clone());
+                        cache_markers.insert(url.url.clone(), *crc32, e_tag.clone(), false, None);
                     }
                 }
             }
@@ -731,9 +1100,13 @@ async fn clean_workspace_dir(client: &kubimo::Client, name: String) {
 /// that manifest, reads it as proof that an archive exists, and refuses to
 /// index itself until a file appears.
 ///
-/// Known limitation: the sweep is driven by the CRs, so an archive whose CRs
-/// are already gone is missed entirely. Deleting by prefix instead would need a
-/// list API that `S3Client` does not have.
+/// Archive generations are found by listing the history directory, and each
+/// one's manifest is read for the content it names: those objects may long
+/// since have left the CRs.
+///
+/// Known limitation: the sweep of the current tree is driven by the CRs, so an
+/// archive whose CRs are already gone is missed entirely, bar what some
+/// generation still names.
 pub async fn clean(
     client: &kubimo::Client,
     s3: &S3Client,
@@ -756,6 +1129,7 @@ pub async fn clean(
             Ok(url) => futs.push(clean_url(s3, url).boxed()),
             Err(err) => tracing::error!("Error building secrets url: {err}"),
         }
+        futs.push(history::purge(s3, bucket, key_prefix).boxed());
     }
     while let Some(workspace_dir) = workspace_dirs.next().await {
         let workspace_dir = match workspace_dir {
@@ -780,8 +1154,14 @@ pub async fn clean(
             // Delete the file's content too. `clean` used to remove only the
             // marimo meta/cache objects, so deleting a workspace left every
             // uploaded file behind in the bucket forever.
+            // Bar store objects, which other workspaces may name too: only
+            // the collector can tell when the last of them is gone.
             if let Some(content) = &file.content {
-                futs.push(clean_url(s3, content.url.clone()).boxed());
+                for url in content.object_urls() {
+                    if !kubimo::is_content_store_url(url) {
+                        futs.push(clean_url(s3, url.clone()).boxed());
+                    }
+                }
             }
             let Some(marimo) = &file.marimo else {
                 continue;
@@ -996,6 +1376,26 @@ pub async fn run(
         }
     };
 
+    // Read before anything is written. A manifest that cannot be read refuses
+    // the cycle: going ahead would drop exactly the caches asked to be kept.
+    let previous_caches = match args.bucket.as_deref().filter(|_| args.keep_caches) {
+        Some(bucket) => match previous_caches(bucket, args.key_prefix.as_deref(), s3).await {
+            Ok(caches) => Some(Arc::new(caches)),
+            Err(err) => {
+                tracing::error!("Error reading the caches to keep; not uploading: {err}");
+                return RunResult {
+                    names: previous_names.clone(),
+                    urls: previous_urls.clone(),
+                    paths: BTreeSet::new(),
+                    refused: true,
+                    failures: 0,
+                    content_bytes: 0,
+                };
+            }
+        },
+        None => None,
+    };
+
     // One counter for the whole cycle, shared with the walk and the workers:
     // every path that increments it is a path where the archive ends up
     // narrower than the tree on disk.
@@ -1036,7 +1436,22 @@ pub async fn run(
             upload_content: args.upload_content,
             upload_permits: upload_permits.clone(),
             keys: keys.clone(),
+            rotate_modified: args.history.is_some() && args.bucket.is_some(),
+            // A store object is shared by every workspace holding the same
+            // bytes, so it cannot be sealed with any one archive's key. A
+            // sealed archive keeps its content under its own keys instead.
+            content_store: args
+                .bucket
+                .as_deref()
+                .filter(|_| args.content_addressed && keys.data_key.is_none())
+                .map(ContentStore::new),
+            compression: args.compression.clone(),
+            chunking: args.bucket.as_deref().and_then(|bucket| {
+                let policy = args.chunking.clone()?;
+                Some(Chunking::new(policy, bucket, args.key_prefix.clone()))
+            }),
             failures: failures.clone(),
+            previous_caches,
         },
         1000,
         std::thread::available_parallelism()
@@ -1055,7 +1470,7 @@ pub async fn run(
         // is what gets deleted from S3, so leaving content out meant a removed
         // or renamed file's object was never swept.
         if let Some(content) = entry.file.as_ref().and_then(|file| file.content.as_ref()) {
-            urls.insert(content.url.clone());
+            urls.extend(content.object_urls().cloned());
         }
         if let Some(marimo) = entry.file.as_ref().and_then(|file| file.marimo.as_ref()) {
             if let Some(url) = marimo.meta_json.as_ref() {
@@ -1132,13 +1547,23 @@ pub async fn run(
     // batch. The manifest url is never part of the stale-url bookkeeping,
     // and neither is the secrets url.
     let mut manifest_uploaded = false;
+    let mut recorded = None;
     if let Some(bucket) = args.bucket.as_deref() {
         // Values are only written when content is: without content the archive
         // cannot be restored at all (`RestoreError::NoContent`), so plaintext
         // values nothing can consume are pure downside. The names still land
         // in the manifest either way.
         if args.upload_content {
-            if !upload_secrets(args, s3, bucket, &workspace_secrets, &upload_permits).await {
+            if !upload_secrets(
+                args,
+                s3,
+                bucket,
+                &workspace_secrets,
+                keys.data_key.as_ref(),
+                &upload_permits,
+            )
+            .await
+            {
                 // A `Values` restore cannot happen without it — the same claim
                 // the manifest failure makes below.
                 failures.fetch_add(1, Ordering::Relaxed);
@@ -1153,21 +1578,45 @@ pub async fn run(
                 Err(err) => tracing::error!("Error building secrets url: {err}"),
             }
         }
-        manifest_uploaded = upload_manifest(
-            args,
-            s3,
-            bucket,
+        let manifest = kubimo::build_manifest(
+            &args.name,
+            args.upload_content,
             &workspace_dirs,
             manifest_secrets,
-            &upload_permits,
-        )
-        .await;
+            keys.data_key.as_ref().map(|key| key.envelope().clone()),
+        );
+        manifest_uploaded = upload_manifest(args, s3, bucket, &manifest, &upload_permits).await;
         if !manifest_uploaded {
             // Without a manifest the archive cannot be restored at all, however
             // many objects reached the bucket.
             failures.fetch_add(1, Ordering::Relaxed);
         }
+        // Cut after the manifest lands, so a generation never describes an
+        // archive that was not written, and before the sweep, which needs to
+        // know what the retained generations still name.
+        if let Some(retention) = &args.history {
+            recorded = Some(
+                history::record(
+                    s3,
+                    bucket,
+                    args.key_prefix.as_deref(),
+                    retention,
+                    manifest_uploaded.then_some(&manifest),
+                    &keys.generation_urls,
+                    &upload_permits,
+                    kubimo::chrono::Utc::now(),
+                )
+                .await,
+            );
+        }
     }
+    let (mut urls_to_delete, deferred_urls) = match recorded.as_ref() {
+        None => (urls_to_delete, BTreeSet::new()),
+        Some(recorded) => protect_history(s3, keys, &urls, urls_to_delete, recorded).await,
+    };
+    // This archive no longer naming a store object says nothing about the
+    // others that may. The collector decides those.
+    urls_to_delete.retain(|url| !kubimo::is_content_store_url(url));
 
     let futs = FuturesUnordered::new();
     for mut dir in workspace_dirs.into_values() {
@@ -1222,7 +1671,10 @@ pub async fn run(
     // honestly.
     let synced_content_bytes =
         (manifest_uploaded && failures.load(Ordering::Relaxed) == 0).then_some(content_bytes);
-    update_workspace_status(args, client, synced_content_bytes).await;
+    let generations = recorded
+        .and_then(Result::ok)
+        .map(|recorded| history::status_generations(&recorded.generations));
+    update_workspace_status(args, client, synced_content_bytes, generations).await;
     let paths = take_paths(paths).await;
     // Drop fingerprints for files that no longer exist, so a watcher running
     // for days does not accumulate an entry per file ever seen. `paths` holds
@@ -1234,6 +1686,9 @@ pub async fn run(
         .map(Path::to_path_buf)
         .collect();
     content_cache.retain_paths(&live).await;
+    // Objects the sweep had to hold back ride along with the live set, so the
+    // next cycle sees them as stale again and retries.
+    urls.extend(deferred_urls);
     RunResult {
         names,
         urls,
@@ -1244,6 +1699,47 @@ pub async fn run(
     }
 }
 
+/// Narrow the stale-object sweep to what no retained generation still names.
+///
+/// Candidates are the objects the tree stopped naming plus those released by
+/// pruned generations. Returns `(to_delete, deferred)`: if the history could
+/// not be read the answer to "is this still needed?" is unknown, and every
+/// candidate is deferred to the next cycle rather than deleted on a guess.
+async fn protect_history(
+    s3: &S3Client,
+    keys: &WorkspaceKeys,
+    live: &BTreeSet<Url>,
+    stale: BTreeSet<Url>,
+    recorded: &Result<Recorded, history::HistoryError>,
+) -> (BTreeSet<Url>, BTreeSet<Url>) {
+    let recorded = match recorded {
+        Ok(recorded) => recorded,
+        Err(err) => {
+            tracing::error!("Could not read archive history; deferring the sweep: {err}");
+            return (BTreeSet::new(), stale);
+        }
+    };
+    let candidates = stale
+        .into_iter()
+        .chain(recorded.released.iter().cloned())
+        .filter(|url| !live.contains(url))
+        .collect::<BTreeSet<_>>();
+    match keys
+        .generation_urls
+        .protected(s3, &recorded.generations)
+        .await
+    {
+        Ok(protected) => (
+            candidates.difference(&protected).cloned().collect(),
+            BTreeSet::new(),
+        ),
+        Err(err) => {
+            tracing::error!("Could not read archive generations; deferring the sweep: {err}");
+            (BTreeSet::new(), candidates)
+        }
+    }
+}
+
 /// Read the cycle's secrets off disk: the root `.env` as KEY/VALUE pairs and
 /// every collected secret file as an inline blob.
 ///
@@ -1327,11 +1823,16 @@ async fn collect_secrets(
 /// staleness — values newer than their manifest — is benign and lasts one
 /// cycle. Uploaded even when empty: skipping would leave a previous cycle's
 /// values behind after the user deleted them.
+///
+/// Sealed whole when the archive has a data key. A restore tells the two
+/// forms apart by the object itself rather than the manifest, which may be a
+/// cycle older than the values.
 async fn upload_secrets(
     args: &UploadOptions,
     s3: &S3Client,
     bucket: &str,
     workspace_secrets: &WorkspaceSecrets,
+    data_key: Option<&DataKey>,
     upload_permits: &Semaphore,
 ) -> bool {
     let url = match kubimo::secrets_url(bucket, args.key_prefix.as_deref()) {
@@ -1348,6 +1849,14 @@ async fn upload_secrets(
             return false;
         }
     };
+    let bytes = match data_key.map(|data_key| data_key.seal(&bytes)) {
+        None => bytes,
+        Some(Ok(sealed)) => sealed,
+        Some(Err(err)) => {
+            tracing::error!("Error sealing secrets: {err}");
+            return false;
+        }
+    };
     let size = bytes.len() as u64;
     let input = std::io::Cursor::new(bytes);
     match s3.upload(&url, input, size, upload_permits).await {
@@ -1375,16 +1884,9 @@ async fn upload_manifest(
     args: &UploadOptions,
     s3: &S3Client,
     bucket: &str,
-    workspace_dirs: &BTreeMap<String, WorkspaceDir>,
-    manifest_secrets: ManifestSecrets,
+    manifest: &kubimo::WorkspaceManifest,
     upload_permits: &Semaphore,
 ) -> bool {
-    let manifest = kubimo::build_manifest(
-        &args.name,
-        args.upload_content,
-        workspace_dirs,
-        manifest_secrets,
-    );
     let url = match kubimo::manifest_url(bucket, args.key_prefix.as_deref()) {
         Ok(url) => url,
         Err(err) => {
@@ -1392,7 +1894,7 @@ async fn upload_manifest(
             return false;
         }
     };
-    let bytes = match serde_json::to_vec(&manifest) {
+    let bytes = match serde_json::to_vec(manifest) {
         Ok(bytes) => bytes,
         Err(err) => {
             tracing::error!("Error serializing manifest: {err}");
@@ -1430,6 +1932,7 @@ async fn update_workspace_status(
     args: &UploadOptions,
     client: &kubimo::Client,
     synced_content_bytes: Option<u64>,
+    generations: Option<Vec<kubimo::WorkspaceArchiveGeneration>>,
 ) {
     // Losing disk usage must not cost the sync record: they describe different
     // things and only one of them is a durability claim.
@@ -1456,9 +1959,13 @@ async fn update_workspace_status(
         // `keyPrefix` is deliberately absent: the agent owns it, and an apply owns
         // exactly the fields it carries, so naming it here would take it over and
         // then drop it on the first batch that has nothing to say.
+        // Generations ride only with a synced batch. Carried alone they would
+        // make this apply own `archive` without `lastSyncedAt`, and the field
+        // would vanish on a batch that did not sync.
         archive: synced_content_bytes.map(|content_bytes| WorkspaceArchiveStatus {
             last_synced_at: Some(kubimo::chrono::Utc::now()),
             total_content_bytes: Some(content_bytes),
+            generations,
             ..Default::default()
         }),
         ..Default::default()
@@ -1552,6 +2059,9 @@ mod tests {
             url: "s3://bucket/abc".parse().unwrap(),
             crc32: Some(1),
             e_tag: Some("e".into()),
+            encrypted: None,
+            codec: None,
+            chunks: None,
         };
 
         // First pass: nothing cached, so the caller would read and upload.
@@ -1719,6 +2229,11 @@ mod tests {
             watch_debounce_millis: 0,
             watch_max_wait_millis: 0,
             watch_poll_millis: 0,
+            history: None,
+            content_addressed: false,
+            compression: None,
+            chunking: None,
+            keep_caches: false,
             name: "bmow-abc".to_string(),
             directory: directory.to_path_buf(),
         }
@@ -1742,6 +2257,7 @@ mod tests {
         let keys = WorkspaceKeys::new(
             WorkspaceDirNameSet::new("bmow-abc".to_string()),
             WorkspaceFileUrlSet::new("bucket".to_string(), None).unwrap(),
+            None,
         );
         let result = run(
             &options,
@@ -1916,6 +2432,7 @@ mod tests {
         let keys = WorkspaceKeys::new(
             WorkspaceDirNameSet::new("bmow-abc".to_string()),
             WorkspaceFileUrlSet::new("bucket".to_string(), None).unwrap(),
+            None,
         );
         let result = run(
             &offline_options(dir.path()),
@@ -1960,6 +2477,7 @@ mod tests {
         let keys = WorkspaceKeys::new(
             WorkspaceDirNameSet::new("bmow-abc".to_string()),
             WorkspaceFileUrlSet::new("bucket".to_string(), None).unwrap(),
+            None,
         );
         let result = run(
             &offline_options(dir.path()),
@@ -1976,4 +2494,102 @@ mod tests {
             "the entry that never reached a WorkspaceDirectory must be reported"
         );
     }
+
+    const NOTEBOOK: &[u8] = b"import marimo\n\napp = marimo.App()\n";
+
+    /// A CacheJob that regenerated only `a.py`'s html must leave the caches
+    /// the archive already had for everything else in the manifest: `b.py`
+    /// has no cache on disk at all, as on a freshly hydrated slot.
+    #[tokio::test]
+    async fn a_selective_cache_run_keeps_the_other_caches_in_the_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.py"), NOTEBOOK).unwrap();
+        std::fs::write(dir.path().join("b.py"), NOTEBOOK).unwrap();
+        std::fs::create_dir(dir.path().join("__marimo__")).unwrap();
+        std::fs::write(dir.path().join("__marimo__/a.html"), b"<html></html>").unwrap();
+
+        let bucket_dir = tempfile::tempdir().unwrap();
+        let bucket = format!("file://{}", bucket_dir.path().display());
+        let root = kubimo::bucket_url(&bucket).unwrap();
+        let cache = |name: &str, format: &str| {
+            serde_json::json!({
+                "format": format,
+                "url": { "url": root.join(name).unwrap() },
+            })
+        };
+        let previous = serde_json::json!({
+            "version": "V1",
+            "workspace": "bmow-abc",
+            "uploadContent": false,
+            "totalContentBytes": 0,
+            "directories": [{
+                "path": "",
+                "entries": [
+                    { "name": "a.py", "file": { "marimo": { "caches": [
+                        cache("old-a.html", "html"),
+                        cache("old-a.md", "md"),
+                    ] } } },
+                    { "name": "b.py", "file": { "marimo": { "caches": [
+                        cache("old-b.ipynb", "ipynb"),
+                    ] } } },
+                ],
+            }],
+        });
+        std::fs::write(
+            bucket_dir.path().join(kubimo::MANIFEST_FILE_NAME),
+            serde_json::to_vec(&previous).unwrap(),
+        )
+        .unwrap();
+
+        let options = UploadOptions {
+            bucket: Some(bucket.clone()),
+            keep_caches: true,
+            max_file_size: 1 << 20,
+            ..offline_options(dir.path())
+        };
+        let keys = WorkspaceKeys::new(
+            WorkspaceDirNameSet::new("bmow-abc".to_string()),
+            WorkspaceFileUrlSet::new(bucket, None).unwrap(),
+            None,
+        );
+        let result = run(
+            &options,
+            &ContentCache::new(),
+            &offline_client(),
+            &S3Client::from_options(Vec::<(String, String)>::new()),
+            &keys,
+            &BTreeSet::new(),
+            &BTreeSet::new(),
+        )
+        .await;
+        assert!(!result.refused);
+
+        let manifest: kubimo::WorkspaceManifest = serde_json::from_slice(
+            &std::fs::read(bucket_dir.path().join(kubimo::MANIFEST_FILE_NAME)).unwrap(),
+        )
+        .unwrap();
+        let caches = manifest_caches(&manifest);
+        let formats = |path: &str| -> Vec<(String, String)> {
+            caches[Path::new(path)]
+                .iter()
+                .map(|cache| {
+                    let url = cache.url.as_ref().unwrap().url.path().to_string();
+                    let name = url.rsplit('/').next().unwrap().to_string();
+                    (cache.format.clone(), name)
+                })
+                .collect()
+        };
+        // `a.py`'s html is the one just generated, not the old object.
+        let a = formats("a.py");
+        assert_eq!(a.len(), 2);
+        assert_eq!(a[0].0, "html");
+        assert_ne!(a[0].1, "old-a.html");
+        assert_eq!(a[1], ("md".to_string(), "old-a.md".to_string()));
+        assert_eq!(
+            formats("b.py"),
+            [("ipynb".to_string(), "old-b.ipynb".to_string())]
+        );
+        // Still named, so not swept.
+        assert!(result.urls.contains(&root.join("old-b.ipynb").unwrap()));
+    }
 }